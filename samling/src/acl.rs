@@ -0,0 +1,396 @@
+//! Path based access control.
+//!
+//! The caller is identified through a task-local set with [`scope`], so an [`AccessControlled`]
+//! store can be mounted in a composite next to other stores and still see who is asking.
+use std::{
+    fmt, io,
+    ops::{BitOr, BitOrAssign},
+    sync::Arc,
+};
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future, StreamExt};
+use globset::{Glob, GlobMatcher};
+use relative_path::{Component, RelativePath, RelativePathBuf};
+
+use crate::{file::link_target, AsyncFileInit, AsyncFileStore, Link, Metadata, Permissions};
+
+tokio::task_local! {
+    static IDENTITY: Arc<str>;
+}
+
+/// Run `fut` on behalf of `identity`.
+///
+/// The identity is task-local, so it isn't seen by tasks spawned from within `fut`. Outside of
+/// a scope the caller is anonymous.
+///
+/// ```ignore
+/// let body = acl::scope(session.user, files.read("admin/report.csv")).await?;
+/// ```
+pub async fn scope<F>(identity: impl Into<Arc<str>>, fut: F) -> F::Output
+where
+    F: Future,
+{
+    IDENTITY.scope(identity.into(), fut).await
+}
+
+/// The identity of the current scope, if any.
+pub fn identity() -> Option<Arc<str>> {
+    IDENTITY.try_with(|identity| identity.clone()).ok()
+}
+
+/// A set of operations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Access(u8);
+
+impl Access {
+    pub const NONE: Access = Access(0);
+    /// `metadata`, `open_file` and `exists`.
+    pub const READ: Access = Access(1);
+    pub const WRITE: Access = Access(1 << 1);
+    pub const DELETE: Access = Access(1 << 2);
+    /// Seeing the path in listings.
+    pub const LIST: Access = Access(1 << 3);
+    pub const ALL: Access = Access(0b1111);
+
+    pub fn contains(self, other: Access) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Access {
+    type Output = Access;
+
+    fn bitor(self, rhs: Access) -> Access {
+        Access(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Access {
+    fn bitor_assign(&mut self, rhs: Access) {
+        self.0 |= rhs.0;
+    }
+}
+
+#[derive(Clone)]
+struct Rule {
+    matcher: GlobMatcher,
+    identity: Option<Arc<str>>,
+    access: Access,
+}
+
+/// Allows operations on paths according to rules, rejecting the rest with `PermissionDenied`.
+///
+/// Each rule grants operations on the paths matching a glob, to everyone or to a single
+/// identity. The grants of every matching rule add up, and anything not granted is denied.
+/// Listings leave out the paths the caller may not list.
+///
+/// ```ignore
+/// let store = AccessControlled::new(assets)
+///     .allow("public/**", Access::READ | Access::LIST)?
+///     .allow_for("admin", "**", Access::ALL)?;
+///
+/// acl::scope("admin", store.rm_file("public/old.css".as_ref())).await?;
+/// ```
+#[derive(Clone)]
+pub struct AccessControlled<T> {
+    inner: T,
+    rules: Arc<Vec<Rule>>,
+}
+
+impl<T> AccessControlled<T> {
+    pub fn new(inner: T) -> AccessControlled<T> {
+        AccessControlled {
+            inner,
+            rules: Arc::default(),
+        }
+    }
+
+    /// Grant `access` to the paths matching `glob` to every caller, including anonymous ones.
+    pub fn allow(self, glob: &str, access: Access) -> io::Result<Self> {
+        self.rule(None, glob, access)
+    }
+
+    /// Grant `access` to the paths matching `glob` to `identity`.
+    pub fn allow_for(
+        self,
+        identity: impl Into<Arc<str>>,
+        glob: &str,
+        access: Access,
+    ) -> io::Result<Self> {
+        self.rule(Some(identity.into()), glob, access)
+    }
+
+    fn rule(mut self, identity: Option<Arc<str>>, glob: &str, access: Access) -> io::Result<Self> {
+        let matcher = Glob::new(glob)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+            .compile_matcher();
+        Arc::make_mut(&mut self.rules).push(Rule {
+            matcher,
+            identity,
+            access,
+        });
+        Ok(self)
+    }
+
+    /// What `identity` may do with `path`.
+    pub fn access(&self, identity: Option<&str>, path: &RelativePath) -> Access {
+        granted(&self.rules, identity, path)
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn check(&self, path: &RelativePath, access: Access) -> io::Result<()> {
+        let identity = identity();
+        if self.access(identity.as_deref(), path).contains(access) {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("access denied: {path}"),
+            ))
+        }
+    }
+}
+
+/// Rules are matched against the normalized path, the one the inner store resolves, so
+/// `public/../admin` is checked as `admin`. Paths leading out of the store get no access.
+fn granted(rules: &[Rule], identity: Option<&str>, path: &RelativePath) -> Access {
+    let path = path.normalize();
+    if path.components().next() == Some(Component::ParentDir) {
+        return Access::NONE;
+    }
+
+    rules
+        .iter()
+        .filter(|rule| {
+            rule.identity
+                .as_deref()
+                .is_none_or(|rule| Some(rule) == identity)
+        })
+        .filter(|rule| rule.matcher.is_match(path.as_str()))
+        .fold(Access::NONE, |access, rule| access | rule.access)
+}
+
+impl<T> fmt::Debug for AccessControlled<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessControlled")
+            .field("inner", &self.inner)
+            .field("rules", &self.rules.len())
+            .finish()
+    }
+}
+
+impl<T> AsyncFileStore for AccessControlled<T>
+where
+    T: AsyncFileStore + Send + Sync,
+{
+    type File = T::File;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            self.check(path, Access::READ)?;
+            self.inner.metadata(path).await
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            self.check(path, Access::READ)?;
+            self.inner.open_file(path).await
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            self.check(path, Access::DELETE)?;
+            self.inner.rm_file(path).await
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            self.check(path, Access::WRITE)?;
+            self.inner.write_file(path, init).await
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        async move {
+            // Captured now, as the listing may be consumed outside of the scope
+            let identity = identity();
+            let rules = self.rules.clone();
+            let list = self.inner.list().await?;
+
+            Ok(list
+                .filter(move |ret| {
+                    let keep = match ret {
+                        Ok(path) => {
+                            granted(&rules, identity.as_deref(), path).contains(Access::LIST)
+                        }
+                        Err(_) => true,
+                    };
+                    async move { keep }
+                })
+                .boxed())
+        }
+    }
+
+    fn exists(&self, path: &RelativePath) -> impl Future<Output = bool> + Send
+    where
+        Self: Sync,
+    {
+        async move { self.check(path, Access::READ).is_ok() && self.inner.exists(path).await }
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.health()
+    }
+
+    /// Passes on the paths the caller may read.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let paths = paths
+            .iter()
+            .filter(|path| self.check(path, Access::READ).is_ok())
+            .cloned()
+            .collect::<Vec<_>>();
+        async move { self.inner.prefetch(&paths).await }
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let paths = crate::prefetch::below(&self.inner, prefix).await?;
+            self.prefetch(&paths).await
+        }
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        async move {
+            self.check(path, Access::WRITE)?;
+            self.inner.set_len(path, len).await
+        }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.check(path, Access::WRITE)?;
+            self.inner.write_range(path, offset, data).await
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.check(path, Access::WRITE)?;
+            self.inner.set_permissions(path, permissions).await
+        }
+    }
+
+    /// Needs write access to the link and read access to what it points at.
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.check(link, Access::WRITE)?;
+            self.check(&link_target(link, target)?, Access::READ)?;
+            self.inner.symlink(target, link).await
+        }
+    }
+
+    /// Needs read access to `src` and write access to `dst`.
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.check(src, Access::READ)?;
+            self.check(dst, Access::WRITE)?;
+            self.inner.hard_link(src, dst).await
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        async move {
+            self.check(path, Access::READ)?;
+            self.inner.read_link(path).await
+        }
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.close()
+    }
+}
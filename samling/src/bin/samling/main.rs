@@ -0,0 +1,146 @@
+mod mounts;
+
+use std::io::{self, Write};
+
+use clap::{Parser, Subcommand};
+use futures::{pin_mut, TryStreamExt};
+use samling::{AsyncFile, AsyncFileStore};
+
+use self::mounts::Mounts;
+
+/// Inspect and manipulate samling stores from the command line.
+#[derive(Parser)]
+#[command(name = "samling", version)]
+struct Cli {
+    /// Mount a store, eg. `/assets=file:///srv/assets`. Defaults to the current directory at `/`.
+    #[arg(short, long = "mount", value_name = "MOUNT=URL", global = true)]
+    mounts: Vec<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List files, optionally limited to a prefix
+    Ls { prefix: Option<String> },
+    /// Write the contents of a file to stdout
+    Cat { path: String },
+    /// Show the metadata of a file
+    Stat { path: String },
+    /// Copy a file between mounts
+    Cp { source: String, target: String },
+    /// Remove a file
+    Rm { path: String },
+    /// Pack the mounts into a single file, loadable with `PackFileStore`
+    Pack {
+        output: std::path::PathBuf,
+        /// Gzip compressible files
+        #[arg(long)]
+        gzip: bool,
+    },
+    /// Serve the mounts over http
+    Serve {
+        #[arg(short, long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Render an html listing for directories without an index.html
+        #[arg(long)]
+        directory_index: bool,
+        /// Stream changes to /__samling/live-reload for reloading pages during development
+        #[arg(long)]
+        watch: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    if let Err(err) = run(cli).await {
+        eprintln!("samling: {err}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> io::Result<()> {
+    let mounts = if cli.mounts.is_empty() {
+        Mounts::parse(["/=."])?
+    } else {
+        Mounts::parse(&cli.mounts)?
+    };
+
+    match cli.command {
+        Command::Ls { prefix } => {
+            let prefix = prefix.as_deref().map(mounts::normalize).unwrap_or_default();
+            let stream = mounts.list().await?;
+            pin_mut!(stream);
+
+            let mut stdout = io::stdout().lock();
+            while let Some(next) = stream.try_next().await? {
+                if next.as_str().starts_with(prefix.as_str()) {
+                    writeln!(stdout, "{next}")?;
+                }
+            }
+        }
+        Command::Cat { path } => {
+            let (store, path) = mounts.resolve(&path)?;
+            let file = store.open_file(&path).await?;
+            let reader = file.reader().await?;
+            pin_mut!(reader);
+
+            let mut stdout = io::stdout().lock();
+            while let Some(next) = reader.try_next().await? {
+                stdout.write_all(&next)?;
+            }
+            stdout.flush()?;
+        }
+        Command::Stat { path } => {
+            let (store, path) = mounts.resolve(&path)?;
+            let meta = store.metadata(&path).await?;
+            println!("path: {}", meta.path);
+            println!("size: {}", meta.size);
+            println!("mime: {}", meta.mime);
+        }
+        Command::Cp { source, target } => {
+            let (source_store, source) = mounts.resolve(&source)?;
+            let (target_store, target) = mounts.resolve(&target)?;
+            samling::util::copy(
+                samling::AsyncPath::new(source_store, source),
+                samling::AsyncPath::new(target_store, target),
+            )
+            .await?;
+        }
+        Command::Rm { path } => {
+            let (store, path) = mounts.resolve(&path)?;
+            store.rm_file(&path).await?;
+        }
+        Command::Pack { output, gzip } => {
+            let compression = if gzip {
+                samling::pack::Compression::Gzip
+            } else {
+                samling::pack::Compression::None
+            };
+            let output =
+                futures::io::AllowStdIo::new(io::BufWriter::new(std::fs::File::create(output)?));
+            let count = samling::pack::PackBuilder::new()
+                .compression(compression)
+                .write(&mounts.into_composite(), output)
+                .await?;
+            eprintln!("samling: packed {count} files");
+        }
+        Command::Serve {
+            addr,
+            directory_index,
+            watch,
+        } => {
+            eprintln!("samling: serving on http://{addr}");
+            let mut server = samling::serve::Server::new(mounts.into_composite())
+                .directory_index(directory_index);
+            if watch {
+                server = server.live_reload(std::time::Duration::from_millis(500));
+            }
+            server.listen(addr).await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use std::{io, path::PathBuf};
+
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+use samling::{
+    fs::FsFileStore, AsyncComposite, AsyncFileStore, AsyncFileStoreExt, BoxAsyncFileStore, Url,
+};
+
+pub struct Mounts {
+    // Sorted by mount length, longest first, so resolution finds the most specific mount
+    mounts: Vec<(RelativePathBuf, BoxAsyncFileStore)>,
+}
+
+impl Mounts {
+    pub fn parse<I>(specs: I) -> io::Result<Mounts>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut mounts = Vec::new();
+
+        for spec in specs {
+            let spec = spec.as_ref();
+            let Some((mount, url)) = spec.split_once('=') else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid mount '{spec}', expected MOUNT=URL"),
+                ));
+            };
+
+            mounts.push((normalize(mount), open_store(url)?));
+        }
+
+        mounts.sort_by(|a, b| b.0.as_str().len().cmp(&a.0.as_str().len()));
+
+        Ok(Mounts { mounts })
+    }
+
+    pub fn resolve(&self, path: &str) -> io::Result<(&BoxAsyncFileStore, RelativePathBuf)> {
+        let path = normalize(path);
+
+        for (mount, store) in &self.mounts {
+            if mount.as_str().is_empty() {
+                return Ok((store, path));
+            }
+
+            if let Ok(rest) = path.strip_prefix(mount) {
+                return Ok((store, rest.to_relative_path_buf()));
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no mount for '{path}'"),
+        ))
+    }
+
+    pub async fn list(&self) -> io::Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>> {
+        let mut streams = Vec::with_capacity(self.mounts.len());
+
+        for (mount, store) in &self.mounts {
+            let mount = mount.clone();
+            let stream = store.list().await?;
+            streams.push(stream.map_ok(move |path| mount.join(path)));
+        }
+
+        Ok(futures::stream::iter(streams).flatten().boxed())
+    }
+
+    pub fn into_composite(self) -> AsyncComposite {
+        let mut composite = AsyncComposite::default();
+        for (mount, store) in self.mounts {
+            composite.register(mount.as_str(), store);
+        }
+        composite
+    }
+}
+
+pub fn normalize(path: &str) -> RelativePathBuf {
+    RelativePath::new(path.trim_start_matches('/')).normalize()
+}
+
+/// Construct a store from an url. Anything that is not a valid url is treated as a local path.
+pub fn open_store(url: &str) -> io::Result<BoxAsyncFileStore> {
+    let path = match Url::parse(url) {
+        Ok(url) if url.scheme() == "file" => url.to_file_path().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid file url '{url}'"),
+            )
+        })?,
+        Ok(url) if url.scheme().len() > 1 => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported store scheme '{}'", url.scheme()),
+            ))
+        }
+        _ => PathBuf::from(url),
+    };
+
+    Ok(FsFileStore::new(path)?.boxed())
+}
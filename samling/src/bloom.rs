@@ -0,0 +1,387 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io,
+    sync::RwLock,
+};
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{AsyncFileInit, AsyncFileStore, FileInit, FileStore, Link, Metadata, Permissions};
+
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// Wraps a store with a bloom filter of the paths it contains.
+///
+/// Lookups for paths the filter has never seen fail with `NotFound` without touching the
+/// store, which makes wrapped stores cheap to probe as members of a `Vec` or composite.
+/// The filter is built from `list` and extended by writes through the wrapper. Removals
+/// can't be reflected in a bloom filter, and files added behind the wrapper's back are
+/// invisible until `rebuild`.
+pub struct Indexed<T> {
+    inner: T,
+    filter: RwLock<Bloom>,
+}
+
+impl<T> Indexed<T>
+where
+    T: AsyncFileStore,
+{
+    pub async fn build(inner: T) -> io::Result<Indexed<T>> {
+        let filter = build_async(&inner).await?;
+        Ok(Indexed {
+            inner,
+            filter: RwLock::new(filter),
+        })
+    }
+
+    /// Rebuild the filter from a fresh listing of the store.
+    pub async fn rebuild(&self) -> io::Result<()> {
+        let filter = build_async(&self.inner).await?;
+        *self.filter.write().unwrap() = filter;
+        Ok(())
+    }
+}
+
+impl<T> Indexed<T>
+where
+    T: FileStore,
+{
+    pub fn build_sync(inner: T) -> io::Result<Indexed<T>> {
+        let filter = build_sync(&inner)?;
+        Ok(Indexed {
+            inner,
+            filter: RwLock::new(filter),
+        })
+    }
+
+    /// Rebuild the filter from a fresh listing of the store.
+    pub fn rebuild_sync(&self) -> io::Result<()> {
+        let filter = build_sync(&self.inner)?;
+        *self.filter.write().unwrap() = filter;
+        Ok(())
+    }
+}
+
+impl<T> Indexed<T> {
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn check(&self, path: &RelativePath) -> io::Result<()> {
+        if self.filter.read().unwrap().contains(path) {
+            Ok(())
+        } else {
+            Err(io::ErrorKind::NotFound.into())
+        }
+    }
+
+    fn insert(&self, path: &RelativePath) {
+        self.filter.write().unwrap().insert(path);
+    }
+}
+
+async fn build_async<T: AsyncFileStore>(store: &T) -> io::Result<Bloom> {
+    let paths = store.list().await?.try_collect::<Vec<_>>().await?;
+    let mut filter = Bloom::new(paths.len());
+    for path in &paths {
+        filter.insert(path);
+    }
+    Ok(filter)
+}
+
+fn build_sync<T: FileStore>(store: &T) -> io::Result<Bloom> {
+    let paths = store.list().collect::<io::Result<Vec<_>>>()?;
+    let mut filter = Bloom::new(paths.len());
+    for path in &paths {
+        filter.insert(path);
+    }
+    Ok(filter)
+}
+
+impl<T> AsyncFileStore for Indexed<T>
+where
+    T: AsyncFileStore + Send + Sync,
+{
+    type File = T::File;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            self.check(path)?;
+            self.inner.metadata(path).await
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            self.check(path)?;
+            self.inner.open_file(path).await
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            self.check(path)?;
+            self.inner.rm_file(path).await
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            self.inner.write_file(path, init).await?;
+            self.insert(path);
+            Ok(())
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.inner.list()
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        self.inner.list_ref()
+    }
+
+    /// Leaves out the paths the filter rules out.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let paths = paths
+            .iter()
+            .filter(|path| self.check(path).is_ok())
+            .cloned()
+            .collect::<Vec<_>>();
+        async move { self.inner.prefetch(&paths).await }
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.prefetch_prefix(prefix)
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.set_permissions(path, permissions)
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.inner.symlink(target, link).await?;
+            self.insert(link);
+            Ok(())
+        }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.inner.hard_link(src, dst).await?;
+            self.insert(dst);
+            Ok(())
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        self.inner.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.close()
+    }
+}
+
+impl<T> FileStore for Indexed<T>
+where
+    T: FileStore,
+{
+    type File = T::File;
+
+    type List = T::List;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
+        self.check(path)?;
+        self.inner.metadata(path)
+    }
+
+    fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
+        self.check(path)?;
+        self.inner.open_file(path)
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
+        self.check(path)?;
+        self.inner.rm_file(path)
+    }
+
+    fn write_file(&self, path: &RelativePath, init: FileInit) -> Result<(), io::Error> {
+        self.inner.write_file(path, init)?;
+        self.insert(path);
+        Ok(())
+    }
+
+    fn list(&self) -> Self::List {
+        self.inner.list()
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        self.inner.write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        self.inner.set_permissions(path, permissions)
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        self.inner.symlink(target, link)?;
+        self.insert(link);
+        Ok(())
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        self.inner.hard_link(src, dst)?;
+        self.insert(dst);
+        Ok(())
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        self.inner.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.inner.close()
+    }
+}
+
+struct Bloom {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl Bloom {
+    fn new(items: usize) -> Bloom {
+        let items = items.max(64) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-items * FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil() as usize;
+        let hashes = ((bits as f64 / items) * ln2).round().max(1.0) as u32;
+
+        Bloom {
+            bits: vec![0; bits.div_ceil(64)],
+            hashes,
+        }
+    }
+
+    fn insert(&mut self, path: &RelativePath) {
+        for idx in self.indices(path) {
+            self.bits[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+
+    fn contains(&self, path: &RelativePath) -> bool {
+        self.indices(path)
+            .all(|idx| self.bits[idx / 64] & (1 << (idx % 64)) != 0)
+    }
+
+    fn indices(&self, path: &RelativePath) -> impl Iterator<Item = usize> {
+        let path = path.normalize();
+        let (a, b) = (hash(&path, 0), hash(&path, 1) | 1);
+        let len = (self.bits.len() * 64) as u64;
+        (0..self.hashes as u64).map(move |i| (a.wrapping_add(i.wrapping_mul(b)) % len) as usize)
+    }
+}
+
+fn hash(path: &RelativePath, seed: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    path.as_str().hash(&mut hasher);
+    hasher.finish()
+}
@@ -1,4 +1,7 @@
-use std::io::{self, Read};
+use std::{
+    any::Any,
+    io::{self, Read},
+};
 
 use bytes::Bytes;
 use futures::{future::BoxFuture, stream::BoxStream};
@@ -6,9 +9,9 @@ use relative_path::{RelativePath, RelativePathBuf};
 use url::Url;
 
 use crate::{
-    file::{AsyncFile, Metadata},
+    file::{AsyncFile, Link, Metadata, Permissions},
     store::AsyncFileStore,
-    AsyncFileInit, File, FileInit, FileStore,
+    AsyncFileInit, File, FileInit, FileStore, StoreStats,
 };
 
 pub type BoxFileStore = Box<dyn DynamicFileStore + Send + Sync>;
@@ -24,6 +27,35 @@ pub trait DynamicFileStore {
     fn write_file(&self, path: &RelativePath, init: FileInit) -> Result<(), io::Error>;
 
     fn list(&self) -> Box<dyn Iterator<Item = Result<RelativePathBuf, io::Error>> + Send>;
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> Result<(), io::Error>;
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> Result<(), io::Error>;
+
+    fn supports_write_range(&self) -> bool;
+
+    fn supports_attributes(&self) -> bool;
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> Result<(), io::Error>;
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> Result<(), io::Error>;
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> Result<(), io::Error>;
+
+    fn read_link(&self, path: &RelativePath) -> Result<Option<Link>, io::Error>;
+
+    fn supports_links(&self) -> bool;
+
+    fn flush(&self) -> Result<(), io::Error>;
+
+    fn close(&self) -> Result<(), io::Error>;
+
+    /// The store behind the box, to be downcast to recover backend specific features.
+    fn as_any(&self) -> &dyn Any;
 }
 
 pub trait DynamicFile {
@@ -32,6 +64,25 @@ pub trait DynamicFile {
     fn reader(&self) -> io::Result<Box<dyn Read + Send>>;
 
     fn url(&self) -> Option<Url>;
+
+    fn native_path(&self) -> Option<&std::path::Path>;
+
+    /// The file behind the box, to be downcast to recover backend specific features.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl dyn DynamicFileStore + Send + Sync {
+    /// The store behind the box if it is a `T`, e.g. to get the root of an `FsFileStore`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+}
+
+impl dyn DynamicFile + Send + Sync {
+    /// The file behind the box if it is a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
 }
 
 pub fn filestore_box<T>(filestore: T) -> BoxFileStore
@@ -48,7 +99,7 @@ pub struct DynamicFileStoreBox<T>(T);
 
 impl<T> DynamicFileStore for DynamicFileStoreBox<T>
 where
-    T: FileStore + Sync,
+    T: FileStore + Sync + 'static,
     T::List: Send + 'static,
     T::File: Send + Sync + 'static,
     <T::File as File>::Body: Send + 'static,
@@ -74,6 +125,54 @@ where
     fn list(&self) -> Box<dyn Iterator<Item = Result<RelativePathBuf, io::Error>> + Send> {
         Box::new(self.0.list())
     }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        self.0.set_len(path, len)
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        self.0.write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.0.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.0.supports_attributes()
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        self.0.set_permissions(path, permissions)
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        self.0.symlink(target, link)
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        self.0.hard_link(src, dst)
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        self.0.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.0.supports_links()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.0.flush()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.0.close()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        &self.0
+    }
 }
 
 pub struct DynamicFileBox<T>(T);
@@ -96,12 +195,20 @@ where
     fn url(&self) -> Option<Url> {
         self.0.url()
     }
+
+    fn native_path(&self) -> Option<&std::path::Path> {
+        self.0.native_path()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        &self.0
+    }
 }
 
 impl FileStore for BoxFileStore {
     type File = BoxFile;
 
-    type List = Box<dyn Iterator<Item = io::Result<RelativePathBuf>>>;
+    type List = Box<dyn Iterator<Item = io::Result<RelativePathBuf>> + Send>;
 
     fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
         (**self).metadata(path)
@@ -122,6 +229,50 @@ impl FileStore for BoxFileStore {
     fn list(&self) -> Self::List {
         (**self).list()
     }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        (**self).set_len(path, len)
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        (**self).write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        (**self).supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        (**self).supports_attributes()
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        (**self).set_permissions(path, permissions)
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        (**self).symlink(target, link)
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        (**self).hard_link(src, dst)
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        (**self).read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        (**self).supports_links()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        (**self).flush()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        (**self).close()
+    }
 }
 
 impl File for BoxFile {
@@ -134,6 +285,14 @@ impl File for BoxFile {
     fn reader(&self) -> Result<Self::Body, io::Error> {
         (**self).reader()
     }
+
+    fn url(&self) -> Option<Url> {
+        (**self).url()
+    }
+
+    fn native_path(&self) -> Option<&std::path::Path> {
+        (**self).native_path()
+    }
 }
 
 pub trait DynamicAsyncFileStore {
@@ -155,6 +314,69 @@ pub trait DynamicAsyncFileStore {
     fn list<'a>(
         &'a self,
     ) -> BoxFuture<'a, Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>>;
+
+    fn list_ref(&self) -> BoxStream<'_, Result<RelativePathBuf, io::Error>>;
+
+    fn stats(&self) -> BoxFuture<'_, Result<StoreStats, io::Error>>;
+
+    fn health(&self) -> BoxFuture<'_, Result<(), io::Error>>;
+
+    fn prefetch<'a>(&'a self, paths: &'a [RelativePathBuf])
+        -> BoxFuture<'a, Result<(), io::Error>>;
+
+    fn prefetch_prefix<'a>(
+        &'a self,
+        prefix: &'a RelativePath,
+    ) -> BoxFuture<'a, Result<(), io::Error>>;
+
+    fn set_len<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        len: u64,
+    ) -> BoxFuture<'a, Result<(), io::Error>>;
+
+    fn write_range<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> BoxFuture<'a, Result<(), io::Error>>;
+
+    fn supports_write_range(&self) -> bool;
+
+    fn supports_attributes(&self) -> bool;
+
+    fn set_permissions<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        permissions: Permissions,
+    ) -> BoxFuture<'a, Result<(), io::Error>>;
+
+    fn symlink<'a>(
+        &'a self,
+        target: &'a RelativePath,
+        link: &'a RelativePath,
+    ) -> BoxFuture<'a, Result<(), io::Error>>;
+
+    fn hard_link<'a>(
+        &'a self,
+        src: &'a RelativePath,
+        dst: &'a RelativePath,
+    ) -> BoxFuture<'a, Result<(), io::Error>>;
+
+    fn read_link<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> BoxFuture<'a, Result<Option<Link>, io::Error>>;
+
+    fn supports_links(&self) -> bool;
+
+    fn flush(&self) -> BoxFuture<'_, Result<(), io::Error>>;
+
+    fn close(&self) -> BoxFuture<'_, Result<(), io::Error>>;
+
+    /// The store behind the box, to be downcast to recover backend specific features.
+    fn as_any(&self) -> &dyn Any;
 }
 
 pub trait DynamicAsyncFile {
@@ -168,6 +390,25 @@ pub trait DynamicAsyncFile {
     ) -> BoxFuture<'a, Result<BoxStream<'static, Result<Bytes, io::Error>>, io::Error>>;
 
     fn url(&self) -> Option<Url>;
+
+    fn native_path(&self) -> Option<&std::path::Path>;
+
+    /// The file behind the box, to be downcast to recover backend specific features.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl dyn DynamicAsyncFileStore + Send + Sync {
+    /// The store behind the box if it is a `T`, e.g. to get the root of an `FsFileStore`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+}
+
+impl dyn DynamicAsyncFile + Send + Sync + '_ {
+    /// The file behind the box if it is a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
 }
 
 pub fn async_filestore_box<T>(filestore: T) -> BoxAsyncFileStore
@@ -179,9 +420,17 @@ where
     Box::new(DynamicFileStoreBox(filestore))
 }
 
+pub fn async_file_box<T>(file: T) -> BoxAsyncFile<'static>
+where
+    T: AsyncFile + Send + Sync + 'static,
+    T::Body: Send + 'static,
+{
+    Box::new(DynamicFileBox(file))
+}
+
 impl<T> DynamicAsyncFileStore for DynamicFileStoreBox<T>
 where
-    T: AsyncFileStore + Sync,
+    T: AsyncFileStore + Sync + 'static,
     T::File: Send + Sync + 'static,
     <T::File as AsyncFile>::Body: Send + 'static,
 {
@@ -198,7 +447,7 @@ where
     ) -> BoxFuture<'a, Result<BoxAsyncFile<'static>, io::Error>> {
         Box::pin(async move {
             let file = self.0.open_file(path).await?;
-            Ok(Box::new(DynamicFileBox(file)) as BoxAsyncFile)
+            Ok(async_file_box(file))
         })
     }
 
@@ -220,11 +469,109 @@ where
     {
         Box::pin(self.0.list())
     }
+
+    fn list_ref(&self) -> BoxStream<'_, Result<RelativePathBuf, io::Error>> {
+        self.0.list_ref()
+    }
+
+    fn stats(&self) -> BoxFuture<'_, Result<StoreStats, io::Error>> {
+        Box::pin(self.0.stats())
+    }
+
+    fn health(&self) -> BoxFuture<'_, Result<(), io::Error>> {
+        Box::pin(self.0.health())
+    }
+
+    fn prefetch<'a>(
+        &'a self,
+        paths: &'a [RelativePathBuf],
+    ) -> BoxFuture<'a, Result<(), io::Error>> {
+        Box::pin(self.0.prefetch(paths))
+    }
+
+    fn prefetch_prefix<'a>(
+        &'a self,
+        prefix: &'a RelativePath,
+    ) -> BoxFuture<'a, Result<(), io::Error>> {
+        Box::pin(self.0.prefetch_prefix(prefix))
+    }
+
+    fn set_len<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        len: u64,
+    ) -> BoxFuture<'a, Result<(), io::Error>> {
+        Box::pin(self.0.set_len(path, len))
+    }
+
+    fn write_range<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> BoxFuture<'a, Result<(), io::Error>> {
+        Box::pin(self.0.write_range(path, offset, data))
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.0.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.0.supports_attributes()
+    }
+
+    fn set_permissions<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        permissions: Permissions,
+    ) -> BoxFuture<'a, Result<(), io::Error>> {
+        Box::pin(self.0.set_permissions(path, permissions))
+    }
+
+    fn symlink<'a>(
+        &'a self,
+        target: &'a RelativePath,
+        link: &'a RelativePath,
+    ) -> BoxFuture<'a, Result<(), io::Error>> {
+        Box::pin(self.0.symlink(target, link))
+    }
+
+    fn hard_link<'a>(
+        &'a self,
+        src: &'a RelativePath,
+        dst: &'a RelativePath,
+    ) -> BoxFuture<'a, Result<(), io::Error>> {
+        Box::pin(self.0.hard_link(src, dst))
+    }
+
+    fn read_link<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> BoxFuture<'a, Result<Option<Link>, io::Error>> {
+        Box::pin(self.0.read_link(path))
+    }
+
+    fn supports_links(&self) -> bool {
+        self.0.supports_links()
+    }
+
+    fn flush(&self) -> BoxFuture<'_, Result<(), io::Error>> {
+        Box::pin(self.0.flush())
+    }
+
+    fn close(&self) -> BoxFuture<'_, Result<(), io::Error>> {
+        Box::pin(self.0.close())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        &self.0
+    }
 }
 
 impl<T> DynamicAsyncFile for DynamicFileBox<T>
 where
-    T: AsyncFile + Send + Sync,
+    T: AsyncFile + Send + Sync + 'static,
     T::Body: Send + 'static,
 {
     fn read_range<'a>(
@@ -246,6 +593,14 @@ where
     fn url(&self) -> Option<Url> {
         self.0.url()
     }
+
+    fn native_path(&self) -> Option<&std::path::Path> {
+        self.0.native_path()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        &self.0
+    }
 }
 
 pub type BoxAsyncFileStore = Box<dyn DynamicAsyncFileStore + Send + Sync>;
@@ -291,6 +646,125 @@ impl AsyncFileStore for BoxAsyncFileStore {
     > + Send {
         async move { (**self).list().await }
     }
+
+    fn list_ref(&self) -> BoxStream<'_, Result<RelativePathBuf, io::Error>>
+    where
+        Self: Sync,
+    {
+        (**self).list_ref()
+    }
+
+    fn stats(&self) -> impl futures::prelude::Future<Output = Result<StoreStats, io::Error>> + Send
+    where
+        Self: Sync,
+    {
+        (**self).stats()
+    }
+
+    fn health(&self) -> impl futures::prelude::Future<Output = Result<(), io::Error>> + Send
+    where
+        Self: Sync,
+    {
+        (**self).health()
+    }
+
+    fn prefetch(
+        &self,
+        paths: &[RelativePathBuf],
+    ) -> impl futures::prelude::Future<Output = Result<(), io::Error>> + Send
+    where
+        Self: Sync,
+    {
+        async move { (**self).prefetch(paths).await }
+    }
+
+    fn prefetch_prefix(
+        &self,
+        prefix: &RelativePath,
+    ) -> impl futures::prelude::Future<Output = Result<(), io::Error>> + Send
+    where
+        Self: Sync,
+    {
+        async move { (**self).prefetch_prefix(prefix).await }
+    }
+
+    fn set_len(
+        &self,
+        path: &RelativePath,
+        len: u64,
+    ) -> impl futures::prelude::Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        async move { (**self).set_len(path, len).await }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl futures::prelude::Future<Output = io::Result<()>> + Send {
+        async move { (**self).write_range(path, offset, data).await }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        (**self).supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        (**self).supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl futures::prelude::Future<Output = io::Result<()>> + Send {
+        async move { (**self).set_permissions(path, permissions).await }
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl futures::prelude::Future<Output = io::Result<()>> + Send {
+        async move { (**self).symlink(target, link).await }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl futures::prelude::Future<Output = io::Result<()>> + Send {
+        async move { (**self).hard_link(src, dst).await }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl futures::prelude::Future<Output = io::Result<Option<Link>>> + Send {
+        async move { (**self).read_link(path).await }
+    }
+
+    fn supports_links(&self) -> bool {
+        (**self).supports_links()
+    }
+
+    fn flush(&self) -> impl futures::prelude::Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (**self).flush()
+    }
+
+    fn close(&self) -> impl futures::prelude::Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (**self).close()
+    }
 }
 
 impl<'a> AsyncFile for BoxAsyncFile<'a> {
@@ -312,4 +786,8 @@ impl<'a> AsyncFile for BoxAsyncFile<'a> {
     fn url(&self) -> Option<Url> {
         (**self).url()
     }
+
+    fn native_path(&self) -> Option<&std::path::Path> {
+        (**self).native_path()
+    }
 }
@@ -0,0 +1,422 @@
+use std::{
+    future::Future,
+    io::{self, Read},
+    pin::Pin,
+    sync::Arc,
+};
+
+use async_stream::try_stream;
+use bytes::{Buf, Bytes};
+use futures::{stream::BoxStream, Stream, StreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+use tokio::runtime::Handle;
+use url::Url;
+
+use crate::{
+    util::Buffer, AsyncFile, AsyncFileBody, AsyncFileInit, AsyncFileStore, File, FileBody,
+    FileInit, FileStore, Link, Metadata, Permissions,
+};
+
+/// Exposes an `AsyncFileStore` as a blocking `FileStore` by running its futures on a runtime.
+///
+/// Like `Handle::block_on` this panics if used from within an async context,
+/// call it from a blocking thread instead.
+pub struct SyncBridge<T> {
+    store: T,
+    handle: Handle,
+}
+
+impl<T> SyncBridge<T> {
+    pub fn new(store: T, handle: Handle) -> SyncBridge<T> {
+        SyncBridge { store, handle }
+    }
+
+    /// Bridge using the runtime of the current context.
+    pub fn current(store: T) -> SyncBridge<T> {
+        SyncBridge::new(store, Handle::current())
+    }
+
+    pub fn into_inner(self) -> T {
+        self.store
+    }
+}
+
+impl<T> FileStore for SyncBridge<T>
+where
+    T: AsyncFileStore + Sync,
+{
+    type File = SyncBridgeFile<T::File>;
+
+    type List = BlockingIter<BoxStream<'static, io::Result<RelativePathBuf>>>;
+
+    fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
+        self.handle.block_on(self.store.metadata(path))
+    }
+
+    fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
+        let file = self.handle.block_on(self.store.open_file(path))?;
+        Ok(SyncBridgeFile {
+            file,
+            handle: self.handle.clone(),
+        })
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
+        self.handle.block_on(self.store.rm_file(path))
+    }
+
+    fn write_file(&self, path: &RelativePath, init: FileInit) -> Result<(), io::Error> {
+        self.handle
+            .block_on(self.store.write_file(path, AsyncFileInit::from(init)))
+    }
+
+    fn list(&self) -> Self::List {
+        let stream = match self.handle.block_on(self.store.list()) {
+            Ok(stream) => stream,
+            Err(err) => futures::stream::once(async move { Err(err) }).boxed(),
+        };
+
+        BlockingIter {
+            stream,
+            handle: self.handle.clone(),
+        }
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        self.handle.block_on(self.store.flush())
+    }
+
+    fn close(&self) -> Result<(), io::Error> {
+        self.handle.block_on(self.store.close())
+    }
+}
+
+pub struct SyncBridgeFile<T> {
+    file: T,
+    handle: Handle,
+}
+
+impl<T> File for SyncBridgeFile<T>
+where
+    T: AsyncFile,
+{
+    type Body = BlockingRead<T::Body>;
+
+    fn read_range(&self, range: std::ops::Range<u64>) -> Result<Bytes, io::Error> {
+        self.handle.block_on(self.file.read_range(range))
+    }
+
+    fn reader(&self) -> Result<Self::Body, io::Error> {
+        let stream = self.handle.block_on(self.file.reader())?;
+        Ok(BlockingRead {
+            stream: Box::pin(stream),
+            current: Bytes::new(),
+            handle: self.handle.clone(),
+        })
+    }
+
+    fn url(&self) -> Option<Url> {
+        self.file.url()
+    }
+
+    fn native_path(&self) -> Option<&std::path::Path> {
+        self.file.native_path()
+    }
+}
+
+/// A blocking `Iterator` over a stream.
+pub struct BlockingIter<S> {
+    stream: S,
+    handle: Handle,
+}
+
+impl<S> Iterator for BlockingIter<S>
+where
+    S: Stream + Unpin,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.handle.block_on(self.stream.next())
+    }
+}
+
+/// A blocking `Read` over a stream of byte chunks.
+pub struct BlockingRead<S> {
+    stream: Pin<Box<S>>,
+    current: Bytes,
+    handle: Handle,
+}
+
+impl<S> Read for BlockingRead<S>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.handle.block_on(self.stream.next()) {
+                Some(next) => self.current = next?,
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.current.len());
+        buf[..len].copy_from_slice(&self.current[..len]);
+        self.current.advance(len);
+
+        Ok(len)
+    }
+}
+
+/// Exposes a blocking `FileStore` as an `AsyncFileStore` by running it on the blocking pool.
+pub struct AsyncBridge<T> {
+    store: Arc<T>,
+}
+
+impl<T> Clone for AsyncBridge<T> {
+    fn clone(&self) -> Self {
+        AsyncBridge {
+            store: self.store.clone(),
+        }
+    }
+}
+
+impl<T> AsyncBridge<T> {
+    pub fn new(store: T) -> AsyncBridge<T> {
+        AsyncBridge {
+            store: Arc::new(store),
+        }
+    }
+}
+
+impl<T> AsyncFileStore for AsyncBridge<T>
+where
+    T: FileStore + Send + Sync + 'static,
+    T::File: Send + Sync + 'static,
+    <T::File as File>::Body: Send + 'static,
+{
+    type File = AsyncBridgeFile<T::File>;
+
+    fn metadata(&self, path: &RelativePath) -> impl Future<Output = io::Result<Metadata>> + Send {
+        let store = self.store.clone();
+        let path = path.to_relative_path_buf();
+        blocking(move || store.metadata(&path))
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Self::File>> + Send {
+        let store = self.store.clone();
+        let path = path.to_relative_path_buf();
+        async move {
+            let file = blocking(move || store.open_file(&path)).await?;
+            Ok(AsyncBridgeFile {
+                file: Arc::new(file),
+            })
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = io::Result<()>> + Send {
+        let store = self.store.clone();
+        let path = path.to_relative_path_buf();
+        blocking(move || store.rm_file(&path))
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let store = self.store.clone();
+        let path = path.to_relative_path_buf();
+        async move {
+            let body = match init.body {
+                AsyncFileBody::Bytes(bytes) => FileBody::Bytes(bytes),
+                #[cfg(feature = "fs")]
+                AsyncFileBody::Path(path) => FileBody::Path(path),
+                body => FileBody::Read(Box::new(BlockingRead {
+                    stream: Box::pin(body.into_stream().await?),
+                    current: Bytes::new(),
+                    handle: Handle::current(),
+                })),
+            };
+            let init = FileInit {
+                body,
+                len: init.len,
+                mime: init.mime,
+                attributes: init.attributes,
+            };
+
+            blocking(move || store.write_file(&path, init)).await
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<Output = io::Result<BoxStream<'static, io::Result<RelativePathBuf>>>> + Send
+    {
+        let store = self.store.clone();
+        async move {
+            let list = blocking(move || Ok(store.list().collect::<Vec<_>>())).await?;
+            Ok(futures::stream::iter(list).boxed())
+        }
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        let store = self.store.clone();
+        let path = path.to_relative_path_buf();
+        blocking(move || store.set_len(&path, len))
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let store = self.store.clone();
+        let path = path.to_relative_path_buf();
+        blocking(move || store.write_range(&path, offset, data))
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.store.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.store.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let store = self.store.clone();
+        let path = path.to_relative_path_buf();
+        blocking(move || store.set_permissions(&path, permissions))
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let store = self.store.clone();
+        let target = target.to_relative_path_buf();
+        let link = link.to_relative_path_buf();
+        blocking(move || store.symlink(&target, &link))
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let store = self.store.clone();
+        let src = src.to_relative_path_buf();
+        let dst = dst.to_relative_path_buf();
+        blocking(move || store.hard_link(&src, &dst))
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        let store = self.store.clone();
+        let path = path.to_relative_path_buf();
+        blocking(move || store.read_link(&path))
+    }
+
+    fn supports_links(&self) -> bool {
+        self.store.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let store = self.store.clone();
+        blocking(move || store.flush())
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let store = self.store.clone();
+        blocking(move || store.close())
+    }
+}
+
+pub struct AsyncBridgeFile<T> {
+    file: Arc<T>,
+}
+
+impl<T> AsyncFile for AsyncBridgeFile<T>
+where
+    T: File + Send + Sync + 'static,
+    T::Body: Send + 'static,
+{
+    type Body = BoxStream<'static, io::Result<Bytes>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = io::Result<Bytes>> + Send {
+        let file = self.file.clone();
+        blocking(move || file.read_range(range))
+    }
+
+    fn reader(&self) -> impl Future<Output = io::Result<Self::Body>> + Send {
+        let file = self.file.clone();
+        async move {
+            let mut reader = blocking(move || file.reader()).await?;
+            let mut buffer = Buffer::acquire();
+
+            let stream = try_stream! {
+                loop {
+                    let (returned, returned_buffer, chunk) = blocking(move || {
+                        let chunk = buffer.read_chunk(&mut reader)?;
+                        Ok((reader, buffer, chunk))
+                    })
+                    .await?;
+                    reader = returned;
+                    buffer = returned_buffer;
+
+                    if chunk.is_empty() {
+                        break;
+                    }
+
+                    yield chunk;
+                }
+            };
+
+            Ok(stream.boxed())
+        }
+    }
+
+    fn url(&self) -> Option<Url> {
+        self.file.url()
+    }
+
+    fn native_path(&self) -> Option<&std::path::Path> {
+        self.file.native_path()
+    }
+}
+
+/// Run a blocking closure on the blocking pool, surfacing a panic or cancellation as an error.
+pub(crate) async fn blocking<F, R>(func: F) -> io::Result<R>
+where
+    F: FnOnce() -> io::Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::task::spawn_blocking(func)
+        .await
+        .map_err(io::Error::other)?
+}
@@ -0,0 +1,440 @@
+use std::{
+    io,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{
+    util::lru::Lru, AsyncFileInit, AsyncFileStore, FileInit, FileStore, Link, Metadata,
+    Permissions, StoreStats,
+};
+
+const CAPACITY: usize = 10_000;
+
+/// Caches `metadata` and `exists` lookups of the wrapped store for `ttl`.
+///
+/// Misses are cached too, so repeated probes for paths a store does not contain (as done by
+/// the composite router) don't reach the backend. Writes and removals through the cache
+/// invalidate the path; changes made to the store directly are only seen once the entry expires
+/// or after `invalidate`. At most 10 000 entries are kept unless set with
+/// [`MetadataCache::capacity`], dropping the least recently used first.
+pub struct MetadataCache<T> {
+    inner: T,
+    ttl: Duration,
+    state: Mutex<State>,
+}
+
+struct State {
+    entries: Lru<RelativePathBuf, Entry>,
+    /// Bumped on every invalidation, so lookups started before one don't cache what they
+    /// found.
+    generation: u64,
+}
+
+struct Entry {
+    expires: Instant,
+    meta: Option<Metadata>,
+}
+
+impl<T> MetadataCache<T> {
+    pub fn new(inner: T, ttl: Duration) -> MetadataCache<T> {
+        MetadataCache {
+            inner,
+            ttl,
+            state: Mutex::new(State {
+                entries: Lru::new(CAPACITY),
+                generation: 0,
+            }),
+        }
+    }
+
+    /// Keep at most `capacity` entries.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.state.get_mut().unwrap().entries = Lru::new(capacity);
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Drop the cached entry for `path`.
+    pub fn invalidate(&self, path: &RelativePath) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.entries.remove(path);
+    }
+
+    /// Drop all cached entries.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.entries.clear();
+    }
+
+    fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+
+    fn get(&self, path: &RelativePath) -> Option<io::Result<Metadata>> {
+        let mut state = self.state.lock().unwrap();
+        let entry = state.entries.get(path)?;
+
+        if entry.expires <= Instant::now() {
+            state.entries.remove(path);
+            return None;
+        }
+
+        Some(match &entry.meta {
+            Some(meta) => Ok(meta.clone()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        })
+    }
+
+    /// Cache a lookup, unless the cache was invalidated since `generation`.
+    fn insert(&self, path: &RelativePath, ret: &io::Result<Metadata>, generation: u64) {
+        let meta = match ret {
+            Ok(meta) => Some(meta.clone()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            // Transient failures are not cached
+            Err(_) => return,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation {
+            return;
+        }
+
+        state.entries.insert(
+            path.to_relative_path_buf(),
+            Entry {
+                expires: Instant::now() + self.ttl,
+                meta,
+            },
+        );
+    }
+}
+
+impl<T> AsyncFileStore for MetadataCache<T>
+where
+    T: AsyncFileStore + Send + Sync,
+{
+    type File = T::File;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            let generation = self.generation();
+            if let Some(ret) = self.get(path) {
+                return ret;
+            }
+
+            let ret = self.inner.metadata(path).await;
+            self.insert(path, &ret, generation);
+            ret
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            if let Some(Err(err)) = self.get(path) {
+                return Err(err);
+            }
+
+            self.inner.open_file(path).await
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let ret = self.inner.rm_file(path).await;
+            self.invalidate(path);
+            ret
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let ret = self.inner.write_file(path, init).await;
+            self.invalidate(path);
+            ret
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.inner.list()
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        self.inner.list_ref()
+    }
+
+    fn exists(&self, path: &RelativePath) -> impl Future<Output = bool> + Send
+    where
+        Self: Sync,
+    {
+        async move { self.metadata(path).await.is_ok() }
+    }
+
+    fn stats(&self) -> impl Future<Output = io::Result<StoreStats>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.stats()
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.health()
+    }
+
+    /// Looks up and caches the metadata of every path, then passes the hint on.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            crate::prefetch::each(paths, |path| async move {
+                self.metadata(&path).await.map(|_| ())
+            })
+            .await?;
+            self.inner.prefetch(paths).await
+        }
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let paths = crate::prefetch::below(&self.inner, prefix).await?;
+            self.prefetch(&paths).await
+        }
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        async move {
+            let ret = self.inner.set_len(path, len).await;
+            self.invalidate(path);
+            ret
+        }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let ret = self.inner.write_range(path, offset, data).await;
+            self.invalidate(path);
+            ret
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let ret = self.inner.set_permissions(path, permissions).await;
+            self.invalidate(path);
+            ret
+        }
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let ret = self.inner.symlink(target, link).await;
+            self.invalidate(link);
+            ret
+        }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let ret = self.inner.hard_link(src, dst).await;
+            self.invalidate(dst);
+            ret
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        self.inner.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.close()
+    }
+}
+
+impl<T> FileStore for MetadataCache<T>
+where
+    T: FileStore,
+{
+    type File = T::File;
+
+    type List = T::List;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
+        let generation = self.generation();
+        if let Some(ret) = self.get(path) {
+            return ret;
+        }
+
+        let ret = self.inner.metadata(path);
+        self.insert(path, &ret, generation);
+        ret
+    }
+
+    fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
+        if let Some(Err(err)) = self.get(path) {
+            return Err(err);
+        }
+
+        self.inner.open_file(path)
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
+        let ret = self.inner.rm_file(path);
+        self.invalidate(path);
+        ret
+    }
+
+    fn write_file(&self, path: &RelativePath, init: FileInit) -> Result<(), io::Error> {
+        let ret = self.inner.write_file(path, init);
+        self.invalidate(path);
+        ret
+    }
+
+    fn list(&self) -> Self::List {
+        self.inner.list()
+    }
+
+    fn exists(&self, path: &RelativePath) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        let ret = self.inner.set_len(path, len);
+        self.invalidate(path);
+        ret
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        let ret = self.inner.write_range(path, offset, data);
+        self.invalidate(path);
+        ret
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        let ret = self.inner.set_permissions(path, permissions);
+        self.invalidate(path);
+        ret
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        let ret = self.inner.symlink(target, link);
+        self.invalidate(link);
+        ret
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        let ret = self.inner.hard_link(src, dst);
+        self.invalidate(dst);
+        ret
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        self.inner.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.inner.close()
+    }
+}
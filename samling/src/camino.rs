@@ -0,0 +1,83 @@
+use std::io;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{AsyncFileStore, AsyncPath, FileStore, Metadata, Path};
+
+/// Convert a relative utf8 path into a store path. Fails for absolute paths.
+pub fn to_relative(path: &Utf8Path) -> io::Result<RelativePathBuf> {
+    RelativePathBuf::from_path(path.as_std_path())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+pub fn to_utf8(path: &RelativePath) -> &Utf8Path {
+    Utf8Path::new(path.as_str())
+}
+
+/// List a store as utf8 paths.
+pub async fn list<S>(store: &S) -> io::Result<BoxStream<'static, io::Result<Utf8PathBuf>>>
+where
+    S: AsyncFileStore,
+{
+    let stream = store.list().await?;
+    Ok(stream
+        .map_ok(|path| Utf8PathBuf::from(path.into_string()))
+        .boxed())
+}
+
+/// List a sync store as utf8 paths.
+pub fn list_sync<S>(store: &S) -> impl Iterator<Item = io::Result<Utf8PathBuf>>
+where
+    S: FileStore,
+{
+    store
+        .list()
+        .map(|path| path.map(|path| Utf8PathBuf::from(path.into_string())))
+}
+
+impl<T> Path<T> {
+    pub fn as_utf8_path(&self) -> &Utf8Path {
+        to_utf8(self)
+    }
+
+    pub fn from_utf8(store: T, path: impl AsRef<Utf8Path>) -> io::Result<Path<T>> {
+        Ok(Path::new(store, to_relative(path.as_ref())?))
+    }
+}
+
+impl<T> AsRef<Utf8Path> for Path<T> {
+    fn as_ref(&self) -> &Utf8Path {
+        self.as_utf8_path()
+    }
+}
+
+impl<T> AsyncPath<T> {
+    pub fn as_utf8_path(&self) -> &Utf8Path {
+        to_utf8(self)
+    }
+
+    pub fn from_utf8(store: T, path: impl AsRef<Utf8Path>) -> io::Result<AsyncPath<T>> {
+        Ok(AsyncPath::new(store, to_relative(path.as_ref())?))
+    }
+}
+
+impl<T> AsRef<Utf8Path> for AsyncPath<T> {
+    fn as_ref(&self) -> &Utf8Path {
+        self.as_utf8_path()
+    }
+}
+
+impl Metadata {
+    pub fn utf8_path(&self) -> &Utf8Path {
+        to_utf8(&self.path)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<Utf8PathBuf> for crate::AsyncFileInit {
+    fn from(value: Utf8PathBuf) -> Self {
+        crate::AsyncFileInit::from(value.into_std_path_buf())
+    }
+}
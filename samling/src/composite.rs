@@ -2,34 +2,229 @@ use relative_path::{RelativePath, RelativePathBuf};
 use std::{
     collections::HashMap,
     io::{self},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
 };
 
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future, StreamExt};
+
+use crate::layered::write_first;
 use crate::{
     boxed::{async_filestore_box, filestore_box, BoxFile, BoxFileStore},
-    AsyncFile, AsyncFileStore, BoxAsyncFile, BoxAsyncFileStore, File, FileStore,
+    file::link_target,
+    AsyncFile, AsyncFileInit, AsyncFileStore, BoxAsyncFile, BoxAsyncFileStore, File, FileStore,
+    Link, Metadata, Permissions, ReadOnly,
 };
 
-#[derive(Default)]
+fn mount_path(mount: &str) -> RelativePathBuf {
+    mount.strip_prefix('/').unwrap_or(mount).into()
+}
+
+/// Cloning is cheap; clones share the mounted stores.
+#[derive(Default, Clone)]
 pub struct SyncComposite {
-    routes: HashMap<RelativePathBuf, Vec<BoxFileStore>>,
+    routes: HashMap<RelativePathBuf, Vec<Arc<SyncMount>>>,
+    fallback: Option<Arc<SyncMount>>,
 }
 
 impl SyncComposite {
-    pub fn register<T>(&mut self, mut mount: &str, filestore: T)
+    pub fn register<T>(&mut self, mount: &str, filestore: T)
     where
         T: FileStore + Send + Sync + 'static,
         T::File: Send + Sync + 'static,
         <T::File as File>::Body: Send,
         T::List: Send,
     {
-        if mount.starts_with("/") {
-            mount = &mount[1..];
-        }
-
         self.routes
-            .entry(mount.into())
+            .entry(mount_path(mount))
             .or_default()
-            .push(filestore_box(filestore));
+            .push(Arc::new(SyncMount::new(filestore)));
+    }
+
+    /// Remove every store mounted at `mount`. Returns whether there were any.
+    pub fn unregister(&mut self, mount: &str) -> bool {
+        self.routes.remove(&mount_path(mount)).is_some()
+    }
+
+    /// Mount `filestore` at `mount` in place of the stores mounted there.
+    pub fn replace<T>(&mut self, mount: &str, filestore: T)
+    where
+        T: FileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as File>::Body: Send,
+        T::List: Send,
+    {
+        self.routes
+            .insert(mount_path(mount), vec![Arc::new(SyncMount::new(filestore))]);
+    }
+
+    /// A store to read paths none of the mounts contain from, e.g. default assets.
+    pub fn set_fallback<T>(&mut self, filestore: T)
+    where
+        T: FileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as File>::Body: Send,
+        T::List: Send,
+    {
+        self.fallback = Some(Arc::new(SyncMount::new(filestore)));
+    }
+
+    /// Remove the fallback store. Returns whether there was one.
+    pub fn clear_fallback(&mut self) -> bool {
+        self.fallback.take().is_some()
+    }
+
+    /// The mounted stores, by mount and in the order they are tried, with the fallback last.
+    pub fn mounts(&self) -> Vec<MountInfo> {
+        let routes = self.routes.iter().flat_map(|(mount, stores)| {
+            stores
+                .iter()
+                .enumerate()
+                .map(|(registration, store)| MountInfo {
+                    mount: Some(mount.clone()),
+                    registration,
+                    priority: 0,
+                    store: store.name,
+                    healthy: true,
+                })
+        });
+        let fallback = self.fallback.iter().map(|store| MountInfo {
+            mount: None,
+            registration: 0,
+            priority: 0,
+            store: store.name,
+            healthy: true,
+        });
+
+        sorted(routes.collect(), fallback)
+    }
+
+    /// Find the store `path` resolves to. See [`AsyncComposite::resolve`].
+    pub fn resolve(&self, path: &RelativePath) -> io::Result<Resolved> {
+        let mut parent = path.parent();
+
+        while let Some(mount) = parent {
+            if let Some(stores) = self.routes.get(mount) {
+                let inner = path.strip_prefix(mount.as_str()).expect("prefix");
+                if let Some(registration) = stores.iter().position(|store| store.exists(inner)) {
+                    return Ok(Resolved {
+                        mount: Some(mount.to_relative_path_buf()),
+                        registration,
+                        priority: 0,
+                        path: inner.to_relative_path_buf(),
+                    });
+                }
+            }
+            parent = mount.parent();
+        }
+
+        match &self.fallback {
+            Some(fallback) if fallback.exists(path) => Ok(Resolved {
+                mount: None,
+                registration: 0,
+                priority: 0,
+                path: path.to_relative_path_buf(),
+            }),
+            _ => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+/// A store mounted in a [`SyncComposite`], with the name of its type.
+struct SyncMount {
+    store: BoxFileStore,
+    name: &'static str,
+}
+
+impl SyncMount {
+    fn new<T>(filestore: T) -> SyncMount
+    where
+        T: FileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as File>::Body: Send,
+        T::List: Send,
+    {
+        SyncMount {
+            store: filestore_box(filestore),
+            name: std::any::type_name::<T>(),
+        }
+    }
+}
+
+impl FileStore for SyncMount {
+    type File = BoxFile;
+
+    type List = <BoxFileStore as FileStore>::List;
+
+    fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
+        self.store.metadata(path)
+    }
+
+    fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
+        self.store.open_file(path)
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
+        self.store.rm_file(path)
+    }
+
+    fn write_file(&self, path: &RelativePath, init: crate::FileInit) -> Result<(), io::Error> {
+        self.store.write_file(path, init)
+    }
+
+    fn list(&self) -> Self::List {
+        self.store.list()
+    }
+
+    fn exists(&self, path: &RelativePath) -> bool {
+        self.store.exists(path)
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        self.store.set_len(path, len)
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        self.store.write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.store.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.store.supports_attributes()
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        self.store.set_permissions(path, permissions)
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        self.store.symlink(target, link)
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        self.store.hard_link(src, dst)
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        self.store.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.store.supports_links()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.store.flush()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.store.close()
     }
 }
 
@@ -39,11 +234,23 @@ impl FileStore for SyncComposite {
     type List = Box<dyn Iterator<Item = io::Result<RelativePathBuf>> + Send>;
 
     fn metadata(&self, path: &RelativePath) -> Result<crate::Metadata, io::Error> {
-        self.routes.metadata(path)
+        match self.routes.metadata(path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => match &self.fallback {
+                Some(fallback) => fallback.metadata(path),
+                None => Err(err),
+            },
+            ret => ret,
+        }
     }
 
     fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
-        self.routes.open_file(path)
+        match self.routes.open_file(path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => match &self.fallback {
+                Some(fallback) => fallback.open_file(path),
+                None => Err(err),
+            },
+            ret => ret,
+        }
     }
 
     fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
@@ -55,77 +262,1437 @@ impl FileStore for SyncComposite {
     }
 
     fn list(&self) -> Self::List {
-        self.routes.list()
+        let routes = self.routes.list();
+        match &self.fallback {
+            Some(fallback) => Box::new(routes.chain(fallback.list())),
+            None => routes,
+        }
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        match self.routes.set_len(path, len) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => match &self.fallback {
+                Some(fallback) => fallback.set_len(path, len),
+                None => Err(err),
+            },
+            ret => ret,
+        }
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        match self.routes.write_range(path, offset, data.clone()) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => match &self.fallback {
+                Some(fallback) => fallback.write_range(path, offset, data),
+                None => Err(err),
+            },
+            ret => ret,
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.routes.supports_write_range()
+            || self
+                .fallback
+                .as_ref()
+                .is_some_and(|fallback| fallback.supports_write_range())
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.routes.supports_attributes()
+            || self
+                .fallback
+                .as_ref()
+                .is_some_and(|fallback| fallback.supports_attributes())
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        match self.routes.set_permissions(path, permissions) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => match &self.fallback {
+                Some(fallback) => fallback.set_permissions(path, permissions),
+                None => Err(err),
+            },
+            ret => ret,
+        }
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        match self.routes.read_link(path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => match &self.fallback {
+                Some(fallback) => fallback.read_link(path),
+                None => Err(err),
+            },
+            ret => ret,
+        }
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let routes = self.routes.flush();
+        match &self.fallback {
+            Some(fallback) => routes.and(fallback.flush()),
+            None => routes,
+        }
+    }
+
+    fn close(&self) -> io::Result<()> {
+        let routes = self.routes.close();
+        match &self.fallback {
+            Some(fallback) => routes.and(fallback.close()),
+            None => routes,
+        }
     }
 }
 
-#[derive(Default)]
+/// Which of several stores mounted at the same path is tried first, among stores of equal
+/// priority.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Shadowing {
+    /// The store registered first shadows those registered after it.
+    #[default]
+    FirstWins,
+    /// The store registered last shadows those registered before it, e.g. for overrides.
+    LastWins,
+}
+
+/// Where an [`AsyncComposite`] creates files which don't exist in any mount yet. Existing
+/// files are always written where they are.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum WritePolicy {
+    /// Creating a file fails with `NotFound`.
+    #[default]
+    Existing,
+    /// Create files in the first store which accepts them, trying the stores of the deepest
+    /// mount containing the path first and the fallback last. Stores refusing the write with
+    /// `PermissionDenied` or `Unsupported` are skipped.
+    FirstWritable,
+    /// Create files in the stores mounted here. Paths outside of the mount fail with
+    /// `NotFound`.
+    Mount(RelativePathBuf),
+}
+
+/// A store mounted in a composite, as listed by [`AsyncComposite::mounts`] and
+/// [`SyncComposite::mounts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    /// Where the store is mounted, or `None` for the fallback.
+    pub mount: Option<RelativePathBuf>,
+    /// Which of the stores registered at the mount it is, counting from 0.
+    pub registration: usize,
+    pub priority: i32,
+    /// The type of the store as it was mounted, as given by [`std::any::type_name`].
+    pub store: &'static str,
+    /// Whether the last health check of the store passed. Stores of a `SyncComposite` are
+    /// always healthy.
+    pub healthy: bool,
+}
+
+/// Order mounts by path, keeping the order the stores of a mount are tried in, with the
+/// fallback last.
+fn sorted(mut mounts: Vec<MountInfo>, fallback: impl Iterator<Item = MountInfo>) -> Vec<MountInfo> {
+    // Stable, so the stores of a mount stay in order
+    mounts.sort_by(|a, b| a.mount.cmp(&b.mount));
+    mounts.extend(fallback);
+    mounts
+}
+
+/// The store a path of a composite resolved to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    /// Where the store is mounted, or `None` for the fallback.
+    pub mount: Option<RelativePathBuf>,
+    /// Which of the stores registered at the mount it is, counting from 0.
+    pub registration: usize,
+    pub priority: i32,
+    /// The path within the store.
+    pub path: RelativePathBuf,
+}
+
+/// Mounts whose last health check failed are skipped when routing and listing, until a later
+/// [`health`](AsyncFileStore::health) check of the composite passes for them again.
+///
+/// Stores mounted at the same path are tried by descending priority, and by the
+/// [`Shadowing`] policy among equal priorities. [`resolve`](AsyncComposite::resolve) tells
+/// which store a path ends up at.
+///
+/// Files are written and removed in the store containing them. Where new files are created
+/// is up to the [`WritePolicy`]; by default they aren't.
+///
+/// Cloning is cheap; clones share the mounted stores and their health.
+#[derive(Default, Clone)]
 pub struct AsyncComposite {
-    routes: HashMap<RelativePathBuf, Vec<BoxAsyncFileStore>>,
+    routes: HashMap<RelativePathBuf, Vec<Arc<Mount>>>,
+    fallback: Option<Arc<Mount>>,
+    shadowing: Shadowing,
+    write_policy: WritePolicy,
 }
 
 impl AsyncComposite {
-    pub fn register<T>(&mut self, mut mount: &str, filestore: T)
+    pub fn builder() -> AsyncCompositeBuilder {
+        AsyncCompositeBuilder::default()
+    }
+
+    pub fn register<T>(&mut self, mount: &str, filestore: T)
     where
         T: AsyncFileStore + Send + Sync + 'static,
         T::File: Send + Sync + 'static,
         <T::File as AsyncFile>::Body: Send,
     {
-        if mount.starts_with("/") {
-            mount = &mount[1..];
+        self.register_with_priority(mount, filestore, 0);
+    }
+
+    /// Mount a store to be tried before the stores of lower priority at the same mount.
+    /// Stores registered with [`register`](AsyncComposite::register) have priority 0.
+    pub fn register_with_priority<T>(&mut self, mount: &str, filestore: T, priority: i32)
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        let stores = self.routes.entry(mount_path(mount)).or_default();
+        let mut mount = Mount::new(filestore);
+        mount.priority = priority;
+        mount.registration = stores.len();
+        stores.push(Arc::new(mount));
+        sort(stores, self.shadowing);
+    }
+
+    /// A store to read paths none of the mounts contain from, e.g. an `Embed` of default
+    /// assets, instead of mounting everything at `/`.
+    pub fn set_fallback<T>(&mut self, filestore: T)
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        self.fallback = Some(Arc::new(Mount::new(filestore)));
+    }
+
+    /// Remove the fallback store. Returns whether there was one.
+    pub fn clear_fallback(&mut self) -> bool {
+        self.fallback.take().is_some()
+    }
+
+    pub fn shadowing(&self) -> Shadowing {
+        self.shadowing
+    }
+
+    pub fn set_shadowing(&mut self, shadowing: Shadowing) {
+        self.shadowing = shadowing;
+        for stores in self.routes.values_mut() {
+            sort(stores, shadowing);
+        }
+    }
+
+    /// Find the store `path` resolves to, e.g. to find out why a path isn't found or which
+    /// of several stores serves it.
+    pub async fn resolve(&self, path: &RelativePath) -> io::Result<Resolved> {
+        let (mount, store, path) = self.locate(path).await.ok_or(io::ErrorKind::NotFound)?;
+
+        Ok(Resolved {
+            mount: mount.map(RelativePath::to_relative_path_buf),
+            registration: store.registration,
+            priority: store.priority,
+            path,
+        })
+    }
+
+    /// The store containing `path`, where it is mounted, and the path within it.
+    async fn locate<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> Option<(Option<&'a RelativePath>, &'a Mount, RelativePathBuf)> {
+        let mut parent = path.parent();
+
+        while let Some(mount) = parent {
+            if let Some(stores) = self.routes.get(mount) {
+                let inner = path.strip_prefix(mount.as_str()).expect("prefix");
+                for store in stores {
+                    if store.exists(inner).await {
+                        return Some((Some(mount), store, inner.to_relative_path_buf()));
+                    }
+                }
+            }
+            parent = mount.parent();
+        }
+
+        match &self.fallback {
+            Some(fallback) if fallback.exists(path).await => {
+                Some((None, fallback, path.to_relative_path_buf()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The stores to try creating `path` in, following the write policy.
+    fn creating(&self, path: &RelativePath) -> io::Result<Vec<(&Mount, RelativePathBuf)>> {
+        let mut candidates = Vec::new();
+
+        match &self.write_policy {
+            WritePolicy::Existing => {}
+            WritePolicy::FirstWritable => {
+                let mut parent = path.parent();
+                while let Some(mount) = parent {
+                    if let Some(stores) = self.routes.get(mount) {
+                        let inner = path.strip_prefix(mount.as_str()).expect("prefix");
+                        candidates.extend(
+                            stores
+                                .iter()
+                                .map(|store| (&**store, inner.to_relative_path_buf())),
+                        );
+                    }
+                    parent = mount.parent();
+                }
+                if let Some(fallback) = &self.fallback {
+                    candidates.push((&**fallback, path.to_relative_path_buf()));
+                }
+            }
+            WritePolicy::Mount(mount) => {
+                if let (Some(stores), Ok(inner)) =
+                    (self.routes.get(mount), path.strip_prefix(mount))
+                {
+                    candidates.extend(
+                        stores
+                            .iter()
+                            .map(|store| (&**store, inner.to_relative_path_buf())),
+                    );
+                }
+            }
+        }
+
+        candidates.retain(|(store, _)| store.is_healthy());
+        if candidates.is_empty() {
+            return Err(io::ErrorKind::NotFound.into());
         }
+        Ok(candidates)
+    }
+
+    pub fn write_policy(&self) -> &WritePolicy {
+        &self.write_policy
+    }
+
+    pub fn set_write_policy(&mut self, policy: WritePolicy) {
+        self.write_policy = policy;
+    }
 
+    /// Remove every store mounted at `mount`. Returns whether there were any.
+    pub fn unregister(&mut self, mount: &str) -> bool {
+        self.routes.remove(&mount_path(mount)).is_some()
+    }
+
+    /// Mount `filestore` at `mount` in place of the stores mounted there.
+    pub fn replace<T>(&mut self, mount: &str, filestore: T)
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
+    {
         self.routes
-            .entry(mount.into())
-            .or_default()
-            .push(async_filestore_box(filestore));
+            .insert(mount_path(mount), vec![Arc::new(Mount::new(filestore))]);
+    }
+
+    /// The mounted stores, by mount and in the order they are tried, with the fallback last.
+    pub fn mounts(&self) -> Vec<MountInfo> {
+        let routes = self
+            .routes
+            .iter()
+            .flat_map(|(mount, stores)| stores.iter().map(|store| store.info(Some(mount.clone()))));
+        let fallback = self.fallback.iter().map(|store| store.info(None));
+
+        sorted(routes.collect(), fallback)
+    }
+
+    /// The mounts taken out of routing by the last health check.
+    pub fn unhealthy(&self) -> Vec<RelativePathBuf> {
+        let mut mounts = self
+            .routes
+            .iter()
+            .filter(|(_, stores)| stores.iter().any(|store| !store.is_healthy()))
+            .map(|(mount, _)| mount.clone())
+            .collect::<Vec<_>>();
+        mounts.sort();
+        mounts
     }
 }
 
-impl AsyncFileStore for AsyncComposite {
-    type File = BoxAsyncFile<'static>;
+/// Order the stores of a mount in the order they are tried.
+fn sort(stores: &mut [Arc<Mount>], shadowing: Shadowing) {
+    stores.sort_by_key(|store| {
+        let registration = match shadowing {
+            Shadowing::FirstWins => store.registration as isize,
+            Shadowing::LastWins => -(store.registration as isize),
+        };
+        (std::cmp::Reverse(store.priority), registration)
+    });
+}
 
-    fn metadata(
-        &self,
-        path: &relative_path::RelativePath,
-    ) -> impl futures::prelude::Future<Output = Result<crate::Metadata, std::io::Error>> + Send
+/// A mounted store and whether its last health check passed.
+struct Mount {
+    store: BoxAsyncFileStore,
+    name: &'static str,
+    healthy: AtomicBool,
+    priority: i32,
+    registration: usize,
+}
+
+impl Mount {
+    fn new<T>(filestore: T) -> Mount
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
     {
-        self.routes.metadata(path)
+        Mount {
+            store: async_filestore_box(filestore),
+            name: std::any::type_name::<T>(),
+            healthy: AtomicBool::new(true),
+            priority: 0,
+            registration: 0,
+        }
+    }
+
+    fn info(&self, mount: Option<RelativePathBuf>) -> MountInfo {
+        MountInfo {
+            mount,
+            registration: self.registration,
+            priority: self.priority,
+            store: self.name,
+            healthy: self.is_healthy(),
+        }
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    fn check(&self) -> io::Result<()> {
+        if self.is_healthy() {
+            Ok(())
+        } else {
+            Err(io::Error::other("mount is unhealthy"))
+        }
+    }
+}
+
+impl AsyncFileStore for Mount {
+    type File = BoxAsyncFile<'static>;
+
+    fn metadata(&self, path: &RelativePath) -> impl Future<Output = io::Result<Metadata>> + Send {
+        async move {
+            self.check()?;
+            self.store.metadata(path).await
+        }
     }
 
     fn open_file(
         &self,
-        path: &relative_path::RelativePath,
-    ) -> impl futures::prelude::Future<Output = Result<Self::File, std::io::Error>> + Send {
-        self.routes.open_file(path)
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Self::File>> + Send {
+        async move {
+            self.check()?;
+            self.store.open_file(path).await
+        }
     }
 
-    fn rm_file(
-        &self,
-        _path: &relative_path::RelativePath,
-    ) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send {
-        async move { Err(std::io::ErrorKind::PermissionDenied.into()) }
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.check()?;
+            self.store.rm_file(path).await
+        }
     }
 
     fn write_file(
         &self,
-        _path: &relative_path::RelativePath,
-        _init: crate::AsyncFileInit,
-    ) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send {
-        async move { Err(std::io::ErrorKind::PermissionDenied.into()) }
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.check()?;
+            self.store.write_file(path, init).await
+        }
     }
 
+    /// Lists nothing while unhealthy, so listing the composite doesn't fail on one mount.
     fn list(
         &self,
-    ) -> impl futures::prelude::Future<
-        Output = Result<
-            futures::prelude::stream::BoxStream<
-                'static,
-                Result<relative_path::RelativePathBuf, std::io::Error>,
-            >,
-            std::io::Error,
-        >,
-    > + Send {
-        self.routes.list()
+    ) -> impl Future<Output = io::Result<BoxStream<'static, io::Result<RelativePathBuf>>>> + Send
+    {
+        async move {
+            if !self.is_healthy() {
+                return Ok(futures::stream::empty().boxed());
+            }
+            self.store.list().await
+        }
+    }
+
+    fn exists(&self, path: &RelativePath) -> impl Future<Output = bool> + Send
+    where
+        Self: Sync,
+    {
+        async move { self.is_healthy() && self.store.exists(path).await }
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let ret = self.store.health().await;
+            self.healthy.store(ret.is_ok(), Ordering::Relaxed);
+            ret
+        }
+    }
+
+    /// Unhealthy mounts aren't warmed.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            if !self.is_healthy() {
+                return Ok(());
+            }
+            self.store.prefetch(paths).await
+        }
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            if !self.is_healthy() {
+                return Ok(());
+            }
+            self.store.prefetch_prefix(prefix).await
+        }
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        async move {
+            self.check()?;
+            self.store.set_len(path, len).await
+        }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.check()?;
+            self.store.write_range(path, offset, data).await
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.store.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.store.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.check()?;
+            self.store.set_permissions(path, permissions).await
+        }
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.check()?;
+            self.store.symlink(target, link).await
+        }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.check()?;
+            self.store.hard_link(src, dst).await
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        self.store.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.store.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.store.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.store.close()
+    }
+}
+
+/// `path` within the store mounted at `mount`, for operations which can't span stores.
+fn within(mount: Option<&RelativePath>, path: &RelativePath) -> io::Result<RelativePathBuf> {
+    let Some(mount) = mount else {
+        return Ok(path.to_relative_path_buf());
+    };
+    match path.strip_prefix(mount.as_str()) {
+        Ok(inner) => Ok(inner.to_relative_path_buf()),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::CrossesDevices,
+            format!("{path} is not in the store mounted at {mount}"),
+        )),
+    }
+}
+
+/// Builds an `AsyncComposite` in one expression.
+///
+/// ```ignore
+/// let files = AsyncComposite::builder()
+///     .mount("/assets", assets)
+///     .mount_readonly("/vendor", Embed::<Vendor>::new())
+///     .fallback(defaults)
+///     .build();
+/// ```
+#[derive(Default)]
+pub struct AsyncCompositeBuilder {
+    composite: AsyncComposite,
+}
+
+impl AsyncCompositeBuilder {
+    pub fn mount<T>(mut self, mount: &str, filestore: T) -> Self
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        self.composite.register(mount, filestore);
+        self
+    }
+
+    /// Mount a store to be tried before the stores of lower priority at the same mount.
+    pub fn mount_with_priority<T>(mut self, mount: &str, filestore: T, priority: i32) -> Self
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        self.composite
+            .register_with_priority(mount, filestore, priority);
+        self
+    }
+
+    pub fn shadowing(mut self, shadowing: Shadowing) -> Self {
+        self.composite.set_shadowing(shadowing);
+        self
+    }
+
+    pub fn write_policy(mut self, policy: WritePolicy) -> Self {
+        self.composite.set_write_policy(policy);
+        self
+    }
+
+    /// Mount a store, rejecting writes and removals through the composite.
+    pub fn mount_readonly<T>(self, mount: &str, filestore: T) -> Self
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        self.mount(mount, ReadOnly::new(filestore))
+    }
+
+    /// A store to try for paths none of the mounts contain.
+    pub fn fallback<T>(mut self, filestore: T) -> Self
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        self.composite.set_fallback(filestore);
+        self
+    }
+
+    pub fn build(self) -> AsyncComposite {
+        self.composite
+    }
+}
+
+impl AsyncFileStore for AsyncComposite {
+    type File = BoxAsyncFile<'static>;
+
+    fn metadata(
+        &self,
+        path: &relative_path::RelativePath,
+    ) -> impl futures::prelude::Future<Output = Result<crate::Metadata, std::io::Error>> + Send
+    {
+        async move {
+            match self.routes.metadata(path).await {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => match &self.fallback {
+                    Some(fallback) => fallback.metadata(path).await,
+                    None => Err(err),
+                },
+                ret => ret,
+            }
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &relative_path::RelativePath,
+    ) -> impl futures::prelude::Future<Output = Result<Self::File, std::io::Error>> + Send {
+        async move {
+            match self.routes.open_file(path).await {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => match &self.fallback {
+                    Some(fallback) => fallback.open_file(path).await,
+                    None => Err(err),
+                },
+                ret => ret,
+            }
+        }
+    }
+
+    fn rm_file(
+        &self,
+        path: &relative_path::RelativePath,
+    ) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send {
+        async move {
+            match self.locate(path).await {
+                Some((_, store, path)) => store.rm_file(&path).await,
+                None => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &relative_path::RelativePath,
+        init: crate::AsyncFileInit,
+    ) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send {
+        async move {
+            if let Some((_, store, path)) = self.locate(path).await {
+                return store.write_file(&path, init).await;
+            }
+            write_first(self.creating(path)?, init).await
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl futures::prelude::Future<
+        Output = Result<
+            futures::prelude::stream::BoxStream<
+                'static,
+                Result<relative_path::RelativePathBuf, std::io::Error>,
+            >,
+            std::io::Error,
+        >,
+    > + Send {
+        async move {
+            let routes = self.routes.list().await?;
+            match &self.fallback {
+                Some(fallback) => Ok(routes.chain(fallback.list().await?).boxed()),
+                None => Ok(routes),
+            }
+        }
+    }
+
+    /// Checks every mount, taking those which fail out of routing and putting those which pass
+    /// back. Fails naming the unhealthy mounts if there are any.
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let mounts = self
+                .routes
+                .iter()
+                .flat_map(|(mount, stores)| stores.iter().map(move |store| (mount.as_str(), store)))
+                .chain(self.fallback.iter().map(|store| ("fallback", store)));
+
+            let checks = mounts.map(|(mount, store)| async move { (mount, store.health().await) });
+            let mut failed = futures::future::join_all(checks)
+                .await
+                .into_iter()
+                .filter_map(|(mount, ret)| ret.err().map(|err| format!("{mount}: {err}")))
+                .collect::<Vec<_>>();
+
+            if failed.is_empty() {
+                return Ok(());
+            }
+
+            failed.sort();
+            Err(io::Error::other(format!(
+                "unhealthy mounts: {}",
+                failed.join(", ")
+            )))
+        }
+    }
+
+    /// Passes each path on to the store it resolves to.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            crate::prefetch::each(paths, |path| async move {
+                match self.locate(&path).await {
+                    Some((_, store, path)) => store.prefetch(&[path]).await,
+                    None => Ok(()),
+                }
+            })
+            .await
+        }
+    }
+
+    /// Passes the prefix on to every mount with files below it, and the fallback.
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            self.routes.prefetch_prefix(prefix).await?;
+            match &self.fallback {
+                Some(fallback) => fallback.prefetch_prefix(prefix).await,
+                None => Ok(()),
+            }
+        }
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        async move {
+            match self.locate(path).await {
+                Some((_, store, path)) => store.set_len(&path, len).await,
+                None => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            match self.locate(path).await {
+                Some((_, store, path)) => store.write_range(&path, offset, data).await,
+                None => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.routes
+            .values()
+            .flatten()
+            .chain(&self.fallback)
+            .any(|mount| mount.supports_write_range())
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.routes
+            .values()
+            .flatten()
+            .chain(&self.fallback)
+            .any(|mount| mount.supports_attributes())
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            match self.locate(path).await {
+                Some((_, store, path)) => store.set_permissions(&path, permissions).await,
+                None => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+    }
+
+    /// Creates the link in the store containing its target, which must be the one the link
+    /// would be in. Fails with `CrossesDevices` otherwise.
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let resolved = link_target(link, target)?;
+            let Some((mount, store, _)) = self.locate(&resolved).await else {
+                return Err(io::ErrorKind::NotFound.into());
+            };
+            store.symlink(target, &within(mount, link)?).await
+        }
+    }
+
+    /// Fails with `CrossesDevices` unless both paths are in the same store.
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let Some((mount, store, src)) = self.locate(src).await else {
+                return Err(io::ErrorKind::NotFound.into());
+            };
+            store.hard_link(&src, &within(mount, dst)?).await
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        async move {
+            match self.locate(path).await {
+                Some((_, store, path)) => store.read_link(&path).await,
+                None => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+    }
+
+    fn supports_links(&self) -> bool {
+        self.routes
+            .values()
+            .flatten()
+            .chain(&self.fallback)
+            .any(|mount| mount.supports_links())
+    }
+
+    /// Flushes every mount, including unhealthy ones, and the fallback.
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let routes = self.routes.flush().await;
+            match &self.fallback {
+                Some(fallback) => routes.and(fallback.flush().await),
+                None => routes,
+            }
+        }
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let routes = self.routes.close().await;
+            match &self.fallback {
+                Some(fallback) => routes.and(fallback.close().await),
+                None => routes,
+            }
+        }
+    }
+}
+
+/// An [`AsyncComposite`] whose mounts can be changed while it is in use, e.g. to move a
+/// tenant from one backend to another without restarting.
+///
+/// Operations run against the mounts as they were when the operation started; changes apply
+/// to operations started afterwards. Clones share the mounts, so one can be kept to manage
+/// them while others serve.
+///
+/// ```ignore
+/// let files = SharedAsyncComposite::new(composite);
+/// let admin = files.clone();
+/// admin.replace("/tenants/acme", s3_store);
+/// ```
+#[derive(Default, Clone)]
+pub struct SharedAsyncComposite {
+    current: Arc<RwLock<Arc<AsyncComposite>>>,
+}
+
+impl SharedAsyncComposite {
+    pub fn new(composite: AsyncComposite) -> SharedAsyncComposite {
+        SharedAsyncComposite {
+            current: Arc::new(RwLock::new(Arc::new(composite))),
+        }
+    }
+
+    /// The mounts as they are now.
+    pub fn snapshot(&self) -> Arc<AsyncComposite> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Change the mounts. Operations in flight keep using the mounts they started with.
+    pub fn update<R>(&self, update: impl FnOnce(&mut AsyncComposite) -> R) -> R {
+        let mut current = self.current.write().unwrap();
+        let mut next = AsyncComposite::clone(&current);
+        let ret = update(&mut next);
+        *current = Arc::new(next);
+        ret
+    }
+
+    pub fn register<T>(&self, mount: &str, filestore: T)
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        self.update(|composite| composite.register(mount, filestore))
+    }
+
+    pub fn register_with_priority<T>(&self, mount: &str, filestore: T, priority: i32)
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        self.update(|composite| composite.register_with_priority(mount, filestore, priority))
+    }
+
+    /// Remove every store mounted at `mount`. Returns whether there were any.
+    pub fn unregister(&self, mount: &str) -> bool {
+        self.update(|composite| composite.unregister(mount))
+    }
+
+    pub fn set_fallback<T>(&self, filestore: T)
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        self.update(|composite| composite.set_fallback(filestore))
+    }
+
+    /// Remove the fallback store. Returns whether there was one.
+    pub fn clear_fallback(&self) -> bool {
+        self.update(|composite| composite.clear_fallback())
+    }
+
+    pub fn set_write_policy(&self, policy: WritePolicy) {
+        self.update(|composite| composite.set_write_policy(policy))
+    }
+
+    /// See [`AsyncComposite::mounts`].
+    pub fn mounts(&self) -> Vec<MountInfo> {
+        self.snapshot().mounts()
+    }
+
+    /// See [`AsyncComposite::resolve`].
+    pub async fn resolve(&self, path: &RelativePath) -> io::Result<Resolved> {
+        self.snapshot().resolve(path).await
+    }
+
+    /// Mount `filestore` at `mount` in place of the stores mounted there.
+    pub fn replace<T>(&self, mount: &str, filestore: T)
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        self.update(|composite| composite.replace(mount, filestore))
+    }
+}
+
+impl From<AsyncComposite> for SharedAsyncComposite {
+    fn from(composite: AsyncComposite) -> Self {
+        SharedAsyncComposite::new(composite)
+    }
+}
+
+impl AsyncFileStore for SharedAsyncComposite {
+    type File = BoxAsyncFile<'static>;
+
+    fn metadata(&self, path: &RelativePath) -> impl Future<Output = io::Result<Metadata>> + Send {
+        let current = self.snapshot();
+        async move { current.metadata(path).await }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Self::File>> + Send {
+        let current = self.snapshot();
+        async move { current.open_file(path).await }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = io::Result<()>> + Send {
+        let current = self.snapshot();
+        async move { current.rm_file(path).await }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let current = self.snapshot();
+        async move { current.write_file(path, init).await }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<Output = io::Result<BoxStream<'static, io::Result<RelativePathBuf>>>> + Send
+    {
+        let current = self.snapshot();
+        async move { current.list().await }
+    }
+
+    fn exists(&self, path: &RelativePath) -> impl Future<Output = bool> + Send
+    where
+        Self: Sync,
+    {
+        let current = self.snapshot();
+        async move { current.exists(path).await }
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let current = self.snapshot();
+        async move { current.health().await }
+    }
+
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let current = self.snapshot();
+        async move { current.prefetch(paths).await }
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let current = self.snapshot();
+        async move { current.prefetch_prefix(prefix).await }
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        let current = self.snapshot();
+        async move { current.set_len(path, len).await }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let current = self.snapshot();
+        async move { current.write_range(path, offset, data).await }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.snapshot().supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.snapshot().supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let current = self.snapshot();
+        async move { current.set_permissions(path, permissions).await }
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let current = self.snapshot();
+        async move { current.symlink(target, link).await }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let current = self.snapshot();
+        async move { current.hard_link(src, dst).await }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        let current = self.snapshot();
+        async move { current.read_link(path).await }
+    }
+
+    fn supports_links(&self) -> bool {
+        self.snapshot().supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let current = self.snapshot();
+        async move { current.flush().await }
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let current = self.snapshot();
+        async move { current.close().await }
+    }
+}
+
+/// A [`SyncComposite`] whose mounts can be changed while it is in use.
+/// See [`SharedAsyncComposite`].
+#[derive(Default, Clone)]
+pub struct SharedSyncComposite {
+    current: Arc<RwLock<Arc<SyncComposite>>>,
+}
+
+impl SharedSyncComposite {
+    pub fn new(composite: SyncComposite) -> SharedSyncComposite {
+        SharedSyncComposite {
+            current: Arc::new(RwLock::new(Arc::new(composite))),
+        }
+    }
+
+    /// The mounts as they are now.
+    pub fn snapshot(&self) -> Arc<SyncComposite> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Change the mounts. Operations in flight keep using the mounts they started with.
+    pub fn update<R>(&self, update: impl FnOnce(&mut SyncComposite) -> R) -> R {
+        let mut current = self.current.write().unwrap();
+        let mut next = SyncComposite::clone(&current);
+        let ret = update(&mut next);
+        *current = Arc::new(next);
+        ret
+    }
+
+    pub fn register<T>(&self, mount: &str, filestore: T)
+    where
+        T: FileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as File>::Body: Send,
+        T::List: Send,
+    {
+        self.update(|composite| composite.register(mount, filestore))
+    }
+
+    /// Remove every store mounted at `mount`. Returns whether there were any.
+    pub fn unregister(&self, mount: &str) -> bool {
+        self.update(|composite| composite.unregister(mount))
+    }
+
+    /// Mount `filestore` at `mount` in place of the stores mounted there.
+    pub fn replace<T>(&self, mount: &str, filestore: T)
+    where
+        T: FileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as File>::Body: Send,
+        T::List: Send,
+    {
+        self.update(|composite| composite.replace(mount, filestore))
+    }
+
+    pub fn set_fallback<T>(&self, filestore: T)
+    where
+        T: FileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as File>::Body: Send,
+        T::List: Send,
+    {
+        self.update(|composite| composite.set_fallback(filestore))
+    }
+
+    /// Remove the fallback store. Returns whether there was one.
+    pub fn clear_fallback(&self) -> bool {
+        self.update(|composite| composite.clear_fallback())
+    }
+
+    /// See [`SyncComposite::mounts`].
+    pub fn mounts(&self) -> Vec<MountInfo> {
+        self.snapshot().mounts()
+    }
+
+    /// See [`SyncComposite::resolve`].
+    pub fn resolve(&self, path: &RelativePath) -> io::Result<Resolved> {
+        self.snapshot().resolve(path)
+    }
+}
+
+impl From<SyncComposite> for SharedSyncComposite {
+    fn from(composite: SyncComposite) -> Self {
+        SharedSyncComposite::new(composite)
+    }
+}
+
+impl FileStore for SharedSyncComposite {
+    type File = BoxFile;
+
+    type List = Box<dyn Iterator<Item = io::Result<RelativePathBuf>> + Send>;
+
+    fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
+        self.snapshot().metadata(path)
+    }
+
+    fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
+        self.snapshot().open_file(path)
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
+        self.snapshot().rm_file(path)
+    }
+
+    fn write_file(&self, path: &RelativePath, init: crate::FileInit) -> Result<(), io::Error> {
+        self.snapshot().write_file(path, init)
+    }
+
+    fn list(&self) -> Self::List {
+        self.snapshot().list()
+    }
+
+    fn exists(&self, path: &RelativePath) -> bool {
+        self.snapshot().exists(path)
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        self.snapshot().set_len(path, len)
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        self.snapshot().write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.snapshot().supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.snapshot().supports_attributes()
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        self.snapshot().set_permissions(path, permissions)
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        self.snapshot().symlink(target, link)
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        self.snapshot().hard_link(src, dst)
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        self.snapshot().read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.snapshot().supports_links()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.snapshot().flush()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.snapshot().close()
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        mock::{MockFileStore, MockOp},
+        MetadataCache,
+    };
+
+    #[test]
+    fn prefetch_warms_the_mounted_stores() {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let assets = MockFileStore::new();
+                assets.insert("app.css", "");
+                assets.insert("img/logo.png", "");
+                let docs = MockFileStore::new();
+                docs.insert("index.html", "");
+
+                let composite = AsyncComposite::builder()
+                    .mount(
+                        "/assets",
+                        MetadataCache::new(assets.clone(), Duration::from_secs(60)),
+                    )
+                    .mount(
+                        "/docs",
+                        MetadataCache::new(docs.clone(), Duration::from_secs(60)),
+                    )
+                    .build();
+
+                composite
+                    .prefetch(&["assets/app.css".into(), "assets/missing".into()])
+                    .await
+                    .unwrap();
+                composite
+                    .prefetch_prefix(RelativePath::new("assets/img"))
+                    .await
+                    .unwrap();
+                assert_eq!(assets.count(MockOp::Metadata, "app.css"), 1);
+                assert_eq!(assets.count(MockOp::Metadata, "img/logo.png"), 1);
+                assert_eq!(docs.count(MockOp::Metadata, "index.html"), 0);
+
+                // Served from the warmed caches
+                composite
+                    .metadata(RelativePath::new("assets/app.css"))
+                    .await
+                    .unwrap();
+                composite
+                    .metadata(RelativePath::new("assets/img/logo.png"))
+                    .await
+                    .unwrap();
+                assert_eq!(assets.count(MockOp::Metadata, "app.css"), 1);
+                assert_eq!(assets.count(MockOp::Metadata, "img/logo.png"), 1);
+            });
     }
 }
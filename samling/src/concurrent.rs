@@ -0,0 +1,233 @@
+use std::io;
+
+use bytes::Bytes;
+use futures::{future::select_ok, stream::BoxStream, Future, FutureExt, StreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{file::link_target, AsyncFileInit, AsyncFileStore, Link, Metadata, Permissions};
+
+/// Like a `Vec` of stores, but probes the stores concurrently and uses the first one to succeed.
+///
+/// Unlike `Vec`, which stores get precedence is not deterministic if more than one contains
+/// a path. At most `limit` stores are probed at once.
+pub struct Concurrent<T> {
+    stores: Vec<T>,
+    limit: usize,
+}
+
+impl<T> Concurrent<T> {
+    pub fn new(stores: Vec<T>) -> Concurrent<T> {
+        let limit = stores.len().max(1);
+        Concurrent { stores, limit }
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(1);
+        self
+    }
+}
+
+impl<T> Concurrent<T>
+where
+    T: AsyncFileStore + Sync,
+{
+    async fn find<'a, F, R, U>(&'a self, func: F) -> io::Result<(&'a T, U)>
+    where
+        F: Fn(&'a T) -> R,
+        R: Future<Output = io::Result<U>> + Send + 'a,
+    {
+        for chunk in self.stores.chunks(self.limit) {
+            let probes = chunk.iter().map(|store| {
+                func(store)
+                    .map(move |ret| ret.map(|ret| (store, ret)))
+                    .boxed()
+            });
+
+            if let Ok((found, _)) = select_ok(probes).await {
+                return Ok(found);
+            }
+        }
+
+        Err(io::ErrorKind::NotFound.into())
+    }
+}
+
+impl<T> AsyncFileStore for Concurrent<T>
+where
+    T: AsyncFileStore + Send + Sync,
+    T::File: Send,
+{
+    type File = T::File;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            let (_, meta) = self.find(|store| store.metadata(path)).await?;
+            Ok(meta)
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            let (_, file) = self.find(|store| store.open_file(path)).await?;
+            Ok(file)
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let (store, _) = self.find(|store| store.metadata(path)).await?;
+            store.rm_file(path).await
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let (store, _) = self.find(|store| store.metadata(path)).await?;
+            store.write_file(path, init).await
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        async move {
+            let mut streams = Vec::with_capacity(self.stores.len());
+
+            for store in &self.stores {
+                streams.push(store.list().await?);
+            }
+
+            Ok(futures::stream::iter(streams).flatten().boxed())
+        }
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        futures::stream::iter(&self.stores)
+            .flat_map(|store| store.list_ref())
+            .boxed()
+    }
+
+    /// Passes the hint on to every store.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.stores.prefetch(paths)
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.stores.prefetch_prefix(prefix)
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let (store, _) = self.find(|store| store.metadata(path)).await?;
+            store.write_range(path, offset, data).await
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.stores.iter().any(|store| store.supports_write_range())
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.stores.iter().any(|store| store.supports_attributes())
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let (store, _) = self.find(|store| store.metadata(path)).await?;
+            store.set_permissions(path, permissions).await
+        }
+    }
+
+    /// Creates the link in the store containing its target.
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let resolved = link_target(link, target)?;
+            let (store, _) = self.find(|store| store.metadata(&resolved)).await?;
+            store.symlink(target, link).await
+        }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let (store, _) = self.find(|store| store.metadata(src)).await?;
+            store.hard_link(src, dst).await
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        async move {
+            let (store, _) = self.find(|store| store.metadata(path)).await?;
+            store.read_link(path).await
+        }
+    }
+
+    fn supports_links(&self) -> bool {
+        self.stores.iter().any(|store| store.supports_links())
+    }
+
+    /// Flushes every store, even when one fails, and returns the first error.
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            futures::future::join_all(self.stores.iter().map(|fs| fs.flush()))
+                .await
+                .into_iter()
+                .collect()
+        }
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            futures::future::join_all(self.stores.iter().map(|fs| fs.close()))
+                .await
+                .into_iter()
+                .collect()
+        }
+    }
+}
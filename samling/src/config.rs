@@ -0,0 +1,170 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+#[cfg(any(feature = "fs", feature = "embed"))]
+use crate::AsyncFileStoreExt;
+use crate::{AsyncComposite, BoxAsyncFileStore};
+
+/// A declarative description of an `AsyncComposite`.
+///
+/// ```toml
+/// [[mount]]
+/// path = "/assets"
+/// type = "fs"
+/// root = "./assets"
+///
+/// [[mount]]
+/// path = "/media"
+/// type = "url"
+/// url = "s3://media-bucket/prefix"
+/// options = { access_key = "...", secret_key = "..." }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default, rename = "mount")]
+    pub mounts: Vec<MountConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MountConfig {
+    pub path: String,
+    #[serde(flatten)]
+    pub store: StoreConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StoreConfig {
+    /// A directory on the local filesystem.
+    Fs { root: PathBuf },
+    /// An embed registered on the builder with `ConfigBuilder::embed`.
+    Embed { name: String },
+    /// A store constructed by the factory registered for the scheme of the url.
+    /// `options` is passed through to the factory, eg. for credentials.
+    Url {
+        url: Url,
+        #[serde(default)]
+        options: BTreeMap<String, String>,
+    },
+}
+
+impl Config {
+    #[cfg(feature = "config-json")]
+    pub fn from_json(input: &str) -> io::Result<Config> {
+        serde_json::from_str(input).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    #[cfg(feature = "config-toml")]
+    pub fn from_toml(input: &str) -> io::Result<Config> {
+        toml::from_str(input).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    #[cfg(feature = "config-yaml")]
+    pub fn from_yaml(input: &str) -> io::Result<Config> {
+        serde_yaml::from_str(input).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+type EmbedFactory = Box<dyn Fn() -> BoxAsyncFileStore + Send + Sync>;
+
+type UrlFactory =
+    Box<dyn Fn(&Url, &BTreeMap<String, String>) -> io::Result<BoxAsyncFileStore> + Send + Sync>;
+
+/// Materializes a `Config` into an `AsyncComposite`.
+///
+/// Embeds and url schemes can not be known from the config alone, so they are registered here.
+#[derive(Default)]
+pub struct ConfigBuilder {
+    embeds: HashMap<String, EmbedFactory>,
+    schemes: HashMap<String, UrlFactory>,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    #[cfg(feature = "embed")]
+    pub fn embed<T>(mut self, name: impl Into<String>) -> Self
+    where
+        T: rust_embed::RustEmbed + Send + Sync + 'static,
+    {
+        self.embeds.insert(
+            name.into(),
+            Box::new(|| crate::embed::Embed::<T>::new().boxed()),
+        );
+        self
+    }
+
+    pub fn scheme<F>(mut self, scheme: impl Into<String>, factory: F) -> Self
+    where
+        F: Fn(&Url, &BTreeMap<String, String>) -> io::Result<BoxAsyncFileStore>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.schemes.insert(scheme.into(), Box::new(factory));
+        self
+    }
+
+    pub fn build(&self, config: &Config) -> io::Result<AsyncComposite> {
+        let mut composite = AsyncComposite::default();
+
+        for mount in &config.mounts {
+            composite.register(&mount.path, self.store(&mount.store)?);
+        }
+
+        Ok(composite)
+    }
+
+    fn store(&self, config: &StoreConfig) -> io::Result<BoxAsyncFileStore> {
+        match config {
+            StoreConfig::Fs { root } => fs_store(root.clone()),
+            StoreConfig::Embed { name } => self.embeds.get(name).map(|m| m()).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no embed registered with name '{name}'"),
+                )
+            }),
+            StoreConfig::Url { url, options } => {
+                if let Some(factory) = self.schemes.get(url.scheme()) {
+                    return factory(url, options);
+                }
+
+                if url.scheme() == "file" {
+                    let root = url.to_file_path().map_err(|_| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("invalid file url '{url}'"),
+                        )
+                    })?;
+                    return fs_store(root);
+                }
+
+                Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("no store registered for scheme '{}'", url.scheme()),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+fn fs_store(root: PathBuf) -> io::Result<BoxAsyncFileStore> {
+    Ok(crate::fs::FsFileStore::new(root)?.boxed())
+}
+
+#[cfg(not(feature = "fs"))]
+fn fs_store(_root: PathBuf) -> io::Result<BoxAsyncFileStore> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "filesystem mounts require the fs feature",
+    ))
+}
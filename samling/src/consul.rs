@@ -0,0 +1,523 @@
+//! Small files in the key-value store of Consul, e.g. templates and config snippets shared by
+//! a fleet.
+//!
+//! Every file is a key below the prefix of the store, with its metadata in a sibling key.
+//! Only plain `http` is spoken, like [`RemoteClient`], so use a local agent.
+use std::{collections::BTreeMap, io, time::Duration, time::SystemTime, time::UNIX_EPOCH};
+
+use async_stream::try_stream;
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future, Stream, StreamExt};
+use hyper::{body::Incoming, header, Method, Request, Response, StatusCode};
+use relative_path::{Component, RelativePath, RelativePathBuf};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::{
+    remote::{self, RemoteClient},
+    util::base64,
+    watch::{Change, ChangeKind},
+    AsyncFile, AsyncFileInit, AsyncFileStore, Attributes, Metadata,
+};
+
+/// Appended to the key of a file for the key holding its metadata.
+const META_SUFFIX: &str = ".samling-meta";
+
+/// How long a blocking query of [`ConsulFileStore::watch`] waits for a change.
+const WAIT: &str = "5m";
+
+/// Files stored as keys of the Consul KV store, below a prefix.
+///
+/// The body of a file is the value of its key, and its mime type, modification time and
+/// attributes are kept as json in the sibling key with `.samling-meta` appended, so paths
+/// may not end with that. Both are written in one transaction. Keys written by other tools
+/// are files too, without the metadata.
+///
+/// Consul limits values to 512KiB, so only small files fit.
+///
+/// ```ignore
+/// let store = ConsulFileStore::new(&"http://127.0.0.1:8500".parse()?, "fleet/templates")
+///     .token(token);
+/// store.write_file("nginx/site.conf".as_ref(), init).await?;
+///
+/// let mut changes = store.watch();
+/// while let Some(change) = changes.try_next().await? {
+///     render(&change.path).await?;
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ConsulFileStore {
+    client: RemoteClient,
+    endpoint: Url,
+    prefix: Vec<String>,
+    token: Option<String>,
+    datacenter: Option<String>,
+}
+
+impl ConsulFileStore {
+    /// The keys below `prefix`, through the agent at `endpoint`.
+    pub fn new(endpoint: &Url, prefix: &str) -> ConsulFileStore {
+        ConsulFileStore {
+            client: RemoteClient::shared(),
+            endpoint: endpoint.clone(),
+            prefix: prefix
+                .split('/')
+                .filter(|segment| !segment.is_empty())
+                .map(str::to_string)
+                .collect(),
+            token: None,
+            datacenter: None,
+        }
+    }
+
+    /// Send requests through `client` instead of the shared one.
+    pub fn client(mut self, client: RemoteClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Authenticate with an ACL token.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Use the KV store of `datacenter` instead of the one of the agent.
+    pub fn datacenter(mut self, datacenter: impl Into<String>) -> Self {
+        self.datacenter = Some(datacenter.into());
+        self
+    }
+
+    /// Follow changes to the files with blocking queries.
+    ///
+    /// The current state is only recorded, like the first poll of [`crate::watch::watch`].
+    /// The stream ends with the first error.
+    pub fn watch(&self) -> impl Stream<Item = io::Result<Change>> + Send + 'static {
+        let store = self.clone();
+        try_stream! {
+            let (mut index, mut previous) = store.versions(0).await?;
+
+            loop {
+                let (next, current) = store.versions(index).await?;
+                // The index went backwards, e.g. after a restore, so start over
+                index = if next < index { 0 } else { next };
+
+                for (path, version) in &current {
+                    match previous.get(path) {
+                        None => yield change(ChangeKind::Added, path),
+                        Some(old) if old != version => yield change(ChangeKind::Modified, path),
+                        Some(_) => {}
+                    }
+                }
+                for path in previous.keys() {
+                    if !current.contains_key(path) {
+                        yield change(ChangeKind::Removed, path);
+                    }
+                }
+
+                previous = current;
+            }
+        }
+    }
+
+    /// The segments of the key of `path`.
+    fn key(&self, path: &RelativePath) -> io::Result<Vec<String>> {
+        let mut key = self.prefix.clone();
+        for component in path.normalize().components() {
+            match component {
+                Component::Normal(name) => key.push(name.to_string()),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!("path escapes the store: {path}"),
+                    ))
+                }
+            }
+        }
+
+        if key.len() == self.prefix.len() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "not a file"));
+        }
+        if path.as_str().ends_with(META_SUFFIX) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("paths ending with {META_SUFFIX} are reserved for metadata"),
+            ));
+        }
+        Ok(key)
+    }
+
+    fn url(&self, segments: &[String], dir: bool) -> io::Result<Url> {
+        let mut url = self.endpoint.clone();
+        {
+            let mut path = url
+                .path_segments_mut()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid consul url"))?;
+            path.pop_if_empty().extend(["v1"]).extend(segments);
+            if dir {
+                path.push("");
+            }
+        }
+        if let Some(datacenter) = &self.datacenter {
+            url.query_pairs_mut().append_pair("dc", datacenter);
+        }
+        Ok(url)
+    }
+
+    fn kv_url(&self, key: &[String]) -> io::Result<Url> {
+        let segments = ["kv".to_string()]
+            .into_iter()
+            .chain(key.iter().cloned())
+            .collect::<Vec<_>>();
+        self.url(&segments, false)
+    }
+
+    fn meta_url(&self, key: &[String]) -> io::Result<Url> {
+        let mut key = key.to_vec();
+        if let Some(last) = key.last_mut() {
+            last.push_str(META_SUFFIX);
+        }
+        self.kv_url(&key)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: &Url,
+        body: Option<Value>,
+    ) -> io::Result<Response<Incoming>> {
+        let mut request = Request::builder().method(method).uri(url.as_str());
+        if let Some(token) = &self.token {
+            request = request.header("X-Consul-Token", token);
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                remote::full(body.to_string())
+            }
+            None => remote::empty(),
+        };
+        let request = request
+            .body(body)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        check(self.client.request(request).await?).await
+    }
+
+    async fn get(&self, url: &Url) -> io::Result<Bytes> {
+        remote::collect(self.send(Method::GET, url, None).await?).await
+    }
+
+    /// The raw value of `url`.
+    async fn value(&self, url: &Url) -> io::Result<Bytes> {
+        let mut url = url.clone();
+        url.query_pairs_mut().append_key_only("raw");
+        self.get(&url).await
+    }
+
+    async fn meta(&self, key: &[String]) -> io::Result<Option<Meta>> {
+        match self.value(&self.meta_url(key)?).await {
+            Ok(meta) => parse(&meta).map(Some),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Run the operations of a transaction on the KV store, all or none.
+    async fn transaction(&self, operations: Vec<Value>) -> io::Result<()> {
+        let url = self.url(&["txn".to_string()], false)?;
+        self.send(Method::PUT, &url, Some(Value::Array(operations)))
+            .await
+            .map(drop)
+    }
+
+    /// The version of every file, with the index of the KV store. Blocks until the index
+    /// passes `index` when it isn't zero.
+    async fn versions(&self, index: u64) -> io::Result<(u64, BTreeMap<RelativePathBuf, u64>)> {
+        let mut url = self.url(
+            &["kv".to_string()]
+                .into_iter()
+                .chain(self.prefix.iter().cloned())
+                .collect::<Vec<_>>(),
+            !self.prefix.is_empty(),
+        )?;
+        url.query_pairs_mut().append_key_only("recurse");
+        if index > 0 {
+            url.query_pairs_mut()
+                .append_pair("index", &index.to_string())
+                .append_pair("wait", WAIT);
+        }
+
+        let response = match self.send(Method::GET, &url, None).await {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            ret => Some(ret?),
+        };
+        let Some(response) = response else {
+            // Nothing below the prefix yet, but the index is still sent
+            return Ok((index.max(1), BTreeMap::new()));
+        };
+
+        let next = response
+            .headers()
+            .get("X-Consul-Index")
+            .and_then(|index| index.to_str().ok())
+            .and_then(|index| index.parse().ok())
+            .unwrap_or(0);
+        let entries: Vec<Entry> = parse(&remote::collect(response).await?)?;
+
+        let versions = entries
+            .into_iter()
+            .filter_map(|entry| Some((self.path_of(&entry.key)?, entry.modify_index)))
+            .collect();
+        Ok((next, versions))
+    }
+
+    /// The path of the file at `key`, unless it is a folder or metadata.
+    fn path_of(&self, key: &str) -> Option<RelativePathBuf> {
+        let mut rest = key;
+        for segment in &self.prefix {
+            rest = rest.strip_prefix(segment.as_str())?;
+            rest = rest.strip_prefix('/').unwrap_or(rest);
+        }
+
+        if rest.is_empty() || rest.ends_with('/') || rest.ends_with(META_SUFFIX) {
+            return None;
+        }
+        Some(RelativePathBuf::from(rest))
+    }
+}
+
+impl AsyncFileStore for ConsulFileStore {
+    type File = ConsulFile;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            let key = self.key(path)?;
+            let path = path.normalize();
+
+            let meta = match self.meta(&key).await? {
+                Some(meta) => meta,
+                None => {
+                    let value = self.value(&self.kv_url(&key)?).await?;
+                    Meta::new(&path, value.len() as u64, None, None, Attributes::default())
+                }
+            };
+
+            Ok(Metadata {
+                path,
+                size: meta.size,
+                mime: meta.mime.parse().unwrap_or(mime::APPLICATION_OCTET_STREAM),
+                modified: meta
+                    .modified
+                    .map(|modified| UNIX_EPOCH + Duration::from_millis(modified)),
+                permissions: None,
+                attributes: meta.attributes,
+            })
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            let key = self.key(path)?;
+            self.value(&self.kv_url(&key)?).await.map(ConsulFile)
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let key = self.key(path)?;
+            // Fails with `NotFound` when there is no such file
+            self.get(&self.kv_url(&key)?).await?;
+
+            let key = key.join("/");
+            self.transaction(vec![
+                json!({ "KV": { "Verb": "delete", "Key": key } }),
+                json!({ "KV": { "Verb": "delete", "Key": format!("{key}{META_SUFFIX}") } }),
+            ])
+            .await
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let key = self.key(path)?.join("/");
+            let mime = init.mime.clone();
+            let attributes = init.attributes.clone();
+            let len = init.len.unwrap_or(0);
+            let value = crate::util::collect(init.into_stream().await?, len).await?;
+
+            let meta = Meta::new(
+                path,
+                value.len() as u64,
+                mime,
+                Some(SystemTime::now()),
+                attributes,
+            );
+            let meta = serde_json::to_vec(&meta).map_err(io::Error::other)?;
+
+            self.transaction(vec![
+                json!({ "KV": { "Verb": "set", "Key": key, "Value": base64::encode(&value) } }),
+                json!({ "KV": {
+                    "Verb": "set",
+                    "Key": format!("{key}{META_SUFFIX}"),
+                    "Value": base64::encode(&meta),
+                } }),
+            ])
+            .await
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        async move {
+            let mut url = self.url(
+                &["kv".to_string()]
+                    .into_iter()
+                    .chain(self.prefix.iter().cloned())
+                    .collect::<Vec<_>>(),
+                !self.prefix.is_empty(),
+            )?;
+            url.query_pairs_mut().append_key_only("keys");
+
+            let keys: Vec<String> = match self.get(&url).await {
+                Ok(body) => parse(&body)?,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+                Err(err) => return Err(err),
+            };
+
+            let paths = keys
+                .iter()
+                .filter_map(|key| self.path_of(key))
+                .map(Ok)
+                .collect::<Vec<_>>();
+            Ok(futures::stream::iter(paths).boxed())
+        }
+    }
+
+    fn supports_attributes(&self) -> bool {
+        true
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let url = self.url(&["status".to_string(), "leader".to_string()], false)?;
+            self.get(&url).await.map(drop)
+        }
+    }
+}
+
+/// The value of a key of a [`ConsulFileStore`].
+pub struct ConsulFile(Bytes);
+
+impl AsyncFile for ConsulFile {
+    type Body = futures::stream::Once<futures::future::Ready<io::Result<Bytes>>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        futures::future::ready(crate::util::slice(&self.0, range))
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        futures::future::ok(futures::stream::once(futures::future::ok(self.0.clone())))
+    }
+}
+
+/// The metadata of a file, kept in its sibling key.
+#[derive(Serialize, Deserialize)]
+struct Meta {
+    size: u64,
+    mime: String,
+    /// Milliseconds since the unix epoch.
+    modified: Option<u64>,
+    #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+    attributes: Attributes,
+}
+
+impl Meta {
+    fn new(
+        path: &RelativePath,
+        size: u64,
+        mime: Option<mime::Mime>,
+        modified: Option<SystemTime>,
+        attributes: Attributes,
+    ) -> Meta {
+        let mime = mime.unwrap_or_else(|| match path.extension() {
+            Some(ext) => mime_guess::from_ext(ext).first_or_octet_stream(),
+            None => mime::APPLICATION_OCTET_STREAM,
+        });
+
+        Meta {
+            size,
+            mime: mime.to_string(),
+            modified: modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_millis() as u64),
+            attributes,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Entry {
+    key: String,
+    modify_index: u64,
+}
+
+fn change(kind: ChangeKind, path: &RelativePathBuf) -> Change {
+    Change {
+        kind,
+        path: path.clone(),
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> io::Result<T> {
+    serde_json::from_slice(body).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Turn error responses into errors. Consul answers with the message as plain text.
+async fn check(response: Response<Incoming>) -> io::Result<Response<Incoming>> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = remote::collect(response).await.unwrap_or_default();
+    let message = String::from_utf8_lossy(&body);
+    let message = match message.trim() {
+        "" => format!("consul {status}"),
+        message => format!("consul {status}: {message}"),
+    };
+
+    let kind = match status {
+        StatusCode::NOT_FOUND => io::ErrorKind::NotFound,
+        StatusCode::FORBIDDEN | StatusCode::UNAUTHORIZED => io::ErrorKind::PermissionDenied,
+        // A transaction rolled back, or a value over the size limit
+        StatusCode::CONFLICT | StatusCode::PAYLOAD_TOO_LARGE | StatusCode::BAD_REQUEST => {
+            io::ErrorKind::InvalidInput
+        }
+        _ => io::ErrorKind::Other,
+    };
+
+    Err(io::Error::new(kind, message))
+}
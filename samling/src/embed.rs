@@ -1,25 +1,133 @@
 use std::{
-    io::{self, Cursor},
+    borrow::Cow,
+    io,
     marker::PhantomData,
+    time::{Duration, SystemTime},
 };
 
-use bytes::Bytes;
+use bytes::{buf::Reader, Buf, Bytes};
 use futures::StreamExt;
 use relative_path::RelativePathBuf;
 
-use crate::{AsyncFile, AsyncFileStore, File, FileStore, Metadata};
-
-pub struct Embed<T>(PhantomData<T>);
+#[cfg(debug_assertions)]
+use crate::bridge::blocking;
+use crate::{AsyncFile, AsyncFileStore, Attributes, File, FileStore, Metadata};
+
+/// A store over the files embedded with `rust_embed`.
+///
+/// Release builds always serve the compiled-in data. In debug builds `rust_embed` itself
+/// reads from the `#[folder]` unless its `debug-embed` feature is enabled; a store created with
+/// [`Embed::with_source`] reads from the given folder regardless, so asset edits show up
+/// without recompiling.
+pub struct Embed<T> {
+    #[cfg(debug_assertions)]
+    source: Option<std::sync::Arc<std::path::Path>>,
+    _embed: PhantomData<T>,
+}
 
 impl<T> Clone for Embed<T> {
     fn clone(&self) -> Self {
-        Embed(PhantomData)
+        Embed {
+            #[cfg(debug_assertions)]
+            source: self.source.clone(),
+            _embed: PhantomData,
+        }
     }
 }
 
 impl<T> Embed<T> {
     pub fn new() -> Embed<T> {
-        Embed(PhantomData)
+        Embed {
+            #[cfg(debug_assertions)]
+            source: None,
+            _embed: PhantomData,
+        }
+    }
+
+    /// In debug builds, read files from `folder` on disk instead of the embedded data. Every
+    /// call goes to disk, so added, changed and removed files are picked up immediately.
+    /// Ignored in release builds.
+    ///
+    /// ```ignore
+    /// let assets = Embed::<Assets>::with_source(concat!(env!("CARGO_MANIFEST_DIR"), "/assets"));
+    /// ```
+    pub fn with_source(folder: impl AsRef<std::path::Path>) -> Embed<T> {
+        #[cfg(not(debug_assertions))]
+        let _ = folder;
+        Embed {
+            #[cfg(debug_assertions)]
+            source: Some(folder.as_ref().into()),
+            _embed: PhantomData,
+        }
+    }
+}
+
+/// Reads from the source folder of a dev-mode `Embed`.
+#[cfg(debug_assertions)]
+mod source {
+    use std::{io, path::Path, path::PathBuf};
+
+    use bytes::Bytes;
+    use relative_path::{Component, RelativePath, RelativePathBuf};
+
+    use crate::{Attributes, Metadata};
+
+    fn resolve(root: &Path, path: &RelativePath) -> io::Result<PathBuf> {
+        let path = path.normalize();
+        if path.components().next() == Some(Component::ParentDir) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(path.to_logical_path(root))
+    }
+
+    pub fn metadata(root: &Path, path: &RelativePath) -> io::Result<Metadata> {
+        let meta = std::fs::metadata(resolve(root, path)?)?;
+        if !meta.is_file() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+
+        Ok(Metadata {
+            size: meta.len(),
+            mime: if let Some(ext) = path.extension() {
+                mime_guess::from_ext(ext).first_or_octet_stream()
+            } else {
+                mime::APPLICATION_OCTET_STREAM
+            },
+            path: path.to_relative_path_buf(),
+            modified: meta.modified().ok(),
+            permissions: None,
+            attributes: Attributes::new(),
+        })
+    }
+
+    pub fn read(root: &Path, path: &RelativePath) -> io::Result<Bytes> {
+        let path = resolve(root, path)?;
+        if !std::fs::metadata(&path)?.is_file() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(std::fs::read(path)?.into())
+    }
+
+    pub fn list(root: &Path) -> io::Result<Vec<RelativePathBuf>> {
+        let mut files = Vec::new();
+        let mut queue = vec![RelativePathBuf::new()];
+
+        while let Some(dir) = queue.pop() {
+            for entry in std::fs::read_dir(dir.to_logical_path(root))? {
+                let entry = entry?;
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+
+                if entry.file_type()?.is_dir() {
+                    queue.push(dir.join(name));
+                } else {
+                    files.push(dir.join(name));
+                }
+            }
+        }
+
+        Ok(files)
     }
 }
 
@@ -39,9 +147,7 @@ where
             let this = self.clone();
             let path = path.to_relative_path_buf();
 
-            tokio::task::spawn_blocking(move || <Embed<T> as FileStore>::metadata(&this, &path))
-                .await
-                .expect("spawn")
+            blocking(move || <Embed<T> as FileStore>::metadata(&this, &path)).await
         }
     }
 
@@ -53,9 +159,7 @@ where
             let this = self.clone();
             let path = path.to_relative_path_buf();
 
-            tokio::task::spawn_blocking(move || <Embed<T> as FileStore>::open_file(&this, &path))
-                .await
-                .expect("spawn")
+            blocking(move || <Embed<T> as FileStore>::open_file(&this, &path)).await
         }
     }
 
@@ -86,12 +190,11 @@ where
         >,
     > + Send {
         async move {
-            let stream = futures::stream::iter(
-                T::iter()
-                    .map(|m| Ok(RelativePathBuf::from(m.as_ref())))
-                    .collect::<Vec<_>>(),
-            );
-            Ok(stream.boxed())
+            let this = self.clone();
+            let files =
+                blocking(move || Ok(<Embed<T> as FileStore>::list(&this).collect::<Vec<_>>()))
+                    .await?;
+            Ok(futures::stream::iter(files).boxed())
         }
     }
 }
@@ -110,7 +213,7 @@ where
     {
         async move {
             let Some(found) = T::get(path.as_str()) else {
-                todo!()
+                return Err(io::ErrorKind::NotFound.into());
             };
 
             let meta = Metadata {
@@ -121,6 +224,9 @@ where
                     mime::APPLICATION_OCTET_STREAM
                 },
                 path: path.to_relative_path_buf(),
+                modified: last_modified(&found),
+                permissions: None,
+                attributes: Attributes::new(),
             };
 
             Ok(meta)
@@ -133,7 +239,7 @@ where
     ) -> impl futures::prelude::Future<Output = Result<Self::File, std::io::Error>> + Send {
         async move {
             T::get(path.as_str())
-                .map(|m| EmbedFile(m, PhantomData))
+                .map(EmbedFile::new)
                 .ok_or_else(|| io::ErrorKind::NotFound.into())
         }
     }
@@ -184,8 +290,13 @@ where
     type List = Box<dyn Iterator<Item = io::Result<RelativePathBuf>> + Send>;
 
     fn metadata(&self, path: &relative_path::RelativePath) -> Result<Metadata, io::Error> {
+        #[cfg(debug_assertions)]
+        if let Some(root) = &self.source {
+            return source::metadata(root, path);
+        }
+
         let Some(found) = T::get(path.as_str()) else {
-            todo!()
+            return Err(io::ErrorKind::NotFound.into());
         };
 
         let meta = Metadata {
@@ -196,14 +307,22 @@ where
                 mime::APPLICATION_OCTET_STREAM
             },
             path: path.to_relative_path_buf(),
+            modified: last_modified(&found),
+            permissions: None,
+            attributes: Attributes::new(),
         };
 
         Ok(meta)
     }
 
     fn open_file(&self, path: &relative_path::RelativePath) -> Result<Self::File, io::Error> {
+        #[cfg(debug_assertions)]
+        if let Some(root) = &self.source {
+            return Ok(EmbedFile(source::read(root, path)?, PhantomData));
+        }
+
         T::get(path.as_str())
-            .map(|m| EmbedFile(m, PhantomData))
+            .map(EmbedFile::new)
             .ok_or_else(|| io::ErrorKind::NotFound.into())
     }
 
@@ -220,6 +339,14 @@ where
     }
 
     fn list(&self) -> Self::List {
+        #[cfg(debug_assertions)]
+        if let Some(root) = &self.source {
+            return match source::list(root) {
+                Ok(files) => Box::new(files.into_iter().map(Ok)),
+                Err(err) => Box::new(std::iter::once(Err(err))),
+            };
+        }
+
         Box::new(
             T::iter()
                 .map(|m| Ok(RelativePathBuf::from(m.as_ref())))
@@ -229,7 +356,27 @@ where
     }
 }
 
-pub struct EmbedFile<T>(rust_embed::EmbeddedFile, PhantomData<T>);
+fn last_modified(file: &rust_embed::EmbeddedFile) -> Option<SystemTime> {
+    file.metadata
+        .last_modified()
+        .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// An embedded file.
+///
+/// Holds the embedded data as `Bytes`, borrowing it directly from the binary when it was
+/// compiled in, so readers are cheap to hand out no matter how large the file is.
+pub struct EmbedFile<T>(Bytes, PhantomData<T>);
+
+impl<T> EmbedFile<T> {
+    fn new(file: rust_embed::EmbeddedFile) -> EmbedFile<T> {
+        let data = match file.data {
+            Cow::Borrowed(data) => Bytes::from_static(data),
+            Cow::Owned(data) => Bytes::from(data),
+        };
+        EmbedFile(data, PhantomData)
+    }
+}
 
 impl<T: rust_embed::RustEmbed + Send + Sync> AsyncFile for EmbedFile<T> {
     type Body = futures::stream::Once<futures::future::Ready<io::Result<Bytes>>>;
@@ -238,13 +385,13 @@ impl<T: rust_embed::RustEmbed + Send + Sync> AsyncFile for EmbedFile<T> {
         &self,
         range: std::ops::Range<u64>,
     ) -> impl futures::prelude::Future<Output = Result<bytes::Bytes, std::io::Error>> + Send {
-        async move { todo!() }
+        futures::future::ready(crate::util::slice(&self.0, range))
     }
 
     fn reader(
         &self,
     ) -> impl futures::prelude::Future<Output = Result<Self::Body, std::io::Error>> + Send {
-        let bytes = Bytes::from(self.0.data.to_vec());
+        let bytes = self.0.clone();
         async move {
             let stream = futures::stream::once(futures::future::ok(bytes));
             Ok(stream)
@@ -253,13 +400,13 @@ impl<T: rust_embed::RustEmbed + Send + Sync> AsyncFile for EmbedFile<T> {
 }
 
 impl<T: rust_embed::RustEmbed + Send + Sync> File for EmbedFile<T> {
-    type Body = Cursor<Vec<u8>>;
+    type Body = Reader<Bytes>;
 
     fn read_range(&self, range: std::ops::Range<u64>) -> Result<Bytes, io::Error> {
-        todo!()
+        crate::util::slice(&self.0, range)
     }
 
     fn reader(&self) -> Result<Self::Body, io::Error> {
-        Ok(Cursor::new(self.0.data.to_vec()))
+        Ok(self.0.clone().reader())
     }
 }
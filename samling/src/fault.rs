@@ -0,0 +1,385 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future, StreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+use url::Url;
+
+use crate::{AsyncFile, AsyncFileInit, AsyncFileStore, Link, Metadata, Permissions};
+
+/// Wraps a store and makes a share of its operations misbehave, for chaos testing.
+///
+/// Each operation independently fails with probability `error_rate` and is delayed with
+/// probability `delay_rate`. Bodies returned by `reader` are cut short with probability
+/// `truncate_rate`; a truncated body ends early without an error, the way a dropped connection
+/// looks to a reader that doesn't check lengths. Use `seed` for reproducible runs.
+///
+/// ```ignore
+/// let store = FaultInjector::new(store)
+///     .error_rate(0.05)
+///     .truncate_rate(0.01)
+///     .delay(0.1, Duration::from_millis(200))
+///     .seed(42);
+/// ```
+pub struct FaultInjector<T> {
+    inner: T,
+    faults: Arc<Faults>,
+}
+
+struct Faults {
+    error_rate: f64,
+    error_kind: io::ErrorKind,
+    truncate_rate: f64,
+    delay_rate: f64,
+    delay: Duration,
+    rng: Mutex<u64>,
+}
+
+impl Clone for Faults {
+    /// A copy continuing from the current state of the random source.
+    fn clone(&self) -> Self {
+        Faults {
+            error_rate: self.error_rate,
+            error_kind: self.error_kind,
+            truncate_rate: self.truncate_rate,
+            delay_rate: self.delay_rate,
+            delay: self.delay,
+            rng: Mutex::new(*self.rng.lock().unwrap()),
+        }
+    }
+}
+
+impl<T> FaultInjector<T> {
+    pub fn new(inner: T) -> FaultInjector<T> {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+
+        FaultInjector {
+            inner,
+            faults: Arc::new(Faults {
+                error_rate: 0.,
+                error_kind: io::ErrorKind::Other,
+                truncate_rate: 0.,
+                delay_rate: 0.,
+                delay: Duration::ZERO,
+                rng: Mutex::new(seed),
+            }),
+        }
+    }
+
+    /// Share of operations, between 0 and 1, which fail.
+    pub fn error_rate(mut self, rate: f64) -> Self {
+        self.faults_mut().error_rate = rate;
+        self
+    }
+
+    /// The kind of the injected errors. Defaults to `Other`.
+    pub fn error_kind(mut self, kind: io::ErrorKind) -> Self {
+        self.faults_mut().error_kind = kind;
+        self
+    }
+
+    /// Share of file bodies, between 0 and 1, which are cut short.
+    pub fn truncate_rate(mut self, rate: f64) -> Self {
+        self.faults_mut().truncate_rate = rate;
+        self
+    }
+
+    /// Delay a share of operations, between 0 and 1, by `delay`.
+    pub fn delay(mut self, rate: f64, delay: Duration) -> Self {
+        let faults = self.faults_mut();
+        faults.delay_rate = rate;
+        faults.delay = delay;
+        self
+    }
+
+    /// Seed the random source, making the injected faults reproducible.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.faults_mut().rng = Mutex::new(seed);
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// The faults to configure. Files already open keep the faults they were opened with.
+    fn faults_mut(&mut self) -> &mut Faults {
+        Arc::make_mut(&mut self.faults)
+    }
+}
+
+impl Faults {
+    /// A uniformly distributed number in `0..1`.
+    fn random(&self) -> f64 {
+        let mut state = self.rng.lock().unwrap();
+        // splitmix64
+        *state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn hit(&self, rate: f64) -> bool {
+        rate > 0. && self.random() < rate
+    }
+
+    /// Decide up front whether an operation is delayed and whether it fails.
+    fn roll(&self) -> (Option<Duration>, io::Result<()>) {
+        let delay = self.hit(self.delay_rate).then_some(self.delay);
+        let ret = if self.hit(self.error_rate) {
+            Err(io::Error::new(self.error_kind, "injected fault"))
+        } else {
+            Ok(())
+        };
+        (delay, ret)
+    }
+
+    async fn inject(&self) -> io::Result<()> {
+        let (delay, ret) = self.roll();
+        if let Some(delay) = delay {
+            futures_timer::Delay::new(delay).await;
+        }
+        ret
+    }
+
+    fn truncate(
+        self: Arc<Self>,
+        stream: BoxStream<'static, io::Result<Bytes>>,
+    ) -> BoxStream<'static, io::Result<Bytes>> {
+        if !self.hit(self.truncate_rate) {
+            return stream;
+        }
+
+        // Every chunk has an even chance of being the last, cut at a random point
+        stream
+            .scan(false, move |done, chunk| {
+                let ret = match chunk {
+                    _ if *done => None,
+                    Ok(chunk) if self.hit(0.5) => {
+                        let len = (chunk.len() as f64 * self.random()) as usize;
+                        *done = true;
+                        Some(Ok(chunk.slice(..len)))
+                    }
+                    other => Some(other),
+                };
+                futures::future::ready(ret)
+            })
+            .boxed()
+    }
+}
+
+impl<T> AsyncFileStore for FaultInjector<T>
+where
+    T: AsyncFileStore + Sync,
+    T::File: Send + Sync,
+    <T::File as AsyncFile>::Body: Send + 'static,
+{
+    type File = FaultFile<T::File>;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            self.faults.inject().await?;
+            self.inner.metadata(path).await
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            self.faults.inject().await?;
+            Ok(FaultFile {
+                file: self.inner.open_file(path).await?,
+                faults: self.faults.clone(),
+            })
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            self.faults.inject().await?;
+            self.inner.rm_file(path).await
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            self.faults.inject().await?;
+            self.inner.write_file(path, init).await
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        async move {
+            self.faults.inject().await?;
+            self.inner.list().await
+        }
+    }
+
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            self.faults.inject().await?;
+            self.inner.prefetch(paths).await
+        }
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            self.faults.inject().await?;
+            self.inner.prefetch_prefix(prefix).await
+        }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.faults.inject().await?;
+            self.inner.write_range(path, offset, data).await
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.faults.inject().await?;
+            self.inner.set_permissions(path, permissions).await
+        }
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.faults.inject().await?;
+            self.inner.symlink(target, link).await
+        }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.faults.inject().await?;
+            self.inner.hard_link(src, dst).await
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        async move {
+            self.faults.inject().await?;
+            self.inner.read_link(path).await
+        }
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.close()
+    }
+}
+
+pub struct FaultFile<F> {
+    file: F,
+    faults: Arc<Faults>,
+}
+
+impl<F> AsyncFile for FaultFile<F>
+where
+    F: AsyncFile + Sync,
+    F::Body: Send + 'static,
+{
+    type Body = BoxStream<'static, io::Result<Bytes>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        async move {
+            self.faults.inject().await?;
+            self.file.read_range(range).await
+        }
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        async move {
+            self.faults.inject().await?;
+            let body = self.file.reader().await?.boxed();
+            Ok(self.faults.clone().truncate(body))
+        }
+    }
+
+    fn url(&self) -> Option<Url> {
+        self.file.url()
+    }
+
+    fn native_path(&self) -> Option<&std::path::Path> {
+        self.file.native_path()
+    }
+}
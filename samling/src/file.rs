@@ -1,16 +1,130 @@
 use bytes::Bytes;
 use futures::Stream;
 use mime::Mime;
-use relative_path::RelativePathBuf;
+use relative_path::{RelativePath, RelativePathBuf};
+use std::collections::BTreeMap;
 use std::future::Future;
 use std::io::{self, Read};
+use std::time::SystemTime;
 use url::Url;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Metadata {
     pub path: RelativePathBuf,
     pub size: u64,
+    #[cfg_attr(feature = "serde", serde(with = "mime_serde"))]
     pub mime: Mime,
+    pub modified: Option<SystemTime>,
+    /// `None` where the backend doesn't keep permissions.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub permissions: Option<Permissions>,
+    /// Empty where the backend doesn't keep attributes. See [`Attributes`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Attributes::is_empty")
+    )]
+    pub attributes: Attributes,
+}
+
+/// Custom key-value attributes of a file, like user metadata on object stores or extended
+/// attributes on disk.
+///
+/// Written with [`with_attribute`](crate::AsyncFileInit::with_attribute) on the init, and only
+/// kept by stores which [`supports_attributes`](crate::AsyncFileStore::supports_attributes).
+pub type Attributes = BTreeMap<String, String>;
+
+/// The unix permission bits of a file, e.g. `0o755` for an executable.
+///
+/// Backends which don't have modes map what they do have onto them, like the readonly flag on
+/// Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Permissions {
+    mode: u32,
+}
+
+impl Permissions {
+    /// Only the permission bits of `mode` are kept, the file type bits are dropped.
+    pub fn from_mode(mode: u32) -> Permissions {
+        Permissions {
+            mode: mode & 0o7777,
+        }
+    }
+
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    /// Whether nobody may write the file.
+    pub fn readonly(&self) -> bool {
+        self.mode & 0o222 == 0
+    }
+
+    /// Clear all write bits, or set the write bit of the owner.
+    pub fn set_readonly(&mut self, readonly: bool) {
+        if readonly {
+            self.mode &= !0o222;
+        } else {
+            self.mode |= 0o200;
+        }
+    }
+
+    /// Whether anybody may execute the file.
+    pub fn executable(&self) -> bool {
+        self.mode & 0o111 != 0
+    }
+}
+
+impl Default for Permissions {
+    fn default() -> Permissions {
+        Permissions::from_mode(0o644)
+    }
+}
+
+/// What a path is a link to, as told by [`read_link`](crate::AsyncFileStore::read_link).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Link {
+    /// A symbolic link. The target is relative to the directory containing the link, like the
+    /// target given to `ln -s`.
+    Symlink(RelativePathBuf),
+    /// One of several names of the same file. Paths with an equal id share their contents.
+    Hard(u64),
+}
+
+/// What a store can do beyond the basic operations, as told by
+/// [`capabilities`](crate::AsyncFileStoreExt::capabilities), e.g. for a client to check
+/// before relying on them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Capabilities {
+    /// Listings are in lexicographic order. See [`AsyncFileStore::SORTED`](crate::AsyncFileStore::SORTED).
+    pub sorted: bool,
+    pub write_range: bool,
+    pub attributes: bool,
+    pub links: bool,
+}
+
+/// Where the symlink at `link` pointing at `target` leads, relative to the root of the store.
+///
+/// Fails with `PermissionDenied` when it would lead out of the store.
+pub(crate) fn link_target(
+    link: &RelativePath,
+    target: &RelativePath,
+) -> io::Result<RelativePathBuf> {
+    let path = link
+        .parent()
+        .unwrap_or(RelativePath::new(""))
+        .join(target)
+        .normalize();
+    if path.components().next() == Some(relative_path::Component::ParentDir) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("link target escapes the store: {target}"),
+        ));
+    }
+    Ok(path)
 }
 
 pub trait AsyncFile {
@@ -25,6 +139,12 @@ pub trait AsyncFile {
     fn url(&self) -> Option<Url> {
         None
     }
+
+    /// The path of the file on the local filesystem, if it is backed by one.
+    /// Allows copies between local files to bypass streaming through userspace.
+    fn native_path(&self) -> Option<&std::path::Path> {
+        None
+    }
 }
 
 pub trait File {
@@ -36,4 +156,25 @@ pub trait File {
     fn url(&self) -> Option<Url> {
         None
     }
+
+    /// The path of the file on the local filesystem, if it is backed by one.
+    fn native_path(&self) -> Option<&std::path::Path> {
+        None
+    }
+}
+
+#[cfg(feature = "serde")]
+pub(crate) mod mime_serde {
+    use mime::Mime;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(mime: &Mime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(mime.as_ref())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Mime, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
 }
@@ -1,16 +1,53 @@
+#[cfg(feature = "tokio")]
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::{io, sync::Arc};
 
-use futures::stream::BoxStream;
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future, StreamExt};
 use relative_path::{RelativePath, RelativePathBuf};
+#[cfg(any(feature = "serde-json", feature = "serde-toml", feature = "serde-yaml"))]
+use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{
-    boxed::async_filestore_box,
-    AsyncFile, AsyncFileInit, AsyncFileStore, BoxAsyncFile, BoxAsyncFileStore, Metadata,
+    boxed::async_filestore_box, AsyncFile, AsyncFileInit, AsyncFileStore, AsyncFileStoreExt,
+    AsyncPath, BoxAsyncFile, BoxAsyncFileStore, Link, Metadata, Permissions, StoreStats,
 };
 
+/// Number of files read at once by [`AsyncFiles::read_many`].
+const READ_MANY_CONCURRENCY: usize = 16;
+
+/// A cheaply cloned handle to a boxed store.
+///
+/// When the last clone is dropped without the store having been [closed](AsyncFiles::close),
+/// it is closed in the background on the current tokio runtime, if there is one. Nothing waits
+/// for that or sees its errors, so close explicitly where durability matters.
 #[derive(Clone)]
 pub struct AsyncFiles {
     store: Arc<BoxAsyncFileStore>,
+    #[cfg(feature = "tokio")]
+    closer: Arc<Closer>,
+}
+
+/// Closes the store once every `AsyncFiles` sharing it is gone.
+#[cfg(feature = "tokio")]
+struct Closer {
+    store: Arc<BoxAsyncFileStore>,
+    closed: AtomicBool,
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for Closer {
+    fn drop(&mut self) {
+        if self.closed.load(Ordering::Acquire) {
+            return;
+        }
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let store = self.store.clone();
+            handle.spawn(async move {
+                let _ = store.close().await;
+            });
+        }
+    }
 }
 
 impl AsyncFiles {
@@ -20,8 +57,14 @@ impl AsyncFiles {
         T::File: Send + Sync + 'static,
         <T::File as AsyncFile>::Body: Send + 'static,
     {
+        let store = Arc::new(async_filestore_box(store));
         AsyncFiles {
-            store: Arc::new(async_filestore_box(store)),
+            #[cfg(feature = "tokio")]
+            closer: Arc::new(Closer {
+                store: store.clone(),
+                closed: AtomicBool::new(false),
+            }),
+            store,
         }
     }
 
@@ -32,7 +75,7 @@ impl AsyncFiles {
     pub async fn open_file(
         &self,
         path: impl AsRef<RelativePath>,
-    ) -> Result<BoxAsyncFile, io::Error> {
+    ) -> Result<BoxAsyncFile<'static>, io::Error> {
         self.store.open_file(path.as_ref()).await
     }
 
@@ -53,6 +96,320 @@ impl AsyncFiles {
     ) -> Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error> {
         self.store.list().await
     }
+
+    pub async fn read(&self, path: impl AsRef<RelativePath>) -> Result<Bytes, io::Error> {
+        AsyncFileStoreExt::read(self, path.as_ref()).await
+    }
+
+    /// Read many files at once, yielding each path with its contents in the order given.
+    ///
+    /// Up to 16 reads are in flight at a time, so a slow file only holds back the files after
+    /// it from being yielded, not from being fetched.
+    ///
+    /// ```ignore
+    /// let mut partials = files.read_many(["header.html", "footer.html", "nav.html"]);
+    /// while let Some((path, data)) = partials.next().await {
+    ///     engine.add_partial(path.file_stem().unwrap(), data?)?;
+    /// }
+    /// ```
+    pub fn read_many<I>(
+        &self,
+        paths: I,
+    ) -> BoxStream<'static, (RelativePathBuf, Result<Bytes, io::Error>)>
+    where
+        I: IntoIterator,
+        I::Item: Into<RelativePathBuf>,
+    {
+        let files = self.clone();
+        let paths = paths.into_iter().map(Into::into).collect::<Vec<_>>();
+
+        futures::stream::iter(paths)
+            .map(move |path| {
+                let files = files.clone();
+                async move {
+                    let ret = files.read(&path).await;
+                    (path, ret)
+                }
+            })
+            .buffered(READ_MANY_CONCURRENCY)
+            .boxed()
+    }
+
+    pub async fn read_to_string(
+        &self,
+        path: impl AsRef<RelativePath>,
+    ) -> Result<String, io::Error> {
+        AsyncFileStoreExt::read_to_string(self, path.as_ref()).await
+    }
+
+    #[cfg(feature = "serde-json")]
+    pub async fn read_json<D: DeserializeOwned>(
+        &self,
+        path: impl AsRef<RelativePath>,
+    ) -> Result<D, io::Error> {
+        AsyncFileStoreExt::read_json(self, path.as_ref()).await
+    }
+
+    #[cfg(feature = "serde-json")]
+    pub async fn write_json<S: Serialize + ?Sized>(
+        &self,
+        path: impl AsRef<RelativePath>,
+        value: &S,
+    ) -> Result<(), io::Error> {
+        AsyncFileStoreExt::write_json(self, path.as_ref(), value).await
+    }
+
+    #[cfg(feature = "serde-toml")]
+    pub async fn read_toml<D: DeserializeOwned>(
+        &self,
+        path: impl AsRef<RelativePath>,
+    ) -> Result<D, io::Error> {
+        AsyncFileStoreExt::read_toml(self, path.as_ref()).await
+    }
+
+    #[cfg(feature = "serde-toml")]
+    pub async fn write_toml<S: Serialize + ?Sized>(
+        &self,
+        path: impl AsRef<RelativePath>,
+        value: &S,
+    ) -> Result<(), io::Error> {
+        AsyncFileStoreExt::write_toml(self, path.as_ref(), value).await
+    }
+
+    #[cfg(feature = "serde-yaml")]
+    pub async fn read_yaml<D: DeserializeOwned>(
+        &self,
+        path: impl AsRef<RelativePath>,
+    ) -> Result<D, io::Error> {
+        AsyncFileStoreExt::read_yaml(self, path.as_ref()).await
+    }
+
+    #[cfg(feature = "serde-yaml")]
+    pub async fn write_yaml<S: Serialize + ?Sized>(
+        &self,
+        path: impl AsRef<RelativePath>,
+        value: &S,
+    ) -> Result<(), io::Error> {
+        AsyncFileStoreExt::write_yaml(self, path.as_ref(), value).await
+    }
+
+    /// Cut a file short or pad it with zeros. See [`AsyncFileStore::set_len`].
+    pub async fn set_len(&self, path: impl AsRef<RelativePath>, len: u64) -> Result<(), io::Error> {
+        self.store.set_len(path.as_ref(), len).await
+    }
+
+    /// Overwrite part of an existing file, starting at `offset`. See
+    /// [`AsyncFileStore::write_range`].
+    pub async fn write_range(
+        &self,
+        path: impl AsRef<RelativePath>,
+        offset: u64,
+        data: impl Into<Bytes>,
+    ) -> Result<(), io::Error> {
+        self.store
+            .write_range(path.as_ref(), offset, data.into())
+            .await
+    }
+
+    /// Change the permissions of a file. See [`AsyncFileStore::set_permissions`].
+    pub async fn set_permissions(
+        &self,
+        path: impl AsRef<RelativePath>,
+        permissions: Permissions,
+    ) -> Result<(), io::Error> {
+        self.store.set_permissions(path.as_ref(), permissions).await
+    }
+
+    /// Create a symbolic link at `link` pointing at `target`. See [`AsyncFileStore::symlink`].
+    pub async fn symlink(
+        &self,
+        target: impl AsRef<RelativePath>,
+        link: impl AsRef<RelativePath>,
+    ) -> Result<(), io::Error> {
+        self.store.symlink(target.as_ref(), link.as_ref()).await
+    }
+
+    /// Give the file `src` a second name. See [`AsyncFileStore::hard_link`].
+    pub async fn hard_link(
+        &self,
+        src: impl AsRef<RelativePath>,
+        dst: impl AsRef<RelativePath>,
+    ) -> Result<(), io::Error> {
+        self.store.hard_link(src.as_ref(), dst.as_ref()).await
+    }
+
+    /// What a path is a link to, if anything. See [`AsyncFileStore::read_link`].
+    pub async fn read_link(
+        &self,
+        path: impl AsRef<RelativePath>,
+    ) -> Result<Option<Link>, io::Error> {
+        self.store.read_link(path.as_ref()).await
+    }
+
+    /// Write out anything the store buffers. See [`AsyncFileStore::flush`].
+    pub async fn flush(&self) -> Result<(), io::Error> {
+        self.store.flush().await
+    }
+
+    /// Flush and release the store, e.g. at shutdown. Every clone shares the store, so none
+    /// of them should be used afterwards.
+    pub async fn close(&self) -> Result<(), io::Error> {
+        #[cfg(feature = "tokio")]
+        self.closer.closed.store(true, Ordering::Release);
+        self.store.close().await
+    }
+
+    /// A handle to `path` in this store.
+    pub fn path(&self, path: impl Into<RelativePathBuf>) -> AsyncPath<AsyncFiles> {
+        AsyncPath::new(self.clone(), path)
+    }
+}
+
+impl AsyncFileStore for AsyncFiles {
+    type File = BoxAsyncFile<'static>;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        self.store.metadata(path)
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        self.store.open_file(path)
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        self.store.rm_file(path)
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        self.store.write_file(path, init)
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.store.list()
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        self.store.list_ref()
+    }
+
+    fn stats(&self) -> impl Future<Output = io::Result<StoreStats>> + Send
+    where
+        Self: Sync,
+    {
+        self.store.stats()
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.store.health()
+    }
+
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.store.prefetch(paths)
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.store.prefetch_prefix(prefix)
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        self.store.set_len(path, len)
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.store.write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.store.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.store.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.store.set_permissions(path, permissions)
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.store.symlink(target, link)
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.store.hard_link(src, dst)
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        self.store.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.store.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.store.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        AsyncFiles::close(self)
+    }
 }
 
 // #[derive(Clone)]
@@ -0,0 +1,388 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, io,
+    sync::Arc,
+};
+
+use futures::{lock::Mutex, stream::BoxStream, Future, StreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    util::{hash_bytes, HashAlgorithm},
+    AsyncFile, AsyncFileInit, AsyncFileStore, AsyncFileStoreExt, Metadata,
+};
+
+/// Logical paths to the content hashed paths they are stored under.
+pub type Manifest = BTreeMap<RelativePathBuf, RelativePathBuf>;
+
+/// The manifest as kept in the store, with the hashed files it no longer references.
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    files: Manifest,
+    /// Hashed files written by the store and since replaced, for [`Fingerprinted::gc`].
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    retired: BTreeSet<RelativePathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcProgress {
+    /// Replaced hashed files looked at so far.
+    pub scanned: u64,
+    /// Unreferenced files found so far.
+    pub unreferenced: u64,
+}
+
+pub type GcCallback = Arc<dyn Fn(GcProgress) + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct GcOptions {
+    /// Report what would be removed without removing anything.
+    pub dry_run: bool,
+    /// Called after every file scanned.
+    pub progress: Option<GcCallback>,
+}
+
+impl fmt::Debug for GcOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GcOptions")
+            .field("dry_run", &self.dry_run)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GcReport {
+    /// Hashed files referenced by the manifest.
+    pub reachable: usize,
+    /// Unreferenced hashed files, removed unless running dry.
+    pub removed: Vec<RelativePathBuf>,
+    pub failed: Vec<(RelativePathBuf, io::Error)>,
+}
+
+/// Stores files under content hashed names for cache busting, e.g. `app.js` as
+/// `app.3f9ab2c1.js`, and resolves logical paths through a JSON manifest kept in the store.
+///
+/// The manifest is an object with the logical paths and their hashed paths under `files`, and
+/// the hashed paths since replaced under `retired`.
+///
+/// Reads of a logical path are served from its hashed file, and the returned metadata carries
+/// the hashed path, so long lived cache headers can be keyed on it. Paths missing from the
+/// manifest, including the hashed paths themselves, are passed through. Listing yields the
+/// logical paths of the manifest.
+///
+/// Files are buffered in memory to be hashed before they are written, and writes are
+/// serialized. Replaced files are left in place, since clients may still reference them, until
+/// [`Fingerprinted::gc`] collects them.
+pub struct Fingerprinted<T> {
+    inner: T,
+    manifest_path: RelativePathBuf,
+    algorithm: HashAlgorithm,
+    hash_len: usize,
+    manifest: Mutex<Option<State>>,
+}
+
+impl<T> Fingerprinted<T> {
+    pub fn new(inner: T) -> Fingerprinted<T> {
+        Fingerprinted {
+            inner,
+            manifest_path: RelativePathBuf::from("manifest.json"),
+            algorithm: HashAlgorithm::default(),
+            hash_len: 8,
+            manifest: Mutex::new(None),
+        }
+    }
+
+    /// Where the manifest is kept in the inner store. Defaults to `manifest.json`.
+    pub fn manifest_path(mut self, path: impl Into<RelativePathBuf>) -> Self {
+        self.manifest_path = path.into().normalize();
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// The number of hex characters of the hash used in names. Defaults to 8.
+    pub fn hash_len(mut self, len: usize) -> Self {
+        self.hash_len = len.max(1);
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> Fingerprinted<T>
+where
+    T: AsyncFileStore + Sync,
+    T::File: Send,
+    <T::File as AsyncFile>::Body: Send,
+{
+    /// The current manifest.
+    pub async fn manifest(&self) -> io::Result<Manifest> {
+        let mut state = self.manifest.lock().await;
+        Ok(self.loaded(&mut state).await?.files.clone())
+    }
+
+    /// The hashed path a logical path is stored under, if it is in the manifest.
+    pub async fn resolve(&self, path: &RelativePath) -> io::Result<Option<RelativePathBuf>> {
+        let mut state = self.manifest.lock().await;
+        Ok(self
+            .loaded(&mut state)
+            .await?
+            .files
+            .get(&path.normalize())
+            .cloned())
+    }
+
+    /// Drop the cached manifest, picking up changes made through other handles.
+    pub async fn reload(&self) {
+        *self.manifest.lock().await = None;
+    }
+
+    async fn loaded<'a>(&self, state: &'a mut Option<State>) -> io::Result<&'a mut State> {
+        if state.is_none() {
+            let loaded = match self.inner.read_json(&self.manifest_path).await {
+                Ok(loaded) => loaded,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => State::default(),
+                Err(err) => return Err(err),
+            };
+            *state = Some(loaded);
+        }
+        Ok(state.as_mut().expect("manifest loaded"))
+    }
+
+    /// Remove the hashed files which were replaced and are no longer referenced by the
+    /// manifest.
+    ///
+    /// Only files recorded as retired in the manifest are considered, so files written to the
+    /// inner store by other means are kept. Writes wait for the collection.
+    pub async fn gc(&self, options: GcOptions) -> io::Result<GcReport> {
+        let mut guard = self.manifest.lock().await;
+        let state = self.loaded(&mut guard).await?;
+        let reachable = state.files.values().cloned().collect::<BTreeSet<_>>();
+
+        let mut report = GcReport {
+            reachable: reachable.len(),
+            ..Default::default()
+        };
+        let mut progress = GcProgress::default();
+        let mut collected = Vec::new();
+
+        for path in &state.retired {
+            progress.scanned += 1;
+
+            if reachable.contains(path) {
+                // Written again since, and referenced once more
+                collected.push(path.clone());
+            } else {
+                progress.unreferenced += 1;
+                let ret = if options.dry_run {
+                    Ok(())
+                } else {
+                    match self.inner.rm_file(path).await {
+                        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                        _ => Ok(()),
+                    }
+                };
+                match ret {
+                    Ok(()) => {
+                        collected.push(path.clone());
+                        report.removed.push(path.clone());
+                    }
+                    Err(err) => report.failed.push((path.clone(), err)),
+                }
+            }
+
+            if let Some(callback) = &options.progress {
+                callback(progress);
+            }
+        }
+
+        if !options.dry_run && !collected.is_empty() {
+            for path in &collected {
+                state.retired.remove(path);
+            }
+            if let Err(err) = self.save(state).await {
+                *guard = None;
+                return Err(err);
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn save(&self, state: &State) -> io::Result<()> {
+        self.inner.write_json(&self.manifest_path, state).await
+    }
+
+    async fn resolved(&self, path: &RelativePath) -> io::Result<RelativePathBuf> {
+        Ok(self
+            .resolve(path)
+            .await?
+            .unwrap_or_else(|| path.normalize()))
+    }
+
+    fn hashed_path(&self, path: &RelativePath, digest: &str) -> RelativePathBuf {
+        let hash = &digest[..self.hash_len.min(digest.len())];
+        let name = match (path.file_stem(), path.extension()) {
+            (Some(stem), Some(ext)) => format!("{stem}.{hash}.{ext}"),
+            (Some(stem), None) => format!("{stem}.{hash}"),
+            _ => hash.to_string(),
+        };
+        path.with_file_name(name)
+    }
+}
+
+impl<T> AsyncFileStore for Fingerprinted<T>
+where
+    T: AsyncFileStore + Send + Sync,
+    T::File: Send,
+    <T::File as AsyncFile>::Body: Send,
+{
+    type File = T::File;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            let path = self.resolved(path).await?;
+            self.inner.metadata(&path).await
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            let path = self.resolved(path).await?;
+            self.inner.open_file(&path).await
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let path = path.normalize();
+            let mut guard = self.manifest.lock().await;
+            let state = self.loaded(&mut guard).await?;
+
+            let Some(hashed) = state.files.remove(&path) else {
+                return self.inner.rm_file(&path).await;
+            };
+
+            if let Err(err) = self.save(state).await {
+                *guard = None;
+                return Err(err);
+            }
+            match self.inner.rm_file(&hashed).await {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let path = path.normalize();
+            let mime = init.mime.clone();
+            let attributes = init.attributes.clone();
+            let data = crate::util::collect(init.into_stream().await?, 0).await?;
+            let digest = hash_bytes(&data, self.algorithm).to_hex();
+            let hashed = self.hashed_path(&path, &digest);
+
+            let len = data.len() as u64;
+            let mut init = AsyncFileInit::from(data).with_len(len);
+            init.mime = mime;
+            init.attributes = attributes;
+
+            // Held while writing, so a collection can't remove the file before it's referenced
+            let mut guard = self.manifest.lock().await;
+            self.inner.write_file(&hashed, init).await?;
+            let state = self.loaded(&mut guard).await?;
+            if state.files.get(&path) != Some(&hashed) {
+                if let Some(replaced) = state.files.insert(path, hashed.clone()) {
+                    state.retired.insert(replaced);
+                }
+                state.retired.remove(&hashed);
+                if let Err(err) = self.save(state).await {
+                    *guard = None;
+                    return Err(err);
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        async move {
+            let paths = self
+                .manifest()
+                .await?
+                .into_keys()
+                .map(Ok)
+                .collect::<Vec<_>>();
+            Ok(futures::stream::iter(paths).boxed())
+        }
+    }
+
+    /// Passes on the content hashed paths the logical ones are stored under.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let mut resolved = Vec::with_capacity(paths.len());
+            for path in paths {
+                resolved.push(self.resolved(path).await?);
+            }
+            self.inner.prefetch(&resolved).await
+        }
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let paths = crate::prefetch::below(self, prefix).await?;
+            self.prefetch(&paths).await
+        }
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.close()
+    }
+}
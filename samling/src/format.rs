@@ -0,0 +1,47 @@
+//! Encoding and decoding of the serde formats supported by the typed read and write helpers.
+
+use std::io;
+
+use bytes::Bytes;
+use serde::{de::DeserializeOwned, Serialize};
+
+fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(feature = "serde-json")]
+pub(crate) fn from_json<D: DeserializeOwned>(data: &[u8]) -> io::Result<D> {
+    serde_json::from_slice(data).map_err(invalid_data)
+}
+
+#[cfg(feature = "serde-json")]
+pub(crate) fn to_json<S: Serialize + ?Sized>(value: &S) -> io::Result<Bytes> {
+    serde_json::to_vec_pretty(value)
+        .map(Bytes::from)
+        .map_err(invalid_data)
+}
+
+#[cfg(feature = "serde-toml")]
+pub(crate) fn from_toml<D: DeserializeOwned>(data: &[u8]) -> io::Result<D> {
+    let data = std::str::from_utf8(data).map_err(invalid_data)?;
+    toml::from_str(data).map_err(invalid_data)
+}
+
+#[cfg(feature = "serde-toml")]
+pub(crate) fn to_toml<S: Serialize + ?Sized>(value: &S) -> io::Result<Bytes> {
+    toml::to_string_pretty(value)
+        .map(Bytes::from)
+        .map_err(invalid_data)
+}
+
+#[cfg(feature = "serde-yaml")]
+pub(crate) fn from_yaml<D: DeserializeOwned>(data: &[u8]) -> io::Result<D> {
+    serde_yaml::from_slice(data).map_err(invalid_data)
+}
+
+#[cfg(feature = "serde-yaml")]
+pub(crate) fn to_yaml<S: Serialize + ?Sized>(value: &S) -> io::Result<Bytes> {
+    serde_yaml::to_string(value)
+        .map(Bytes::from)
+        .map_err(invalid_data)
+}
@@ -1,32 +1,93 @@
+#[cfg(feature = "ignore")]
+mod ignore;
+
 use std::{
-    io::{self, SeekFrom, Write},
-    os::unix::fs::MetadataExt,
-    path::PathBuf,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
 };
 
 use async_stream::try_stream;
 use bytes::Bytes;
-use futures::{stream::BoxStream, Future, TryStreamExt};
-use relative_path::{RelativePath, RelativePathBuf};
+use futures::{
+    future::{maybe_done, BoxFuture},
+    stream::BoxStream,
+    Future, FutureExt, TryStreamExt,
+};
+use relative_path::{Component, RelativePath, RelativePathBuf};
 use std::collections::VecDeque;
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
-use tokio_util::io::ReaderStream;
 use url::Url;
 
-use crate::{AsyncFile, AsyncFileInit, AsyncFileStore, File, FileInit, FileStore, Metadata};
+use crate::{
+    file::link_target, util::Buffer, AsyncFile, AsyncFileBody, AsyncFileInit, AsyncFileStore,
+    Attributes, File, FileBody, FileInit, FileStore, Link, Metadata, Permissions,
+};
+
+/// Runs the blocking filesystem calls of an `FsFileStore`.
+///
+/// `std::fs` has no async counterpart, so every backend ends up on a thread pool. Abstracting
+/// over that pool is enough to make the store usable outside of tokio.
+pub trait Runtime: Clone + Send + Sync + 'static {
+    fn unblock<F, T>(&self, func: F) -> impl Future<Output = io::Result<T>> + Send
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static;
+}
+
+/// Runs blocking calls on tokio's blocking pool.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tokio;
+
+impl Runtime for Tokio {
+    fn unblock<F, T>(&self, func: F) -> impl Future<Output = io::Result<T>> + Send
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        async move {
+            tokio::task::spawn_blocking(func)
+                .await
+                .map_err(io::Error::other)?
+        }
+    }
+}
+
+/// Runs blocking calls on the runtime-neutral thread pool of the `blocking` crate,
+/// as used by smol and async-std.
+#[cfg(feature = "unblock")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Unblock;
 
-impl AsyncFileStore for FsFileStore {
-    type File = FsFile;
+#[cfg(feature = "unblock")]
+impl Runtime for Unblock {
+    fn unblock<F, T>(&self, func: F) -> impl Future<Output = io::Result<T>> + Send
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        blocking::unblock(func)
+    }
+}
+
+impl<R> AsyncFileStore for FsFileStore<R>
+where
+    R: Runtime,
+{
+    type File = FsFile<R>;
 
     fn metadata(
         &self,
         path: &RelativePath,
     ) -> impl Future<Output = Result<Metadata, std::io::Error>> + Send {
-        async move {
-            let meta = tokio::fs::metadata(path.to_logical_path(&self.root)).await?;
+        let full_path = self.resolve(path);
+        let options = self.options;
+        let path = path.to_relative_path_buf();
+        self.runtime.unblock(move || {
+            let full_path = full_path?;
+            let meta = options.stat(&full_path)?;
 
             if !meta.is_file() {
-                return Err(io::Error::other("not a file"));
+                return Err(not_a_file());
             }
 
             let mime = if let Some(ext) = path.extension() {
@@ -36,38 +97,57 @@ impl AsyncFileStore for FsFileStore {
             };
 
             Ok(Metadata {
-                path: path.to_relative_path_buf(),
-                size: meta.size(),
+                path,
+                size: meta.len(),
                 mime,
+                modified: meta.modified().ok(),
+                permissions: Some(permissions_of(&meta)),
+                attributes: options.attributes_of(&full_path)?,
             })
-        }
+        })
     }
 
     fn open_file(
         &self,
         path: &RelativePath,
     ) -> impl Future<Output = Result<Self::File, std::io::Error>> + Send {
-        async move {
-            let full_path = path.to_logical_path(&self.root);
-            if !full_path.is_file() {
-                panic!("file does not exits")
+        let full_path = self.resolve(path);
+        let options = self.options;
+        let runtime = self.runtime.clone();
+        let read = self.read;
+        #[cfg(feature = "mmap")]
+        let mmap = self.mmap;
+        self.runtime.unblock(move || {
+            let full_path = full_path?;
+            if !options.stat(&full_path)?.is_file() {
+                return Err(not_a_file());
             }
 
-            Ok(FsFile { path: full_path })
-        }
+            Ok(FsFile {
+                #[cfg(feature = "mmap")]
+                map: if mmap {
+                    Some(map_file(&full_path)?)
+                } else {
+                    None
+                },
+                path: full_path,
+                runtime,
+                read,
+            })
+        })
     }
 
     fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
-        async move {
-            let full_path = path.to_logical_path(&self.root);
-            if !full_path.is_file() {
+        let full_path = self.resolve(path);
+        let options = self.options;
+        self.runtime.unblock(move || {
+            let full_path = full_path?;
+            if !options.stat(&full_path).is_ok_and(|meta| meta.is_file()) {
                 return Ok(());
             }
 
-            tokio::fs::remove_file(full_path).await?;
-
-            Ok(())
-        }
+            std::fs::remove_file(full_path)
+        })
     }
 
     fn write_file(
@@ -75,31 +155,69 @@ impl AsyncFileStore for FsFileStore {
         path: &RelativePath,
         init: AsyncFileInit,
     ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        let full_path = self.resolve(path);
+        let options = self.options;
+        let runtime = self.runtime.clone();
         async move {
-            let full_path = path.to_logical_path(&self.root);
-            match init {
-                AsyncFileInit::Bytes(bs) => {
-                    tokio::fs::write(&full_path, &bs).await?;
+            let full_path = full_path?;
+            let target = full_path.clone();
+            if options.mkdirs_on_write || options.symlinks != SymlinkPolicy::Follow {
+                let path = full_path.clone();
+                runtime
+                    .unblock(move || {
+                        options.check_write(&path)?;
+                        match path.parent() {
+                            Some(parent) if options.mkdirs_on_write => {
+                                std::fs::create_dir_all(parent)
+                            }
+                            _ => Ok(()),
+                        }
+                    })
+                    .await?;
+            }
+
+            let len = init.len;
+            match init.body {
+                AsyncFileBody::Bytes(bs) => {
+                    runtime
+                        .unblock(move || std::fs::write(&full_path, &bs))
+                        .await?;
                 }
-                AsyncFileInit::Stream(mut stream) => {
-                    let mut file = tokio::fs::OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .truncate(true)
-                        .open(&full_path)
+                AsyncFileBody::Path(path) => {
+                    runtime
+                        .unblock(move || copy_file(&path, &full_path))
+                        .await?;
+                }
+                body => {
+                    let mut stream = body.into_stream().await?;
+                    let mut file = runtime
+                        .unblock(move || create_file(&full_path, len))
                         .await?;
 
-                    while let Some(mut next) = stream.try_next().await? {
-                        file.write_all_buf(&mut next).await?;
+                    let mut written = 0;
+                    while let Some(next) = stream.try_next().await? {
+                        written += next.len() as u64;
+                        file = runtime
+                            .unblock(move || {
+                                file.write_all(&next)?;
+                                Ok(file)
+                            })
+                            .await?;
                     }
 
-                    file.flush().await?;
-                }
-                AsyncFileInit::Path(path) => {
-                    tokio::fs::copy(path, full_path).await?;
+                    runtime
+                        .unblock(move || finish_file(file, len, written))
+                        .await?;
                 }
             }
 
+            if options.xattrs() && !init.attributes.is_empty() {
+                let attributes = init.attributes;
+                runtime
+                    .unblock(move || write_xattrs(&target, &attributes))
+                    .await?;
+            }
+
             Ok(())
         }
     }
@@ -110,28 +228,79 @@ impl AsyncFileStore for FsFileStore {
         Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
     > + Send {
         let root = self.root.clone();
+        let options = self.options;
+        let runtime = self.runtime.clone();
+        #[cfg(feature = "ignore")]
+        let matcher = self
+            .ignore
+            .as_ref()
+            .map(|rules| std::sync::Arc::new(rules.matcher()));
         async move {
             let stream = try_stream! {
 
+              // Every queued directory carries the resolved paths of itself and its
+              // ancestors, so links leading back up the tree are not walked forever
               let mut queue = VecDeque::default();
-              queue.push_back(root.clone());
+              queue.push_back((root.clone(), vec![root.clone()]));
               loop {
-                let Some(next) = queue.pop_front() else {
+                let Some((next, ancestors)) = queue.pop_front() else {
                   break;
                 };
 
-                let mut read_dir = tokio::fs::read_dir(&next).await?;
+                #[cfg(feature = "ignore")]
+                let matcher = matcher.clone();
+                let entries = runtime.unblock(move || {
+                  let mut entries = Vec::new();
+                  for entry in std::fs::read_dir(&next)? {
+                    let entry = entry?;
+                    if !options.include_hidden && is_hidden(&entry.file_name()) {
+                      continue;
+                    }
 
-                while let Some(next) = read_dir.next_entry().await? {
-                  let path = next.path();
-                  if path.is_dir() {
-                    queue.push_back(path);
-                    continue;
+                    let path = entry.path();
+                    let file_type = entry.file_type()?;
+                    let is_dir = if file_type.is_symlink() {
+                      match options.symlinks {
+                        SymlinkPolicy::Follow => path.is_dir(),
+                        SymlinkPolicy::Skip => continue,
+                        SymlinkPolicy::Error => return Err(symlink_error(&path)),
+                      }
+                    } else {
+                      file_type.is_dir()
+                    };
+
+                    #[cfg(feature = "ignore")]
+                    if matcher.as_ref().is_some_and(|matcher| matcher.is_ignored(&path, is_dir)) {
+                      continue;
+                    }
+
+                    let real = if !is_dir {
+                      None
+                    } else if file_type.is_symlink() {
+                      Some(std::fs::canonicalize(&path)?)
+                    } else {
+                      ancestors.last().map(|dir| dir.join(entry.file_name()))
+                    };
+
+                    if real.as_ref().is_some_and(|real| ancestors.contains(real)) {
+                      continue;
+                    }
+
+                    entries.push((path, real));
                   }
+                  Ok((entries, ancestors))
+                }).await?;
 
-                  let rel_path = pathdiff::diff_paths(&path, &root);
+                let (entries, ancestors) = entries;
+                for (path, real) in entries {
+                  if let Some(real) = real {
+                    let mut ancestors = ancestors.clone();
+                    ancestors.push(real);
+                    queue.push_back((path, ancestors));
+                    continue;
+                  }
 
-                  yield RelativePathBuf::from_path(rel_path.unwrap()).unwrap();
+                  yield relative(&path, &root)?;
                 }
               }
 
@@ -140,71 +309,287 @@ impl AsyncFileStore for FsFileStore {
             Ok(Box::pin(stream) as BoxStream<'static, Result<RelativePathBuf, io::Error>>)
         }
     }
+
+    /// Resizes the file in place.
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let full_path = self.resolve(path);
+        let options = self.options;
+        self.runtime
+            .unblock(move || resize_file(&full_path?, options, len))
+    }
+
+    /// Seeks and writes in place.
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let full_path = self.resolve(path);
+        let options = self.options;
+        self.runtime
+            .unblock(move || write_at(&full_path?, options, offset, &data))
+    }
+
+    fn supports_write_range(&self) -> bool {
+        true
+    }
+
+    /// Sets the mode on unix. Elsewhere only the readonly flag is kept.
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let full_path = self.resolve(path);
+        let options = self.options;
+        self.runtime
+            .unblock(move || apply_permissions(&full_path?, options, permissions))
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.options.xattrs()
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let paths = link_target(link, target)
+            .and_then(|resolved| self.resolve(&resolved))
+            .and_then(|_| self.resolve(link));
+        let target = target.to_path("");
+        let options = self.options;
+        self.runtime
+            .unblock(move || make_symlink(&target, &paths?, options))
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let paths = self
+            .resolve(src)
+            .and_then(|src| Ok((src, self.resolve(dst)?)));
+        let options = self.options;
+        self.runtime.unblock(move || {
+            let (src, dst) = paths?;
+            make_hard_link(&src, &dst, options)
+        })
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        let full_path = self.resolve(path);
+        let path = path.to_relative_path_buf();
+        let options = self.options;
+        self.runtime
+            .unblock(move || link_of(&full_path?, &path, options))
+    }
+
+    fn supports_links(&self) -> bool {
+        cfg!(any(unix, windows))
+    }
 }
 
-impl AsyncFile for FsFile {
-    type Body = ReaderStream<tokio::fs::File>;
+impl<R> AsyncFile for FsFile<R>
+where
+    R: Runtime,
+{
+    type Body = BoxStream<'static, Result<Bytes, io::Error>>;
 
     fn read_range(
         &self,
         range: std::ops::Range<u64>,
     ) -> impl Future<Output = Result<Bytes, std::io::Error>> + Send {
+        #[cfg(feature = "mmap")]
+        let map = self.map.clone();
+        let path = self.path.clone();
+        let runtime = self.runtime.clone();
         async move {
-            let mut opts = tokio::fs::OpenOptions::new();
-            let mut file = opts.read(true).open(&self.path).await?;
-            file.seek(SeekFrom::Start(range.start)).await?;
-
-            let count = (range.end - range.start) as usize;
-            let mut buf = vec![0; count];
-            file.read_exact(&mut buf).await?;
+            #[cfg(feature = "mmap")]
+            if let Some(map) = map {
+                return crate::util::slice(&map, range);
+            }
 
-            Ok(buf.into())
+            runtime.unblock(move || read_at(&path, range)).await
         }
     }
 
     fn reader(&self) -> impl Future<Output = Result<Self::Body, std::io::Error>> + Send {
+        let path = self.path.clone();
+        let runtime = self.runtime.clone();
+        let read = self.read;
         async move {
-            let mut opts = tokio::fs::OpenOptions::new();
-            let file = opts.read(true).open(&self.path).await?;
-            Ok(ReaderStream::new(file))
+            let file = runtime
+                .unblock(move || std::fs::OpenOptions::new().read(true).open(&path))
+                .await?;
+
+            let mut buffer = Buffer::acquire();
+            if let Some(size) = read.chunk_size {
+                buffer = buffer.chunk_size(size);
+            }
+
+            let stream = try_stream! {
+                let mut next = maybe_done(read_chunk(&runtime, file, buffer));
+                loop {
+                    (&mut next).await;
+                    let (file, buffer, chunk) = Pin::new(&mut next)
+                        .take_output()
+                        .expect("chunk read to completion")?;
+
+                    if chunk.is_empty() {
+                        break;
+                    }
+
+                    next = maybe_done(read_chunk(&runtime, file, buffer));
+                    if read.read_ahead {
+                        // Start reading the next chunk while this one is being consumed
+                        let _ = futures::poll!(&mut next);
+                    }
+
+                    yield chunk;
+                }
+            };
+
+            Ok(Box::pin(stream) as Self::Body)
         }
     }
 
     fn url(&self) -> Option<url::Url> {
         Url::from_file_path(&self.path).ok()
     }
+
+    fn native_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
 }
 
 // Sync
 
-pub struct FsFileStore {
+pub struct FsFileStore<R = Tokio> {
     root: PathBuf,
+    runtime: R,
+    options: Options,
+    read: ReadOptions,
+    #[cfg(feature = "mmap")]
+    mmap: bool,
+    #[cfg(feature = "ignore")]
+    ignore: Option<std::sync::Arc<ignore::IgnoreRules>>,
 }
 
 impl FsFileStore {
-    pub fn new(path: PathBuf) -> Result<FsFileStore, io::Error> {
-        Ok(FsFileStore {
-            root: std::fs::canonicalize(path)?,
-        })
+    pub fn new(path: impl AsRef<Path>) -> Result<FsFileStore, io::Error> {
+        FsFileStore::builder(path).build()
     }
 
-    pub async fn new_async(path: PathBuf) -> Result<FsFileStore, io::Error> {
-        Ok(FsFileStore {
-            root: tokio::fs::canonicalize(path).await?,
-        })
+    pub async fn new_async(path: impl AsRef<Path>) -> Result<FsFileStore, io::Error> {
+        FsFileStore::builder(path).build_async().await
+    }
+
+    pub fn builder(path: impl AsRef<Path>) -> FsFileStoreBuilder {
+        FsFileStoreBuilder {
+            root: path.as_ref().to_path_buf(),
+            runtime: Tokio,
+            create_root: false,
+            options: Options::default(),
+            #[cfg(feature = "ignore")]
+            ignore_patterns: Vec::new(),
+            #[cfg(feature = "ignore")]
+            ignore_files: false,
+        }
+    }
+}
+
+impl<R> FsFileStore<R>
+where
+    R: Runtime,
+{
+    pub fn with_runtime(path: impl AsRef<Path>, runtime: R) -> Result<FsFileStore<R>, io::Error> {
+        FsFileStore::builder(path).runtime(runtime).build()
+    }
+
+    pub async fn with_runtime_async(
+        path: impl AsRef<Path>,
+        runtime: R,
+    ) -> Result<FsFileStore<R>, io::Error> {
+        FsFileStore::builder(path)
+            .runtime(runtime)
+            .build_async()
+            .await
+    }
+
+    fn resolve(&self, path: &RelativePath) -> io::Result<PathBuf> {
+        if !self.options.include_hidden
+            && path.components().any(
+                |component| matches!(component, Component::Normal(name) if name.starts_with('.')),
+            )
+        {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+
+        #[cfg(windows)]
+        for component in path.components() {
+            if let Component::Normal(name) = component {
+                check_windows_name(name)?;
+            }
+        }
+
+        Ok(path.to_logical_path(&self.root))
+    }
+
+    /// The canonicalized directory the store is rooted at.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Maximum size of the chunks streamed by `reader`. Defaults to the chunk size of
+    /// `util::Config`.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.read.chunk_size = Some(size.max(1));
+        self
+    }
+
+    /// Read the next chunk of a `reader` in the background while the current one is
+    /// consumed.
+    pub fn read_ahead(mut self, enabled: bool) -> Self {
+        self.read.read_ahead = enabled;
+        self
+    }
+
+    /// Memory map files when they are opened, so `read_range` slices the map instead of
+    /// issuing a seek and read per call.
+    ///
+    /// Files must not be truncated by other processes while they are open; reading a
+    /// truncated region of a map terminates the process.
+    #[cfg(feature = "mmap")]
+    pub fn mmap(mut self, enabled: bool) -> Self {
+        self.mmap = enabled;
+        self
     }
 }
 
-impl FileStore for FsFileStore {
-    type File = FsFile;
+impl<R> FileStore for FsFileStore<R>
+where
+    R: Runtime,
+{
+    type File = FsFile<R>;
 
     type List = Box<dyn Iterator<Item = io::Result<RelativePathBuf>> + Send>;
 
     fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
-        let meta = std::fs::metadata(path.to_logical_path(&self.root))?;
+        let full_path = self.resolve(path)?;
+        let meta = self.options.stat(&full_path)?;
 
         if !meta.is_file() {
-            return Err(io::Error::other("not a file"));
+            return Err(not_a_file());
         }
 
         let mime = if let Some(ext) = path.extension() {
@@ -215,23 +600,40 @@ impl FileStore for FsFileStore {
 
         Ok(Metadata {
             path: path.to_relative_path_buf(),
-            size: meta.size(),
+            size: meta.len(),
             mime,
+            modified: meta.modified().ok(),
+            permissions: Some(permissions_of(&meta)),
+            attributes: self.options.attributes_of(&full_path)?,
         })
     }
 
     fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
-        let full_path = path.to_logical_path(&self.root);
-        if !full_path.is_file() {
-            panic!("file does not exits")
+        let full_path = self.resolve(path)?;
+        if !self.options.stat(&full_path)?.is_file() {
+            return Err(not_a_file());
         }
 
-        Ok(FsFile { path: full_path })
+        Ok(FsFile {
+            #[cfg(feature = "mmap")]
+            map: if self.mmap {
+                Some(map_file(&full_path)?)
+            } else {
+                None
+            },
+            path: full_path,
+            runtime: self.runtime.clone(),
+            read: self.read,
+        })
     }
 
     fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
-        let full_path = path.to_logical_path(&self.root);
-        if !full_path.is_file() {
+        let full_path = self.resolve(path)?;
+        if !self
+            .options
+            .stat(&full_path)
+            .is_ok_and(|meta| meta.is_file())
+        {
             return Ok(());
         }
 
@@ -241,25 +643,30 @@ impl FileStore for FsFileStore {
     }
 
     fn write_file(&self, path: &RelativePath, init: FileInit) -> Result<(), io::Error> {
-        let full_path = path.to_logical_path(&self.root);
-        match init {
-            FileInit::Bytes(bs) => {
-                std::fs::write(&full_path, &bs)?;
+        let full_path = self.resolve(path)?;
+        self.options.check_write(&full_path)?;
+        if self.options.mkdirs_on_write {
+            if let Some(parent) = full_path.parent() {
+                std::fs::create_dir_all(parent)?;
             }
-            FileInit::Read(mut stream) => {
-                let mut file = std::fs::OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&full_path)?;
-
-                std::io::copy(&mut stream, &mut file)?;
+        }
 
-                file.flush()?;
+        match init.body {
+            FileBody::Bytes(bs) => {
+                std::fs::write(&full_path, &bs)?;
             }
-            FileInit::Path(path) => {
-                std::fs::copy(path, full_path)?;
+            FileBody::Read(mut stream) => {
+                let mut file = create_file(&full_path, init.len)?;
+                let written = std::io::copy(&mut stream, &mut file)?;
+                finish_file(file, init.len, written)?;
             }
+            FileBody::Path(path) => {
+                copy_file(&path, &full_path)?;
+            }
+        }
+
+        if self.options.xattrs() && !init.attributes.is_empty() {
+            write_xattrs(&full_path, &init.attributes)?;
         }
 
         Ok(())
@@ -267,39 +674,825 @@ impl FileStore for FsFileStore {
 
     fn list(&self) -> Self::List {
         let root = self.root.clone();
+        let options = self.options;
+        #[cfg(feature = "ignore")]
+        let matcher = self.ignore.as_ref().map(|rules| rules.matcher());
         Box::new(
             walkdir::WalkDir::new(&self.root)
+                .follow_links(options.symlinks == SymlinkPolicy::Follow)
                 .into_iter()
-                .filter_map(move |m| match m {
-                    Ok(m) => {
-                        let path = m.path();
-                        let rel_path = pathdiff::diff_paths(&path, &root);
-
-                        rel_path
-                            .and_then(|m| RelativePathBuf::from_path(m).ok())
-                            .map(Ok)
+                .filter_entry(move |entry| {
+                    #[cfg(feature = "ignore")]
+                    if entry.depth() > 0
+                        && matcher.as_ref().is_some_and(|matcher| {
+                            matcher.is_ignored(entry.path(), entry.file_type().is_dir())
+                        })
+                    {
+                        return false;
                     }
-                    Err(err) => {
-                        // Some(Err(err));
-                        panic!()
+
+                    (options.include_hidden || entry.depth() == 0 || !is_hidden(entry.file_name()))
+                        && (options.symlinks != SymlinkPolicy::Skip || !entry.path_is_symlink())
+                })
+                .filter_map(move |m| match m {
+                    Ok(m) if options.symlinks == SymlinkPolicy::Error && m.path_is_symlink() => {
+                        Some(Err(symlink_error(m.path())))
                     }
+                    Ok(m) if m.file_type().is_dir() => None,
+                    Ok(m) => Some(relative(m.path(), &root)),
+                    // Links leading back to an ancestor are skipped, like the async list does
+                    Err(err) if err.loop_ancestor().is_some() => None,
+                    Err(err) => Some(Err(err.into())),
                 }),
         )
     }
+
+    /// Resizes the file in place.
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        resize_file(&self.resolve(path)?, self.options, len)
+    }
+
+    /// Seeks and writes in place.
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        write_at(&self.resolve(path)?, self.options, offset, &data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        true
+    }
+
+    /// Sets the mode on unix. Elsewhere only the readonly flag is kept.
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        apply_permissions(&self.resolve(path)?, self.options, permissions)
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.options.xattrs()
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        self.resolve(&link_target(link, target)?)?;
+        make_symlink(&target.to_path(""), &self.resolve(link)?, self.options)
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        make_hard_link(&self.resolve(src)?, &self.resolve(dst)?, self.options)
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        link_of(&self.resolve(path)?, path, self.options)
+    }
+
+    fn supports_links(&self) -> bool {
+        cfg!(any(unix, windows))
+    }
 }
 
-pub struct FsFile {
+pub struct FsFile<R = Tokio> {
     path: PathBuf,
+    runtime: R,
+    read: ReadOptions,
+    #[cfg(feature = "mmap")]
+    map: Option<Bytes>,
 }
 
-impl File for FsFile {
+impl<R> File for FsFile<R> {
     type Body = std::fs::File;
 
     fn read_range(&self, range: std::ops::Range<u64>) -> Result<Bytes, io::Error> {
-        todo!()
+        #[cfg(feature = "mmap")]
+        if let Some(map) = &self.map {
+            return crate::util::slice(map, range);
+        }
+
+        read_at(&self.path, range)
     }
 
     fn reader(&self) -> Result<Self::Body, io::Error> {
         std::fs::OpenOptions::new().read(true).open(&self.path)
     }
+
+    fn url(&self) -> Option<url::Url> {
+        Url::from_file_path(&self.path).ok()
+    }
+
+    fn native_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// Configures a `FsFileStore`.
+pub struct FsFileStoreBuilder<R = Tokio> {
+    root: PathBuf,
+    runtime: R,
+    create_root: bool,
+    options: Options,
+    #[cfg(feature = "ignore")]
+    ignore_patterns: Vec<String>,
+    #[cfg(feature = "ignore")]
+    ignore_files: bool,
+}
+
+impl<R> FsFileStoreBuilder<R>
+where
+    R: Runtime,
+{
+    /// Run blocking calls on `runtime` instead of tokio.
+    pub fn runtime<T: Runtime>(self, runtime: T) -> FsFileStoreBuilder<T> {
+        FsFileStoreBuilder {
+            root: self.root,
+            runtime,
+            create_root: self.create_root,
+            options: self.options,
+            #[cfg(feature = "ignore")]
+            ignore_patterns: self.ignore_patterns,
+            #[cfg(feature = "ignore")]
+            ignore_files: self.ignore_files,
+        }
+    }
+
+    /// Create the root directory if it doesn't exist. Defaults to false.
+    pub fn create_root(mut self, enabled: bool) -> Self {
+        self.create_root = enabled;
+        self
+    }
+
+    /// Resolve symbolic links. When disabled, links are reported as missing and skipped
+    /// by `list`. Defaults to true.
+    pub fn follow_symlinks(mut self, enabled: bool) -> Self {
+        self.options.symlinks = if enabled {
+            SymlinkPolicy::Follow
+        } else {
+            SymlinkPolicy::Skip
+        };
+        self
+    }
+
+    /// How symbolic links are treated by `metadata`, `open_file` and `list`. Defaults to
+    /// [`SymlinkPolicy::Follow`].
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.options.symlinks = policy;
+        self
+    }
+
+    /// Expose files and directories whose name starts with a dot, like `.git` or `.env`.
+    /// When disabled, they are left out of listings and reported as missing. Defaults to
+    /// false.
+    pub fn include_hidden(mut self, enabled: bool) -> Self {
+        self.options.include_hidden = enabled;
+        self
+    }
+
+    #[deprecated(note = "renamed to `include_hidden`, which defaults to false")]
+    pub fn allow_hidden(self, enabled: bool) -> Self {
+        self.include_hidden(enabled)
+    }
+
+    /// Create missing parent directories when writing a file. Defaults to false.
+    pub fn mkdirs_on_write(mut self, enabled: bool) -> Self {
+        self.options.mkdirs_on_write = enabled;
+        self
+    }
+
+    /// Keep the [`Attributes`] of files as extended attributes in the `user.` namespace.
+    /// Only available on Linux, and on filesystems with xattr support. Defaults to false, as
+    /// it costs extra syscalls on every `metadata`.
+    pub fn xattrs(mut self, enabled: bool) -> Self {
+        self.options.xattrs = enabled;
+        self
+    }
+
+    /// Skip files and directories excluded by `.gitignore` and `.ignore` files while
+    /// listing. Defaults to false.
+    #[cfg(feature = "ignore")]
+    pub fn ignore_files(mut self, enabled: bool) -> Self {
+        self.ignore_files = enabled;
+        self
+    }
+
+    /// Skip files and directories matching the gitignore-style `pattern` while listing,
+    /// e.g. `node_modules/` or `*.tmp`. Takes precedence over ignore files.
+    #[cfg(feature = "ignore")]
+    pub fn ignore(mut self, pattern: impl Into<String>) -> Self {
+        self.ignore_patterns.push(pattern.into());
+        self
+    }
+
+    pub fn build(self) -> io::Result<FsFileStore<R>> {
+        let root = canonicalize_root(&self.root, self.create_root)?;
+        self.finish(root)
+    }
+
+    pub async fn build_async(self) -> io::Result<FsFileStore<R>> {
+        let path = self.root.clone();
+        let create_root = self.create_root;
+        let root = self
+            .runtime
+            .unblock(move || canonicalize_root(&path, create_root))
+            .await?;
+        self.finish(root)
+    }
+
+    fn finish(self, root: PathBuf) -> io::Result<FsFileStore<R>> {
+        #[cfg(feature = "ignore")]
+        let ignore = if self.ignore_files || !self.ignore_patterns.is_empty() {
+            let rules = ignore::IgnoreRules::new(&root, &self.ignore_patterns, self.ignore_files)?;
+            Some(std::sync::Arc::new(rules))
+        } else {
+            None
+        };
+
+        let options = Options {
+            root_depth: root.components().count(),
+            ..self.options
+        };
+
+        Ok(FsFileStore {
+            root,
+            runtime: self.runtime,
+            options,
+            read: ReadOptions::default(),
+            #[cfg(feature = "mmap")]
+            mmap: false,
+            #[cfg(feature = "ignore")]
+            ignore,
+        })
+    }
+}
+
+fn canonicalize_root(path: &Path, create: bool) -> io::Result<PathBuf> {
+    if create {
+        std::fs::create_dir_all(path)?;
+    }
+    std::fs::canonicalize(path)
+}
+
+/// How a `FsFileStore` treats symbolic links, both for the files themselves and for the
+/// directories on the way to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Resolve links. Directory links leading back to one of their ancestors are not walked
+    /// by `list`.
+    #[default]
+    Follow,
+    /// Report links as missing and leave them out of listings.
+    Skip,
+    /// Fail with `ErrorKind::Unsupported` when a link is encountered.
+    Error,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Options {
+    symlinks: SymlinkPolicy,
+    include_hidden: bool,
+    mkdirs_on_write: bool,
+    xattrs: bool,
+    /// Number of components of the root, telling which components of a resolved path are
+    /// within the store.
+    root_depth: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            symlinks: SymlinkPolicy::Follow,
+            include_hidden: false,
+            mkdirs_on_write: false,
+            xattrs: false,
+            root_depth: 0,
+        }
+    }
+}
+
+impl Options {
+    fn xattrs(&self) -> bool {
+        cfg!(target_os = "linux") && self.xattrs
+    }
+
+    /// The extended attributes of `path` if enabled. Filesystems without xattr support have
+    /// none.
+    fn attributes_of(&self, path: &Path) -> io::Result<Attributes> {
+        if !self.xattrs() {
+            return Ok(Attributes::new());
+        }
+        match read_xattrs(path) {
+            Err(err) if err.kind() == io::ErrorKind::Unsupported => Ok(Attributes::new()),
+            ret => ret,
+        }
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<std::fs::Metadata> {
+        if self.symlinks == SymlinkPolicy::Follow {
+            return std::fs::metadata(path);
+        }
+
+        self.check_parents(path)?;
+        let meta = std::fs::symlink_metadata(path)?;
+        if meta.file_type().is_symlink() {
+            return Err(self.link_error(path));
+        }
+
+        Ok(meta)
+    }
+
+    /// Apply the link policy to the directories between the root and `path`, so a linked
+    /// directory can't lead outside the root when links aren't followed.
+    fn check_parents(&self, path: &Path) -> io::Result<()> {
+        if self.symlinks == SymlinkPolicy::Follow {
+            return Ok(());
+        }
+
+        let depth = path.components().count();
+        let parents = path
+            .ancestors()
+            .skip(1)
+            .take(depth.saturating_sub(self.root_depth + 1))
+            .collect::<Vec<_>>();
+
+        for parent in parents.into_iter().rev() {
+            match std::fs::symlink_metadata(parent) {
+                Ok(meta) if meta.file_type().is_symlink() => return Err(self.link_error(parent)),
+                Ok(_) => {}
+                // Nothing below a missing directory can be a link
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply the link policy to every component of a path about to be written, including
+    /// the file itself, which may not exist yet.
+    fn check_write(&self, path: &Path) -> io::Result<()> {
+        if self.symlinks == SymlinkPolicy::Follow {
+            return Ok(());
+        }
+
+        self.check_parents(path)?;
+        match std::fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_symlink() => Err(self.link_error(path)),
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn link_error(&self, path: &Path) -> io::Error {
+        if self.symlinks == SymlinkPolicy::Error {
+            return symlink_error(path);
+        }
+
+        io::Error::new(io::ErrorKind::NotFound, "symbolic links are not followed")
+    }
+}
+
+fn symlink_error(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} is a symbolic link", path.display()),
+    )
+}
+
+fn not_a_file() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "not a file")
+}
+
+/// Read `range` of the file at `path` with a seek and an exact read.
+fn read_at(path: &Path, range: std::ops::Range<u64>) -> io::Result<Bytes> {
+    let count = range.end.checked_sub(range.start).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "range ends before it starts")
+    })?;
+
+    let mut file = std::fs::OpenOptions::new().read(true).open(path)?;
+    // Checked before allocating, so a huge range can't exhaust memory
+    if range.end > file.metadata()?.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "range out of bounds",
+        ));
+    }
+    file.seek(SeekFrom::Start(range.start))?;
+
+    let mut buf = vec![0; count as usize];
+    file.read_exact(&mut buf)?;
+
+    Ok(buf.into())
+}
+
+fn is_hidden(name: &std::ffi::OsStr) -> bool {
+    name.as_encoded_bytes().starts_with(b".")
+}
+
+/// The path of a listed file relative to the root, joined with `/` on every platform.
+fn relative(path: &Path, root: &Path) -> io::Result<RelativePathBuf> {
+    pathdiff::diff_paths(path, root)
+        .and_then(|path| RelativePathBuf::from_path(path).ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a valid relative path", path.display()),
+            )
+        })
+}
+
+/// Reject names Windows would interpret as something other than a plain file name.
+///
+/// A relative path only treats `/` as separator, so a backslash or drive prefix inside a component
+/// would otherwise escape the root once the path is joined natively.
+#[cfg(any(windows, test))]
+fn check_windows_name(name: &str) -> io::Result<()> {
+    const RESERVED: &[&str] = &[
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    let invalid = name.contains(['\\', ':', '<', '>', '"', '|', '?', '*'])
+        || name.chars().any(char::is_control)
+        || name.ends_with(['.', ' '])
+        || RESERVED
+            .iter()
+            .any(|reserved| stem.eq_ignore_ascii_case(reserved));
+
+    if invalid {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{name:?} is not a valid file name on windows"),
+        ));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ReadOptions {
+    chunk_size: Option<usize>,
+    read_ahead: bool,
+}
+
+fn read_chunk<R: Runtime>(
+    runtime: &R,
+    mut file: std::fs::File,
+    mut buffer: Buffer,
+) -> BoxFuture<'static, io::Result<(std::fs::File, Buffer, Bytes)>> {
+    let runtime = runtime.clone();
+    async move {
+        runtime
+            .unblock(move || {
+                let chunk = buffer.read_chunk(&mut file)?;
+                Ok((file, buffer, chunk))
+            })
+            .await
+    }
+    .boxed()
+}
+
+#[cfg(feature = "mmap")]
+fn map_file(path: &Path) -> io::Result<Bytes> {
+    let file = std::fs::File::open(path)?;
+    // SAFETY: the map is read only. See `FsFileStore::mmap` for the truncation caveat.
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    Ok(Bytes::from_owner(map))
+}
+
+/// Create or truncate a file, reserving `len` bytes when the size is known up front.
+fn create_file(path: &Path, len: Option<u64>) -> io::Result<std::fs::File> {
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+
+    if let Some(len) = len {
+        file.set_len(len)?;
+    }
+
+    Ok(file)
+}
+
+/// Flush a file created by `create_file`, trimming it if the body was shorter than its hint.
+fn finish_file(mut file: std::fs::File, len: Option<u64>, written: u64) -> io::Result<()> {
+    file.flush()?;
+    if len.is_some_and(|len| len != written) {
+        file.set_len(written)?;
+    }
+    Ok(())
+}
+
+/// Truncate or extend an existing file, leaving a sparse tail where supported.
+fn resize_file(path: &Path, options: Options, len: u64) -> io::Result<()> {
+    if !options.stat(path)?.is_file() {
+        return Err(not_a_file());
+    }
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(path)?
+        .set_len(len)
+}
+
+/// Write `data` into an existing file at `offset`. Seeking past the end leaves a gap which
+/// reads as zeros.
+fn write_at(path: &Path, options: Options, offset: u64, data: &[u8]) -> io::Result<()> {
+    if !options.stat(path)?.is_file() {
+        return Err(not_a_file());
+    }
+    let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(data)
+}
+
+fn permissions_of(meta: &std::fs::Metadata) -> Permissions {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Permissions::from_mode(meta.permissions().mode())
+    }
+    #[cfg(not(unix))]
+    {
+        let mut permissions = Permissions::default();
+        permissions.set_readonly(meta.permissions().readonly());
+        permissions
+    }
+}
+
+fn apply_permissions(path: &Path, options: Options, permissions: Permissions) -> io::Result<()> {
+    let meta = options.stat(path)?;
+    if !meta.is_file() {
+        return Err(not_a_file());
+    }
+
+    #[cfg(unix)]
+    let native = {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::Permissions::from_mode(permissions.mode())
+    };
+    #[cfg(not(unix))]
+    let native = {
+        let mut native = meta.permissions();
+        native.set_readonly(permissions.readonly());
+        native
+    };
+
+    std::fs::set_permissions(path, native)
+}
+
+fn make_symlink(target: &Path, link: &Path, options: Options) -> io::Result<()> {
+    options.check_write(link)?;
+    if options.mkdirs_on_write {
+        if let Some(parent) = link.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(target, link);
+    #[cfg(windows)]
+    return std::os::windows::fs::symlink_file(target, link);
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (target, link);
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+fn make_hard_link(src: &Path, dst: &Path, options: Options) -> io::Result<()> {
+    if !options.stat(src)?.is_file() {
+        return Err(not_a_file());
+    }
+    options.check_write(dst)?;
+    if options.mkdirs_on_write {
+        if let Some(parent) = dst.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    std::fs::hard_link(src, dst)
+}
+
+/// What the file at `path` is a link to. Symlinks with absolute targets or targets outside
+/// the root are reported as plain files, as they can't be recreated within a store.
+fn link_of(path: &Path, link: &RelativePath, options: Options) -> io::Result<Option<Link>> {
+    options.check_parents(path)?;
+    let meta = std::fs::symlink_metadata(path)?;
+
+    if meta.file_type().is_symlink() {
+        if !options.stat(path)?.is_file() {
+            return Err(not_a_file());
+        }
+        let target = std::fs::read_link(path)?;
+        return Ok(RelativePathBuf::from_path(&target)
+            .ok()
+            .filter(|target| link_target(link, target).is_ok())
+            .map(Link::Symlink));
+    }
+
+    if !meta.is_file() {
+        return Err(not_a_file());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::hash::{Hash, Hasher};
+        use std::os::unix::fs::MetadataExt;
+
+        if meta.nlink() > 1 {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            (meta.dev(), meta.ino()).hash(&mut hasher);
+            return Ok(Some(Link::Hard(hasher.finish())));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Extended attributes are kept in the namespace open to unprivileged processes.
+const XATTR_NAMESPACE: &str = "user.";
+
+#[cfg(target_os = "linux")]
+fn read_xattrs(path: &Path) -> io::Result<Attributes> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: the path is nul terminated and the buffer is valid for `len` bytes
+    let names =
+        xattr_buffer(|buf, len| unsafe { libc::listxattr(path.as_ptr(), buf.cast(), len) })?;
+
+    let mut attributes = Attributes::new();
+    for name in names.split(|b| *b == 0) {
+        let Some(key) = name.strip_prefix(XATTR_NAMESPACE.as_bytes()) else {
+            continue;
+        };
+        let name = CString::new(name)?;
+        // SAFETY: as above
+        let value = xattr_buffer(|buf, len| unsafe {
+            libc::getxattr(path.as_ptr(), name.as_ptr(), buf.cast(), len)
+        })?;
+
+        // Attributes which aren't text weren't written through a store
+        if let (Ok(key), Ok(value)) = (std::str::from_utf8(key), String::from_utf8(value)) {
+            attributes.insert(key.to_string(), value);
+        }
+    }
+
+    Ok(attributes)
+}
+
+/// Size a buffer with a first call and fill it with a second, again if it grew in between.
+#[cfg(target_os = "linux")]
+fn xattr_buffer(call: impl Fn(*mut u8, usize) -> libc::ssize_t) -> io::Result<Vec<u8>> {
+    loop {
+        let len = call(std::ptr::null_mut(), 0);
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buf = vec![0; len as usize];
+        let len = call(buf.as_mut_ptr(), buf.len());
+        if len >= 0 {
+            buf.truncate(len as usize);
+            return Ok(buf);
+        }
+
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ERANGE) {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn write_xattrs(path: &Path, attributes: &Attributes) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    for (key, value) in attributes {
+        let name = CString::new(format!("{XATTR_NAMESPACE}{key}"))?;
+        // SAFETY: both strings are nul terminated and the value is valid for its length
+        let ret = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+            )
+        };
+        if ret == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_xattrs(_path: &Path) -> io::Result<Attributes> {
+    Ok(Attributes::new())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn write_xattrs(_path: &Path, _attributes: &Attributes) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Copy a file, cloning it on filesystems with reflink support.
+/// Falls back to `std::fs::copy`, which uses `copy_file_range` where available.
+fn copy_file(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    if reflink(from, to).is_ok() {
+        return Ok(());
+    }
+
+    std::fs::copy(from, to)?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    // _IOW(0x94, 9, int)
+    const FICLONE: u64 = 0x40049409;
+
+    let source = std::fs::File::open(from)?;
+    let target = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(to)?;
+
+    // SAFETY: both descriptors are valid for the duration of the call
+    let ret = unsafe { libc::ioctl(target.as_raw_fd(), FICLONE as _, source.as_raw_fd()) };
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_joins_with_slash() {
+        let root = std::env::temp_dir();
+        let path = root.join("assets").join("css").join("app.css");
+        assert_eq!(relative(&path, &root).unwrap(), "assets/css/app.css");
+    }
+
+    #[test]
+    fn relative_of_root_is_empty() {
+        let root = std::env::temp_dir();
+        assert_eq!(relative(&root, &root).unwrap(), "");
+    }
+
+    #[test]
+    fn windows_reserved_names() {
+        for name in [
+            "CON",
+            "con",
+            "nul.txt",
+            "Aux.tar.gz",
+            "COM1",
+            "lpt9.log",
+            "PRN .md",
+        ] {
+            assert!(check_windows_name(name).is_err(), "{name}");
+        }
+        for name in [
+            "CONFIG.sys",
+            "console",
+            "COM10",
+            "auxiliary.txt",
+            "nul_device",
+        ] {
+            assert!(check_windows_name(name).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn windows_separators_and_prefixes() {
+        for name in ["a\\b", "..\\secret", "C:", "c:file", "\\\\server"] {
+            let err = check_windows_name(name).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{name}");
+        }
+    }
+
+    #[test]
+    fn windows_invalid_characters_and_endings() {
+        for name in [
+            "a<b",
+            "a>b",
+            "a\"b",
+            "a|b",
+            "a?b",
+            "a*b",
+            "tab\there",
+            "dot.",
+            "space ",
+        ] {
+            assert!(check_windows_name(name).is_err(), "{name:?}");
+        }
+        assert!(check_windows_name("report 2024.final.csv").is_ok());
+    }
 }
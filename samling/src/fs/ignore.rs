@@ -0,0 +1,111 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+
+/// The files read for ignore rules in every listed directory.
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+/// The explicit ignore patterns of a `FsFileStore`, and whether ignore files are respected.
+pub(super) struct IgnoreRules {
+    root: PathBuf,
+    patterns: Gitignore,
+    files: bool,
+}
+
+impl IgnoreRules {
+    pub fn new(root: &Path, patterns: &[String], files: bool) -> io::Result<IgnoreRules> {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in patterns {
+            builder
+                .add_line(None, pattern)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        }
+
+        let patterns = builder
+            .build()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        Ok(IgnoreRules {
+            root: root.to_path_buf(),
+            patterns,
+            files,
+        })
+    }
+
+    /// A matcher for a single listing. Ignore files are read once per listing, so changes to
+    /// them are picked up by the next one.
+    pub fn matcher(self: &Arc<Self>) -> Matcher {
+        Matcher {
+            rules: self.clone(),
+            dirs: Mutex::default(),
+        }
+    }
+}
+
+pub(super) struct Matcher {
+    rules: Arc<IgnoreRules>,
+    dirs: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl Matcher {
+    /// Whether `path` is excluded. Explicit patterns take precedence over ignore files, and
+    /// the ignore files of deeper directories over those of their parents.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if let Some(ignored) = decide(self.rules.patterns.matched(path, is_dir)) {
+            return ignored;
+        }
+
+        if !self.rules.files {
+            return false;
+        }
+
+        let mut dir = path.parent();
+        while let Some(current) = dir.filter(|dir| dir.starts_with(&self.rules.root)) {
+            if let Some(ignore) = self.load(current) {
+                if let Some(ignored) = decide(ignore.matched(path, is_dir)) {
+                    return ignored;
+                }
+            }
+            dir = current.parent();
+        }
+
+        false
+    }
+
+    fn load(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        let mut dirs = self.dirs.lock().unwrap();
+        dirs.entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let mut builder = GitignoreBuilder::new(dir);
+                for name in IGNORE_FILES {
+                    let path = dir.join(name);
+                    if path.is_file() {
+                        // Invalid lines are skipped, like git does
+                        let _ = builder.add(path);
+                    }
+                }
+                builder
+                    .build()
+                    .ok()
+                    .filter(|ignore| !ignore.is_empty())
+                    .map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+fn decide<T>(matched: Match<T>) -> Option<bool> {
+    match matched {
+        Match::None => None,
+        Match::Ignore(_) => Some(true),
+        Match::Whitelist(_) => Some(false),
+    }
+}
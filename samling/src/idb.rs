@@ -0,0 +1,295 @@
+use std::{
+    io,
+    time::{Duration, SystemTime},
+};
+
+use bytes::{Bytes, BytesMut};
+use futures::{stream::BoxStream, Future, StreamExt, TryStreamExt};
+use js_sys::{Array, Object, Reflect, Uint8Array};
+use relative_path::{RelativePath, RelativePathBuf};
+use send_wrapper::SendWrapper;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbFactory, IdbObjectStore, IdbRequest, IdbTransactionMode};
+
+use crate::{AsyncFile, AsyncFileInit, AsyncFileStore, Attributes, Metadata};
+
+const META_STORE: &str = "meta";
+const BODY_STORE: &str = "body";
+
+/// A file store backed by IndexedDB.
+///
+/// Metadata and bodies are kept in separate object stores so that `metadata` and `list`
+/// never have to load file contents.
+pub struct IdbFileStore {
+    db: SendWrapper<IdbDatabase>,
+}
+
+impl IdbFileStore {
+    pub async fn open(name: &str) -> Result<IdbFileStore, io::Error> {
+        SendWrapper::new(async move {
+            let factory = factory()?;
+            let req = factory.open_with_u32(name, 1).map_err(js_error)?;
+
+            let upgrade = req.clone();
+            let on_upgrade = Closure::once_into_js(move |_: web_sys::Event| {
+                let Ok(db) = upgrade.result().and_then(|m| m.dyn_into::<IdbDatabase>()) else {
+                    return;
+                };
+                let names = db.object_store_names();
+                for store in [META_STORE, BODY_STORE] {
+                    if !names.contains(store) {
+                        let _ = db.create_object_store(store);
+                    }
+                }
+            });
+            req.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+            let db = request(&req)
+                .await?
+                .dyn_into::<IdbDatabase>()
+                .map_err(js_error)?;
+
+            Ok(IdbFileStore {
+                db: SendWrapper::new(db),
+            })
+        })
+        .await
+    }
+
+    fn object_stores(
+        &self,
+        mode: IdbTransactionMode,
+    ) -> Result<(IdbObjectStore, IdbObjectStore), io::Error> {
+        let names = Array::of2(
+            &JsValue::from_str(META_STORE),
+            &JsValue::from_str(BODY_STORE),
+        );
+        let tx = self
+            .db
+            .transaction_with_str_sequence_and_mode(&names, mode)
+            .map_err(js_error)?;
+        Ok((
+            tx.object_store(META_STORE).map_err(js_error)?,
+            tx.object_store(BODY_STORE).map_err(js_error)?,
+        ))
+    }
+
+    fn meta_store(&self) -> Result<IdbObjectStore, io::Error> {
+        self.db
+            .transaction_with_str(META_STORE)
+            .and_then(|tx| tx.object_store(META_STORE))
+            .map_err(js_error)
+    }
+}
+
+impl AsyncFileStore for IdbFileStore {
+    type File = IdbFile;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        SendWrapper::new(async move {
+            let store = self.meta_store()?;
+            let req = store.get(&key(path)).map_err(js_error)?;
+            let value = request(&req).await?;
+            if value.is_undefined() {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+
+            let size = Reflect::get(&value, &"size".into())
+                .ok()
+                .and_then(|m| m.as_f64())
+                .unwrap_or_default();
+            let mime = Reflect::get(&value, &"mime".into())
+                .ok()
+                .and_then(|m| m.as_string())
+                .and_then(|m| m.parse().ok())
+                .unwrap_or(mime::APPLICATION_OCTET_STREAM);
+            let modified = Reflect::get(&value, &"modified".into())
+                .ok()
+                .and_then(|m| m.as_f64())
+                .map(|ms| SystemTime::UNIX_EPOCH + Duration::from_millis(ms as u64));
+
+            Ok(Metadata {
+                path: path.to_relative_path_buf(),
+                size: size as u64,
+                mime,
+                modified,
+                permissions: None,
+                attributes: Attributes::new(),
+            })
+        })
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        SendWrapper::new(async move {
+            let (_, store) = self.object_stores(IdbTransactionMode::Readonly)?;
+            let req = store.get(&key(path)).map_err(js_error)?;
+            let value = request(&req).await?;
+            if value.is_undefined() {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+
+            let data = value.dyn_into::<Uint8Array>().map_err(js_error)?;
+
+            Ok(IdbFile {
+                data: data.to_vec().into(),
+            })
+        })
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        SendWrapper::new(async move {
+            let (meta, body) = self.object_stores(IdbTransactionMode::Readwrite)?;
+            let key = key(path);
+            request(&meta.delete(&key).map_err(js_error)?).await?;
+            request(&body.delete(&key).map_err(js_error)?).await?;
+            Ok(())
+        })
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        SendWrapper::new(async move {
+            let hint = init.mime.clone();
+            let mut stream = init.into_stream().await?;
+            let mut output = BytesMut::new();
+            while let Some(next) = stream.try_next().await? {
+                output.extend(next);
+            }
+
+            let mime = if let Some(mime) = hint {
+                mime
+            } else if let Some(ext) = path.extension() {
+                mime_guess::from_ext(ext).first_or_octet_stream()
+            } else {
+                mime::APPLICATION_OCTET_STREAM
+            };
+
+            let record = Object::new();
+            Reflect::set(&record, &"size".into(), &(output.len() as f64).into())
+                .map_err(js_error)?;
+            Reflect::set(&record, &"mime".into(), &JsValue::from_str(mime.as_ref()))
+                .map_err(js_error)?;
+            Reflect::set(&record, &"modified".into(), &js_sys::Date::now().into())
+                .map_err(js_error)?;
+
+            let (meta, body) = self.object_stores(IdbTransactionMode::Readwrite)?;
+            let key = key(path);
+            let data = Uint8Array::from(&output[..]);
+            request(&body.put_with_key(&data, &key).map_err(js_error)?).await?;
+            request(&meta.put_with_key(&record, &key).map_err(js_error)?).await?;
+
+            Ok(())
+        })
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        SendWrapper::new(async move {
+            let store = self.meta_store()?;
+            let req = store.get_all_keys().map_err(js_error)?;
+            let keys = request(&req).await?.dyn_into::<Array>().map_err(js_error)?;
+
+            let keys = keys
+                .iter()
+                .filter_map(|m| m.as_string())
+                .map(|m| Ok(RelativePathBuf::from(m)))
+                .collect::<Vec<_>>();
+
+            Ok(futures::stream::iter(keys).boxed())
+        })
+    }
+}
+
+pub struct IdbFile {
+    data: Bytes,
+}
+
+impl AsyncFile for IdbFile {
+    type Body = futures::stream::Once<futures::future::Ready<io::Result<Bytes>>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        let len = self.data.len() as u64;
+        let ret = if range.start > range.end || range.end > len {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "range out of bounds",
+            ))
+        } else {
+            Ok(self.data.slice(range.start as usize..range.end as usize))
+        };
+        async move { ret }
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        let data = self.data.clone();
+        async move { Ok(futures::stream::once(futures::future::ok(data))) }
+    }
+}
+
+fn key(path: &RelativePath) -> JsValue {
+    JsValue::from_str(path.normalize().as_str())
+}
+
+fn js_error(err: impl Into<JsValue>) -> io::Error {
+    io::Error::other(format!("{:?}", err.into()))
+}
+
+fn factory() -> Result<IdbFactory, io::Error> {
+    let global = js_sys::global();
+    let factory = if let Some(window) = global.dyn_ref::<web_sys::Window>() {
+        window.indexed_db()
+    } else if let Some(worker) = global.dyn_ref::<web_sys::WorkerGlobalScope>() {
+        worker.indexed_db()
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "indexeddb is not available",
+        ));
+    };
+
+    factory
+        .map_err(js_error)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "indexeddb is not available"))
+}
+
+async fn request(req: &IdbRequest) -> Result<JsValue, io::Error> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let success = req.clone();
+        let on_success = Closure::once_into_js(move |_: web_sys::Event| {
+            let value = success.result().unwrap_or(JsValue::UNDEFINED);
+            let _ = resolve.call1(&JsValue::UNDEFINED, &value);
+        });
+
+        let failure = req.clone();
+        let on_error = Closure::once_into_js(move |_: web_sys::Event| {
+            let err = failure
+                .error()
+                .ok()
+                .flatten()
+                .map(JsValue::from)
+                .unwrap_or(JsValue::UNDEFINED);
+            let _ = reject.call1(&JsValue::UNDEFINED, &err);
+        });
+
+        req.set_onsuccess(Some(on_success.unchecked_ref()));
+        req.set_onerror(Some(on_error.unchecked_ref()));
+    });
+
+    JsFuture::from(promise).await.map_err(js_error)
+}
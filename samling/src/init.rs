@@ -0,0 +1,402 @@
+use std::{
+    io::{self, Read},
+    pin::Pin,
+};
+
+use bytes::Bytes;
+use futures::{io::AsyncRead, stream::BoxStream, Stream, StreamExt};
+use mime::Mime;
+#[cfg(feature = "serde-json")]
+use serde::Serialize;
+
+use crate::{
+    boxed::{async_file_box, BoxAsyncFile},
+    util::Buffer,
+    AsyncFile, Attributes,
+};
+
+/// The contents of a file to write to a sync store.
+#[non_exhaustive]
+pub enum FileBody {
+    Bytes(Bytes),
+    Read(Box<dyn Read + Send>),
+    #[cfg(feature = "fs")]
+    Path(std::path::PathBuf),
+}
+
+/// The contents of a file to write to an async store.
+#[non_exhaustive]
+pub enum AsyncFileBody {
+    Bytes(Bytes),
+    Stream(BoxStream<'static, io::Result<Bytes>>),
+    Reader(Pin<Box<dyn AsyncRead + Send>>),
+    #[cfg(feature = "fs")]
+    Path(std::path::PathBuf),
+    /// A file opened from another store.
+    File(BoxAsyncFile<'static>),
+}
+
+impl AsyncFileBody {
+    /// Turn the body into a stream of chunks, whatever its source.
+    pub async fn into_stream(self) -> io::Result<BoxStream<'static, io::Result<Bytes>>> {
+        let ret = match self {
+            AsyncFileBody::Bytes(bs) => futures::stream::once(async move { Ok(bs) }).boxed(),
+            AsyncFileBody::Stream(stream) => stream,
+            AsyncFileBody::Reader(reader) => reader_stream(reader).boxed(),
+            #[cfg(feature = "fs")]
+            AsyncFileBody::Path(path) => crate::util::file_stream(&path).await?.boxed(),
+            AsyncFileBody::File(file) => file.reader().await?,
+        };
+
+        Ok(ret)
+    }
+}
+
+/// A file to write to a sync store.
+///
+/// Besides the body, a writer can hint the final size and content type of the file. Backends use
+/// the size to pre-allocate or choose between single-shot and multipart uploads, and the content
+/// type instead of guessing one from the extension. Attributes are kept by stores which
+/// [`supports_attributes`](crate::FileStore::supports_attributes), and ignored by the rest.
+pub struct FileInit {
+    pub body: FileBody,
+    pub len: Option<u64>,
+    pub mime: Option<Mime>,
+    pub attributes: Attributes,
+}
+
+/// A file to write to an async store. See [`FileInit`].
+pub struct AsyncFileInit {
+    pub body: AsyncFileBody,
+    pub len: Option<u64>,
+    pub mime: Option<Mime>,
+    pub attributes: Attributes,
+}
+
+impl FileInit {
+    pub fn new(body: FileBody) -> FileInit {
+        FileInit {
+            body,
+            len: None,
+            mime: None,
+            attributes: Attributes::new(),
+        }
+    }
+
+    pub fn from_reader<R>(reader: R) -> FileInit
+    where
+        R: Read + Send + 'static,
+    {
+        FileInit::new(FileBody::Read(Box::new(reader)))
+    }
+
+    /// Serialize `value` as JSON.
+    #[cfg(feature = "serde-json")]
+    pub fn json<S: Serialize + ?Sized>(value: &S) -> io::Result<FileInit> {
+        let init = FileInit::from(crate::format::to_json(value)?);
+        Ok(init.with_mime(mime::APPLICATION_JSON))
+    }
+
+    /// The size of the file in bytes, if known up front.
+    pub fn with_len(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        self
+    }
+
+    pub fn with_mime(mut self, mime: Mime) -> Self {
+        self.mime = Some(mime);
+        self
+    }
+
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    /// The hinted size, or the size of the body when it is already in memory.
+    pub fn len_hint(&self) -> Option<u64> {
+        match &self.body {
+            FileBody::Bytes(bs) => Some(bs.len() as u64),
+            _ => self.len,
+        }
+    }
+
+    /// An empty body, which creates the file or truncates it to nothing. Follow it with
+    /// [`set_len`](crate::FileStore::set_len) to preallocate.
+    pub fn truncate_only() -> FileInit {
+        FileInit::from(Bytes::new())
+    }
+
+    /// A copy of the init, if its body can be read again, i.e. it is in memory or a path.
+    pub fn try_clone(&self) -> Option<FileInit> {
+        let body = match &self.body {
+            FileBody::Bytes(bytes) => FileBody::Bytes(bytes.clone()),
+            #[cfg(feature = "fs")]
+            FileBody::Path(path) => FileBody::Path(path.clone()),
+            FileBody::Read(_) => return None,
+        };
+
+        Some(FileInit {
+            body,
+            len: self.len,
+            mime: self.mime.clone(),
+            attributes: self.attributes.clone(),
+        })
+    }
+}
+
+impl AsyncFileInit {
+    pub fn new(body: AsyncFileBody) -> AsyncFileInit {
+        AsyncFileInit {
+            body,
+            len: None,
+            mime: None,
+            attributes: Attributes::new(),
+        }
+    }
+
+    pub fn stream<T>(stream: T) -> AsyncFileInit
+    where
+        T: Stream<Item = io::Result<Bytes>> + Send + 'static,
+    {
+        AsyncFileInit::new(AsyncFileBody::Stream(stream.boxed()))
+    }
+
+    /// Stream the contents of an async reader.
+    pub fn from_reader<R>(reader: R) -> AsyncFileInit
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        AsyncFileInit::new(AsyncFileBody::Reader(Box::pin(reader)))
+    }
+
+    /// Copy a file opened from another store.
+    pub fn from_file<F>(file: F) -> AsyncFileInit
+    where
+        F: AsyncFile + Send + Sync + 'static,
+        F::Body: Send + 'static,
+    {
+        AsyncFileInit::new(AsyncFileBody::File(async_file_box(file)))
+    }
+
+    /// Serialize `value` as JSON.
+    #[cfg(feature = "serde-json")]
+    pub fn json<S: Serialize + ?Sized>(value: &S) -> io::Result<AsyncFileInit> {
+        let init = AsyncFileInit::from(crate::format::to_json(value)?);
+        Ok(init.with_mime(mime::APPLICATION_JSON))
+    }
+
+    /// The size of the file in bytes, if known up front.
+    pub fn with_len(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        self
+    }
+
+    pub fn with_mime(mut self, mime: Mime) -> Self {
+        self.mime = Some(mime);
+        self
+    }
+
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    /// The hinted size, or the size of the body when it is already in memory.
+    pub fn len_hint(&self) -> Option<u64> {
+        match &self.body {
+            AsyncFileBody::Bytes(bs) => Some(bs.len() as u64),
+            _ => self.len,
+        }
+    }
+
+    pub async fn into_stream(self) -> io::Result<BoxStream<'static, io::Result<Bytes>>> {
+        self.body.into_stream().await
+    }
+
+    /// An empty body, which creates the file or truncates it to nothing. Follow it with
+    /// [`set_len`](crate::AsyncFileStore::set_len) to preallocate.
+    pub fn truncate_only() -> AsyncFileInit {
+        AsyncFileInit::from(Bytes::new())
+    }
+
+    /// A copy of the init, if its body can be read again, i.e. it is in memory or a path.
+    pub fn try_clone(&self) -> Option<AsyncFileInit> {
+        let body = match &self.body {
+            AsyncFileBody::Bytes(bytes) => AsyncFileBody::Bytes(bytes.clone()),
+            #[cfg(feature = "fs")]
+            AsyncFileBody::Path(path) => AsyncFileBody::Path(path.clone()),
+            _ => return None,
+        };
+
+        Some(AsyncFileInit {
+            body,
+            len: self.len,
+            mime: self.mime.clone(),
+            attributes: self.attributes.clone(),
+        })
+    }
+}
+
+impl From<FileBody> for FileInit {
+    fn from(value: FileBody) -> Self {
+        FileInit::new(value)
+    }
+}
+
+impl From<Bytes> for FileInit {
+    fn from(value: Bytes) -> Self {
+        FileInit::new(FileBody::Bytes(value))
+    }
+}
+
+impl From<Vec<u8>> for FileInit {
+    fn from(value: Vec<u8>) -> Self {
+        FileInit::from(Bytes::from(value))
+    }
+}
+
+impl From<&'static [u8]> for FileInit {
+    fn from(value: &'static [u8]) -> Self {
+        FileInit::from(Bytes::from_static(value))
+    }
+}
+
+impl From<String> for FileInit {
+    fn from(value: String) -> Self {
+        FileInit::from(Bytes::from(value))
+    }
+}
+
+impl From<&'static str> for FileInit {
+    fn from(value: &'static str) -> Self {
+        FileInit::from(Bytes::from_static(value.as_bytes()))
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<std::path::PathBuf> for FileInit {
+    fn from(value: std::path::PathBuf) -> Self {
+        FileInit::new(FileBody::Path(value))
+    }
+}
+
+impl From<AsyncFileBody> for AsyncFileInit {
+    fn from(value: AsyncFileBody) -> Self {
+        AsyncFileInit::new(value)
+    }
+}
+
+impl From<Bytes> for AsyncFileInit {
+    fn from(value: Bytes) -> Self {
+        AsyncFileInit::new(AsyncFileBody::Bytes(value))
+    }
+}
+
+impl From<Vec<u8>> for AsyncFileInit {
+    fn from(value: Vec<u8>) -> Self {
+        AsyncFileInit::from(Bytes::from(value))
+    }
+}
+
+impl From<&'static [u8]> for AsyncFileInit {
+    fn from(value: &'static [u8]) -> Self {
+        AsyncFileInit::from(Bytes::from_static(value))
+    }
+}
+
+impl From<String> for AsyncFileInit {
+    fn from(value: String) -> Self {
+        AsyncFileInit::from(Bytes::from(value))
+    }
+}
+
+impl From<&'static str> for AsyncFileInit {
+    fn from(value: &'static str) -> Self {
+        AsyncFileInit::from(Bytes::from_static(value.as_bytes()))
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<std::path::PathBuf> for AsyncFileInit {
+    fn from(value: std::path::PathBuf) -> Self {
+        AsyncFileInit::new(AsyncFileBody::Path(value))
+    }
+}
+
+/// Readers of sync bodies are read inline, so they should not block for long.
+impl From<FileBody> for AsyncFileBody {
+    fn from(value: FileBody) -> Self {
+        match value {
+            FileBody::Bytes(bytes) => AsyncFileBody::Bytes(bytes),
+            FileBody::Read(reader) => AsyncFileBody::Stream(read_stream(reader).boxed()),
+            #[cfg(feature = "fs")]
+            FileBody::Path(path) => AsyncFileBody::Path(path),
+        }
+    }
+}
+
+impl From<FileInit> for AsyncFileInit {
+    fn from(value: FileInit) -> Self {
+        AsyncFileInit {
+            body: value.body.into(),
+            len: value.len,
+            mime: value.mime,
+            attributes: value.attributes,
+        }
+    }
+}
+
+impl From<BoxStream<'static, io::Result<Bytes>>> for AsyncFileInit {
+    fn from(value: BoxStream<'static, io::Result<Bytes>>) -> Self {
+        AsyncFileInit::new(AsyncFileBody::Stream(value))
+    }
+}
+
+impl From<Pin<Box<dyn AsyncRead + Send>>> for AsyncFileInit {
+    fn from(value: Pin<Box<dyn AsyncRead + Send>>) -> Self {
+        AsyncFileInit::new(AsyncFileBody::Reader(value))
+    }
+}
+
+impl From<BoxAsyncFile<'static>> for AsyncFileInit {
+    fn from(value: BoxAsyncFile<'static>) -> Self {
+        AsyncFileInit::new(AsyncFileBody::File(value))
+    }
+}
+
+fn reader_stream(
+    mut reader: Pin<Box<dyn AsyncRead + Send>>,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+    async_stream::try_stream! {
+        let mut buffer = Buffer::acquire();
+        loop {
+            let chunk = buffer.read_chunk_async(&mut reader).await?;
+            if chunk.is_empty() {
+                break;
+            }
+            yield chunk;
+        }
+    }
+}
+
+pub(crate) fn read_stream(
+    mut reader: Box<dyn Read + Send>,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+    let mut done = false;
+    let mut buffer = Buffer::acquire();
+    futures::stream::iter(std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+
+        match buffer.read_chunk(&mut reader) {
+            Ok(chunk) if chunk.is_empty() => None,
+            Ok(chunk) => Some(Ok(chunk)),
+            Err(err) => {
+                done = true;
+                Some(Err(err))
+            }
+        }
+    }))
+}
@@ -0,0 +1,650 @@
+//! ConfigMaps and Secrets of a Kubernetes namespace as files.
+//!
+//! Every key of an object is a file at `<name>/<key>`, like the files of a mounted volume.
+//! Only plain `http` is spoken, like [`RemoteClient`], so the api server is reached through
+//! `kubectl proxy` or an authenticating sidecar.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    time::SystemTime,
+};
+
+use async_stream::try_stream;
+use bytes::{Bytes, BytesMut};
+use futures::{pin_mut, stream::BoxStream, Future, Stream, StreamExt, TryStreamExt};
+use hyper::{body::Incoming, header, Method, Request, Response, StatusCode};
+use relative_path::{Component, RelativePath, RelativePathBuf};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::{
+    remote::{self, RemoteBody, RemoteClient},
+    util::{base64, rfc3339},
+    watch::{Change, ChangeKind},
+    AsyncFile, AsyncFileInit, AsyncFileStore, Attributes, Metadata,
+};
+
+/// Objects fetched per request while listing.
+const PAGE: usize = 500;
+
+/// The kind of objects a [`KubeStore`] exposes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KubeKind {
+    ConfigMap,
+    Secret,
+}
+
+impl KubeKind {
+    fn resource(self) -> &'static str {
+        match self {
+            KubeKind::ConfigMap => "configmaps",
+            KubeKind::Secret => "secrets",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            KubeKind::ConfigMap => "ConfigMap",
+            KubeKind::Secret => "Secret",
+        }
+    }
+}
+
+/// The values of the keys of an object.
+type Values = BTreeMap<String, Bytes>;
+
+/// The values of every object by name.
+type State = BTreeMap<String, Values>;
+
+/// The ConfigMaps or Secrets of a namespace, with every key a file at `<name>/<key>`.
+///
+/// Read-only unless made [`writable`](KubeStore::writable). Writing a key patches the object,
+/// creating it if needed; removing the last key of an object leaves it empty.
+/// [`watch`](KubeStore::watch) follows changes as they happen instead of polling.
+///
+/// ```ignore
+/// let api: Url = "http://127.0.0.1:8001".parse()?;
+/// let config = KubeStore::config_maps(&api, "payments");
+/// let settings = config.read("api/settings.toml").await?;
+///
+/// let mut changes = config.watch();
+/// while let Some(change) = changes.try_next().await? {
+///     reload(&change.path).await?;
+/// }
+/// ```
+#[derive(Clone)]
+pub struct KubeStore {
+    client: RemoteClient,
+    endpoint: Url,
+    namespace: String,
+    kind: KubeKind,
+    token: Option<String>,
+    writable: bool,
+}
+
+impl KubeStore {
+    /// The objects of `kind` in `namespace`, through the api server at `endpoint`.
+    pub fn new(endpoint: &Url, namespace: impl Into<String>, kind: KubeKind) -> KubeStore {
+        KubeStore {
+            client: RemoteClient::shared(),
+            endpoint: endpoint.clone(),
+            namespace: namespace.into(),
+            kind,
+            token: None,
+            writable: false,
+        }
+    }
+
+    pub fn config_maps(endpoint: &Url, namespace: impl Into<String>) -> KubeStore {
+        KubeStore::new(endpoint, namespace, KubeKind::ConfigMap)
+    }
+
+    pub fn secrets(endpoint: &Url, namespace: impl Into<String>) -> KubeStore {
+        KubeStore::new(endpoint, namespace, KubeKind::Secret)
+    }
+
+    /// Send requests through `client` instead of the shared one.
+    pub fn client(mut self, client: RemoteClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Authenticate with a bearer token, e.g. of a service account.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Allow writing and removing keys.
+    pub fn writable(mut self, writable: bool) -> Self {
+        self.writable = writable;
+        self
+    }
+
+    pub fn kind(&self) -> KubeKind {
+        self.kind
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Follow changes to the keys of every object with a watch on the api server.
+    ///
+    /// The current state is only recorded, like the first poll of [`crate::watch::watch`].
+    /// When the watch expires the objects are listed again, and changes missed in between
+    /// are yielded. The stream ends with the first error.
+    pub fn watch(&self) -> impl Stream<Item = io::Result<Change>> + Send + 'static {
+        let store = self.clone();
+        try_stream! {
+            let (mut version, mut state) = store.snapshot().await?;
+
+            loop {
+                let mut url = store.url(None)?;
+                url.query_pairs_mut()
+                    .append_pair("watch", "1")
+                    .append_pair("resourceVersion", &version)
+                    .append_pair("allowWatchBookmarks", "true");
+
+                let response = store.send(Method::GET, &url, None).await?;
+                let events = lines(remote::body_stream(response));
+                pin_mut!(events);
+
+                let mut expired = false;
+                while let Some(line) = events.try_next().await? {
+                    let event: WatchEvent = parse(&line)?;
+                    match event.kind.as_str() {
+                        "ADDED" | "MODIFIED" | "DELETED" => {
+                            let object: Object = serde_json::from_value(event.object)
+                                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                            let name = object.metadata.name.clone();
+                            let values = match event.kind.as_str() {
+                                "DELETED" => Values::new(),
+                                _ => store.values(&object)?,
+                            };
+                            version = object.metadata.resource_version;
+
+                            for change in diff(&name, state.get(&name), &values) {
+                                yield change;
+                            }
+                            if values.is_empty() {
+                                state.remove(&name);
+                            } else {
+                                state.insert(name, values);
+                            }
+                        }
+                        "BOOKMARK" => {
+                            let bookmark = &event.object["metadata"]["resourceVersion"];
+                            if let Some(bookmark) = bookmark.as_str() {
+                                version = bookmark.to_string();
+                            }
+                        }
+                        // Most likely `410 Gone`, when the version is too old to watch from
+                        _ => {
+                            expired = true;
+                            break;
+                        }
+                    }
+                }
+
+                if expired {
+                    let (current_version, current) = store.snapshot().await?;
+                    let names = state
+                        .keys()
+                        .chain(current.keys())
+                        .cloned()
+                        .collect::<BTreeSet<_>>();
+                    for name in names {
+                        let values = current.get(&name).cloned().unwrap_or_default();
+                        for change in diff(&name, state.get(&name), &values) {
+                            yield change;
+                        }
+                    }
+                    version = current_version;
+                    state = current;
+                }
+            }
+        }
+    }
+
+    /// The url of the collection, or of the object `name` in it.
+    fn url(&self, name: Option<&str>) -> io::Result<Url> {
+        let mut url = self.endpoint.clone();
+        url.path_segments_mut()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid api server url"))?
+            .pop_if_empty()
+            .extend([
+                "api",
+                "v1",
+                "namespaces",
+                &self.namespace,
+                self.kind.resource(),
+            ])
+            .extend(name);
+        Ok(url)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: &Url,
+        body: Option<(&str, Value)>,
+    ) -> io::Result<Response<Incoming>> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(url.as_str())
+            .header(header::ACCEPT, "application/json");
+        if let Some(token) = &self.token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+
+        let body: RemoteBody = match body {
+            Some((content_type, body)) => {
+                request = request.header(header::CONTENT_TYPE, content_type);
+                remote::full(body.to_string())
+            }
+            None => remote::empty(),
+        };
+        let request = request
+            .body(body)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        check(self.client.request(request).await?).await
+    }
+
+    async fn json<T>(&self, url: &Url) -> io::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let body = remote::collect(self.send(Method::GET, url, None).await?).await?;
+        parse(&body)
+    }
+
+    async fn object(&self, name: &str) -> io::Result<Object> {
+        self.json(&self.url(Some(name))?).await
+    }
+
+    /// Every object, with the version to watch from.
+    async fn snapshot(&self) -> io::Result<(String, State)> {
+        let mut state = State::new();
+        let mut next: Option<String> = None;
+
+        loop {
+            let mut url = self.url(None)?;
+            url.query_pairs_mut()
+                .append_pair("limit", &PAGE.to_string());
+            if let Some(next) = &next {
+                url.query_pairs_mut().append_pair("continue", next);
+            }
+
+            let list: ObjectList = self.json(&url).await?;
+            for object in &list.items {
+                state.insert(object.metadata.name.clone(), self.values(object)?);
+            }
+
+            match list.metadata.next {
+                Some(token) if !token.is_empty() => next = Some(token),
+                _ => return Ok((list.metadata.resource_version, state)),
+            }
+        }
+    }
+
+    /// The decoded values of the keys of `object`.
+    fn values(&self, object: &Object) -> io::Result<Values> {
+        let mut values = Values::new();
+        for (key, value) in &object.data {
+            let value = match self.kind {
+                KubeKind::ConfigMap => Bytes::from(value.clone()),
+                KubeKind::Secret => base64::decode(value)?,
+            };
+            values.insert(key.clone(), value);
+        }
+        for (key, value) in &object.binary_data {
+            values.insert(key.clone(), base64::decode(value)?);
+        }
+        Ok(values)
+    }
+
+    /// The fields setting `key` to `value`, or removing it.
+    fn fields(&self, key: &str, value: Option<&[u8]>) -> Value {
+        let encoded = value.map(base64::encode);
+        match (self.kind, value.map(std::str::from_utf8)) {
+            (KubeKind::Secret, _) => json!({ "data": { key: encoded } }),
+            (KubeKind::ConfigMap, Some(Ok(text))) => {
+                json!({ "data": { key: text }, "binaryData": { key: null } })
+            }
+            (KubeKind::ConfigMap, _) => {
+                json!({ "data": { key: null }, "binaryData": { key: encoded } })
+            }
+        }
+    }
+
+    fn check_writable(&self) -> io::Result<()> {
+        if self.writable {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} store is read-only", self.kind.name()),
+            ))
+        }
+    }
+
+    async fn patch(&self, name: &str, key: &str, value: Option<&[u8]>) -> io::Result<()> {
+        let patch = self.fields(key, value);
+        self.send(
+            Method::PATCH,
+            &self.url(Some(name))?,
+            Some(("application/merge-patch+json", patch)),
+        )
+        .await
+        .map(drop)
+    }
+
+    async fn create(&self, name: &str, key: &str, value: &[u8]) -> io::Result<()> {
+        let mut object = self.fields(key, Some(value));
+        // Nulls only mean removal in a patch
+        if let Some(fields) = object.as_object_mut() {
+            fields.retain(|_, field| field.as_object().is_some_and(|field| !field[key].is_null()));
+            fields.insert("apiVersion".into(), "v1".into());
+            fields.insert("kind".into(), self.kind.name().into());
+            fields.insert("metadata".into(), json!({ "name": name }));
+        }
+
+        self.send(
+            Method::POST,
+            &self.url(None)?,
+            Some(("application/json", object)),
+        )
+        .await
+        .map(drop)
+    }
+}
+
+/// Split `path` into the name of an object and one of its keys.
+fn split(path: &RelativePath) -> io::Result<(String, String)> {
+    let path = path.normalize();
+    let mut components = path.components();
+    match (components.next(), components.next(), components.next()) {
+        (Some(Component::Normal(name)), Some(Component::Normal(key)), None) => {
+            Ok((name.to_string(), key.to_string()))
+        }
+        (Some(Component::ParentDir), ..) => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("path escapes the store: {path}"),
+        )),
+        _ => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("not a <name>/<key> path: {path}"),
+        )),
+    }
+}
+
+impl AsyncFileStore for KubeStore {
+    type File = KubeFile;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            let (name, key) = split(path)?;
+            let object = self.object(&name).await?;
+            let value = self
+                .values(&object)?
+                .remove(&key)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+
+            let mime = match RelativePath::new(&key).extension() {
+                Some(ext) => mime_guess::from_ext(ext).first_or_octet_stream(),
+                None => mime::APPLICATION_OCTET_STREAM,
+            };
+
+            Ok(Metadata {
+                path: path.normalize(),
+                size: value.len() as u64,
+                mime,
+                modified: object.metadata.modified(),
+                permissions: None,
+                attributes: Attributes::default(),
+            })
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            let (name, key) = split(path)?;
+            let object = self.object(&name).await?;
+            self.values(&object)?
+                .remove(&key)
+                .map(KubeFile)
+                .ok_or_else(|| io::ErrorKind::NotFound.into())
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            self.check_writable()?;
+            let (name, key) = split(path)?;
+            let object = self.object(&name).await?;
+            if !self.values(&object)?.contains_key(&key) {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+            self.patch(&name, &key, None).await
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            self.check_writable()?;
+            let (name, key) = split(path)?;
+            let len = init.len.unwrap_or(0);
+            let value = crate::util::collect(init.into_stream().await?, len).await?;
+
+            match self.patch(&name, &key, Some(&value)).await {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    match self.create(&name, &key, &value).await {
+                        // Created by someone else in the meantime
+                        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                            self.patch(&name, &key, Some(&value)).await
+                        }
+                        ret => ret,
+                    }
+                }
+                ret => ret,
+            }
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        async move {
+            let (_, state) = self.snapshot().await?;
+            let paths = state
+                .into_iter()
+                .flat_map(|(name, values)| {
+                    values
+                        .into_keys()
+                        .map(move |key| Ok(RelativePathBuf::from(format!("{name}/{key}"))))
+                })
+                .collect::<Vec<_>>();
+            Ok(futures::stream::iter(paths).boxed())
+        }
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let mut url = self.url(None)?;
+            url.query_pairs_mut().append_pair("limit", "1");
+            self.send(Method::GET, &url, None).await.map(drop)
+        }
+    }
+}
+
+/// The value of a key of a [`KubeStore`].
+pub struct KubeFile(Bytes);
+
+impl AsyncFile for KubeFile {
+    type Body = futures::stream::Once<futures::future::Ready<io::Result<Bytes>>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        futures::future::ready(crate::util::slice(&self.0, range))
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        futures::future::ok(futures::stream::once(futures::future::ok(self.0.clone())))
+    }
+}
+
+#[derive(Deserialize)]
+struct Object {
+    metadata: ObjectMeta,
+    #[serde(default)]
+    data: BTreeMap<String, String>,
+    #[serde(default, rename = "binaryData")]
+    binary_data: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObjectMeta {
+    name: String,
+    #[serde(default)]
+    resource_version: String,
+    creation_timestamp: Option<String>,
+    #[serde(default)]
+    managed_fields: Vec<ManagedField>,
+}
+
+impl ObjectMeta {
+    /// When the object was last changed, as far as its managed fields tell.
+    fn modified(&self) -> Option<SystemTime> {
+        self.managed_fields
+            .iter()
+            .filter_map(|field| field.time.as_deref())
+            .chain(self.creation_timestamp.as_deref())
+            .filter_map(rfc3339::parse)
+            .max()
+    }
+}
+
+#[derive(Deserialize)]
+struct ManagedField {
+    time: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ObjectList {
+    metadata: ListMeta,
+    items: Vec<Object>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListMeta {
+    #[serde(default)]
+    resource_version: String,
+    #[serde(rename = "continue")]
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WatchEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    object: Value,
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> io::Result<T> {
+    serde_json::from_slice(body).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// The changes to the keys of object `name` from `old` to `new`.
+fn diff(name: &str, old: Option<&Values>, new: &Values) -> Vec<Change> {
+    let empty = Values::new();
+    let old = old.unwrap_or(&empty);
+    let change = |kind, key: &str| Change {
+        kind,
+        path: RelativePathBuf::from(format!("{name}/{key}")),
+    };
+
+    let mut changes = Vec::new();
+    for (key, value) in new {
+        match old.get(key) {
+            None => changes.push(change(ChangeKind::Added, key)),
+            Some(previous) if previous != value => changes.push(change(ChangeKind::Modified, key)),
+            Some(_) => {}
+        }
+    }
+    for key in old.keys() {
+        if !new.contains_key(key) {
+            changes.push(change(ChangeKind::Removed, key));
+        }
+    }
+    changes
+}
+
+/// Split a body into its lines, without the newlines.
+fn lines<S>(body: S) -> impl Stream<Item = io::Result<Bytes>> + Send
+where
+    S: Stream<Item = io::Result<Bytes>> + Send,
+{
+    try_stream! {
+        pin_mut!(body);
+        let mut buffer = BytesMut::new();
+
+        while let Some(chunk) = body.try_next().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                let line = buffer.split_to(end + 1).freeze().slice(..end);
+                if !line.trim_ascii().is_empty() {
+                    yield line;
+                }
+            }
+        }
+    }
+}
+
+/// Turn error responses into errors, with the message of the `Status` sent by the api server.
+async fn check(response: Response<Incoming>) -> io::Result<Response<Incoming>> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = remote::collect(response).await.unwrap_or_default();
+    let message = parse::<Value>(&body)
+        .ok()
+        .and_then(|status| status["message"].as_str().map(str::to_string))
+        .unwrap_or_else(|| String::from_utf8_lossy(&body).into_owned());
+
+    let kind = match status {
+        StatusCode::NOT_FOUND => io::ErrorKind::NotFound,
+        StatusCode::CONFLICT => io::ErrorKind::AlreadyExists,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => io::ErrorKind::PermissionDenied,
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => io::ErrorKind::InvalidInput,
+        _ => io::ErrorKind::Other,
+    };
+
+    Err(io::Error::new(
+        kind,
+        format!("kubernetes {status}: {message}"),
+    ))
+}
@@ -0,0 +1,372 @@
+use std::io;
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{AsyncFileInit, AsyncFileStore, FileInit, FileStore, Link, Metadata, Permissions};
+
+/// Where a [`Layered`] store creates files which don't exist in any layer yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LayerWrite {
+    /// Only files which exist are written; creating one fails with `NotFound`, like a `Vec`.
+    #[default]
+    Existing,
+    /// Create files in the first layer which accepts them.
+    FirstWritable,
+    /// Create files in the layer at this index.
+    Layer(usize),
+}
+
+/// Whether a store refused a write outright, so the next candidate can be tried.
+pub(crate) fn refused(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::PermissionDenied
+            | io::ErrorKind::Unsupported
+            | io::ErrorKind::ReadOnlyFilesystem
+    )
+}
+
+/// Write to the first candidate which doesn't refuse the write.
+///
+/// Bodies which can't be read twice are only offered to the first candidate.
+pub(crate) async fn write_first<S>(
+    candidates: Vec<(&S, RelativePathBuf)>,
+    init: AsyncFileInit,
+) -> io::Result<()>
+where
+    S: AsyncFileStore,
+{
+    let mut init = Some(init);
+    let mut last = None;
+    let count = candidates.len();
+
+    for (i, (store, path)) in candidates.into_iter().enumerate() {
+        let Some(current) = init.take() else {
+            break;
+        };
+
+        let attempt = match current.try_clone() {
+            Some(copy) if i + 1 < count => {
+                init = Some(current);
+                copy
+            }
+            _ => current,
+        };
+
+        match store.write_file(&path, attempt).await {
+            Err(err) if refused(&err) => last = Some(err),
+            ret => return ret,
+        }
+    }
+
+    Err(last.unwrap_or_else(|| io::ErrorKind::NotFound.into()))
+}
+
+/// Stores stacked on top of each other, e.g. a writable directory over read-only defaults.
+///
+/// Reads go to the first layer containing the path, like a `Vec`. Existing files are written in
+/// the layer containing them, and new files where the [`LayerWrite`] policy says. With
+/// `FirstWritable`, layers refusing a write with `PermissionDenied` or `Unsupported` are
+/// skipped; a streamed body can only be offered to one layer, so spool it (see `Spooled`) to
+/// let it fall through.
+///
+/// ```ignore
+/// let store = Layered::new(vec![uploads.boxed(), defaults.boxed()]).write(LayerWrite::Layer(0));
+/// ```
+pub struct Layered<T> {
+    layers: Vec<T>,
+    write: LayerWrite,
+}
+
+impl<T> Layered<T> {
+    pub fn new(layers: Vec<T>) -> Layered<T> {
+        Layered {
+            layers,
+            write: LayerWrite::default(),
+        }
+    }
+
+    pub fn write(mut self, write: LayerWrite) -> Self {
+        self.write = write;
+        self
+    }
+
+    pub fn layers(&self) -> &[T] {
+        &self.layers
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.layers
+    }
+
+    /// The layers to create `path` in, in the order to try them.
+    fn creating(&self) -> io::Result<&[T]> {
+        match self.write {
+            LayerWrite::Existing => Err(io::ErrorKind::NotFound.into()),
+            LayerWrite::FirstWritable => Ok(&self.layers),
+            LayerWrite::Layer(index) => self
+                .layers
+                .get(index..index + 1)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such layer")),
+        }
+    }
+}
+
+impl<T> AsyncFileStore for Layered<T>
+where
+    T: AsyncFileStore + Send + Sync,
+    T::File: Send,
+{
+    type File = T::File;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        self.layers.metadata(path)
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        self.layers.open_file(path)
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        self.layers.rm_file(path)
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            for layer in &self.layers {
+                if layer.exists(path).await {
+                    return layer.write_file(path, init).await;
+                }
+            }
+
+            let candidates = self
+                .creating()?
+                .iter()
+                .map(|layer| (layer, path.to_relative_path_buf()))
+                .collect();
+            write_first(candidates, init).await
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.layers.list()
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        self.layers.list_ref()
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.layers.health()
+    }
+
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.layers.prefetch(paths)
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.layers.prefetch_prefix(prefix)
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        self.layers.set_len(path, len)
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.layers.write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.layers.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.layers.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.layers.set_permissions(path, permissions)
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.layers.symlink(target, link)
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.layers.hard_link(src, dst)
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        self.layers.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.layers.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.layers.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.layers.close()
+    }
+}
+
+impl<T> FileStore for Layered<T>
+where
+    T: FileStore,
+{
+    type File = T::File;
+
+    type List = <Vec<T> as FileStore>::List;
+
+    fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
+        self.layers.metadata(path)
+    }
+
+    fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
+        self.layers.open_file(path)
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
+        self.layers.rm_file(path)
+    }
+
+    fn write_file(&self, path: &RelativePath, init: FileInit) -> Result<(), io::Error> {
+        if let Some(layer) = self.layers.iter().find(|layer| layer.exists(path)) {
+            return layer.write_file(path, init);
+        }
+
+        let layers = self.creating()?;
+        let mut init = Some(init);
+        let mut last = None;
+
+        for (i, layer) in layers.iter().enumerate() {
+            let Some(current) = init.take() else {
+                break;
+            };
+
+            let attempt = match current.try_clone() {
+                Some(copy) if i + 1 < layers.len() => {
+                    init = Some(current);
+                    copy
+                }
+                _ => current,
+            };
+
+            match layer.write_file(path, attempt) {
+                Err(err) if refused(&err) => last = Some(err),
+                ret => return ret,
+            }
+        }
+
+        Err(last.unwrap_or_else(|| io::ErrorKind::NotFound.into()))
+    }
+
+    fn list(&self) -> Self::List {
+        self.layers.list()
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        self.layers.set_len(path, len)
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        self.layers.write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.layers.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.layers.supports_attributes()
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        self.layers.set_permissions(path, permissions)
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        self.layers.symlink(target, link)
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        self.layers.hard_link(src, dst)
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        self.layers.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.layers.supports_links()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.layers.flush()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.layers.close()
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream::BoxStream, Future, StreamExt};
+use mime::Mime;
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{AsyncFile, AsyncFileInit, AsyncFileStore, Attributes, Metadata};
+
+/// Produces the contents of a file of a [`VirtualStore`] on demand.
+pub trait Generator: Send + Sync {
+    fn generate<'a>(&'a self, path: &'a RelativePath) -> BoxFuture<'a, io::Result<AsyncFileInit>>;
+}
+
+impl<F, U, T> Generator for F
+where
+    F: Fn(&RelativePath) -> U + Send + Sync,
+    U: Future<Output = io::Result<T>> + Send + 'static,
+    T: Into<AsyncFileInit>,
+{
+    fn generate<'a>(&'a self, path: &'a RelativePath) -> BoxFuture<'a, io::Result<AsyncFileInit>> {
+        let future = self(path);
+        Box::pin(async move { future.await.map(Into::into) })
+    }
+}
+
+/// A file of a [`VirtualStore`] with the metadata declared for it.
+pub struct VirtualEntry {
+    generator: Arc<dyn Generator>,
+    len: Option<u64>,
+    mime: Option<Mime>,
+    modified: Option<SystemTime>,
+    cached: bool,
+}
+
+impl VirtualEntry {
+    pub fn new(generator: impl Generator + 'static) -> VirtualEntry {
+        VirtualEntry {
+            generator: Arc::new(generator),
+            len: None,
+            mime: None,
+            modified: None,
+            cached: false,
+        }
+    }
+
+    /// The size of the generated file. When both the size and the content type are declared,
+    /// `metadata` answers without generating the file.
+    pub fn with_len(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        self
+    }
+
+    pub fn with_mime(mut self, mime: Mime) -> Self {
+        self.mime = Some(mime);
+        self
+    }
+
+    pub fn with_modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
+    /// Generate the file once and keep the output until the cache is cleared.
+    pub fn cached(mut self) -> Self {
+        self.cached = true;
+        self
+    }
+}
+
+/// A read only store of computed files.
+///
+/// Every path maps to a generator called when the file is read, so files like `sitemap.xml`
+/// or `healthz.json` can be mounted next to real files in a composite. Outputs are only kept
+/// for entries marked as [`cached`](VirtualEntry::cached).
+///
+/// ```ignore
+/// let store = VirtualStore::new()
+///     .file("healthz.json", |_: &RelativePath| async { AsyncFileInit::json(&health()) })
+///     .entry("sitemap.xml", VirtualEntry::new(sitemap).with_mime(mime::TEXT_XML).cached());
+/// ```
+#[derive(Default)]
+pub struct VirtualStore {
+    entries: BTreeMap<RelativePathBuf, VirtualEntry>,
+    cache: Mutex<HashMap<RelativePathBuf, (Mime, Bytes)>>,
+}
+
+impl VirtualStore {
+    pub fn new() -> VirtualStore {
+        VirtualStore::default()
+    }
+
+    /// Generate the file at `path` with `generator` on every read.
+    pub fn file(self, path: impl AsRef<RelativePath>, generator: impl Generator + 'static) -> Self {
+        self.entry(path, VirtualEntry::new(generator))
+    }
+
+    pub fn entry(mut self, path: impl AsRef<RelativePath>, entry: VirtualEntry) -> Self {
+        self.entries.insert(path.as_ref().normalize(), entry);
+        self
+    }
+
+    /// Drop the cached output of `path`, generating it again on the next read.
+    pub fn invalidate(&self, path: impl AsRef<RelativePath>) {
+        self.cache
+            .lock()
+            .unwrap()
+            .remove(&path.as_ref().normalize());
+    }
+
+    /// Drop every cached output.
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    fn find(&self, path: &RelativePath) -> io::Result<(RelativePathBuf, &VirtualEntry)> {
+        let path = path.normalize();
+        match self.entries.get(&path) {
+            Some(entry) => Ok((path, entry)),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    async fn output(&self, path: &RelativePath, entry: &VirtualEntry) -> io::Result<(Mime, Bytes)> {
+        if entry.cached {
+            if let Some(output) = self.cache.lock().unwrap().get(path) {
+                return Ok(output.clone());
+            }
+        }
+
+        let init = entry.generator.generate(path).await?;
+        let mime = entry
+            .mime
+            .clone()
+            .or_else(|| init.mime.clone())
+            .unwrap_or_else(|| guess_mime(path));
+        let size = init.len_hint().or(entry.len).unwrap_or_default();
+        let data = crate::util::collect(init.into_stream().await?, size).await?;
+
+        if entry.cached {
+            self.cache
+                .lock()
+                .unwrap()
+                .insert(path.to_relative_path_buf(), (mime.clone(), data.clone()));
+        }
+
+        Ok((mime, data))
+    }
+}
+
+fn guess_mime(path: &RelativePath) -> Mime {
+    match path.extension() {
+        Some(ext) => mime_guess::from_ext(ext).first_or_octet_stream(),
+        None => mime::APPLICATION_OCTET_STREAM,
+    }
+}
+
+impl AsyncFileStore for VirtualStore {
+    type File = VirtualFile;
+
+    /// Entries are kept in a `BTreeMap`.
+    const SORTED: bool = true;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            let (path, entry) = self.find(path)?;
+
+            let (mime, size) = match (&entry.mime, entry.len) {
+                (Some(mime), Some(len)) => (mime.clone(), len),
+                _ => {
+                    let (mime, data) = self.output(&path, entry).await?;
+                    (mime, data.len() as u64)
+                }
+            };
+
+            Ok(Metadata {
+                path,
+                size,
+                mime,
+                modified: entry.modified,
+                permissions: None,
+                attributes: Attributes::new(),
+            })
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            let (path, entry) = self.find(path)?;
+            let (_, data) = self.output(&path, entry).await?;
+            Ok(VirtualFile(data))
+        }
+    }
+
+    fn rm_file(&self, _path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move { Err(io::ErrorKind::PermissionDenied.into()) }
+    }
+
+    fn write_file(
+        &self,
+        _path: &RelativePath,
+        _init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move { Err(io::ErrorKind::PermissionDenied.into()) }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        let paths = self.entries.keys().cloned().map(Ok).collect::<Vec<_>>();
+        async move { Ok(futures::stream::iter(paths).boxed()) }
+    }
+}
+
+/// The generated contents of a file of a [`VirtualStore`].
+pub struct VirtualFile(Bytes);
+
+impl AsyncFile for VirtualFile {
+    type Body = futures::stream::Once<futures::future::Ready<io::Result<Bytes>>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        futures::future::ready(crate::util::slice(&self.0, range))
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        futures::future::ok(futures::stream::once(futures::future::ok(self.0.clone())))
+    }
+}
@@ -1,27 +1,127 @@
+mod bloom;
 mod boxed;
+mod cache;
 mod composite;
+mod concurrent;
 mod either;
 mod file;
 mod files;
+#[cfg(any(feature = "serde-json", feature = "serde-toml", feature = "serde-yaml"))]
+mod format;
+mod init;
+mod layered;
+mod lazy;
+mod list;
+mod observe;
 mod path;
+mod prefetch;
 mod prefix;
+mod readonly;
+mod registry;
+mod stats;
 mod store;
+mod tenant;
+mod tree;
 pub mod util;
+mod walk;
+
+#[cfg(feature = "acl")]
+pub mod acl;
+
+#[cfg(feature = "bridge")]
+pub mod bridge;
+
+#[cfg(feature = "camino")]
+pub mod camino;
+
+#[cfg(feature = "config")]
+pub mod config;
+
+#[cfg(feature = "consul")]
+pub mod consul;
 
 #[cfg(feature = "embed")]
 pub mod embed;
 
+#[cfg(feature = "fault")]
+pub mod fault;
+
+#[cfg(feature = "fingerprint")]
+pub mod fingerprint;
+
 #[cfg(feature = "fs")]
 pub mod fs;
 
+#[cfg(feature = "wasm")]
+pub mod idb;
+
+#[cfg(feature = "kube")]
+pub mod kube;
+
+#[cfg(feature = "mock")]
+pub mod mock;
+
+#[cfg(feature = "otel")]
+pub mod otel;
+
+#[cfg(feature = "pack")]
+pub mod pack;
+
+#[cfg(feature = "pipeline")]
+pub mod pipeline;
+
+#[cfg(feature = "rclone")]
+pub mod rclone;
+
+#[cfg(feature = "remote")]
+pub mod remote;
+
+#[cfg(feature = "rest")]
+pub mod rest;
+
+#[cfg(feature = "serve")]
+pub mod serve;
+
+#[cfg(feature = "signed")]
+pub mod signed;
+
+#[cfg(feature = "spool")]
+pub mod spool;
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub mod uring;
+
+#[cfg(feature = "vfs")]
+pub mod vfs;
+
+#[cfg(feature = "watch")]
+pub mod watch;
+
+#[cfg(feature = "webhdfs")]
+pub mod webhdfs;
+
 pub use self::{
+    bloom::Indexed,
     boxed::{BoxAsyncFile, BoxAsyncFileStore, BoxFile, BoxFileStore},
+    cache::MetadataCache,
     composite::*,
+    concurrent::Concurrent,
     file::*,
     files::AsyncFiles,
+    init::*,
+    layered::{LayerWrite, Layered},
+    lazy::{Generator, VirtualEntry, VirtualFile, VirtualStore},
+    list::{ListOptions, Order},
+    observe::{Observed, Observer},
     path::*,
-    prefix::Prefixed,
+    prefix::{Prefixed, PrefixedList},
+    readonly::ReadOnly,
+    registry::{RegistryHook, StoreRegistry},
+    stats::StoreStats,
     store::*,
+    tenant::{QuotaHook, Tenant, TenantStores},
+    tree::{Node, Tree, TreeDiff},
+    walk::{DirFilter, EntryKind, WalkEntry, WalkOptions},
 };
 
 pub use url::Url;
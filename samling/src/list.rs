@@ -0,0 +1,170 @@
+use std::{collections::HashSet, io};
+
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{either::Either, AsyncFileStore, FileStore};
+
+/// The order of a sorted listing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Order {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// How to list a store with `list_with`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListOptions {
+    /// Yield paths in path order, comparing them component by component like `RelativePath`
+    /// does, so the same files list the same way from every backend, e.g. for manifests and
+    /// hashes.
+    ///
+    /// Unless the store lists in order already (see [`AsyncFileStore::SORTED`]), the whole
+    /// listing is read before the first path is yielded.
+    pub sort: Option<Order>,
+    /// Only yield paths below this directory.
+    pub prefix: Option<RelativePathBuf>,
+    /// Only yield files nested at most this many directories below the prefix, or the root.
+    /// Directories this cuts off are yielded once instead, as their path with a trailing
+    /// `/`, like the common prefixes of S3 listings. With `Some(0)`, the listing holds the
+    /// top-level entries.
+    ///
+    /// The store is still listed in full; only what is yielded is limited.
+    pub max_depth: Option<usize>,
+}
+
+impl ListOptions {
+    /// List in ascending path order.
+    pub fn sorted() -> ListOptions {
+        ListOptions {
+            sort: Some(Order::Ascending),
+            ..Default::default()
+        }
+    }
+
+    /// List the files and directories right below `prefix`.
+    pub fn shallow(prefix: impl Into<RelativePathBuf>) -> ListOptions {
+        ListOptions {
+            prefix: Some(prefix.into()),
+            max_depth: Some(0),
+            ..Default::default()
+        }
+    }
+
+    fn filter(&self) -> Option<Filter> {
+        if self.prefix.is_none() && self.max_depth.is_none() {
+            return None;
+        }
+
+        Some(Filter {
+            prefix: self.prefix.as_deref().map(RelativePath::normalize),
+            max_depth: self.max_depth,
+            seen: HashSet::new(),
+        })
+    }
+}
+
+/// Applies the prefix and depth of [`ListOptions`] to a listing.
+struct Filter {
+    prefix: Option<RelativePathBuf>,
+    max_depth: Option<usize>,
+    seen: HashSet<RelativePathBuf>,
+}
+
+impl Filter {
+    fn apply(&mut self, path: RelativePathBuf) -> Option<RelativePathBuf> {
+        let path = path.normalize();
+        let inner = match &self.prefix {
+            Some(prefix) => path.strip_prefix(prefix).ok()?,
+            None => &path,
+        };
+        if inner.as_str().is_empty() {
+            return None;
+        }
+
+        let Some(max) = self.max_depth else {
+            return Some(path);
+        };
+
+        let components = inner.components().collect::<Vec<_>>();
+        if components.len() <= max + 1 {
+            return Some(path);
+        }
+
+        let mut dir = self.prefix.clone().unwrap_or_default();
+        for component in &components[..=max] {
+            dir.push(component.as_str());
+        }
+        if !self.seen.insert(dir.clone()) {
+            return None;
+        }
+        Some(RelativePathBuf::from(format!("{dir}/")))
+    }
+}
+
+/// Sort `paths` into `order`, when they are known to be ascending already or not.
+fn sort(paths: &mut [RelativePathBuf], order: Order, sorted: bool) {
+    if !sorted {
+        paths.sort();
+    }
+    if order == Order::Descending {
+        paths.reverse();
+    }
+}
+
+pub(crate) async fn list_with<S>(
+    store: &S,
+    options: ListOptions,
+) -> io::Result<BoxStream<'static, io::Result<RelativePathBuf>>>
+where
+    S: AsyncFileStore + Sync,
+{
+    let mut list = store.list().await?;
+    if let Some(mut filter) = options.filter() {
+        list = list
+            .try_filter_map(move |path| futures::future::ready(Ok(filter.apply(path))))
+            .boxed();
+    }
+
+    let order = match options.sort {
+        None => return Ok(list),
+        Some(Order::Ascending) if S::SORTED => return Ok(list),
+        Some(order) => order,
+    };
+
+    let mut paths = list.try_collect::<Vec<_>>().await?;
+    sort(&mut paths, order, S::SORTED);
+    Ok(futures::stream::iter(paths.into_iter().map(Ok)).boxed())
+}
+
+pub(crate) fn list_with_sync<S>(
+    store: &S,
+    options: ListOptions,
+) -> impl Iterator<Item = io::Result<RelativePathBuf>>
+where
+    S: FileStore,
+{
+    let mut filter = options.filter();
+    let list = store
+        .list()
+        .filter_map(move |path| match (path, &mut filter) {
+            (Ok(path), Some(filter)) => filter.apply(path).map(Ok),
+            (path, _) => Some(path),
+        });
+
+    let order = match options.sort {
+        None => return Either::Left(list),
+        Some(Order::Ascending) if S::SORTED => return Either::Left(list),
+        Some(order) => order,
+    };
+
+    let paths = match list.collect::<io::Result<Vec<_>>>() {
+        Ok(mut paths) => {
+            sort(&mut paths, order, S::SORTED);
+            paths.into_iter().map(Ok).collect()
+        }
+        Err(err) => vec![Err(err)],
+    };
+    Either::Right(paths.into_iter())
+}
@@ -0,0 +1,679 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{stream::BoxStream, Future, StreamExt, TryStreamExt};
+use mime::Mime;
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{
+    file::link_target, AsyncFile, AsyncFileInit, AsyncFileStore, Attributes, File, FileBody,
+    FileInit, FileStore, Link, Metadata, Permissions,
+};
+
+/// The store operations recorded by a [`MockFileStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MockOp {
+    Metadata,
+    OpenFile,
+    RmFile,
+    WriteFile,
+    WriteRange,
+    SetPermissions,
+    Symlink,
+    HardLink,
+    ReadLink,
+    List,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockCall {
+    pub op: MockOp,
+    /// `None` for `List`.
+    pub path: Option<RelativePathBuf>,
+}
+
+/// Identifies a snapshot taken with [`MockFileStore::snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SnapshotId(u64);
+
+/// An in-memory store for tests, and the crate's in-memory store in general.
+///
+/// Files are kept in memory and behave like a regular store, while tests can script failures
+/// for specific paths, slow every call down and inspect the calls the store received. It is
+/// not limited to tests: with [`MockFileStore::snapshot`] and [`MockFileStore::restore`] it
+/// also serves preview environments which need to roll their state back.
+/// Clones share the same state, so a handle can be kept after moving the store into the
+/// middleware under test.
+///
+/// ```ignore
+/// let mock = MockFileStore::new();
+/// mock.insert("index.html", "<h1>Hello</h1>");
+/// mock.fail(MockOp::OpenFile, "index.html", io::ErrorKind::TimedOut.into());
+///
+/// let store = Retry::new(mock.clone());
+/// store.read("index.html").await?;
+/// assert_eq!(mock.count(MockOp::OpenFile, "index.html"), 2);
+/// ```
+#[derive(Clone, Default)]
+pub struct MockFileStore {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    files: HashMap<RelativePathBuf, MockEntry>,
+    failures: HashMap<(Option<MockOp>, RelativePathBuf), VecDeque<io::Error>>,
+    list_failures: VecDeque<io::Error>,
+    calls: Vec<MockCall>,
+    latency: Duration,
+    snapshots: HashMap<SnapshotId, HashMap<RelativePathBuf, MockEntry>>,
+    next_snapshot: u64,
+    next_inode: u64,
+}
+
+impl State {
+    fn entry(&mut self, data: Bytes, mime: Option<Mime>) -> MockEntry {
+        self.next_inode += 1;
+        MockEntry {
+            data,
+            mime,
+            modified: SystemTime::now(),
+            permissions: None,
+            inode: self.next_inode,
+            symlink: None,
+        }
+    }
+
+    /// Follow symlinks from `path` to the entry they lead to.
+    fn resolve(&self, path: &RelativePath) -> io::Result<RelativePathBuf> {
+        let mut path = path.normalize();
+        for _ in 0..MAX_SYMLINKS {
+            match self
+                .files
+                .get(&path)
+                .and_then(|entry| entry.symlink.as_ref())
+            {
+                Some(target) => path = link_target(&path, target)?,
+                None => return Ok(path),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "too many levels of symbolic links",
+        ))
+    }
+
+    /// Hand the entry at `path` on to the other names of its file.
+    fn sync_links(&mut self, path: &RelativePath) {
+        let Some(entry) = self.files.get(path).cloned() else {
+            return;
+        };
+        for (other, linked) in self.files.iter_mut() {
+            if linked.inode == entry.inode && linked.symlink.is_none() && other != path {
+                *linked = entry.clone();
+            }
+        }
+    }
+}
+
+/// Symlinks followed before giving up, like `ELOOP`.
+const MAX_SYMLINKS: usize = 40;
+
+#[derive(Clone)]
+struct MockEntry {
+    data: Bytes,
+    mime: Option<Mime>,
+    modified: SystemTime,
+    permissions: Option<Permissions>,
+    /// Shared by hard links to the same file.
+    inode: u64,
+    symlink: Option<RelativePathBuf>,
+}
+
+impl MockFileStore {
+    pub fn new() -> MockFileStore {
+        MockFileStore::default()
+    }
+
+    /// Add or replace a file without recording a call.
+    pub fn insert(&self, path: impl AsRef<RelativePath>, data: impl Into<Bytes>) {
+        let mut state = self.state.lock().unwrap();
+        let entry = state.entry(data.into(), None);
+        state.files.insert(path.as_ref().normalize(), entry);
+    }
+
+    /// The current contents of a file, following symlinks, without recording a call.
+    pub fn get(&self, path: impl AsRef<RelativePath>) -> Option<Bytes> {
+        let state = self.state.lock().unwrap();
+        let path = state.resolve(path.as_ref()).ok()?;
+        state.files.get(&path).map(|entry| entry.data.clone())
+    }
+
+    /// Fail the next `op` on `path` with `error`. Queued errors are returned in order.
+    pub fn fail(&self, op: MockOp, path: impl AsRef<RelativePath>, error: io::Error) {
+        self.push_failure(Some(op), path.as_ref(), error);
+    }
+
+    /// Fail the next operation of any kind on `path` with `error`.
+    pub fn fail_any(&self, path: impl AsRef<RelativePath>, error: io::Error) {
+        self.push_failure(None, path.as_ref(), error);
+    }
+
+    /// Fail the next `list` with `error`.
+    pub fn fail_list(&self, error: io::Error) {
+        self.state.lock().unwrap().list_failures.push_back(error);
+    }
+
+    /// Delay every call by `latency`.
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().latency = latency;
+    }
+
+    /// Every call received so far, in order.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// The number of times `op` was called for `path`.
+    pub fn count(&self, op: MockOp, path: impl AsRef<RelativePath>) -> usize {
+        let path = path.as_ref().normalize();
+        let state = self.state.lock().unwrap();
+        state
+            .calls
+            .iter()
+            .filter(|call| call.op == op && call.path.as_ref() == Some(&path))
+            .count()
+    }
+
+    pub fn clear_calls(&self) {
+        self.state.lock().unwrap().calls.clear();
+    }
+
+    /// Checkpoint the files of the store. Contents are shared with the live files, so only
+    /// the index is copied.
+    pub fn snapshot(&self) -> SnapshotId {
+        let mut state = self.state.lock().unwrap();
+        let id = SnapshotId(state.next_snapshot);
+        state.next_snapshot += 1;
+        let files = state.files.clone();
+        state.snapshots.insert(id, files);
+        id
+    }
+
+    /// Roll the files back to a snapshot, which stays available to restore again.
+    /// Fails with `NotFound` for unknown or released snapshots.
+    pub fn restore(&self, id: SnapshotId) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let files = state
+            .snapshots
+            .get(&id)
+            .cloned()
+            .ok_or(io::ErrorKind::NotFound)?;
+        state.files = files;
+        Ok(())
+    }
+
+    /// Forget a snapshot.
+    pub fn release(&self, id: SnapshotId) {
+        self.state.lock().unwrap().snapshots.remove(&id);
+    }
+
+    fn push_failure(&self, op: Option<MockOp>, path: &RelativePath, error: io::Error) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .entry((op, path.normalize()))
+            .or_default()
+            .push_back(error);
+    }
+
+    /// Record a call and pop the failure scripted for it, if any.
+    fn record(&self, op: MockOp, path: Option<&RelativePath>) -> (Duration, io::Result<()>) {
+        let mut state = self.state.lock().unwrap();
+        let path = path.map(RelativePath::normalize);
+        state.calls.push(MockCall {
+            op,
+            path: path.clone(),
+        });
+
+        let failure = match path {
+            Some(path) => {
+                let mut pop = |key: (Option<MockOp>, RelativePathBuf)| {
+                    state.failures.get_mut(&key).and_then(VecDeque::pop_front)
+                };
+                pop((Some(op), path.clone())).or_else(|| pop((None, path)))
+            }
+            None => state.list_failures.pop_front(),
+        };
+
+        (state.latency, failure.map_or(Ok(()), Err))
+    }
+
+    fn metadata_of(&self, path: &RelativePath) -> io::Result<Metadata> {
+        let path = path.normalize();
+        let state = self.state.lock().unwrap();
+        let entry = state
+            .files
+            .get(&state.resolve(&path)?)
+            .ok_or(io::ErrorKind::NotFound)?;
+
+        let mime = entry
+            .mime
+            .clone()
+            .unwrap_or_else(|| match path.extension() {
+                Some(ext) => mime_guess::from_ext(ext).first_or_octet_stream(),
+                None => mime::APPLICATION_OCTET_STREAM,
+            });
+
+        Ok(Metadata {
+            path,
+            size: entry.data.len() as u64,
+            mime,
+            modified: Some(entry.modified),
+            permissions: entry.permissions,
+            attributes: Attributes::new(),
+        })
+    }
+
+    fn open(&self, path: &RelativePath) -> io::Result<MockFile> {
+        let state = self.state.lock().unwrap();
+        match state.files.get(&state.resolve(path)?) {
+            Some(entry) => Ok(MockFile(entry.data.clone())),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn remove(&self, path: &RelativePath) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match state.files.remove(&path.normalize()) {
+            Some(_) => Ok(()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn store(&self, path: &RelativePath, data: Bytes, mime: Option<Mime>) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let path = state.resolve(path)?;
+        let mut entry = state.entry(data, mime);
+        if let Some(existing) = state.files.get(&path) {
+            entry.inode = existing.inode;
+        }
+        state.files.insert(path.clone(), entry);
+        state.sync_links(&path);
+        Ok(())
+    }
+
+    fn patch(&self, path: &RelativePath, offset: u64, data: &[u8]) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let path = state.resolve(path)?;
+        let entry = state.files.get_mut(&path).ok_or(io::ErrorKind::NotFound)?;
+
+        let start = usize::try_from(offset)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "offset too large"))?;
+        let mut patched = BytesMut::from(&entry.data[..]);
+        if patched.len() < start + data.len() {
+            patched.resize(start + data.len(), 0);
+        }
+        patched[start..start + data.len()].copy_from_slice(data);
+
+        entry.data = patched.freeze();
+        entry.modified = SystemTime::now();
+        state.sync_links(&path);
+        Ok(())
+    }
+
+    fn chmod(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let path = state.resolve(path)?;
+        let entry = state.files.get_mut(&path).ok_or(io::ErrorKind::NotFound)?;
+        entry.permissions = Some(permissions);
+        state.sync_links(&path);
+        Ok(())
+    }
+
+    fn make_symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        link_target(link, target)?;
+        let mut state = self.state.lock().unwrap();
+        let link = link.normalize();
+        if state.files.contains_key(&link) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        let mut entry = state.entry(Bytes::new(), None);
+        entry.symlink = Some(target.to_relative_path_buf());
+        state.files.insert(link, entry);
+        Ok(())
+    }
+
+    fn make_hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let dst = dst.normalize();
+        if state.files.contains_key(&dst) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        let entry = state
+            .files
+            .get(&state.resolve(src)?)
+            .cloned()
+            .ok_or(io::ErrorKind::NotFound)?;
+        state.files.insert(dst, entry);
+        Ok(())
+    }
+
+    fn link_of(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        let state = self.state.lock().unwrap();
+        let entry = state
+            .files
+            .get(&path.normalize())
+            .ok_or(io::ErrorKind::NotFound)?;
+
+        if let Some(target) = &entry.symlink {
+            return Ok(Some(Link::Symlink(target.clone())));
+        }
+
+        let names = state
+            .files
+            .values()
+            .filter(|other| other.inode == entry.inode && other.symlink.is_none())
+            .count();
+        Ok((names > 1).then_some(Link::Hard(entry.inode)))
+    }
+
+    fn paths(&self) -> Vec<RelativePathBuf> {
+        let state = self.state.lock().unwrap();
+        state.files.keys().cloned().collect()
+    }
+}
+
+async fn delay(latency: Duration) {
+    if !latency.is_zero() {
+        futures_timer::Delay::new(latency).await;
+    }
+}
+
+fn block(latency: Duration) {
+    if !latency.is_zero() {
+        std::thread::sleep(latency);
+    }
+}
+
+impl AsyncFileStore for MockFileStore {
+    type File = MockFile;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        let (latency, ret) = self.record(MockOp::Metadata, Some(path));
+        async move {
+            delay(latency).await;
+            ret?;
+            self.metadata_of(path)
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        let (latency, ret) = self.record(MockOp::OpenFile, Some(path));
+        async move {
+            delay(latency).await;
+            ret?;
+            self.open(path)
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        let (latency, ret) = self.record(MockOp::RmFile, Some(path));
+        async move {
+            delay(latency).await;
+            ret?;
+            self.remove(path)
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        let (latency, ret) = self.record(MockOp::WriteFile, Some(path));
+        async move {
+            delay(latency).await;
+            ret?;
+
+            let mime = init.mime.clone();
+            let data = init
+                .into_stream()
+                .await?
+                .try_fold(BytesMut::new(), |mut data, chunk| async move {
+                    data.extend_from_slice(&chunk);
+                    Ok(data)
+                })
+                .await?;
+
+            self.store(path, data.freeze(), mime)
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        let (latency, ret) = self.record(MockOp::List, None);
+        async move {
+            delay(latency).await;
+            ret?;
+            Ok(futures::stream::iter(self.paths().into_iter().map(Ok)).boxed())
+        }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let (latency, ret) = self.record(MockOp::WriteRange, Some(path));
+        async move {
+            delay(latency).await;
+            ret?;
+            self.patch(path, offset, &data)
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        true
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let (latency, ret) = self.record(MockOp::SetPermissions, Some(path));
+        async move {
+            delay(latency).await;
+            ret?;
+            self.chmod(path, permissions)
+        }
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let (latency, ret) = self.record(MockOp::Symlink, Some(link));
+        async move {
+            delay(latency).await;
+            ret?;
+            self.make_symlink(target, link)
+        }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let (latency, ret) = self.record(MockOp::HardLink, Some(dst));
+        async move {
+            delay(latency).await;
+            ret?;
+            self.make_hard_link(src, dst)
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        let (latency, ret) = self.record(MockOp::ReadLink, Some(path));
+        async move {
+            delay(latency).await;
+            ret?;
+            self.link_of(path)
+        }
+    }
+
+    fn supports_links(&self) -> bool {
+        true
+    }
+}
+
+impl FileStore for MockFileStore {
+    type File = MockFile;
+    type List = std::vec::IntoIter<io::Result<RelativePathBuf>>;
+
+    fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
+        let (latency, ret) = self.record(MockOp::Metadata, Some(path));
+        block(latency);
+        ret?;
+        self.metadata_of(path)
+    }
+
+    fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
+        let (latency, ret) = self.record(MockOp::OpenFile, Some(path));
+        block(latency);
+        ret?;
+        self.open(path)
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
+        let (latency, ret) = self.record(MockOp::RmFile, Some(path));
+        block(latency);
+        ret?;
+        self.remove(path)
+    }
+
+    fn write_file(&self, path: &RelativePath, init: FileInit) -> Result<(), io::Error> {
+        let (latency, ret) = self.record(MockOp::WriteFile, Some(path));
+        block(latency);
+        ret?;
+
+        let data = match init.body {
+            FileBody::Bytes(bs) => bs,
+            FileBody::Read(mut reader) => {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+                data.into()
+            }
+            #[cfg(feature = "fs")]
+            FileBody::Path(path) => std::fs::read(path)?.into(),
+        };
+
+        self.store(path, data, init.mime)
+    }
+
+    fn list(&self) -> Self::List {
+        let (latency, ret) = self.record(MockOp::List, None);
+        block(latency);
+        match ret {
+            Ok(()) => self.paths().into_iter().map(Ok).collect::<Vec<_>>(),
+            // The sync listing can't fail up front, so the failure is its only item
+            Err(err) => vec![Err(err)],
+        }
+        .into_iter()
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        let (latency, ret) = self.record(MockOp::WriteRange, Some(path));
+        block(latency);
+        ret?;
+        self.patch(path, offset, &data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        true
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        let (latency, ret) = self.record(MockOp::SetPermissions, Some(path));
+        block(latency);
+        ret?;
+        self.chmod(path, permissions)
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        let (latency, ret) = self.record(MockOp::Symlink, Some(link));
+        block(latency);
+        ret?;
+        self.make_symlink(target, link)
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        let (latency, ret) = self.record(MockOp::HardLink, Some(dst));
+        block(latency);
+        ret?;
+        self.make_hard_link(src, dst)
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        let (latency, ret) = self.record(MockOp::ReadLink, Some(path));
+        block(latency);
+        ret?;
+        self.link_of(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        true
+    }
+}
+
+pub struct MockFile(Bytes);
+
+impl AsyncFile for MockFile {
+    type Body = futures::stream::Once<futures::future::Ready<io::Result<Bytes>>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        futures::future::ready(crate::util::slice(&self.0, range))
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        let data = self.0.clone();
+        futures::future::ready(Ok(futures::stream::once(futures::future::ready(Ok(data)))))
+    }
+}
+
+impl File for MockFile {
+    type Body = bytes::buf::Reader<Bytes>;
+
+    fn read_range(&self, range: std::ops::Range<u64>) -> Result<Bytes, io::Error> {
+        crate::util::slice(&self.0, range)
+    }
+
+    fn reader(&self) -> Result<Self::Body, io::Error> {
+        Ok(self.0.clone().reader())
+    }
+}
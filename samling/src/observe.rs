@@ -0,0 +1,244 @@
+use std::{io, sync::Arc};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream::BoxStream, Future};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{AsyncFileInit, AsyncFileStore, Link, Metadata, Permissions, StoreStats};
+
+/// Notified by [`Observed`] after files are written or removed, e.g. to purge a CDN or update
+/// a search index.
+pub trait Observer: Send + Sync {
+    fn written<'a>(&'a self, path: &'a RelativePath, meta: &'a Metadata) -> BoxFuture<'a, ()> {
+        let _ = (path, meta);
+        Box::pin(async {})
+    }
+
+    fn removed<'a>(&'a self, path: &'a RelativePath) -> BoxFuture<'a, ()> {
+        let _ = path;
+        Box::pin(async {})
+    }
+}
+
+/// Invokes observers after every successful `write_file` and `rm_file`.
+///
+/// Observers run concurrently and are awaited before the operation returns. They are not
+/// notified of failed operations, nor of writes whose metadata can't be read back.
+pub struct Observed<T> {
+    inner: T,
+    observers: Vec<Arc<dyn Observer>>,
+}
+
+impl<T> Observed<T> {
+    pub fn new(inner: T) -> Observed<T> {
+        Observed {
+            inner,
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn observe(mut self, observer: impl Observer + 'static) -> Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> AsyncFileStore for Observed<T>
+where
+    T: AsyncFileStore + Sync,
+{
+    type File = T::File;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        self.inner.metadata(path)
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        self.inner.open_file(path)
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            self.inner.rm_file(path).await?;
+            futures::future::join_all(self.observers.iter().map(|o| o.removed(path))).await;
+            Ok(())
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            self.inner.write_file(path, init).await?;
+            if self.observers.is_empty() {
+                return Ok(());
+            }
+
+            if let Ok(meta) = self.inner.metadata(path).await {
+                futures::future::join_all(self.observers.iter().map(|o| o.written(path, &meta)))
+                    .await;
+            }
+            Ok(())
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.inner.list()
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        self.inner.list_ref()
+    }
+
+    fn stats(&self) -> impl Future<Output = io::Result<StoreStats>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.stats()
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.health()
+    }
+
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.prefetch(paths)
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.prefetch_prefix(prefix)
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.inner.write_range(path, offset, data).await?;
+            if self.observers.is_empty() {
+                return Ok(());
+            }
+
+            if let Ok(meta) = self.inner.metadata(path).await {
+                futures::future::join_all(self.observers.iter().map(|o| o.written(path, &meta)))
+                    .await;
+            }
+            Ok(())
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.set_permissions(path, permissions)
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.inner.symlink(target, link).await?;
+            if self.observers.is_empty() {
+                return Ok(());
+            }
+
+            if let Ok(meta) = self.inner.metadata(link).await {
+                futures::future::join_all(self.observers.iter().map(|o| o.written(link, &meta)))
+                    .await;
+            }
+            Ok(())
+        }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.inner.hard_link(src, dst).await?;
+            if self.observers.is_empty() {
+                return Ok(());
+            }
+
+            if let Ok(meta) = self.inner.metadata(dst).await {
+                futures::future::join_all(self.observers.iter().map(|o| o.written(dst, &meta)))
+                    .await;
+            }
+            Ok(())
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        self.inner.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.close()
+    }
+}
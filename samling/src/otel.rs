@@ -0,0 +1,388 @@
+use std::{io, sync::Arc, time::Instant};
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future, StreamExt, TryStreamExt};
+use opentelemetry::{
+    global::{self, BoxedTracer},
+    metrics::{Counter, Histogram},
+    trace::{Span, SpanKind, Status, Tracer},
+    KeyValue,
+};
+use relative_path::{RelativePath, RelativePathBuf};
+use url::Url;
+
+use crate::{AsyncFile, AsyncFileInit, AsyncFileStore, Link, Metadata, Permissions};
+
+/// Records a span and metrics for every operation on the wrapped store, using the global
+/// OpenTelemetry tracer and meter provider.
+///
+/// Spans are named `samling.<operation>` and carry `storage.system`, `storage.bucket`,
+/// `storage.key` and, where known, `storage.bytes`. Operations are timed by the
+/// `samling.operation.duration` histogram and bytes moved counted by `samling.io`, both without
+/// the key to keep cardinality low.
+///
+/// ```ignore
+/// let store = Telemetry::new(store, "s3").bucket("assets");
+/// ```
+pub struct Telemetry<T> {
+    inner: T,
+    instruments: Arc<Instruments>,
+}
+
+struct Instruments {
+    system: String,
+    bucket: Option<String>,
+    tracer: BoxedTracer,
+    duration: Histogram<f64>,
+    bytes: Counter<u64>,
+}
+
+impl Clone for Instruments {
+    /// A copy sharing the metric instruments, with the tracer taken from the global provider
+    /// again.
+    fn clone(&self) -> Self {
+        Instruments {
+            system: self.system.clone(),
+            bucket: self.bucket.clone(),
+            tracer: global::tracer("samling"),
+            duration: self.duration.clone(),
+            bytes: self.bytes.clone(),
+        }
+    }
+}
+
+impl<T> Telemetry<T> {
+    /// `system` names the backend, e.g. `fs`, `s3` or `embed`.
+    pub fn new(inner: T, system: impl Into<String>) -> Telemetry<T> {
+        let meter = global::meter("samling");
+        Telemetry {
+            inner,
+            instruments: Arc::new(Instruments {
+                system: system.into(),
+                bucket: None,
+                tracer: global::tracer("samling"),
+                duration: meter
+                    .f64_histogram("samling.operation.duration")
+                    .with_unit("s")
+                    .with_description("Duration of store operations")
+                    .init(),
+                bytes: meter
+                    .u64_counter("samling.io")
+                    .with_unit("By")
+                    .with_description("Bytes read from and written to stores")
+                    .init(),
+            }),
+        }
+    }
+
+    /// The bucket, container or root the store operates on.
+    pub fn bucket(mut self, bucket: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.instruments).bucket = Some(bucket.into());
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl Instruments {
+    fn attributes(&self) -> Vec<KeyValue> {
+        let mut attributes = vec![KeyValue::new("storage.system", self.system.clone())];
+        if let Some(bucket) = &self.bucket {
+            attributes.push(KeyValue::new("storage.bucket", bucket.clone()));
+        }
+        attributes
+    }
+
+    async fn record<F, R>(
+        &self,
+        operation: &'static str,
+        path: Option<&RelativePath>,
+        bytes: Option<u64>,
+        future: F,
+    ) -> io::Result<R>
+    where
+        F: Future<Output = io::Result<R>>,
+    {
+        let mut attributes = self.attributes();
+        attributes.push(KeyValue::new("storage.operation", operation));
+
+        let mut span_attributes = attributes.clone();
+        if let Some(path) = path {
+            span_attributes.push(KeyValue::new("storage.key", path.to_string()));
+        }
+        if let Some(bytes) = bytes {
+            span_attributes.push(KeyValue::new("storage.bytes", bytes as i64));
+        }
+
+        let mut span = self
+            .tracer
+            .span_builder(format!("samling.{operation}"))
+            .with_kind(SpanKind::Client)
+            .with_attributes(span_attributes)
+            .start(&self.tracer);
+
+        let start = Instant::now();
+        let ret = future.await;
+
+        if let Err(err) = &ret {
+            span.set_status(Status::error(err.to_string()));
+            attributes.push(KeyValue::new("error.type", format!("{:?}", err.kind())));
+        }
+        span.end();
+        self.duration
+            .record(start.elapsed().as_secs_f64(), &attributes);
+
+        ret
+    }
+
+    fn count(&self, direction: &'static str, bytes: u64) {
+        let mut attributes = self.attributes();
+        attributes.push(KeyValue::new("direction", direction));
+        self.bytes.add(bytes, &attributes);
+    }
+}
+
+impl<T> AsyncFileStore for Telemetry<T>
+where
+    T: AsyncFileStore + Sync,
+    T::File: Send + Sync,
+    <T::File as AsyncFile>::Body: Send + 'static,
+{
+    type File = TelemetryFile<T::File>;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        self.instruments
+            .record("metadata", Some(path), None, self.inner.metadata(path))
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            let file = self
+                .instruments
+                .record("open_file", Some(path), None, self.inner.open_file(path))
+                .await?;
+            Ok(TelemetryFile {
+                file,
+                path: path.to_relative_path_buf(),
+                instruments: self.instruments.clone(),
+            })
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        self.instruments
+            .record("rm_file", Some(path), None, self.inner.rm_file(path))
+    }
+
+    /// The written bytes are only recorded when the init has a length hint.
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let len = init.len;
+            self.instruments
+                .record(
+                    "write_file",
+                    Some(path),
+                    len,
+                    self.inner.write_file(path, init),
+                )
+                .await?;
+            if let Some(len) = len {
+                self.instruments.count("write", len);
+            }
+            Ok(())
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.instruments
+            .record("list", None, None, self.inner.list())
+    }
+
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.instruments
+            .record("prefetch", None, None, self.inner.prefetch(paths))
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.instruments.record(
+            "prefetch_prefix",
+            Some(prefix),
+            None,
+            self.inner.prefetch_prefix(prefix),
+        )
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let len = data.len() as u64;
+            self.instruments
+                .record(
+                    "write_range",
+                    Some(path),
+                    Some(len),
+                    self.inner.write_range(path, offset, data),
+                )
+                .await?;
+            self.instruments.count("write", len);
+            Ok(())
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.instruments.record(
+            "set_permissions",
+            Some(path),
+            None,
+            self.inner.set_permissions(path, permissions),
+        )
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.instruments.record(
+            "symlink",
+            Some(link),
+            None,
+            self.inner.symlink(target, link),
+        )
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.instruments
+            .record("hard_link", Some(dst), None, self.inner.hard_link(src, dst))
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        self.instruments
+            .record("read_link", Some(path), None, self.inner.read_link(path))
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.close()
+    }
+}
+
+pub struct TelemetryFile<F> {
+    file: F,
+    path: RelativePathBuf,
+    instruments: Arc<Instruments>,
+}
+
+impl<F> AsyncFile for TelemetryFile<F>
+where
+    F: AsyncFile + Sync,
+    F::Body: Send + 'static,
+{
+    type Body = BoxStream<'static, io::Result<Bytes>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        async move {
+            let len = range.end.saturating_sub(range.start);
+            let bytes = self
+                .instruments
+                .record(
+                    "read_range",
+                    Some(&self.path),
+                    Some(len),
+                    self.file.read_range(range),
+                )
+                .await?;
+            self.instruments.count("read", bytes.len() as u64);
+            Ok(bytes)
+        }
+    }
+
+    /// The span covers opening the body; bytes are counted as the body is consumed.
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        async move {
+            let body = self
+                .instruments
+                .record("reader", Some(&self.path), None, self.file.reader())
+                .await?;
+            let instruments = self.instruments.clone();
+            Ok(body
+                .inspect_ok(move |chunk| instruments.count("read", chunk.len() as u64))
+                .boxed())
+        }
+    }
+
+    fn url(&self) -> Option<Url> {
+        self.file.url()
+    }
+
+    fn native_path(&self) -> Option<&std::path::Path> {
+        self.file.native_path()
+    }
+}
@@ -0,0 +1,579 @@
+//! A single file bundle of a store, loadable at runtime.
+//!
+//! The format is the data of every file, followed by an index and a fixed size footer, so a
+//! pack can be written in a single pass:
+//!
+//! ```text
+//! data     entries, stored raw or gzip compressed
+//! index    count: u32, then per entry
+//!          path_len: u16, path, mime_len: u16, mime,
+//!          offset: u64, stored: u64, size: u64, modified: u64, compression: u8
+//! footer   index_offset: u64, index_len: u64, magic: b"SMLPACK1"
+//! ```
+//!
+//! Integers are little endian, `modified` is in seconds since the unix epoch with `u64::MAX`
+//! for unknown.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_compression::futures::{bufread::GzipDecoder, write::GzipEncoder};
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{
+    io::{AsyncWrite, BufReader},
+    lock::Mutex,
+    pin_mut,
+    stream::BoxStream,
+    AsyncWriteExt, Future, StreamExt, TryStreamExt,
+};
+use mime::Mime;
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{
+    boxed::async_file_box, AsyncFile, AsyncFileBody, AsyncFileInit, AsyncFileStore, Attributes,
+    BoxAsyncFile, Metadata, StoreStats,
+};
+
+const MAGIC: &[u8; 8] = b"SMLPACK1";
+const FOOTER: u64 = 24;
+const UNKNOWN_MODIFIED: u64 = u64::MAX;
+/// The most deflate can expand its input, bounding the size of compressed entries.
+const DEFLATE_MAX_RATIO: u64 = 1032;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Gzip files with a compressible mime type. Ranges of compressed files are read by
+    /// decompressing the whole file, once per opened file.
+    Gzip,
+}
+
+impl Compression {
+    fn tag(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Gzip => 1,
+        }
+    }
+
+    fn from_tag(tag: u8) -> io::Result<Compression> {
+        match tag {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Gzip),
+            _ => Err(invalid("unknown compression")),
+        }
+    }
+}
+
+/// Packs every file of a store into a single file.
+///
+/// ```ignore
+/// let output = AllowStdIo::new(std::fs::File::create("assets.pack")?);
+/// PackBuilder::new().compression(Compression::Gzip).write(&store, output).await?;
+/// let pack = PackFileStore::load(&FsFileStore::new(".")?, "assets.pack".as_ref()).await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PackBuilder {
+    compression: Compression,
+}
+
+impl PackBuilder {
+    pub fn new() -> PackBuilder {
+        PackBuilder::default()
+    }
+
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Write the pack to `writer`, returning the number of files packed.
+    pub async fn write<S, W>(&self, store: &S, writer: W) -> io::Result<u64>
+    where
+        S: AsyncFileStore,
+        W: AsyncWrite + Unpin,
+    {
+        let mut paths = store
+            .list()
+            .await?
+            .map_ok(|path| path.normalize())
+            .try_collect::<Vec<_>>()
+            .await?;
+        paths.sort();
+        paths.dedup();
+
+        let mut writer = Counting {
+            inner: writer,
+            written: 0,
+        };
+        let mut index = Vec::new();
+        let mut count = 0u32;
+
+        for path in &paths {
+            let meta = store.metadata(path).await?;
+            let file = store.open_file(path).await?;
+            let reader = file.reader().await?;
+            pin_mut!(reader);
+
+            let compression = match self.compression {
+                Compression::Gzip if compressible(&meta.mime) => Compression::Gzip,
+                _ => Compression::None,
+            };
+
+            let offset = writer.written;
+            let mut size = 0u64;
+            match compression {
+                Compression::None => {
+                    while let Some(chunk) = reader.try_next().await? {
+                        size += chunk.len() as u64;
+                        writer.write_all(&chunk).await?;
+                    }
+                }
+                Compression::Gzip => {
+                    let mut encoder = GzipEncoder::new(&mut writer);
+                    while let Some(chunk) = reader.try_next().await? {
+                        size += chunk.len() as u64;
+                        encoder.write_all(&chunk).await?;
+                    }
+                    encoder.close().await?;
+                }
+            }
+
+            let path = path.as_str().as_bytes();
+            let mime = meta.mime.as_ref().as_bytes();
+            let modified = meta
+                .modified
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .map_or(UNKNOWN_MODIFIED, |m| m.as_secs());
+
+            index.extend_from_slice(&short_len(path)?.to_le_bytes());
+            index.extend_from_slice(path);
+            index.extend_from_slice(&short_len(mime)?.to_le_bytes());
+            index.extend_from_slice(mime);
+            index.extend_from_slice(&offset.to_le_bytes());
+            index.extend_from_slice(&(writer.written - offset).to_le_bytes());
+            index.extend_from_slice(&size.to_le_bytes());
+            index.extend_from_slice(&modified.to_le_bytes());
+            index.push(compression.tag());
+            count = count
+                .checked_add(1)
+                .ok_or_else(|| invalid("too many files"))?;
+        }
+
+        let index_offset = writer.written;
+        writer.write_all(&count.to_le_bytes()).await?;
+        writer.write_all(&index).await?;
+        let index_len = writer.written - index_offset;
+
+        writer.write_all(&index_offset.to_le_bytes()).await?;
+        writer.write_all(&index_len.to_le_bytes()).await?;
+        writer.write_all(MAGIC).await?;
+        writer.inner.flush().await?;
+
+        Ok(count as u64)
+    }
+}
+
+/// Serves the files of a pack written by [`PackBuilder`], reading ranges of uncompressed files
+/// straight from the pack.
+#[derive(Clone)]
+pub struct PackFileStore {
+    source: Arc<BoxAsyncFile<'static>>,
+    entries: Arc<BTreeMap<RelativePathBuf, Entry>>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    mime: Mime,
+    offset: u64,
+    stored: u64,
+    size: u64,
+    modified: Option<SystemTime>,
+    compression: Compression,
+}
+
+impl PackFileStore {
+    /// Open a pack from a file of `size` bytes.
+    pub async fn open<F>(file: F, size: u64) -> io::Result<PackFileStore>
+    where
+        F: AsyncFile + Send + Sync + 'static,
+        F::Body: Send + 'static,
+    {
+        if size < FOOTER {
+            return Err(invalid("not a pack"));
+        }
+
+        let mut footer = file.read_range(size - FOOTER..size).await?;
+        if footer.len() as u64 != FOOTER {
+            return Err(invalid("not a pack"));
+        }
+        let index_offset = footer.get_u64_le();
+        let index_len = footer.get_u64_le();
+        if &footer[..] != MAGIC {
+            return Err(invalid("not a pack"));
+        }
+
+        let index_end = index_offset
+            .checked_add(index_len)
+            .filter(|end| *end <= size - FOOTER)
+            .ok_or_else(|| invalid("index out of bounds"))?;
+        let index = file.read_range(index_offset..index_end).await?;
+
+        Ok(PackFileStore {
+            source: Arc::new(async_file_box(file)),
+            entries: Arc::new(parse_index(index, index_offset)?),
+        })
+    }
+
+    /// Open a pack held in memory, e.g. one included with `include_bytes!`.
+    pub async fn from_bytes(bytes: impl Into<Bytes>) -> io::Result<PackFileStore> {
+        let bytes = bytes.into();
+        let size = bytes.len() as u64;
+        PackFileStore::open(PackBytes(bytes), size).await
+    }
+
+    /// Open the pack at `path` in `store`.
+    pub async fn load<S>(store: &S, path: &RelativePath) -> io::Result<PackFileStore>
+    where
+        S: AsyncFileStore,
+        S::File: Send + Sync + 'static,
+        <S::File as AsyncFile>::Body: Send + 'static,
+    {
+        let meta = store.metadata(path).await?;
+        let file = store.open_file(path).await?;
+        PackFileStore::open(file, meta.size).await
+    }
+
+    fn entry(&self, path: &RelativePath) -> io::Result<&Entry> {
+        self.entries
+            .get(&path.normalize())
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+}
+
+fn parse_index(mut index: Bytes, data_len: u64) -> io::Result<BTreeMap<RelativePathBuf, Entry>> {
+    fn take(index: &mut Bytes, len: usize) -> io::Result<Bytes> {
+        if index.remaining() < len {
+            return Err(invalid("truncated index"));
+        }
+        Ok(index.split_to(len))
+    }
+
+    fn string(index: &mut Bytes) -> io::Result<String> {
+        let len = take(index, 2)?.get_u16_le() as usize;
+        String::from_utf8(take(index, len)?.to_vec()).map_err(|_| invalid("invalid utf-8"))
+    }
+
+    let count = take(&mut index, 4)?.get_u32_le();
+    let mut entries = BTreeMap::new();
+
+    for _ in 0..count {
+        let path = RelativePathBuf::from(string(&mut index)?);
+        let mime = string(&mut index)?
+            .parse::<Mime>()
+            .map_err(|_| invalid("invalid mime type"))?;
+
+        let mut fields = take(&mut index, 33)?;
+        let offset = fields.get_u64_le();
+        let stored = fields.get_u64_le();
+        let size = fields.get_u64_le();
+        let modified = match fields.get_u64_le() {
+            UNKNOWN_MODIFIED => None,
+            secs => Some(
+                UNIX_EPOCH
+                    .checked_add(Duration::from_secs(secs))
+                    .ok_or_else(|| invalid("invalid modified time"))?,
+            ),
+        };
+        let compression = Compression::from_tag(fields.get_u8())?;
+
+        if !matches!(offset.checked_add(stored), Some(end) if end <= data_len) {
+            return Err(invalid("entry out of bounds"));
+        }
+
+        let valid_size = match compression {
+            Compression::None => size == stored,
+            Compression::Gzip => size <= stored.saturating_mul(DEFLATE_MAX_RATIO),
+        };
+        if !valid_size {
+            return Err(invalid("invalid entry size"));
+        }
+
+        entries.insert(
+            path,
+            Entry {
+                mime,
+                offset,
+                stored,
+                size,
+                modified,
+                compression,
+            },
+        );
+    }
+
+    Ok(entries)
+}
+
+impl AsyncFileStore for PackFileStore {
+    type File = PackFile;
+
+    /// Entries are kept in a `BTreeMap`.
+    const SORTED: bool = true;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        let ret = self.entry(path).map(|entry| Metadata {
+            path: path.normalize(),
+            size: entry.size,
+            mime: entry.mime.clone(),
+            modified: entry.modified,
+            permissions: None,
+            attributes: Attributes::new(),
+        });
+        futures::future::ready(ret)
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        let ret = self.entry(path).map(|entry| PackFile {
+            source: self.source.clone(),
+            entry: entry.clone(),
+            inflated: Mutex::new(None),
+        });
+        futures::future::ready(ret)
+    }
+
+    fn rm_file(&self, _path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move { Err(io::ErrorKind::PermissionDenied.into()) }
+    }
+
+    fn write_file(
+        &self,
+        _path: &RelativePath,
+        _init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move { Err(io::ErrorKind::PermissionDenied.into()) }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        let paths = self.entries.keys().cloned().map(Ok).collect::<Vec<_>>();
+        futures::future::ready(Ok(futures::stream::iter(paths).boxed()))
+    }
+
+    /// Computed from the index, without touching the pack.
+    fn stats(&self) -> impl Future<Output = io::Result<StoreStats>> + Send
+    where
+        Self: Sync,
+    {
+        let mut stats = StoreStats::default();
+        for (path, entry) in self.entries.iter() {
+            stats.add(&Metadata {
+                path: path.clone(),
+                size: entry.size,
+                mime: entry.mime.clone(),
+                modified: entry.modified,
+                permissions: None,
+                attributes: Attributes::new(),
+            });
+        }
+        futures::future::ok(stats)
+    }
+}
+
+pub struct PackFile {
+    source: Arc<BoxAsyncFile<'static>>,
+    entry: Entry,
+    /// The decompressed file, kept for further ranges once one has been read.
+    inflated: Mutex<Option<Bytes>>,
+}
+
+impl PackFile {
+    /// The stored bytes of the file, still compressed.
+    fn stored(&self) -> impl futures::Stream<Item = io::Result<Bytes>> + Send + 'static {
+        crate::util::read_range_stream(
+            Slice {
+                source: self.source.clone(),
+                offset: self.entry.offset,
+            },
+            0..self.entry.stored,
+        )
+    }
+
+    async fn decompressed(&self) -> io::Result<BoxStream<'static, io::Result<Bytes>>> {
+        match self.entry.compression {
+            Compression::None => Ok(self.stored().boxed()),
+            Compression::Gzip => {
+                let reader = BufReader::new(self.stored().boxed().into_async_read());
+                AsyncFileBody::Reader(Box::pin(GzipDecoder::new(reader)))
+                    .into_stream()
+                    .await
+            }
+        }
+    }
+}
+
+impl PackFile {
+    /// The decompressed file, which must be exactly the size given in the index.
+    async fn inflated(&self) -> io::Result<Bytes> {
+        let mut inflated = self.inflated.lock().await;
+        if let Some(data) = &*inflated {
+            return Ok(data.clone());
+        }
+
+        let body = self.decompressed().await?;
+        pin_mut!(body);
+
+        let mut output = BytesMut::new();
+        while let Some(chunk) = body.try_next().await? {
+            if output.len() as u64 + chunk.len() as u64 > self.entry.size {
+                return Err(invalid("entry larger than its size"));
+            }
+            output.extend_from_slice(&chunk);
+        }
+        if output.len() as u64 != self.entry.size {
+            return Err(invalid("entry smaller than its size"));
+        }
+
+        let data = output.freeze();
+        *inflated = Some(data.clone());
+        Ok(data)
+    }
+}
+
+impl AsyncFile for PackFile {
+    type Body = BoxStream<'static, io::Result<Bytes>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        async move {
+            if range.start > range.end || range.end > self.entry.size {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "range out of bounds",
+                ));
+            }
+
+            match self.entry.compression {
+                Compression::None => {
+                    let offset = self.entry.offset;
+                    self.source
+                        .read_range(offset + range.start..offset + range.end)
+                        .await
+                }
+                Compression::Gzip => crate::util::slice(&self.inflated().await?, range),
+            }
+        }
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        self.decompressed()
+    }
+}
+
+/// The part of the pack starting at `offset`.
+struct Slice {
+    source: Arc<BoxAsyncFile<'static>>,
+    offset: u64,
+}
+
+impl AsyncFile for Slice {
+    type Body = BoxStream<'static, io::Result<Bytes>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        self.source
+            .read_range(self.offset + range.start..self.offset + range.end)
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        async move { Err(io::ErrorKind::Unsupported.into()) }
+    }
+}
+
+struct PackBytes(Bytes);
+
+impl AsyncFile for PackBytes {
+    type Body = futures::stream::Once<futures::future::Ready<io::Result<Bytes>>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        futures::future::ready(crate::util::slice(&self.0, range))
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        let data = self.0.clone();
+        futures::future::ready(Ok(futures::stream::once(futures::future::ready(Ok(data)))))
+    }
+}
+
+/// Counts the bytes written, to record offsets. Closing only flushes, so an encoder can be
+/// finished without closing the output.
+struct Counting<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Counting<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let ret = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = ret {
+            self.written += written as u64;
+        }
+        ret
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+}
+
+fn compressible(mime: &Mime) -> bool {
+    match (mime.type_(), mime.subtype()) {
+        (mime::TEXT, _) | (mime::IMAGE, mime::SVG) => true,
+        (mime::APPLICATION, subtype) => {
+            matches!(
+                subtype.as_str(),
+                "javascript" | "json" | "xml" | "wasm" | "manifest+json" | "octet-stream"
+            )
+        }
+        _ => false,
+    }
+}
+
+fn short_len(data: &[u8]) -> io::Result<u16> {
+    u16::try_from(data.len()).map_err(|_| invalid("path or mime type too long"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
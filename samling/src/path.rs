@@ -1,9 +1,14 @@
 use std::io;
 
+use bytes::Bytes;
 use relative_path::{RelativePath, RelativePathBuf};
 
-use crate::{AsyncFileInit, AsyncFileStore, Metadata};
+use crate::{
+    AsyncFile, AsyncFileInit, AsyncFileStore, AsyncFileStoreExt, FileInit, FileStore, FileStoreExt,
+    Metadata,
+};
 
+/// A path bound to a sync store.
 #[derive(Debug, Clone)]
 pub struct Path<T> {
     store: T,
@@ -17,14 +22,85 @@ impl<T> core::ops::Deref for Path<T> {
     }
 }
 
+impl<T> Path<T> {
+    pub fn new(store: T, path: impl Into<RelativePathBuf>) -> Path<T> {
+        Path {
+            store,
+            path: path.into(),
+        }
+    }
+
+    pub fn store(&self) -> &T {
+        &self.store
+    }
+}
+
 impl<T> Path<T>
 where
-    T: AsyncFileStore,
+    T: FileStore,
 {
-    pub fn new(store: T, path: RelativePathBuf) -> Path<T> {
-        Path { store, path }
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        self.store.metadata(&self.path)
+    }
+
+    pub fn open(&self) -> io::Result<T::File> {
+        self.store.open_file(&self.path)
+    }
+
+    /// Read the whole file into memory.
+    pub fn read(&self) -> io::Result<Bytes> {
+        FileStoreExt::read(&self.store, &self.path)
+    }
+
+    /// Read the whole file into memory as utf8.
+    pub fn read_to_string(&self) -> io::Result<String> {
+        FileStoreExt::read_to_string(&self.store, &self.path)
+    }
+
+    pub fn write(&self, body: impl Into<FileInit>) -> io::Result<()> {
+        self.store.write_file(&self.path, body.into())
+    }
+
+    pub fn delete(&self) -> io::Result<()> {
+        self.store.rm_file(&self.path)
+    }
+
+    pub fn exists(&self) -> bool {
+        self.store.exists(&self.path)
+    }
+}
+
+/// A path bound to an async store.
+#[derive(Debug, Clone)]
+pub struct AsyncPath<T> {
+    store: T,
+    path: RelativePathBuf,
+}
+
+impl<T> core::ops::Deref for AsyncPath<T> {
+    type Target = RelativePath;
+    fn deref(&self) -> &Self::Target {
+        &self.path
     }
+}
+
+impl<T> AsyncPath<T> {
+    pub fn new(store: T, path: impl Into<RelativePathBuf>) -> AsyncPath<T> {
+        AsyncPath {
+            store,
+            path: path.into(),
+        }
+    }
+
+    pub fn store(&self) -> &T {
+        &self.store
+    }
+}
 
+impl<T> AsyncPath<T>
+where
+    T: AsyncFileStore,
+{
     pub async fn metadata(&self) -> io::Result<Metadata> {
         self.store.metadata(&self.path).await
     }
@@ -33,7 +109,38 @@ where
         self.store.open_file(&self.path).await
     }
 
+    /// Read the whole file into memory.
+    pub async fn read(&self) -> io::Result<Bytes>
+    where
+        T: Sync,
+        T::File: Send,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        AsyncFileStoreExt::read(&self.store, &self.path).await
+    }
+
+    /// Read the whole file into memory as utf8.
+    pub async fn read_to_string(&self) -> io::Result<String>
+    where
+        T: Sync,
+        T::File: Send,
+        <T::File as AsyncFile>::Body: Send,
+    {
+        AsyncFileStoreExt::read_to_string(&self.store, &self.path).await
+    }
+
     pub async fn write(&self, body: impl Into<AsyncFileInit>) -> io::Result<()> {
         self.store.write_file(&self.path, body.into()).await
     }
+
+    pub async fn delete(&self) -> io::Result<()> {
+        self.store.rm_file(&self.path).await
+    }
+
+    pub async fn exists(&self) -> bool
+    where
+        T: Sync,
+    {
+        self.store.exists(&self.path).await
+    }
 }
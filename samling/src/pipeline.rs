@@ -0,0 +1,369 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::UNIX_EPOCH,
+};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream::BoxStream, Future};
+use globset::{Glob, GlobMatcher};
+use mime::Mime;
+use relative_path::{RelativePath, RelativePathBuf};
+use url::Url;
+
+use crate::{either::Either, util::lru::Lru, AsyncFile, AsyncFileInit, AsyncFileStore, Metadata};
+
+const CACHE_CAPACITY: usize = 256;
+
+/// Transforms the body of a file, e.g. compiling scss or rendering markdown.
+pub trait Processor: Send + Sync {
+    fn process<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        input: Bytes,
+    ) -> BoxFuture<'a, io::Result<Bytes>>;
+}
+
+impl<F> Processor for F
+where
+    F: Fn(&RelativePath, Bytes) -> io::Result<Bytes> + Send + Sync,
+{
+    fn process<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        input: Bytes,
+    ) -> BoxFuture<'a, io::Result<Bytes>> {
+        Box::pin(futures::future::ready(self(path, input)))
+    }
+}
+
+enum Rule {
+    /// Serve `name.<to>` by processing `name.<from>`.
+    Extension {
+        from: String,
+        to: String,
+        mime: Mime,
+        processor: Arc<dyn Processor>,
+    },
+    /// Process the matching files in place.
+    Glob {
+        matcher: GlobMatcher,
+        processor: Arc<dyn Processor>,
+    },
+}
+
+/// Turns a store into an on-demand asset pipeline by transforming file bodies on read.
+///
+/// Extension rules serve a derived file from its source, e.g. `style.css` compiled from
+/// `style.scss`; glob rules transform matching files in place. The first matching rule wins,
+/// and paths without a matching rule or source are passed through. Outputs are cached until
+/// the size or modification time of the source changes, keeping the most recently used 256
+/// unless set with [`Pipeline::cache_capacity`].
+///
+/// ```ignore
+/// let store = Pipeline::new(store)
+///     .extension("scss", "css", |_: &RelativePath, input: Bytes| compile_scss(input))
+///     .extension("md", "html", markdown)
+///     .glob("templates/**/*.html", render)?;
+/// ```
+pub struct Pipeline<T> {
+    inner: T,
+    rules: Vec<Rule>,
+    /// Outputs by the index of the rule producing them and their path.
+    cache: Mutex<Lru<(usize, RelativePathBuf), (String, Bytes)>>,
+}
+
+impl<T> Pipeline<T> {
+    pub fn new(inner: T) -> Pipeline<T> {
+        Pipeline {
+            inner,
+            rules: Vec::new(),
+            cache: Mutex::new(Lru::new(CACHE_CAPACITY)),
+        }
+    }
+
+    /// Keep at most `capacity` outputs cached.
+    pub fn cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache = Mutex::new(Lru::new(capacity));
+        self
+    }
+
+    /// Serve files with the extension `to` by processing the source with the extension `from`.
+    pub fn extension(mut self, from: &str, to: &str, processor: impl Processor + 'static) -> Self {
+        self.rules.push(Rule::Extension {
+            from: from.trim_start_matches('.').to_string(),
+            to: to.trim_start_matches('.').to_string(),
+            mime: mime_guess::from_ext(to.trim_start_matches('.')).first_or_octet_stream(),
+            processor: Arc::new(processor),
+        });
+        self
+    }
+
+    /// Process the files matching `glob` in place.
+    pub fn glob(mut self, glob: &str, processor: impl Processor + 'static) -> io::Result<Self> {
+        let matcher = Glob::new(glob)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+            .compile_matcher();
+        self.rules.push(Rule::Glob {
+            matcher,
+            processor: Arc::new(processor),
+        });
+        Ok(self)
+    }
+
+    /// Drop every cached output.
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> Pipeline<T>
+where
+    T: AsyncFileStore + Sync,
+    T::File: Send,
+    <T::File as AsyncFile>::Body: Send,
+{
+    /// The index of the rule, its processor and the metadata of the source for `path`, if a
+    /// rule applies.
+    async fn source(
+        &self,
+        path: &RelativePath,
+    ) -> io::Result<Option<(Metadata, Option<Mime>, Source<'_>)>> {
+        for (index, rule) in self.rules.iter().enumerate() {
+            match rule {
+                Rule::Extension {
+                    from,
+                    to,
+                    mime,
+                    processor,
+                } if path.extension() == Some(to.as_str()) => {
+                    match self.inner.metadata(&path.with_extension(from)).await {
+                        Ok(meta) => {
+                            return Ok(Some((meta, Some(mime.clone()), (index, processor))))
+                        }
+                        Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                        Err(err) => return Err(err),
+                    }
+                }
+                Rule::Glob { matcher, processor } if matcher.is_match(path.as_str()) => {
+                    let meta = self.inner.metadata(path).await?;
+                    return Ok(Some((meta, None, (index, processor))));
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    async fn output(
+        &self,
+        path: &RelativePath,
+        source: &Metadata,
+        (rule, processor): Source<'_>,
+    ) -> io::Result<Bytes> {
+        let key = (rule, path.normalize());
+        let tag = tag(source);
+        if let Some((cached, output)) = self.cache.lock().unwrap().get(&key) {
+            if *cached == tag {
+                return Ok(output.clone());
+            }
+        }
+
+        let file = self.inner.open_file(&source.path).await?;
+        let input = crate::util::collect(file.reader().await?, source.size).await?;
+        let output = processor.process(path, input).await?;
+
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(key, (tag, output.clone()));
+        Ok(output)
+    }
+}
+
+/// The index of a rule and its processor.
+type Source<'a> = (usize, &'a Arc<dyn Processor>);
+
+fn tag(meta: &Metadata) -> String {
+    let modified = meta
+        .modified
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|m| m.as_nanos())
+        .unwrap_or_default();
+    format!("{:x}-{:x}", meta.size, modified)
+}
+
+impl<T> AsyncFileStore for Pipeline<T>
+where
+    T: AsyncFileStore + Send + Sync,
+    T::File: Send + Sync,
+    <T::File as AsyncFile>::Body: Send,
+{
+    type File = PipelineFile<T::File>;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            let Some((source, mime, processor)) = self.source(path).await? else {
+                return self.inner.metadata(path).await;
+            };
+
+            let output = self.output(path, &source, processor).await?;
+            Ok(Metadata {
+                path: path.normalize(),
+                size: output.len() as u64,
+                mime: mime.unwrap_or(source.mime),
+                modified: source.modified,
+                permissions: source.permissions,
+                attributes: source.attributes,
+            })
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            match self.source(path).await? {
+                Some((source, _, processor)) => Ok(PipelineFile::Processed(
+                    self.output(path, &source, processor).await?,
+                )),
+                None => Ok(PipelineFile::Source(self.inner.open_file(path).await?)),
+            }
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        self.inner.rm_file(path)
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        self.inner.write_file(path, init)
+    }
+
+    /// Lists the files of the inner store; derived files are not listed.
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.inner.list()
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        self.inner.list_ref()
+    }
+
+    /// Processes the paths with a matching rule so their outputs are cached, and passes the
+    /// hint on for the rest.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            crate::prefetch::each(paths, |path| async move {
+                if let Some((source, _, processor)) = self.source(&path).await? {
+                    self.output(&path, &source, processor).await?;
+                }
+                Ok(())
+            })
+            .await?;
+            self.inner.prefetch(paths).await
+        }
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let paths = crate::prefetch::below(&self.inner, prefix).await?;
+            self.prefetch(&paths).await
+        }
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.close()
+    }
+}
+
+pub enum PipelineFile<F> {
+    Source(F),
+    Processed(Bytes),
+}
+
+impl<F> AsyncFile for PipelineFile<F>
+where
+    F: AsyncFile + Sync,
+{
+    type Body = Either<F::Body, futures::stream::Once<futures::future::Ready<io::Result<Bytes>>>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        async move {
+            match self {
+                PipelineFile::Source(file) => file.read_range(range).await,
+                PipelineFile::Processed(data) => crate::util::slice(data, range),
+            }
+        }
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        async move {
+            match self {
+                PipelineFile::Source(file) => Ok(Either::Left(file.reader().await?)),
+                PipelineFile::Processed(data) => Ok(Either::Right(futures::stream::once(
+                    futures::future::ready(Ok(data.clone())),
+                ))),
+            }
+        }
+    }
+
+    fn url(&self) -> Option<Url> {
+        match self {
+            PipelineFile::Source(file) => file.url(),
+            PipelineFile::Processed(_) => None,
+        }
+    }
+
+    fn native_path(&self) -> Option<&std::path::Path> {
+        match self {
+            PipelineFile::Source(file) => file.native_path(),
+            PipelineFile::Processed(_) => None,
+        }
+    }
+}
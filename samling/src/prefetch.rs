@@ -0,0 +1,40 @@
+use std::io;
+
+use futures::{Future, StreamExt, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::AsyncFileStore;
+
+/// Number of paths warmed at once.
+const CONCURRENCY: usize = 16;
+
+/// Warm every path with `warm`, ignoring paths which do not exist.
+pub(crate) async fn each<F, U>(paths: &[RelativePathBuf], warm: F) -> io::Result<()>
+where
+    F: FnMut(RelativePathBuf) -> U,
+    U: Future<Output = io::Result<()>>,
+{
+    futures::stream::iter(paths.iter().cloned())
+        .map(warm)
+        .buffer_unordered(CONCURRENCY)
+        .map(|ret| match ret {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            other => other,
+        })
+        .try_collect()
+        .await
+}
+
+/// The files of `store` below `prefix`.
+pub(crate) async fn below<S>(store: &S, prefix: &RelativePath) -> io::Result<Vec<RelativePathBuf>>
+where
+    S: AsyncFileStore + ?Sized,
+{
+    let prefix = prefix.normalize();
+    store
+        .list()
+        .await?
+        .try_filter(|path| futures::future::ready(path.normalize().starts_with(&prefix)))
+        .try_collect()
+        .await
+}
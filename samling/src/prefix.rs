@@ -1,7 +1,13 @@
-use relative_path::RelativePathBuf;
+use bytes::Bytes;
+use futures::{stream::BoxStream, StreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
 
-use crate::{AsyncFileStore, FileStore};
+use crate::{file::link_target, AsyncFileStore, FileStore, Link, Permissions};
 
+/// Exposes the part of a store below `mount` as a store of its own.
+///
+/// Paths are joined onto the mount, and listings only contain the files below it, with the
+/// mount stripped.
 pub struct Prefixed<T> {
     inner: T,
     mount: RelativePathBuf,
@@ -14,6 +20,52 @@ impl<T> Prefixed<T> {
             mount: mount.into(),
         }
     }
+
+    pub fn mount(&self) -> &RelativePathBuf {
+        &self.mount
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// `path` relative to `mount`, if it is below it.
+fn unmount(mount: &RelativePath, path: &RelativePath) -> Option<RelativePathBuf> {
+    match path.strip_prefix(mount) {
+        Ok(path) if !path.as_str().is_empty() => Some(path.to_relative_path_buf()),
+        _ => None,
+    }
+}
+
+/// The listing of a [`Prefixed`] store.
+pub struct PrefixedList<I> {
+    inner: I,
+    mount: RelativePathBuf,
+}
+
+impl<I> Iterator for PrefixedList<I>
+where
+    I: Iterator<Item = Result<RelativePathBuf, std::io::Error>>,
+{
+    type Item = Result<RelativePathBuf, std::io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(path) => {
+                    if let Some(path) = unmount(&self.mount, &path) {
+                        return Some(Ok(path));
+                    }
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
 }
 
 impl<T> FileStore for Prefixed<T>
@@ -22,7 +74,9 @@ where
 {
     type File = T::File;
 
-    type List = T::List;
+    type List = PrefixedList<T::List>;
+
+    const SORTED: bool = T::SORTED;
 
     fn metadata(
         &self,
@@ -48,7 +102,66 @@ where
     }
 
     fn list(&self) -> Self::List {
-        self.inner.list()
+        PrefixedList {
+            inner: self.inner.list(),
+            mount: self.mount.clone(),
+        }
+    }
+
+    fn set_len(&self, path: &relative_path::RelativePath, len: u64) -> Result<(), std::io::Error> {
+        self.inner.set_len(&self.mount.join(path), len)
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> Result<(), std::io::Error> {
+        self.inner.write_range(&self.mount.join(path), offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> Result<(), std::io::Error> {
+        self.inner
+            .set_permissions(&self.mount.join(path), permissions)
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> Result<(), std::io::Error> {
+        link_target(link, target)?;
+        self.inner.symlink(target, &self.mount.join(link))
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> Result<(), std::io::Error> {
+        self.inner
+            .hard_link(&self.mount.join(src), &self.mount.join(dst))
+    }
+
+    fn read_link(&self, path: &RelativePath) -> Result<Option<Link>, std::io::Error> {
+        self.inner.read_link(&self.mount.join(path))
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> Result<(), std::io::Error> {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> Result<(), std::io::Error> {
+        self.inner.close()
     }
 }
 
@@ -58,6 +171,8 @@ where
 {
     type File = T::File;
 
+    const SORTED: bool = T::SORTED;
+
     fn metadata(
         &self,
         path: &relative_path::RelativePath,
@@ -96,6 +211,153 @@ where
             std::io::Error,
         >,
     > + Send {
-        async move { self.inner.list().await }
+        async move {
+            let mount = self.mount.clone();
+            let list = self.inner.list().await?;
+
+            Ok(list
+                .filter_map(move |ret| {
+                    let ret = match ret {
+                        Ok(path) => unmount(&mount, &path).map(Ok),
+                        Err(err) => Some(Err(err)),
+                    };
+                    async move { ret }
+                })
+                .boxed())
+        }
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, Result<RelativePathBuf, std::io::Error>>
+    where
+        Self: Sync,
+    {
+        self.inner
+            .list_ref()
+            .filter_map(|ret| {
+                let ret = match ret {
+                    Ok(path) => unmount(&self.mount, &path).map(Ok),
+                    Err(err) => Some(Err(err)),
+                };
+                async move { ret }
+            })
+            .boxed()
+    }
+
+    fn prefetch(
+        &self,
+        paths: &[RelativePathBuf],
+    ) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send
+    where
+        Self: Sync,
+    {
+        let paths = paths
+            .iter()
+            .map(|path| self.mount.join(path))
+            .collect::<Vec<_>>();
+        async move { self.inner.prefetch(&paths).await }
+    }
+
+    fn prefetch_prefix(
+        &self,
+        prefix: &RelativePath,
+    ) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send
+    where
+        Self: Sync,
+    {
+        async move { self.inner.prefetch_prefix(&self.mount.join(prefix)).await }
+    }
+
+    fn set_len(
+        &self,
+        path: &relative_path::RelativePath,
+        len: u64,
+    ) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        async move { self.inner.set_len(&self.mount.join(path), len).await }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send {
+        async move {
+            self.inner
+                .write_range(&self.mount.join(path), offset, data)
+                .await
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send {
+        async move {
+            self.inner
+                .set_permissions(&self.mount.join(path), permissions)
+                .await
+        }
+    }
+
+    /// Targets leading out of the mount are rejected with `PermissionDenied`.
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send {
+        async move {
+            link_target(link, target)?;
+            self.inner.symlink(target, &self.mount.join(link)).await
+        }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send {
+        async move {
+            self.inner
+                .hard_link(&self.mount.join(src), &self.mount.join(dst))
+                .await
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl futures::prelude::Future<Output = Result<Option<Link>, std::io::Error>> + Send {
+        async move { self.inner.read_link(&self.mount.join(path)).await }
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> impl futures::prelude::Future<Output = Result<(), std::io::Error>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.close()
     }
 }
@@ -0,0 +1,435 @@
+//! Any remote configured in rclone, through the remote control API of a local rclone daemon.
+//!
+//! The daemon is started with `rclone rcd --rc-serve`, so the contents of files are served
+//! next to the API. Only plain `http` is spoken, like [`RemoteClient`].
+use std::{
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future, StreamExt};
+use hyper::{body::Incoming, header, Method, Request, Response, StatusCode};
+use relative_path::{Component, RelativePath, RelativePathBuf};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::{
+    remote::{self, RemoteClient},
+    util::{base64, rfc3339},
+    AsyncFile, AsyncFileBody, AsyncFileInit, AsyncFileStore, Attributes, Metadata,
+};
+
+/// Separates the parts of an upload. Differs between uploads, so file contents can't end the
+/// part early.
+fn boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    format!("samling-{nanos:x}")
+}
+
+/// Files of an rclone remote, e.g. `gdrive:backups` or `s3:bucket/prefix`, through a local
+/// rclone daemon using the configuration of the user running it.
+///
+/// ```ignore
+/// // rclone rcd --rc-serve --rc-user admin --rc-pass secret
+/// let store = RcloneFileStore::new(&"http://127.0.0.1:5572".parse()?, "gdrive:backups")?
+///     .auth("admin", "secret");
+/// store.write_file("2024/db.sql.gz".as_ref(), init).await?;
+/// store.copy_file("2024/db.sql.gz".as_ref(), "latest/db.sql.gz".as_ref()).await?;
+/// ```
+#[derive(Clone)]
+pub struct RcloneFileStore {
+    client: RemoteClient,
+    endpoint: Url,
+    fs: String,
+    authorization: Option<String>,
+}
+
+impl RcloneFileStore {
+    /// The files of remote `fs`, through the daemon listening at `endpoint`.
+    pub fn new(endpoint: &Url, fs: impl Into<String>) -> io::Result<RcloneFileStore> {
+        if !matches!(endpoint.scheme(), "http" | "https") || endpoint.cannot_be_a_base() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "rclone endpoints must be http or https urls",
+            ));
+        }
+
+        Ok(RcloneFileStore {
+            client: RemoteClient::shared(),
+            endpoint: endpoint.clone(),
+            fs: fs.into(),
+            authorization: None,
+        })
+    }
+
+    /// Send requests through `client` instead of the shared one.
+    pub fn client(mut self, client: RemoteClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Authenticate as given to the daemon with `--rc-user` and `--rc-pass`.
+    pub fn auth(mut self, user: &str, password: &str) -> Self {
+        let credentials = base64::encode(format!("{user}:{password}").as_bytes());
+        self.authorization = Some(format!("Basic {credentials}"));
+        self
+    }
+
+    /// The remote, as given to [`new`](RcloneFileStore::new).
+    pub fn fs(&self) -> &str {
+        &self.fs
+    }
+
+    /// Copy the file at `from` to `to` within the remote, server side where the remote
+    /// supports it.
+    pub async fn copy_file(&self, from: &RelativePath, to: &RelativePath) -> io::Result<()> {
+        let params = json!({
+            "srcFs": self.fs,
+            "srcRemote": remote_path(from)?,
+            "dstFs": self.fs,
+            "dstRemote": remote_path(to)?,
+        });
+        self.call::<Value>("operations/copyfile", params)
+            .await
+            .map(drop)
+    }
+
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.endpoint.clone();
+        url.path_segments_mut()
+            .expect("http url")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn request(&self, method: Method, url: &Url) -> hyper::http::request::Builder {
+        let request = Request::builder().method(method).uri(url.as_str());
+        match &self.authorization {
+            Some(authorization) => request.header(header::AUTHORIZATION, authorization),
+            None => request,
+        }
+    }
+
+    /// Call the command `command` of the API, e.g. `operations/stat`.
+    async fn call<T>(&self, command: &str, params: Value) -> io::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let url = self.url(&command.split('/').collect::<Vec<_>>());
+        let request = self
+            .request(Method::POST, &url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(remote::full(params.to_string()))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        let response = check(self.client.request(request).await?).await?;
+        parse(&remote::collect(response).await?)
+    }
+
+    async fn stat(&self, path: &RelativePath) -> io::Result<Item> {
+        let stat: StatResponse = self
+            .call(
+                "operations/stat",
+                json!({ "fs": self.fs, "remote": remote_path(path)? }),
+            )
+            .await?;
+
+        match stat.item {
+            Some(item) if !item.is_dir => Ok(item),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "not a file")),
+        }
+    }
+}
+
+impl AsyncFileStore for RcloneFileStore {
+    type File = RcloneFile;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            let item = self.stat(path).await?;
+            let path = path.normalize();
+
+            let mime = match item.mime_type.as_deref().and_then(|mime| mime.parse().ok()) {
+                Some(mime) => mime,
+                None => match path.extension() {
+                    Some(ext) => mime_guess::from_ext(ext).first_or_octet_stream(),
+                    None => mime::APPLICATION_OCTET_STREAM,
+                },
+            };
+
+            Ok(Metadata {
+                path,
+                // Some remotes don't know the size, e.g. google docs
+                size: item.size.max(0) as u64,
+                mime,
+                modified: rfc3339::parse(&item.mod_time),
+                permissions: None,
+                attributes: Attributes::default(),
+            })
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            self.stat(path).await?;
+
+            let remote = remote_path(path)?;
+            let fs = format!("[{}]", self.fs);
+            let segments = std::iter::once(fs.as_str())
+                .chain(remote.split('/'))
+                .collect::<Vec<_>>();
+
+            Ok(RcloneFile {
+                url: self.url(&segments),
+                store: self.clone(),
+            })
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let params = json!({ "fs": self.fs, "remote": remote_path(path)? });
+            self.call::<Value>("operations/deletefile", params)
+                .await
+                .map(drop)
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let remote = remote_path(path)?;
+            let (dir, name) = remote.rsplit_once('/').unwrap_or(("", &remote));
+            if name.is_empty() || name.contains(['"', '\\', '\r', '\n']) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("file name can't be uploaded through rclone: {name}"),
+                ));
+            }
+
+            let mime = init.mime.clone().unwrap_or(mime::APPLICATION_OCTET_STREAM);
+            let boundary = boundary();
+            let head = Bytes::from(format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; \
+                 filename=\"{name}\"\r\nContent-Type: {mime}\r\n\r\n"
+            ));
+            let tail = Bytes::from(format!("\r\n--{boundary}--\r\n"));
+
+            let mut url = self.url(&["operations", "uploadfile"]);
+            url.query_pairs_mut()
+                .append_pair("fs", &self.fs)
+                .append_pair("remote", dir);
+
+            let mut request = self.request(Method::POST, &url).header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={boundary}"),
+            );
+            let body = match init.body {
+                AsyncFileBody::Bytes(bytes) => remote::full([head, bytes, tail].concat()),
+                body => {
+                    if let Some(len) = init.len {
+                        let len = head.len() as u64 + len + tail.len() as u64;
+                        request = request.header(header::CONTENT_LENGTH, len);
+                    }
+                    let body = body.into_stream().await?;
+                    remote::stream(
+                        futures::stream::once(futures::future::ok(head))
+                            .chain(body)
+                            .chain(futures::stream::once(futures::future::ok(tail))),
+                    )
+                }
+            };
+            let request = request
+                .body(body)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+            check(self.client.request(request).await?).await.map(drop)
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        async move {
+            let params = json!({
+                "fs": self.fs,
+                "remote": "",
+                "opt": {
+                    "recurse": true,
+                    "filesOnly": true,
+                    "noModTime": true,
+                    "noMimeType": true,
+                },
+            });
+            let list: ListResponse = match self.call("operations/list", params).await {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => ListResponse::default(),
+                ret => ret?,
+            };
+
+            let paths = list
+                .list
+                .into_iter()
+                .filter(|item| !item.is_dir)
+                .map(|item| Ok(RelativePathBuf::from(item.path)))
+                .collect::<Vec<_>>();
+            Ok(futures::stream::iter(paths).boxed())
+        }
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            self.call::<Value>("operations/fsinfo", json!({ "fs": self.fs }))
+                .await
+                .map(drop)
+        }
+    }
+}
+
+/// A file in a [`RcloneFileStore`], served by the daemon.
+pub struct RcloneFile {
+    store: RcloneFileStore,
+    url: Url,
+}
+
+impl RcloneFile {
+    async fn get(&self, range: Option<std::ops::Range<u64>>) -> io::Result<Response<Incoming>> {
+        let mut request = self.store.request(Method::GET, &self.url);
+        if let Some(range) = range {
+            request = request.header(
+                header::RANGE,
+                format!("bytes={}-{}", range.start, range.end - 1),
+            );
+        }
+        let request = request
+            .body(remote::empty())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        check(self.store.client.request(request).await?).await
+    }
+}
+
+impl AsyncFile for RcloneFile {
+    type Body = BoxStream<'static, io::Result<Bytes>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        async move {
+            if range.is_empty() {
+                return Ok(Bytes::new());
+            }
+
+            let response = self.get(Some(range.clone())).await?;
+            let partial = response.status() == StatusCode::PARTIAL_CONTENT;
+            let body = remote::collect(response).await?;
+            if partial {
+                Ok(body)
+            } else {
+                // The whole file, when the remote can't seek
+                crate::util::slice(&body, range)
+            }
+        }
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        async move {
+            let response = self.get(None).await?;
+            Ok(remote::body_stream(response).boxed())
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Item {
+    path: String,
+    #[serde(default)]
+    size: i64,
+    mime_type: Option<String>,
+    #[serde(default)]
+    mod_time: String,
+    #[serde(default)]
+    is_dir: bool,
+}
+
+#[derive(Deserialize)]
+struct StatResponse {
+    item: Option<Item>,
+}
+
+#[derive(Deserialize, Default)]
+struct ListResponse {
+    list: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// `path` as a path within the remote. Fails with `PermissionDenied` when it would lead out
+/// of it.
+fn remote_path(path: &RelativePath) -> io::Result<String> {
+    let normalized = path.normalize();
+    let mut remote = Vec::new();
+    for component in normalized.components() {
+        match component {
+            Component::Normal(name) => remote.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("path escapes the store: {path}"),
+                ))
+            }
+        }
+    }
+    Ok(remote.join("/"))
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> io::Result<T> {
+    serde_json::from_slice(body).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Turn error responses into errors. The daemon answers with the error as json.
+async fn check(response: Response<Incoming>) -> io::Result<Response<Incoming>> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = remote::collect(response).await.unwrap_or_default();
+    let message = match serde_json::from_slice::<ErrorResponse>(&body) {
+        Ok(error) => error.error,
+        Err(_) => String::from_utf8_lossy(&body).trim().to_string(),
+    };
+
+    let kind = match status {
+        StatusCode::NOT_FOUND => io::ErrorKind::NotFound,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => io::ErrorKind::PermissionDenied,
+        StatusCode::BAD_REQUEST => io::ErrorKind::InvalidInput,
+        _ => io::ErrorKind::Other,
+    };
+
+    Err(io::Error::new(kind, format!("rclone {status}: {message}")))
+}
@@ -0,0 +1,249 @@
+use std::io;
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{
+    AsyncFileInit, AsyncFileStore, FileInit, FileStore, Link, Metadata, Permissions, StoreStats,
+};
+
+/// Rejects writes and removals with `PermissionDenied`, passing reads through to the inner store.
+#[derive(Debug, Clone)]
+pub struct ReadOnly<T>(T);
+
+impl<T> ReadOnly<T> {
+    pub fn new(inner: T) -> ReadOnly<T> {
+        ReadOnly(inner)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> AsyncFileStore for ReadOnly<T>
+where
+    T: AsyncFileStore + Sync,
+{
+    type File = T::File;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        self.0.metadata(path)
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        self.0.open_file(path)
+    }
+
+    fn rm_file(&self, _path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move { Err(io::ErrorKind::PermissionDenied.into()) }
+    }
+
+    fn write_file(
+        &self,
+        _path: &RelativePath,
+        _init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move { Err(io::ErrorKind::PermissionDenied.into()) }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.0.list()
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        self.0.list_ref()
+    }
+
+    fn exists(&self, path: &RelativePath) -> impl Future<Output = bool> + Send
+    where
+        Self: Sync,
+    {
+        self.0.exists(path)
+    }
+
+    fn stats(&self) -> impl Future<Output = io::Result<StoreStats>> + Send
+    where
+        Self: Sync,
+    {
+        self.0.stats()
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.0.health()
+    }
+
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.0.prefetch(paths)
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.0.prefetch_prefix(prefix)
+    }
+
+    fn set_len(
+        &self,
+        _path: &RelativePath,
+        _len: u64,
+    ) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        async move { Err(io::ErrorKind::PermissionDenied.into()) }
+    }
+
+    fn write_range(
+        &self,
+        _path: &RelativePath,
+        _offset: u64,
+        _data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move { Err(io::ErrorKind::PermissionDenied.into()) }
+    }
+
+    fn set_permissions(
+        &self,
+        _path: &RelativePath,
+        _permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move { Err(io::ErrorKind::PermissionDenied.into()) }
+    }
+
+    fn symlink(
+        &self,
+        _target: &RelativePath,
+        _link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move { Err(io::ErrorKind::PermissionDenied.into()) }
+    }
+
+    fn hard_link(
+        &self,
+        _src: &RelativePath,
+        _dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move { Err(io::ErrorKind::PermissionDenied.into()) }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        self.0.read_link(path)
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.0.supports_attributes()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.0.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.0.close()
+    }
+}
+
+impl<T> FileStore for ReadOnly<T>
+where
+    T: FileStore,
+{
+    type File = T::File;
+
+    type List = T::List;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
+        self.0.metadata(path)
+    }
+
+    fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
+        self.0.open_file(path)
+    }
+
+    fn rm_file(&self, _path: &RelativePath) -> Result<(), io::Error> {
+        Err(io::ErrorKind::PermissionDenied.into())
+    }
+
+    fn write_file(&self, _path: &RelativePath, _init: FileInit) -> Result<(), io::Error> {
+        Err(io::ErrorKind::PermissionDenied.into())
+    }
+
+    fn list(&self) -> Self::List {
+        self.0.list()
+    }
+
+    fn exists(&self, path: &RelativePath) -> bool {
+        self.0.exists(path)
+    }
+
+    fn set_len(&self, _path: &RelativePath, _len: u64) -> io::Result<()> {
+        Err(io::ErrorKind::PermissionDenied.into())
+    }
+
+    fn write_range(&self, _path: &RelativePath, _offset: u64, _data: Bytes) -> io::Result<()> {
+        Err(io::ErrorKind::PermissionDenied.into())
+    }
+
+    fn set_permissions(&self, _path: &RelativePath, _permissions: Permissions) -> io::Result<()> {
+        Err(io::ErrorKind::PermissionDenied.into())
+    }
+
+    fn symlink(&self, _target: &RelativePath, _link: &RelativePath) -> io::Result<()> {
+        Err(io::ErrorKind::PermissionDenied.into())
+    }
+
+    fn hard_link(&self, _src: &RelativePath, _dst: &RelativePath) -> io::Result<()> {
+        Err(io::ErrorKind::PermissionDenied.into())
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        self.0.read_link(path)
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.0.supports_attributes()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.0.flush()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.0.close()
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use crate::{boxed::async_filestore_box, AsyncFile, AsyncFileStore, BoxAsyncFileStore};
+
+/// Told about stores coming and going in a [`StoreRegistry`], e.g. to log them or to warm
+/// caches.
+///
+/// Hooks are called after the registry was changed, outside of its lock, so they may use the
+/// registry themselves.
+pub trait RegistryHook: Send + Sync {
+    fn registered(&self, name: &str, store: &Arc<BoxAsyncFileStore>) {
+        let _ = (name, store);
+    }
+
+    /// Called with a store which was removed or replaced by another. Handles still held keep
+    /// working.
+    fn removed(&self, name: &str, store: &Arc<BoxAsyncFileStore>) {
+        let _ = (name, store);
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    stores: RwLock<BTreeMap<String, Arc<BoxAsyncFileStore>>>,
+    hooks: RwLock<Vec<Arc<dyn RegistryHook>>>,
+}
+
+/// Stores looked up by name, so components can ask for the store they need instead of having
+/// every handle passed down through their constructors.
+///
+/// Clones share the same stores. [`global`](StoreRegistry::global) is a registry for the whole
+/// process, for plugins which have no other way to reach the application.
+///
+/// ```ignore
+/// let registry = StoreRegistry::new().hook(LogHook);
+/// registry.register("uploads", Prefixed::new(s3, "uploads"));
+/// registry.register("assets", FsFileStore::new("./assets")?);
+///
+/// let uploads = registry.get("uploads")?;
+/// uploads.write_file("avatar.png".as_ref(), init).await?;
+///
+/// registry.close().await?;
+/// ```
+#[derive(Clone, Default)]
+pub struct StoreRegistry {
+    inner: Arc<Inner>,
+}
+
+impl StoreRegistry {
+    pub fn new() -> StoreRegistry {
+        StoreRegistry::default()
+    }
+
+    /// The registry of the process, empty until stores are registered in it.
+    pub fn global() -> &'static StoreRegistry {
+        static GLOBAL: OnceLock<StoreRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(StoreRegistry::new)
+    }
+
+    /// Call `hook` whenever a store is registered or removed, in every clone of the registry.
+    pub fn hook(self, hook: impl RegistryHook + 'static) -> Self {
+        self.inner.hooks.write().unwrap().push(Arc::new(hook));
+        self
+    }
+
+    /// Register `store` as `name`, returning the store it replaces, if any.
+    pub fn register<T>(&self, name: impl Into<String>, store: T) -> Option<Arc<BoxAsyncFileStore>>
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send + 'static,
+    {
+        let name = name.into();
+        let store = Arc::new(async_filestore_box(store));
+        let old = self
+            .inner
+            .stores
+            .write()
+            .unwrap()
+            .insert(name.clone(), store.clone());
+
+        for hook in self.hooks() {
+            if let Some(old) = &old {
+                hook.removed(&name, old);
+            }
+            hook.registered(&name, &store);
+        }
+
+        old
+    }
+
+    /// The store registered as `name`. Fails with `NotFound` if there is none.
+    pub fn get(&self, name: &str) -> io::Result<Arc<BoxAsyncFileStore>> {
+        self.inner
+            .stores
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no store registered as {name:?}"),
+                )
+            })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.inner.stores.read().unwrap().contains_key(name)
+    }
+
+    /// Remove the store registered as `name`, returning it if there was one.
+    pub fn remove(&self, name: &str) -> Option<Arc<BoxAsyncFileStore>> {
+        let old = self.inner.stores.write().unwrap().remove(name)?;
+        for hook in self.hooks() {
+            hook.removed(name, &old);
+        }
+        Some(old)
+    }
+
+    /// The names of the registered stores, in order.
+    pub fn names(&self) -> Vec<String> {
+        self.inner.stores.read().unwrap().keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.stores.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Flushes every store, even when one fails, and returns the first error.
+    pub async fn flush(&self) -> io::Result<()> {
+        let stores = self.inner.stores.read().unwrap().clone();
+        let results = futures::future::join_all(stores.values().map(|store| store.flush())).await;
+        results.into_iter().collect()
+    }
+
+    /// Remove every store and close it, even when one fails, and return the first error.
+    pub async fn close(&self) -> io::Result<()> {
+        let stores = std::mem::take(&mut *self.inner.stores.write().unwrap());
+        let hooks = self.hooks();
+        for (name, store) in &stores {
+            for hook in &hooks {
+                hook.removed(name, store);
+            }
+        }
+
+        let results = futures::future::join_all(stores.values().map(|store| store.close())).await;
+        results.into_iter().collect()
+    }
+
+    /// The hooks as they are now, so they can be called without holding the lock.
+    fn hooks(&self) -> Vec<Arc<dyn RegistryHook>> {
+        self.inner.hooks.read().unwrap().clone()
+    }
+}
+
+impl fmt::Debug for StoreRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoreRegistry")
+            .field("stores", &self.names())
+            .field("hooks", &self.hooks().len())
+            .finish()
+    }
+}
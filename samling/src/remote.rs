@@ -0,0 +1,209 @@
+//! A shared HTTP client for remote backends.
+//!
+//! Every HTTP-based backend takes a [`RemoteClient`], and uses [`RemoteClient::shared`] when not
+//! given one, so a composite of several remote mounts shares one connection pool and DNS cache
+//! instead of each keeping its own.
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex, OnceLock},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, Stream, TryStreamExt};
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, Empty, Full, StreamBody};
+use hyper::{
+    body::{Frame, Incoming},
+    Request, Response,
+};
+use hyper_util::{
+    client::legacy::{
+        connect::{
+            dns::{GaiResolver, Name},
+            HttpConnector,
+        },
+        Client,
+    },
+    rt::{TokioExecutor, TokioTimer},
+};
+use tower_service::Service;
+
+/// The body of a request sent through a [`RemoteClient`].
+pub type RemoteBody = UnsyncBoxBody<Bytes, io::Error>;
+
+/// Connection pool and resolver settings of a [`RemoteClient`].
+#[derive(Debug, Clone)]
+pub struct RemoteConfig {
+    /// Maximum number of idle connections kept per host.
+    pub max_idle_per_host: usize,
+    /// Close idle connections after this long. `None` keeps them until the server does.
+    pub idle_timeout: Option<Duration>,
+    /// Interval of TCP keep-alive probes. `None` disables them.
+    pub keep_alive: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    /// How long resolved addresses are reused. Zero disables the DNS cache.
+    pub dns_ttl: Duration,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        RemoteConfig {
+            max_idle_per_host: 32,
+            idle_timeout: Some(Duration::from_secs(90)),
+            keep_alive: Some(Duration::from_secs(60)),
+            connect_timeout: Some(Duration::from_secs(10)),
+            dns_ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A pooled HTTP/1 client. Cloning is cheap, and clones share their pool.
+///
+/// Only plain `http` is spoken; requests run on the current tokio runtime.
+///
+/// ```ignore
+/// let client = RemoteClient::new(RemoteConfig { max_idle_per_host: 8, ..Default::default() });
+/// let response = client.request(Request::get(url).body(remote::empty())?).await?;
+/// let data = remote::collect(response).await?;
+/// ```
+#[derive(Clone)]
+pub struct RemoteClient {
+    client: Client<HttpConnector<CachingResolver>, RemoteBody>,
+}
+
+impl RemoteClient {
+    pub fn new(config: RemoteConfig) -> RemoteClient {
+        let mut connector = HttpConnector::new_with_resolver(CachingResolver::new(config.dns_ttl));
+        connector.set_keepalive(config.keep_alive);
+        connector.set_connect_timeout(config.connect_timeout);
+        connector.set_nodelay(true);
+
+        let client = Client::builder(TokioExecutor::new())
+            .pool_timer(TokioTimer::new())
+            .pool_idle_timeout(config.idle_timeout)
+            .pool_max_idle_per_host(config.max_idle_per_host)
+            .build(connector);
+
+        RemoteClient { client }
+    }
+
+    /// The client shared by every backend which isn't given one, created with the default
+    /// configuration on first use.
+    pub fn shared() -> RemoteClient {
+        static SHARED: OnceLock<RemoteClient> = OnceLock::new();
+        SHARED
+            .get_or_init(|| RemoteClient::new(RemoteConfig::default()))
+            .clone()
+    }
+
+    pub async fn request(&self, request: Request<RemoteBody>) -> io::Result<Response<Incoming>> {
+        self.client.request(request).await.map_err(|err| {
+            let kind = if err.is_connect() {
+                io::ErrorKind::ConnectionRefused
+            } else {
+                io::ErrorKind::Other
+            };
+            io::Error::new(kind, err)
+        })
+    }
+}
+
+impl Default for RemoteClient {
+    fn default() -> Self {
+        RemoteClient::shared()
+    }
+}
+
+/// A request without a body.
+pub fn empty() -> RemoteBody {
+    Empty::new().map_err(|never| match never {}).boxed_unsync()
+}
+
+/// A request body already in memory.
+pub fn full(data: impl Into<Bytes>) -> RemoteBody {
+    Full::new(data.into())
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
+
+/// A request body streamed from `stream`.
+pub fn stream<S>(stream: S) -> RemoteBody
+where
+    S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+{
+    StreamBody::new(stream.map_ok(Frame::data)).boxed_unsync()
+}
+
+/// Read the body of a response into memory.
+pub async fn collect(response: Response<Incoming>) -> io::Result<Bytes> {
+    let body = response
+        .into_body()
+        .collect()
+        .await
+        .map_err(io::Error::other)?;
+    Ok(body.to_bytes())
+}
+
+/// The body of a response as a stream of chunks.
+pub fn body_stream(
+    response: Response<Incoming>,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+    http_body_util::BodyDataStream::new(response.into_body()).map_err(io::Error::other)
+}
+
+/// Resolved addresses by name, with the time they expire.
+type DnsCache = Mutex<HashMap<Name, (Instant, Vec<SocketAddr>)>>;
+
+/// Resolves with `getaddrinfo`, reusing answers for a while.
+#[derive(Clone)]
+struct CachingResolver {
+    ttl: Duration,
+    inner: GaiResolver,
+    cache: Arc<DnsCache>,
+}
+
+impl CachingResolver {
+    fn new(ttl: Duration) -> CachingResolver {
+        CachingResolver {
+            ttl,
+            inner: GaiResolver::new(),
+            cache: Arc::default(),
+        }
+    }
+}
+
+impl Service<Name> for CachingResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = BoxFuture<'static, io::Result<Self::Response>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        if let Some((expires, addrs)) = self.cache.lock().unwrap().get(&name) {
+            if *expires > Instant::now() {
+                let addrs = addrs.clone();
+                return Box::pin(async move { Ok(addrs.into_iter()) });
+            }
+        }
+
+        let mut inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let ttl = self.ttl;
+        Box::pin(async move {
+            let addrs = inner.call(name.clone()).await?.collect::<Vec<_>>();
+            if !ttl.is_zero() {
+                cache
+                    .lock()
+                    .unwrap()
+                    .insert(name, (Instant::now() + ttl, addrs.clone()));
+            }
+            Ok(addrs.into_iter())
+        })
+    }
+}
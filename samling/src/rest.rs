@@ -0,0 +1,748 @@
+//! A plain REST protocol for reaching a store over the network, with a [`RestServer`] exposing
+//! any store and a [`RestFileStore`] speaking to it.
+//!
+//! | Request | |
+//! | --- | --- |
+//! | `GET /files/<path>` | The contents of the file, honoring `Range` |
+//! | `HEAD /files/<path>` | The metadata of the file, as headers |
+//! | `PUT /files/<path>` | Write the body as the file |
+//! | `DELETE /files/<path>` | Remove the file |
+//! | `GET /list?after=<path>&limit=<n>&prefix=<path>` | A page of paths, as json |
+//! | `GET /health` | `204` when the store is healthy |
+//!
+//! Besides `Content-Type` and `Content-Length`, metadata is carried in `X-Samling-Modified`
+//! (milliseconds since the unix epoch), `X-Samling-Mode` (octal) and one
+//! `X-Samling-Attribute: <key>=<value>` per attribute, both percent encoded. Errors are
+//! answered with a status for the kind of the error and its message as text.
+use std::{
+    collections::BTreeSet,
+    convert::Infallible,
+    io,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use async_stream::try_stream;
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future, StreamExt, TryStreamExt};
+use http_body_util::{BodyExt, BodyStream, Full, StreamBody};
+use hyper::{
+    body::{Frame, Incoming},
+    header::{self, HeaderMap},
+    http::{request, response},
+    Method, Request, Response, StatusCode,
+};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use url::Url;
+
+use crate::{
+    remote::{self, RemoteClient},
+    serve::{self, range, Body},
+    AsyncFile, AsyncFileBody, AsyncFileInit, AsyncFileStore, AsyncFiles, Attributes, Metadata,
+    Permissions,
+};
+
+const MODIFIED: &str = "x-samling-modified";
+const MODE: &str = "x-samling-mode";
+const ATTRIBUTE: &str = "x-samling-attribute";
+
+/// Paths per page of a listing, unless the client asks for fewer.
+const PAGE_SIZE: usize = 1000;
+
+/// Serves a store over the REST protocol of this module, for [`RestFileStore`] or any other
+/// http client.
+///
+/// Unlike [`Server`](crate::serve::Server), which serves files to browsers, this exposes the
+/// whole store, writes included. Wrap the store in
+/// [`ReadOnly`](crate::ReadOnly) to only expose reads.
+///
+/// ```ignore
+/// RestServer::new(FsFileStore::new("./data")?)
+///     .token(std::env::var("STORE_TOKEN")?)
+///     .listen("0.0.0.0:8080")
+///     .await?;
+/// ```
+#[derive(Clone)]
+pub struct RestServer {
+    files: AsyncFiles,
+    token: Option<Arc<str>>,
+    page_size: usize,
+}
+
+impl RestServer {
+    pub fn new<T>(store: T) -> RestServer
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send + 'static,
+    {
+        RestServer::from_files(AsyncFiles::new(store))
+    }
+
+    pub fn from_files(files: AsyncFiles) -> RestServer {
+        RestServer {
+            files,
+            token: None,
+            page_size: PAGE_SIZE,
+        }
+    }
+
+    /// Only answer requests with `Authorization: Bearer <token>`.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into().into());
+        self
+    }
+
+    /// The most paths in a page of a listing. Defaults to 1000.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    pub async fn listen(self, addr: impl ToSocketAddrs) -> io::Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let io = hyper_util::rt::TokioIo::new(stream);
+            let this = self.clone();
+
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(move |req: Request<Incoming>| {
+                    let this = this.clone();
+                    async move { Ok::<_, Infallible>(this.handle(req).await) }
+                });
+
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(io, service)
+                    .await;
+            });
+        }
+    }
+
+    pub async fn handle<B>(&self, req: Request<B>) -> Response<Body>
+    where
+        B: hyper::body::Body<Data = Bytes> + Send + 'static,
+        B::Error: std::error::Error + Send + Sync + 'static,
+    {
+        if !self.authorized(req.headers()) {
+            return error(&io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "missing or invalid token",
+            ));
+        }
+
+        let path = req.uri().path().to_string();
+        let result = match (req.method().clone(), path.as_str()) {
+            (Method::GET, "/health") => self.files.health().await.map(|_| no_content()),
+            (Method::GET, "/list") => self.list(&req).await,
+            (method, path) => match path.strip_prefix("/files/") {
+                Some(path) => match serve::request_path(path) {
+                    Some(path) => self.file(method, &path, req).await,
+                    None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid path")),
+                },
+                None => Err(io::Error::new(io::ErrorKind::NotFound, "no such endpoint")),
+            },
+        };
+
+        result.unwrap_or_else(|err| error(&err))
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        let Some(given) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+
+        // Compare in constant time, so the token can't be guessed byte by byte
+        given.len() == token.len()
+            && given
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    async fn file<B>(
+        &self,
+        method: Method,
+        path: &RelativePath,
+        req: Request<B>,
+    ) -> io::Result<Response<Body>>
+    where
+        B: hyper::body::Body<Data = Bytes> + Send + 'static,
+        B::Error: std::error::Error + Send + Sync + 'static,
+    {
+        match method {
+            Method::GET | Method::HEAD => {
+                let meta = self.files.metadata(path).await?;
+                let builder = metadata_headers(Response::builder(), &meta);
+                if method == Method::HEAD {
+                    return Ok(builder
+                        .header(header::CONTENT_TYPE, meta.mime.as_ref())
+                        .header(header::CONTENT_LENGTH, meta.size)
+                        .body(serve::empty())
+                        .expect("response"));
+                }
+
+                let file = self.files.open_file(path).await?;
+                let range = req
+                    .headers()
+                    .get(header::RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| range::parse(value, meta.size));
+
+                let response = match range {
+                    Some(Ok(ranges)) => {
+                        let response = range::respond(file, ranges, meta.size, &meta.mime);
+                        let mut builder = builder
+                            .status(StatusCode::PARTIAL_CONTENT)
+                            .header(header::CONTENT_TYPE, response.content_type)
+                            .header(header::CONTENT_LENGTH, response.content_length);
+                        if let Some(content_range) = response.content_range {
+                            builder = builder.header(header::CONTENT_RANGE, content_range);
+                        }
+                        builder
+                            .body(StreamBody::new(response.body.map_ok(Frame::data)).boxed_unsync())
+                    }
+                    Some(Err(_)) => Response::builder()
+                        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                        .header(
+                            header::CONTENT_RANGE,
+                            range::unsatisfied_content_range(meta.size),
+                        )
+                        .body(serve::empty()),
+                    None => {
+                        let reader = file.reader().await?;
+                        builder
+                            .header(header::CONTENT_TYPE, meta.mime.as_ref())
+                            .header(header::CONTENT_LENGTH, meta.size)
+                            .body(StreamBody::new(reader.map_ok(Frame::data)).boxed_unsync())
+                    }
+                };
+                Ok(response.expect("response"))
+            }
+            Method::PUT => {
+                let (parts, body) = req.into_parts();
+                let body = TryStreamExt::map_err(BodyStream::new(body), io::Error::other)
+                    .try_filter_map(|frame| futures::future::ok(frame.into_data().ok()));
+
+                let mut init = AsyncFileInit::stream(body);
+                init.len = parts
+                    .headers
+                    .get(header::CONTENT_LENGTH)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok());
+                init.mime = parts
+                    .headers
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok());
+                init.attributes = attributes(&parts.headers);
+
+                self.files.write_file(path, init).await?;
+                Ok(no_content())
+            }
+            Method::DELETE => {
+                self.files.rm_file(path).await?;
+                Ok(no_content())
+            }
+            _ => Ok(Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .header(header::ALLOW, "GET, HEAD, PUT, DELETE")
+                .body(serve::empty())
+                .expect("response")),
+        }
+    }
+
+    /// The first paths in order after `after`. Every page goes through the whole listing of
+    /// the store, as stores don't list in any particular order.
+    async fn list<B>(&self, req: &Request<B>) -> io::Result<Response<Body>> {
+        let mut after = None;
+        let mut prefix = None;
+        let mut limit = self.page_size;
+        for (key, value) in url::form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes())
+        {
+            match &*key {
+                "after" => after = Some(value.into_owned()),
+                "prefix" => prefix = Some(RelativePathBuf::from(value.into_owned())),
+                "limit" => {
+                    limit = value
+                        .parse::<usize>()
+                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+                        .clamp(1, self.page_size)
+                }
+                _ => {}
+            }
+        }
+
+        let mut page = BTreeSet::new();
+        let mut more = false;
+        let mut paths = self.files.list().await?;
+        while let Some(path) = paths.try_next().await? {
+            // Compared by component, the order of the page
+            if after
+                .as_deref()
+                .is_some_and(|after| path.as_relative_path() <= RelativePath::new(after))
+            {
+                continue;
+            }
+            if prefix
+                .as_ref()
+                .is_some_and(|prefix| !path.starts_with(prefix))
+            {
+                continue;
+            }
+
+            page.insert(path);
+            if page.len() > limit {
+                page.pop_last();
+                more = true;
+            }
+        }
+
+        let next = more.then(|| page.last().cloned()).flatten();
+        let body = serde_json::to_vec(&Page {
+            paths: page.into_iter().collect(),
+            next,
+        })
+        .map_err(io::Error::other)?;
+
+        Ok(Response::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_LENGTH, body.len())
+            .body(full(body))
+            .expect("response"))
+    }
+}
+
+/// A store on the other end of a [`RestServer`].
+///
+/// ```ignore
+/// let store = RestFileStore::new(&"http://storage.internal:8080".parse()?)?.token(token);
+/// store.write_file("reports/today.csv".as_ref(), init).await?;
+/// ```
+#[derive(Clone)]
+pub struct RestFileStore {
+    client: RemoteClient,
+    endpoint: Url,
+    token: Option<String>,
+}
+
+impl RestFileStore {
+    /// The store served at `endpoint`, which may have a path when the server is mounted below
+    /// one.
+    pub fn new(endpoint: &Url) -> io::Result<RestFileStore> {
+        if !matches!(endpoint.scheme(), "http" | "https") || endpoint.cannot_be_a_base() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "rest endpoints must be http or https urls",
+            ));
+        }
+
+        Ok(RestFileStore {
+            client: RemoteClient::shared(),
+            endpoint: endpoint.clone(),
+            token: None,
+        })
+    }
+
+    /// Send requests through `client` instead of the shared one.
+    pub fn client(mut self, client: RemoteClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Authenticate with the token given to [`RestServer::token`].
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.endpoint.clone();
+        url.path_segments_mut()
+            .expect("http url")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    /// The url of the file at `path`. Fails with `PermissionDenied` when the path would lead
+    /// out of the store.
+    fn file_url(&self, path: &RelativePath) -> io::Result<Url> {
+        let path = path.normalize();
+        if path.as_str().starts_with("..") {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("path escapes the store: {path}"),
+            ));
+        }
+
+        let segments = std::iter::once("files")
+            .chain(
+                path.as_str()
+                    .split('/')
+                    .filter(|segment| !segment.is_empty()),
+            )
+            .collect::<Vec<_>>();
+        Ok(self.url(&segments))
+    }
+
+    fn request(&self, method: Method, url: &Url) -> request::Builder {
+        let request = Request::builder().method(method).uri(url.as_str());
+        match &self.token {
+            Some(token) => request.header(header::AUTHORIZATION, format!("Bearer {token}")),
+            None => request,
+        }
+    }
+
+    async fn send(
+        &self,
+        request: request::Builder,
+        body: remote::RemoteBody,
+    ) -> io::Result<Response<Incoming>> {
+        let request = request
+            .body(body)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        check(self.client.request(request).await?).await
+    }
+
+    async fn page(&self, after: Option<&str>) -> io::Result<Page> {
+        let mut url = self.url(&["list"]);
+        if let Some(after) = after {
+            url.query_pairs_mut().append_pair("after", after);
+        }
+
+        let response = self
+            .send(self.request(Method::GET, &url), remote::empty())
+            .await?;
+        serde_json::from_slice(&remote::collect(response).await?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+impl AsyncFileStore for RestFileStore {
+    type File = RestFile;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            let url = self.file_url(path)?;
+            let response = self
+                .send(self.request(Method::HEAD, &url), remote::empty())
+                .await?;
+            let headers = response.headers();
+
+            let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+            Ok(Metadata {
+                path: path.normalize(),
+                size: header(header::CONTENT_LENGTH.as_str())
+                    .and_then(|len| len.parse().ok())
+                    .unwrap_or(0),
+                mime: header(header::CONTENT_TYPE.as_str())
+                    .and_then(|mime| mime.parse().ok())
+                    .unwrap_or(mime::APPLICATION_OCTET_STREAM),
+                modified: header(MODIFIED)
+                    .and_then(|modified| modified.parse().ok())
+                    .map(|modified| UNIX_EPOCH + Duration::from_millis(modified)),
+                permissions: header(MODE)
+                    .and_then(|mode| u32::from_str_radix(mode, 8).ok())
+                    .map(Permissions::from_mode),
+                attributes: attributes(headers),
+            })
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            self.metadata(path).await?;
+            Ok(RestFile {
+                url: self.file_url(path)?,
+                store: self.clone(),
+            })
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let url = self.file_url(path)?;
+            self.send(self.request(Method::DELETE, &url), remote::empty())
+                .await
+                .map(drop)
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let url = self.file_url(path)?;
+            let mut request = self.request(Method::PUT, &url);
+            if let Some(mime) = &init.mime {
+                request = request.header(header::CONTENT_TYPE, mime.as_ref());
+            }
+            for (key, value) in &init.attributes {
+                request = request.header(ATTRIBUTE, encode_attribute(key, value));
+            }
+
+            let body = match init.body {
+                AsyncFileBody::Bytes(bytes) => remote::full(bytes),
+                body => {
+                    if let Some(len) = init.len {
+                        request = request.header(header::CONTENT_LENGTH, len);
+                    }
+                    remote::stream(body.into_stream().await?)
+                }
+            };
+            self.send(request, body).await.map(drop)
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        let store = self.clone();
+        async move {
+            let first = store.page(None).await?;
+            let stream = try_stream! {
+                let mut page = first;
+                loop {
+                    for path in page.paths {
+                        yield path;
+                    }
+                    let Some(next) = page.next else {
+                        break;
+                    };
+                    page = store.page(Some(next.as_str())).await?;
+                }
+            };
+
+            Ok(stream.boxed())
+        }
+    }
+
+    fn supports_attributes(&self) -> bool {
+        true
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let url = self.url(&["health"]);
+            self.send(self.request(Method::GET, &url), remote::empty())
+                .await
+                .map(drop)
+        }
+    }
+}
+
+/// A file in a [`RestFileStore`].
+pub struct RestFile {
+    store: RestFileStore,
+    url: Url,
+}
+
+impl AsyncFile for RestFile {
+    type Body = BoxStream<'static, io::Result<Bytes>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        async move {
+            if range.is_empty() {
+                return Ok(Bytes::new());
+            }
+
+            let request = self.store.request(Method::GET, &self.url).header(
+                header::RANGE,
+                format!("bytes={}-{}", range.start, range.end - 1),
+            );
+            let response = self.store.send(request, remote::empty()).await?;
+            remote::collect(response).await
+        }
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        async move {
+            let request = self.store.request(Method::GET, &self.url);
+            let response = self.store.send(request, remote::empty()).await?;
+            Ok(remote::body_stream(response).boxed())
+        }
+    }
+}
+
+/// A page of a listing.
+#[derive(Serialize, Deserialize)]
+struct Page {
+    paths: Vec<RelativePathBuf>,
+    /// Passed as `after` for the next page, if there is one.
+    next: Option<RelativePathBuf>,
+}
+
+/// The headers for `meta`, except for its type and size which depend on the range served.
+fn metadata_headers(mut builder: response::Builder, meta: &Metadata) -> response::Builder {
+    builder = builder.header(header::ACCEPT_RANGES, "bytes");
+
+    if let Some(modified) = meta.modified {
+        builder = builder.header(header::LAST_MODIFIED, httpdate::fmt_http_date(modified));
+        if let Ok(since) = modified.duration_since(UNIX_EPOCH) {
+            builder = builder.header(MODIFIED, since.as_millis().to_string());
+        }
+    }
+    if let Some(permissions) = meta.permissions {
+        builder = builder.header(MODE, format!("{:o}", permissions.mode()));
+    }
+    for (key, value) in &meta.attributes {
+        builder = builder.header(ATTRIBUTE, encode_attribute(key, value));
+    }
+
+    builder
+}
+
+fn encode_attribute(key: &str, value: &str) -> String {
+    format!(
+        "{}={}",
+        utf8_percent_encode(key, NON_ALPHANUMERIC),
+        utf8_percent_encode(value, NON_ALPHANUMERIC)
+    )
+}
+
+/// The attributes in the headers, skipping any which aren't well formed.
+fn attributes(headers: &HeaderMap) -> Attributes {
+    headers
+        .get_all(ATTRIBUTE)
+        .iter()
+        .filter_map(|value| {
+            let (key, value) = value.to_str().ok()?.split_once('=')?;
+            Some((
+                percent_decode_str(key).decode_utf8().ok()?.into_owned(),
+                percent_decode_str(value).decode_utf8().ok()?.into_owned(),
+            ))
+        })
+        .collect()
+}
+
+fn no_content() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(serve::empty())
+        .expect("response")
+}
+
+fn full(data: impl Into<Bytes>) -> Body {
+    Full::new(data.into())
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
+
+/// The response for `err`, with a status for its kind and its message as text.
+fn error(err: &io::Error) -> Response<Body> {
+    let status = match err.kind() {
+        io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        io::ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+        io::ErrorKind::AlreadyExists => StatusCode::CONFLICT,
+        io::ErrorKind::Unsupported => StatusCode::NOT_IMPLEMENTED,
+        io::ErrorKind::TimedOut => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(full(err.to_string()))
+        .expect("response")
+}
+
+/// Turn error responses into errors, with the kind [`error`] answered with.
+async fn check(response: Response<Incoming>) -> io::Result<Response<Incoming>> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let kind = match status {
+        StatusCode::NOT_FOUND => io::ErrorKind::NotFound,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => io::ErrorKind::PermissionDenied,
+        StatusCode::BAD_REQUEST | StatusCode::RANGE_NOT_SATISFIABLE => io::ErrorKind::InvalidInput,
+        StatusCode::CONFLICT => io::ErrorKind::AlreadyExists,
+        StatusCode::NOT_IMPLEMENTED => io::ErrorKind::Unsupported,
+        StatusCode::GATEWAY_TIMEOUT => io::ErrorKind::TimedOut,
+        _ => io::ErrorKind::Other,
+    };
+
+    // HEAD responses have no message
+    let body = remote::collect(response).await.unwrap_or_default();
+    let message = match String::from_utf8_lossy(&body).trim() {
+        "" => status.to_string(),
+        message => message.to_string(),
+    };
+    Err(io::Error::new(kind, message))
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::MockFileStore;
+
+    #[test]
+    fn list_pages_in_component_order() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let mock = MockFileStore::new();
+                // `a/b` sorts before `a-c` by component, but after it as a string
+                for path in ["a/b", "a-c", "x", "a/b/c", "a.d"] {
+                    mock.insert(path, "");
+                }
+
+                let addr = std::net::TcpListener::bind("127.0.0.1:0")
+                    .unwrap()
+                    .local_addr()
+                    .unwrap();
+                tokio::spawn(RestServer::new(mock).page_size(1).listen(addr));
+                tokio::task::yield_now().await;
+
+                let url = format!("http://{addr}").parse().unwrap();
+                let store = RestFileStore::new(&url).unwrap();
+                let mut listed = store
+                    .list()
+                    .await
+                    .unwrap()
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .unwrap();
+                listed.sort();
+
+                let mut expected = ["a/b", "a-c", "x", "a/b/c", "a.d"]
+                    .map(RelativePathBuf::from)
+                    .to_vec();
+                expected.sort();
+                assert_eq!(listed, expected);
+            });
+    }
+}
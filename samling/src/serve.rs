@@ -0,0 +1,475 @@
+pub mod cache;
+pub mod conditional;
+pub mod encoding;
+pub mod index;
+#[cfg(feature = "watch")]
+mod live_reload;
+pub mod range;
+pub mod spa;
+
+use std::{convert::Infallible, io, sync::Arc, time::SystemTime};
+
+use bytes::Bytes;
+use futures::TryStreamExt;
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, Empty, Full, StreamBody};
+use hyper::{
+    body::{Frame, Incoming},
+    header,
+    http::response,
+    Method, Request, Response, StatusCode,
+};
+use relative_path::{RelativePath, RelativePathBuf};
+use tokio::net::{TcpListener, ToSocketAddrs};
+
+use self::{
+    cache::{CachePolicy, CacheRule},
+    conditional::{Condition, Validators},
+    encoding::Encoding,
+    index::{HtmlIndex, IndexRenderer, JsonIndex},
+    spa::Spa,
+};
+use crate::{AsyncFile, AsyncFileStore, AsyncFiles, Metadata};
+
+pub type Body = UnsyncBoxBody<Bytes, io::Error>;
+
+/// Serves the files of a store over http.
+///
+/// Supports `HEAD`, byte ranges, etags and optionally pre-compressed variants and html or json
+/// listings for directories.
+#[derive(Clone)]
+pub struct Server {
+    files: AsyncFiles,
+    index_file: Option<Arc<str>>,
+    directory_index: bool,
+    index_renderer: Arc<dyn IndexRenderer>,
+    precompressed: bool,
+    spa: Option<Arc<Spa>>,
+    cache_policy: Option<Arc<CachePolicy>>,
+    #[cfg(feature = "watch")]
+    live_reload: Option<Arc<live_reload::LiveReload>>,
+}
+
+impl Server {
+    pub fn new<T>(store: T) -> Server
+    where
+        T: AsyncFileStore + Send + Sync + 'static,
+        T::File: Send + Sync + 'static,
+        <T::File as AsyncFile>::Body: Send + 'static,
+    {
+        Server::from_files(AsyncFiles::new(store))
+    }
+
+    pub fn from_files(files: AsyncFiles) -> Server {
+        Server {
+            files,
+            index_file: Some("index.html".into()),
+            directory_index: false,
+            index_renderer: Arc::new(HtmlIndex::new()),
+            precompressed: false,
+            spa: None,
+            cache_policy: None,
+            #[cfg(feature = "watch")]
+            live_reload: None,
+        }
+    }
+
+    /// The file served when a directory is requested. Defaults to `index.html`.
+    pub fn index_file(mut self, name: Option<&str>) -> Self {
+        self.index_file = name.map(Into::into);
+        self
+    }
+
+    /// Render a listing for directories without an index file. Requests accepting
+    /// `application/json` get a [`JsonIndex`], everything else the index renderer.
+    pub fn directory_index(mut self, enable: bool) -> Self {
+        self.directory_index = enable;
+        self
+    }
+
+    /// Customize the listing of directories. Defaults to a plain [`HtmlIndex`].
+    pub fn index_renderer(mut self, renderer: impl IndexRenderer + 'static) -> Self {
+        self.index_renderer = Arc::new(renderer);
+        self
+    }
+
+    /// Serve `foo.js.br` or `foo.js.gz` in place of `foo.js` when the client accepts the
+    /// encoding and the sibling exists, with the matching `Content-Encoding`.
+    pub fn precompressed(mut self, enable: bool) -> Self {
+        self.precompressed = enable;
+        self
+    }
+
+    /// Serve `index_path` instead of `404` for paths which don't look like assets, for single
+    /// page applications.
+    pub fn spa(self, index_path: impl Into<RelativePathBuf>) -> Self {
+        self.spa_options(Spa::new(index_path))
+    }
+
+    /// Like [`Server::spa`], configuring which paths are assets.
+    pub fn spa_options(mut self, spa: Spa) -> Self {
+        self.spa = Some(Arc::new(spa));
+        self
+    }
+
+    /// Attach `Cache-Control` and `Expires` headers to files, by the first rule matching the
+    /// path of the file in the store.
+    pub fn cache_policy(mut self, policy: CachePolicy) -> Self {
+        self.cache_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Poll the store every `interval` and stream changes as server-sent events from
+    /// `/__samling/live-reload`, for reloading frontends during development. Pages can include
+    /// `/__samling/live-reload.js` to reload on any change.
+    ///
+    /// The store is polled once for all connections, and only while one is open.
+    #[cfg(feature = "watch")]
+    pub fn live_reload(mut self, interval: std::time::Duration) -> Self {
+        self.live_reload = Some(Arc::new(live_reload::LiveReload::new(interval)));
+        self
+    }
+
+    pub async fn listen(self, addr: impl ToSocketAddrs) -> io::Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let io = hyper_util::rt::TokioIo::new(stream);
+            let this = self.clone();
+
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(move |req: Request<Incoming>| {
+                    let this = this.clone();
+                    let (parts, _) = req.into_parts();
+                    let req = Request::from_parts(parts, ());
+                    async move { Ok::<_, Infallible>(this.handle(&req).await) }
+                });
+
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(io, service)
+                    .await;
+            });
+        }
+    }
+
+    pub async fn handle<B>(&self, req: &Request<B>) -> Response<Body> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return status(StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        #[cfg(feature = "watch")]
+        if let Some(live_reload) = &self.live_reload {
+            match req.uri().path() {
+                LIVE_RELOAD_EVENTS => return self.live_reload_events(live_reload),
+                LIVE_RELOAD_SCRIPT => {
+                    return Response::builder()
+                        .header(header::CONTENT_TYPE, "text/javascript; charset=utf-8")
+                        .header(header::CACHE_CONTROL, "no-cache")
+                        .body(full(req, Bytes::from_static(LIVE_RELOAD_JS)))
+                        .expect("response")
+                }
+                _ => {}
+            }
+        }
+
+        let Some(path) = request_path(req.uri().path()) else {
+            return status(StatusCode::BAD_REQUEST);
+        };
+
+        match self.files.metadata(&path).await {
+            Ok(meta) => return self.serve_file(req, &path, meta).await,
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                return status(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            Err(_) => {}
+        }
+
+        if let Some(index_file) = &self.index_file {
+            let index = path.join(&**index_file);
+            if let Ok(meta) = self.files.metadata(&index).await {
+                return self.serve_file(req, &index, meta).await;
+            }
+        }
+
+        if self.directory_index {
+            if let Ok(Some(listing)) = index::read(&self.files, &path).await {
+                // Relative links in the listing only resolve correctly below a trailing slash
+                if !req.uri().path().ends_with('/') {
+                    return Response::builder()
+                        .status(StatusCode::MOVED_PERMANENTLY)
+                        .header(header::LOCATION, format!("{}/", req.uri().path()))
+                        .body(empty())
+                        .expect("response");
+                }
+
+                let wants_json = req
+                    .headers()
+                    .get(header::ACCEPT)
+                    .and_then(|m| m.to_str().ok())
+                    .is_some_and(|m| m.contains("application/json"));
+                let renderer: &dyn IndexRenderer = if wants_json {
+                    &JsonIndex
+                } else {
+                    &*self.index_renderer
+                };
+
+                let body = renderer.render(&listing);
+                return Response::builder()
+                    .header(header::CONTENT_TYPE, renderer.content_type())
+                    .header(header::CONTENT_LENGTH, body.len())
+                    .header(header::VARY, "accept")
+                    .body(full(req, body.into()))
+                    .expect("response");
+            }
+        }
+
+        if let Some(spa) = &self.spa {
+            if !spa.is_asset(&path) {
+                match self.files.metadata(spa.index()).await {
+                    Ok(meta) => return self.serve_file(req, spa.index(), meta).await,
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                        return status(StatusCode::INTERNAL_SERVER_ERROR)
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+
+        status(StatusCode::NOT_FOUND)
+    }
+
+    async fn serve_file<B>(
+        &self,
+        req: &Request<B>,
+        path: &RelativePath,
+        meta: Metadata,
+    ) -> Response<Body> {
+        let mime = meta.mime.clone();
+        let cache = self
+            .cache_policy
+            .as_ref()
+            .and_then(|policy| policy.resolve(&meta.path));
+        let (path, meta, encoding) = self.negotiate(req, path, meta).await;
+
+        let validators = Validators::from_metadata(&meta);
+        let etag = validators.etag();
+
+        if validators.evaluate(req.headers()) == Condition::NotModified {
+            let mut builder = Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .header(header::ETAG, etag);
+            if self.precompressed {
+                builder = builder.header(header::VARY, "accept-encoding");
+            }
+            if let Some(modified) = validators.last_modified_header() {
+                builder = builder.header(header::LAST_MODIFIED, modified);
+            }
+            if let Some(cache) = cache {
+                builder = cache_headers(builder, cache);
+            }
+            return builder.body(empty()).expect("response");
+        }
+
+        let file = match self.files.open_file(&path).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return status(StatusCode::NOT_FOUND)
+            }
+            Err(_) => return status(StatusCode::INTERNAL_SERVER_ERROR),
+        };
+
+        let mut builder = Response::builder()
+            .header(header::ACCEPT_RANGES, "bytes")
+            .header(header::ETAG, etag);
+
+        if let Some(modified) = validators.last_modified_header() {
+            builder = builder.header(header::LAST_MODIFIED, modified);
+        }
+
+        if self.precompressed {
+            builder = builder.header(header::VARY, "accept-encoding");
+        }
+
+        if let Some(cache) = cache {
+            builder = cache_headers(builder, cache);
+        }
+
+        if let Some(encoding) = encoding {
+            builder = builder.header(header::CONTENT_ENCODING, encoding.name());
+        }
+
+        let range = req
+            .headers()
+            .get(header::RANGE)
+            .and_then(|m| m.to_str().ok())
+            .and_then(|m| range::parse(m, meta.size));
+
+        match range {
+            Some(Ok(ranges)) => {
+                let response = range::respond(file, ranges, meta.size, &mime);
+                if let Some(content_range) = response.content_range {
+                    builder = builder.header(header::CONTENT_RANGE, content_range);
+                }
+
+                let body = if req.method() == Method::HEAD {
+                    empty()
+                } else {
+                    StreamBody::new(response.body.map_ok(Frame::data)).boxed_unsync()
+                };
+
+                builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(header::CONTENT_TYPE, response.content_type)
+                    .header(header::CONTENT_LENGTH, response.content_length)
+                    .body(body)
+                    .expect("response")
+            }
+            Some(Err(_)) => Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(
+                    header::CONTENT_RANGE,
+                    range::unsatisfied_content_range(meta.size),
+                )
+                .body(empty())
+                .expect("response"),
+            None => {
+                let body = if req.method() == Method::HEAD {
+                    empty()
+                } else {
+                    let Ok(reader) = file.reader().await else {
+                        return status(StatusCode::INTERNAL_SERVER_ERROR);
+                    };
+                    StreamBody::new(reader.map_ok(Frame::data)).boxed_unsync()
+                };
+
+                builder
+                    .header(header::CONTENT_TYPE, mime.as_ref())
+                    .header(header::CONTENT_LENGTH, meta.size)
+                    .body(body)
+                    .expect("response")
+            }
+        }
+    }
+
+    #[cfg(feature = "watch")]
+    fn live_reload_events(&self, live_reload: &live_reload::LiveReload) -> Response<Body> {
+        use futures::StreamExt;
+
+        let events = live_reload.subscribe(&self.files).map_ok(Frame::data);
+        let connected = futures::stream::once(async {
+            Ok::<_, io::Error>(Frame::data(Bytes::from_static(b": connected\n\n")))
+        });
+
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(StreamBody::new(connected.chain(events)).boxed_unsync())
+            .expect("response")
+    }
+
+    /// Pick the pre-compressed sibling of `path` to serve, if enabled and accepted.
+    async fn negotiate<B>(
+        &self,
+        req: &Request<B>,
+        path: &RelativePath,
+        meta: Metadata,
+    ) -> (RelativePathBuf, Metadata, Option<Encoding>) {
+        let accept = req
+            .headers()
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|m| m.to_str().ok());
+
+        if let (true, Some(accept)) = (self.precompressed, accept) {
+            for encoding in encoding::accepted(accept) {
+                let sibling = encoding.sibling(path);
+                if let Ok(meta) = self.files.metadata(&sibling).await {
+                    return (sibling, meta, Some(encoding));
+                }
+            }
+        }
+
+        (path.to_relative_path_buf(), meta, None)
+    }
+}
+
+#[cfg(feature = "watch")]
+const LIVE_RELOAD_EVENTS: &str = "/__samling/live-reload";
+#[cfg(feature = "watch")]
+const LIVE_RELOAD_SCRIPT: &str = "/__samling/live-reload.js";
+#[cfg(feature = "watch")]
+const LIVE_RELOAD_JS: &[u8] =
+    b"new EventSource(\"/__samling/live-reload\").onmessage = () => location.reload();\n";
+
+pub(crate) fn request_path(path: &str) -> Option<RelativePathBuf> {
+    let path = percent_encoding::percent_decode_str(path)
+        .decode_utf8()
+        .ok()?;
+    let path = RelativePath::new(path.trim_start_matches('/')).normalize();
+
+    if path.as_str().starts_with("..") {
+        return None;
+    }
+
+    Some(path)
+}
+
+fn cache_headers(mut builder: response::Builder, cache: &CacheRule) -> response::Builder {
+    builder = builder.header(header::CACHE_CONTROL, cache.cache_control());
+    if let Some(expires) = cache.expires_header(SystemTime::now()) {
+        builder = builder.header(header::EXPIRES, expires);
+    }
+    builder
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(empty())
+        .expect("response")
+}
+
+pub(crate) fn empty() -> Body {
+    Empty::<Bytes>::new()
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
+
+fn full<B>(req: &Request<B>, bytes: Bytes) -> Body {
+    if req.method() == Method::HEAD {
+        return empty();
+    }
+    Full::<Bytes>::new(bytes)
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{mock::MockFileStore, AsyncComposite};
+
+    #[test]
+    fn cache_policy_matches_the_path_through_a_mount() {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let assets = MockFileStore::new();
+                assets.insert("app.js", "console.log(1)");
+                let store = AsyncComposite::builder().mount("/assets", assets).build();
+
+                let policy = CachePolicy::new()
+                    .rule("assets/**", CacheRule::immutable())
+                    .unwrap();
+                let server = Server::new(store).cache_policy(policy);
+
+                let req = Request::get("/assets/app.js").body(()).unwrap();
+                let res = server.handle(&req).await;
+                assert_eq!(res.status(), StatusCode::OK);
+                assert_eq!(
+                    res.headers().get(header::CACHE_CONTROL).unwrap(),
+                    CacheRule::immutable().cache_control()
+                );
+            });
+    }
+}
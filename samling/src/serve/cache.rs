@@ -0,0 +1,128 @@
+//! `Cache-Control` and `Expires` headers chosen per file by glob rules.
+
+use std::{
+    io,
+    time::{Duration, SystemTime},
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use hyper::header::HeaderValue;
+use relative_path::RelativePath;
+
+/// The caching headers of the files matched by a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheRule {
+    cache_control: HeaderValue,
+    expires: Option<Duration>,
+}
+
+impl CacheRule {
+    /// A rule sending `cache_control` as is, failing if it isn't a valid header value.
+    pub fn new(cache_control: impl Into<String>) -> io::Result<CacheRule> {
+        let cache_control = HeaderValue::try_from(cache_control.into())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        Ok(CacheRule::from_value(cache_control))
+    }
+
+    fn from_value(cache_control: HeaderValue) -> CacheRule {
+        CacheRule {
+            cache_control,
+            expires: None,
+        }
+    }
+
+    /// Cache for a year without revalidating, for fingerprinted assets.
+    pub fn immutable() -> CacheRule {
+        CacheRule::from_value(HeaderValue::from_static(
+            "public, max-age=31536000, immutable",
+        ))
+        .expires(Duration::from_secs(31536000))
+    }
+
+    /// Always revalidate, e.g. for `index.html`.
+    pub fn no_cache() -> CacheRule {
+        CacheRule::from_value(HeaderValue::from_static("no-cache"))
+    }
+
+    pub fn max_age(max_age: Duration) -> CacheRule {
+        let cache_control = format!("public, max-age={}", max_age.as_secs());
+        CacheRule::from_value(HeaderValue::try_from(cache_control).expect("header value"))
+            .expires(max_age)
+    }
+
+    /// Also send an `Expires` header, `expires` from the time of the response.
+    pub fn expires(mut self, expires: Duration) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    /// The value of the `Cache-Control` header.
+    pub fn cache_control(&self) -> &HeaderValue {
+        &self.cache_control
+    }
+
+    /// The value of the `Expires` header for a response sent at `now`.
+    pub fn expires_header(&self, now: SystemTime) -> Option<String> {
+        self.expires
+            .map(|expires| httpdate::fmt_http_date(now + expires))
+    }
+}
+
+/// An ordered list of glob rules; the first rule matching the path of a file applies.
+///
+/// ```ignore
+/// let policy = CachePolicy::new()
+///     .rule("index.html", CacheRule::no_cache())?
+///     .rule("assets/**", CacheRule::immutable())?
+///     .rule("**", CacheRule::max_age(Duration::from_secs(300)))?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct CachePolicy {
+    globs: Vec<Glob>,
+    rules: Vec<CacheRule>,
+    set: GlobSet,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        CachePolicy::new()
+    }
+}
+
+impl CachePolicy {
+    pub fn new() -> CachePolicy {
+        CachePolicy {
+            globs: Vec::new(),
+            rules: Vec::new(),
+            set: GlobSet::empty(),
+        }
+    }
+
+    /// Append a rule for the paths matching `glob`, e.g. `**/*.css` or `index.html`.
+    pub fn rule(mut self, glob: &str, rule: CacheRule) -> io::Result<Self> {
+        let glob =
+            Glob::new(glob).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        self.globs.push(glob);
+        self.rules.push(rule);
+
+        let mut builder = GlobSetBuilder::new();
+        for glob in &self.globs {
+            builder.add(glob.clone());
+        }
+        self.set = builder
+            .build()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        Ok(self)
+    }
+
+    /// The rule applying to `path`, if any.
+    pub fn resolve(&self, path: &RelativePath) -> Option<&CacheRule> {
+        let path = path.normalize();
+        self.set
+            .matches(path.as_str())
+            .into_iter()
+            .min()
+            .map(|idx| &self.rules[idx])
+    }
+}
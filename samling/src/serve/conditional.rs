@@ -0,0 +1,139 @@
+//! Evaluating conditional requests against file metadata, independent of the HTTP server.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hyper::{header, HeaderMap, StatusCode};
+
+use crate::Metadata;
+
+/// The outcome of evaluating the preconditions of a `GET` or `HEAD` request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// Serve the file.
+    Modified,
+    /// The client's copy is current; respond with `304 Not Modified`.
+    NotModified,
+}
+
+impl Condition {
+    pub fn status(self) -> StatusCode {
+        match self {
+            Condition::Modified => StatusCode::OK,
+            Condition::NotModified => StatusCode::NOT_MODIFIED,
+        }
+    }
+}
+
+/// The validators of a file: its entity tag and modification time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    etag: String,
+    last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    /// Derive a strong ETag from the size and modification time of a file.
+    pub fn from_metadata(meta: &Metadata) -> Validators {
+        let modified = meta
+            .modified
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|m| m.as_secs())
+            .unwrap_or_default();
+
+        Validators {
+            etag: format!("\"{:x}-{:x}\"", meta.size, modified),
+            last_modified: meta.modified,
+        }
+    }
+
+    /// Derive the ETag from a digest of the contents, which stays the same when a file is
+    /// rewritten with identical contents or served from several machines.
+    #[cfg(feature = "hash")]
+    pub fn from_digest(digest: &crate::util::Digest, meta: &Metadata) -> Validators {
+        Validators {
+            etag: format!("\"{digest}\""),
+            last_modified: meta.modified,
+        }
+    }
+
+    /// Hash `file` and derive the ETag from the digest.
+    #[cfg(feature = "hash")]
+    pub async fn from_file<F>(
+        file: &F,
+        meta: &Metadata,
+        algorithm: crate::util::HashAlgorithm,
+    ) -> std::io::Result<Validators>
+    where
+        F: crate::AsyncFile,
+    {
+        let digest = crate::util::hash_contents(file, algorithm).await?;
+        Ok(Validators::from_digest(&digest, meta))
+    }
+
+    /// The quoted value of the `ETag` header.
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        self.last_modified
+    }
+
+    /// The value of the `Last-Modified` header, if the modification time is known.
+    pub fn last_modified_header(&self) -> Option<String> {
+        self.last_modified.map(httpdate::fmt_http_date)
+    }
+
+    /// Evaluate the `If-None-Match` and `If-Modified-Since` headers of a request.
+    pub fn evaluate(&self, headers: &HeaderMap) -> Condition {
+        let value = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        self.evaluate_values(
+            value(header::IF_NONE_MATCH),
+            value(header::IF_MODIFIED_SINCE),
+        )
+    }
+
+    /// Evaluate the raw values of the `If-None-Match` and `If-Modified-Since` headers.
+    ///
+    /// As required by the RFC, `If-Modified-Since` is ignored when `If-None-Match` is present.
+    pub fn evaluate_values(
+        &self,
+        if_none_match: Option<&str>,
+        if_modified_since: Option<&str>,
+    ) -> Condition {
+        if let Some(tags) = if_none_match {
+            return if self.matches(tags) {
+                Condition::NotModified
+            } else {
+                Condition::Modified
+            };
+        }
+
+        let since = if_modified_since.and_then(|value| httpdate::parse_http_date(value).ok());
+        match (self.last_modified, since) {
+            // http dates only have second precision
+            (Some(modified), Some(since)) if seconds(modified) <= seconds(since) => {
+                Condition::NotModified
+            }
+            _ => Condition::Modified,
+        }
+    }
+
+    /// Weak comparison against a list of entity tags, as used by `If-None-Match`.
+    fn matches(&self, tags: &str) -> bool {
+        let etag = opaque(&self.etag);
+        tags.split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || opaque(tag) == etag)
+    }
+}
+
+fn opaque(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,77 @@
+//! Negotiating pre-compressed variants of a file, independent of the HTTP server.
+
+use relative_path::{RelativePath, RelativePathBuf};
+
+/// A content coding for which compressed siblings can exist, e.g. `app.js.br` next to `app.js`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    /// In order of preference.
+    pub const ALL: [Encoding; 2] = [Encoding::Brotli, Encoding::Gzip];
+
+    /// The value of the `Content-Encoding` header.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// The extension of the compressed sibling.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gz",
+        }
+    }
+
+    /// The path of the compressed sibling of `path`.
+    pub fn sibling(self, path: &RelativePath) -> RelativePathBuf {
+        RelativePathBuf::from(format!("{}.{}", path.as_str(), self.extension()))
+    }
+}
+
+/// The encodings accepted by an `Accept-Encoding` header, in order of preference.
+///
+/// Brotli is preferred over gzip regardless of the quality values, as long as neither is
+/// refused with `q=0`.
+pub fn accepted(accept_encoding: &str) -> Vec<Encoding> {
+    let mut wildcard = false;
+    let mut listed = Vec::new();
+
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';').map(str::trim);
+        let name = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let refused = parts.any(|param| {
+            param
+                .strip_prefix("q=")
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .is_some_and(|q| q <= 0.)
+        });
+
+        let encoding = match name.as_str() {
+            "br" => Encoding::Brotli,
+            "gzip" | "x-gzip" => Encoding::Gzip,
+            "*" => {
+                wildcard = !refused;
+                continue;
+            }
+            _ => continue,
+        };
+        listed.push((encoding, refused));
+    }
+
+    Encoding::ALL
+        .into_iter()
+        .filter(
+            |encoding| match listed.iter().find(|(listed, _)| listed == encoding) {
+                Some((_, refused)) => !refused,
+                None => wildcard,
+            },
+        )
+        .collect()
+}
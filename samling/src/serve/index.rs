@@ -0,0 +1,256 @@
+//! Directory listings, rendered as html or json.
+
+use std::{collections::BTreeMap, fmt::Write, io, time::UNIX_EPOCH};
+
+use futures::{StreamExt, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{AsyncFiles, Metadata};
+
+/// The direct children of a directory.
+#[derive(Debug, Clone)]
+pub struct Listing {
+    pub path: RelativePathBuf,
+    /// Sorted by name, directories and files mixed.
+    pub entries: Vec<DirEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    /// The file name; directories end with a `/`.
+    pub name: String,
+    pub is_dir: bool,
+    /// `None` for directories.
+    pub meta: Option<Metadata>,
+}
+
+/// Collect the direct children of `dir`. Returns `None` if the directory has no entries.
+pub async fn read(files: &AsyncFiles, dir: &RelativePath) -> io::Result<Option<Listing>> {
+    let prefix = if dir.as_str().is_empty() {
+        String::new()
+    } else {
+        format!("{}/", dir.as_str())
+    };
+
+    // Name to the path of the file, `None` for directories
+    let mut names = BTreeMap::new();
+    let mut stream = files.list().await?;
+
+    while let Some(next) = stream.try_next().await? {
+        let Some(rest) = next.as_str().strip_prefix(prefix.as_str()) else {
+            continue;
+        };
+
+        match rest.split_once('/') {
+            Some((dir, _)) => names.insert(format!("{dir}/"), None),
+            None => names.insert(rest.to_string(), Some(next.clone())),
+        };
+    }
+
+    if names.is_empty() {
+        return Ok(None);
+    }
+
+    let entries = futures::stream::iter(names)
+        .map(|(name, path)| async move {
+            let meta = match &path {
+                Some(path) => Some(files.metadata(path).await?),
+                None => None,
+            };
+            io::Result::Ok(DirEntry {
+                name,
+                is_dir: path.is_none(),
+                meta,
+            })
+        })
+        .buffered(8)
+        .try_collect()
+        .await?;
+
+    Ok(Some(Listing {
+        path: dir.to_relative_path_buf(),
+        entries,
+    }))
+}
+
+/// Turns a [`Listing`] into a response body.
+///
+/// Implement this to style listings beyond what [`HtmlIndex`] allows.
+pub trait IndexRenderer: Send + Sync {
+    /// The value of the `Content-Type` header.
+    fn content_type(&self) -> &str;
+
+    fn render(&self, listing: &Listing) -> String;
+}
+
+/// The default html listing, with hooks for a stylesheet and custom header and footer markup.
+#[derive(Debug, Clone, Default)]
+pub struct HtmlIndex {
+    stylesheet: Option<String>,
+    header: Option<String>,
+    footer: Option<String>,
+}
+
+impl HtmlIndex {
+    pub fn new() -> HtmlIndex {
+        HtmlIndex::default()
+    }
+
+    /// Link a stylesheet from the head of the page.
+    pub fn stylesheet(mut self, href: impl Into<String>) -> Self {
+        self.stylesheet = Some(href.into());
+        self
+    }
+
+    /// Raw html inserted before the listing, in place of the default heading.
+    pub fn header(mut self, html: impl Into<String>) -> Self {
+        self.header = Some(html.into());
+        self
+    }
+
+    /// Raw html inserted after the listing.
+    pub fn footer(mut self, html: impl Into<String>) -> Self {
+        self.footer = Some(html.into());
+        self
+    }
+}
+
+impl IndexRenderer for HtmlIndex {
+    fn content_type(&self) -> &str {
+        "text/html; charset=utf-8"
+    }
+
+    fn render(&self, listing: &Listing) -> String {
+        let title = escape(&display_path(&listing.path));
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title>"
+        );
+        if let Some(href) = &self.stylesheet {
+            let _ = write!(html, "<link rel=\"stylesheet\" href=\"{}\">", escape(href));
+        }
+        html.push_str("</head><body>");
+
+        match &self.header {
+            Some(header) => html.push_str(header),
+            None => {
+                let _ = write!(html, "<h1>{title}</h1>");
+            }
+        }
+
+        html.push_str("<ul>");
+        if !listing.path.as_str().is_empty() {
+            html.push_str("<li class=\"dir\"><a href=\"../\">../</a></li>");
+        }
+
+        for entry in &listing.entries {
+            let name = escape(&entry.name);
+            let class = if entry.is_dir { "dir" } else { "file" };
+            let _ = write!(html, "<li class=\"{class}\"><a href=\"{name}\">{name}</a>");
+            if let Some(meta) = &entry.meta {
+                let _ = write!(html, " <span class=\"size\">{}</span>", meta.size);
+            }
+            html.push_str("</li>");
+        }
+        html.push_str("</ul>");
+
+        if let Some(footer) = &self.footer {
+            html.push_str(footer);
+        }
+        html.push_str("</body></html>");
+
+        html
+    }
+}
+
+/// A json listing: `{"path": "/dir/", "entries": [{"name", "dir", "size", "mime", "modified"}]}`,
+/// with `modified` in seconds since the unix epoch.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonIndex;
+
+impl IndexRenderer for JsonIndex {
+    fn content_type(&self) -> &str {
+        "application/json"
+    }
+
+    fn render(&self, listing: &Listing) -> String {
+        let mut json = String::new();
+        let _ = write!(
+            json,
+            "{{\"path\":\"{}\",\"entries\":[",
+            escape_json(&display_path(&listing.path))
+        );
+
+        for (idx, entry) in listing.entries.iter().enumerate() {
+            if idx > 0 {
+                json.push(',');
+            }
+            let _ = write!(
+                json,
+                "{{\"name\":\"{}\",\"dir\":{}",
+                escape_json(&entry.name),
+                entry.is_dir
+            );
+            if let Some(meta) = &entry.meta {
+                let _ = write!(
+                    json,
+                    ",\"size\":{},\"mime\":\"{}\"",
+                    meta.size,
+                    escape_json(meta.mime.as_ref())
+                );
+                if let Some(modified) = meta
+                    .modified
+                    .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                {
+                    let _ = write!(json, ",\"modified\":{}", modified.as_secs());
+                }
+            }
+            json.push('}');
+        }
+
+        json.push_str("]}");
+        json
+    }
+}
+
+fn display_path(path: &RelativePath) -> String {
+    if path.as_str().is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{}/", path.as_str())
+    }
+}
+
+fn escape(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+pub(super) fn escape_json(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(output, "\\u{:04x}", c as u32);
+            }
+            c => output.push(c),
+        }
+    }
+    output
+}
@@ -0,0 +1,118 @@
+//! Change events for live reloading, from a single watcher shared by every connection.
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use futures::{
+    future::{select, Either},
+    pin_mut, Stream, StreamExt,
+};
+use tokio::sync::broadcast;
+
+use super::index;
+use crate::{watch::Change, AsyncFiles};
+
+/// Events not yet sent to a slow connection before it starts skipping them.
+const BUFFER: usize = 64;
+
+/// Watches the store while at least one connection is listening, fanning the changes out
+/// to all of them.
+pub(crate) struct LiveReload {
+    interval: Duration,
+    sender: Arc<Mutex<Option<broadcast::Sender<Bytes>>>>,
+}
+
+impl LiveReload {
+    pub fn new(interval: Duration) -> LiveReload {
+        LiveReload {
+            interval,
+            sender: Arc::default(),
+        }
+    }
+
+    /// The server-sent events of the changes to `files`, starting the watcher unless it
+    /// runs already.
+    pub fn subscribe(&self, files: &AsyncFiles) -> impl Stream<Item = io::Result<Bytes>> {
+        let mut slot = self.sender.lock().unwrap();
+        let receiver = match &*slot {
+            Some(sender) => sender.subscribe(),
+            None => {
+                let (sender, receiver) = broadcast::channel(BUFFER);
+                tokio::spawn(run(
+                    files.clone(),
+                    self.interval,
+                    sender.clone(),
+                    self.sender.clone(),
+                ));
+                *slot = Some(sender);
+                receiver
+            }
+        };
+
+        futures::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((Ok(event), receiver)),
+                    // Any change reloads, so missed ones don't matter
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+/// Broadcast the changes of `files` until nobody listens or watching fails.
+async fn run(
+    files: AsyncFiles,
+    interval: Duration,
+    sender: broadcast::Sender<Bytes>,
+    slot: Arc<Mutex<Option<broadcast::Sender<Bytes>>>>,
+) {
+    let changes = crate::watch::watch(files, interval);
+    pin_mut!(changes);
+
+    loop {
+        // Wake up at least every interval to notice when the last connection went away
+        let next = changes.next();
+        let tick = futures_timer::Delay::new(interval);
+        pin_mut!(tick);
+
+        let change = match select(next, tick).await {
+            Either::Left((Some(Ok(change)), _)) => Some(change),
+            Either::Left(_) => None,
+            Either::Right(_) => {
+                let mut slot = slot.lock().unwrap();
+                if sender.receiver_count() == 0 {
+                    *slot = None;
+                    return;
+                }
+                continue;
+            }
+        };
+
+        let mut slot = slot.lock().unwrap();
+        match change {
+            Some(change) if sender.receiver_count() > 0 => {
+                let _ = sender.send(event(&change));
+            }
+            // Closing the channel ends the streams of all connections
+            _ => {
+                *slot = None;
+                return;
+            }
+        }
+    }
+}
+
+fn event(change: &Change) -> Bytes {
+    Bytes::from(format!(
+        "data: {{\"kind\":\"{}\",\"path\":\"{}\"}}\n\n",
+        change.kind.as_str(),
+        index::escape_json(change.path.as_str())
+    ))
+}
@@ -0,0 +1,168 @@
+//! Range requests (RFC 7233), independent of the HTTP server.
+
+use std::{
+    io,
+    ops::Range,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
+use mime::Mime;
+
+use crate::{util::range_chunks, AsyncFile};
+
+/// Requests asking for more ranges than this are served whole.
+const MAX_RANGES: usize = 16;
+
+/// None of the requested ranges overlap the file; respond with `416 Range Not Satisfiable`
+/// and [`unsatisfied_content_range`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unsatisfiable;
+
+/// Parse a `Range` header for a body of `size` bytes.
+///
+/// Returns `None` when the header should be ignored and the whole file served (unknown unit,
+/// malformed or too many ranges). Ranges which can't be satisfied are dropped; overlapping and
+/// adjacent ranges are merged.
+pub fn parse(header: &str, size: u64) -> Option<Result<Vec<Range<u64>>, Unsatisfiable>> {
+    let specs = header.trim().strip_prefix("bytes=")?;
+    if specs.split(',').count() > MAX_RANGES {
+        return None;
+    }
+
+    let mut ranges = Vec::new();
+    for spec in specs
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+    {
+        if let Some(range) = parse_spec(spec, size)? {
+            ranges.push(range);
+        }
+    }
+
+    if ranges.is_empty() {
+        return Some(Err(Unsatisfiable));
+    }
+
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    Some(Ok(merged))
+}
+
+/// Parse a single range. The outer `None` means the spec is malformed, the inner one that it
+/// can't be satisfied.
+fn parse_spec(spec: &str, size: u64) -> Option<Option<Range<u64>>> {
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        let suffix = end.parse::<u64>().ok()?;
+        size.saturating_sub(suffix)..size
+    } else {
+        let start = start.parse::<u64>().ok()?;
+        let end = if end.is_empty() {
+            size
+        } else {
+            let end = end.parse::<u64>().ok()?;
+            if end < start {
+                return None;
+            }
+            end.saturating_add(1).min(size)
+        };
+        start..end
+    };
+
+    Some((range.start < range.end).then_some(range))
+}
+
+/// The `Content-Range` value of a satisfied range.
+pub fn content_range(range: &Range<u64>, size: u64) -> String {
+    format!("bytes {}-{}/{}", range.start, range.end - 1, size)
+}
+
+/// The `Content-Range` value of a `416` response.
+pub fn unsatisfied_content_range(size: u64) -> String {
+    format!("bytes */{size}")
+}
+
+/// The headers and body of a `206 Partial Content` response.
+pub struct RangeResponse {
+    pub content_type: String,
+    /// Only set for a single range; multipart bodies carry one per part.
+    pub content_range: Option<String>,
+    pub content_length: u64,
+    pub body: BoxStream<'static, io::Result<Bytes>>,
+}
+
+/// Build the response for `ranges` of `file`, as returned by [`parse`].
+///
+/// A single range is streamed as is, several as a `multipart/byteranges` body.
+pub fn respond<F>(file: F, ranges: Vec<Range<u64>>, size: u64, mime: &Mime) -> RangeResponse
+where
+    F: AsyncFile + Send + Sync + 'static,
+{
+    if let [range] = ranges.as_slice() {
+        return RangeResponse {
+            content_type: mime.to_string(),
+            content_range: Some(content_range(range, size)),
+            content_length: range.end - range.start,
+            body: crate::util::read_range_stream(file, range.clone()).boxed(),
+        };
+    }
+
+    let boundary = boundary();
+    let parts = ranges
+        .into_iter()
+        .map(|range| {
+            let head = format!(
+                "--{boundary}\r\nContent-Type: {mime}\r\nContent-Range: {}\r\n\r\n",
+                content_range(&range, size)
+            );
+            (Bytes::from(head), range)
+        })
+        .collect::<Vec<_>>();
+    let tail = Bytes::from(format!("--{boundary}--\r\n"));
+
+    let content_length = parts
+        .iter()
+        .map(|(head, range)| head.len() as u64 + (range.end - range.start) + 2)
+        .sum::<u64>()
+        + tail.len() as u64;
+
+    let body = async_stream::try_stream! {
+        for (head, range) in parts {
+            yield head;
+            let chunks = range_chunks(&file, range);
+            futures::pin_mut!(chunks);
+            while let Some(chunk) = chunks.try_next().await? {
+                yield chunk;
+            }
+            yield Bytes::from_static(b"\r\n");
+        }
+        yield tail;
+    };
+
+    RangeResponse {
+        content_type: format!("multipart/byteranges; boundary={boundary}"),
+        content_range: None,
+        content_length,
+        body: body.boxed(),
+    }
+}
+
+fn boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    format!("samling-{nanos:x}")
+}
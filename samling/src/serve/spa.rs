@@ -0,0 +1,71 @@
+//! Falling back to the index of a single page application.
+
+use relative_path::{RelativePath, RelativePathBuf};
+
+/// Serve an index file with `200` for paths which don't exist and don't look like assets, so
+/// client side routes resolve.
+///
+/// By default any path with an extension is an asset and gets a regular `404`.
+#[derive(Debug, Clone)]
+pub struct Spa {
+    index: RelativePathBuf,
+    asset_prefixes: Vec<String>,
+    asset_extensions: Option<Vec<String>>,
+}
+
+impl Spa {
+    pub fn new(index: impl Into<RelativePathBuf>) -> Spa {
+        Spa {
+            index: index.into().normalize(),
+            asset_prefixes: Vec::new(),
+            asset_extensions: None,
+        }
+    }
+
+    /// Treat every path below `prefix`, e.g. `assets` or `api`, as an asset.
+    pub fn asset_prefix(mut self, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        self.asset_prefixes
+            .push(prefix.trim_matches('/').to_string());
+        self
+    }
+
+    /// Only treat paths with these extensions as assets, instead of any extension. Useful when
+    /// client side routes contain dots.
+    pub fn asset_extensions<I>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.asset_extensions = Some(
+            extensions
+                .into_iter()
+                .map(|ext| ext.into().trim_start_matches('.').to_ascii_lowercase())
+                .collect(),
+        );
+        self
+    }
+
+    pub fn index(&self) -> &RelativePath {
+        &self.index
+    }
+
+    pub fn is_asset(&self, path: &RelativePath) -> bool {
+        let below_prefix = self.asset_prefixes.iter().any(|prefix| {
+            path.as_str()
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        });
+        if below_prefix {
+            return true;
+        }
+
+        match (path.extension(), &self.asset_extensions) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(ext), Some(extensions)) => extensions
+                .iter()
+                .any(|candidate| candidate.eq_ignore_ascii_case(ext)),
+        }
+    }
+}
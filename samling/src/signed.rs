@@ -0,0 +1,329 @@
+//! Time-limited links to private files, verified with an HMAC.
+//!
+//! A [`UrlSigner`] signs a path and an expiry into a token. A [`Signed`] store only opens files
+//! when the token of the current [`scope`] is valid for the path, so private files can be
+//! shared on backends which can't presign urls themselves.
+use std::{
+    fmt, io,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future};
+use hmac::{Hmac, Mac};
+use relative_path::{RelativePath, RelativePathBuf};
+use sha2::Sha256;
+use url::Url;
+
+use crate::{AsyncFileInit, AsyncFileStore, Link, Metadata, Permissions, StoreStats};
+
+tokio::task_local! {
+    static TOKEN: Arc<str>;
+}
+
+/// Run `fut` with the token a request was made with, e.g. the `token` query parameter.
+pub async fn scope<F>(token: impl Into<Arc<str>>, fut: F) -> F::Output
+where
+    F: Future,
+{
+    TOKEN.scope(token.into(), fut).await
+}
+
+/// The token of the current scope, if any.
+pub fn token() -> Option<Arc<str>> {
+    TOKEN.try_with(|token| token.clone()).ok()
+}
+
+/// Signs and verifies tokens of the form `{expiry}.{signature}`, where the expiry is in
+/// seconds since the unix epoch and the signature is a hex HMAC-SHA256 of the path and expiry.
+#[derive(Clone)]
+pub struct UrlSigner {
+    key: Arc<[u8]>,
+}
+
+impl UrlSigner {
+    pub fn new(key: impl AsRef<[u8]>) -> UrlSigner {
+        UrlSigner {
+            key: key.as_ref().into(),
+        }
+    }
+
+    /// A token granting access to `path` until `expires`.
+    pub fn sign(&self, path: &RelativePath, expires: SystemTime) -> String {
+        let expires = expires
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut token = format!("{expires}.");
+        for byte in self.mac(path, expires).finalize().into_bytes() {
+            token.push_str(&format!("{byte:02x}"));
+        }
+        token
+    }
+
+    /// A token granting access to `path` for `ttl` from now.
+    pub fn sign_for(&self, path: &RelativePath, ttl: Duration) -> String {
+        self.sign(path, SystemTime::now() + ttl)
+    }
+
+    /// `path` joined onto `base`, with a `token` query parameter granting access until
+    /// `expires`.
+    pub fn url(&self, base: &Url, path: &RelativePath, expires: SystemTime) -> io::Result<Url> {
+        let mut url = base
+            .join(path.as_str())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        url.query_pairs_mut()
+            .append_pair("token", &self.sign(path, expires));
+        Ok(url)
+    }
+
+    /// Check that `token` grants access to `path` and hasn't expired. Fails with
+    /// `PermissionDenied` otherwise.
+    pub fn verify(&self, path: &RelativePath, token: &str) -> io::Result<()> {
+        let denied = |msg: &str| io::Error::new(io::ErrorKind::PermissionDenied, msg.to_string());
+
+        let (expires, signature) = token
+            .split_once('.')
+            .ok_or_else(|| denied("malformed token"))?;
+        let expires = expires
+            .parse::<u64>()
+            .map_err(|_| denied("malformed token"))?;
+        let signature = decode_hex(signature).ok_or_else(|| denied("malformed token"))?;
+
+        self.mac(path, expires)
+            .verify_slice(&signature)
+            .map_err(|_| denied("invalid signature"))?;
+
+        if UNIX_EPOCH + Duration::from_secs(expires) < SystemTime::now() {
+            return Err(denied("token expired"));
+        }
+
+        Ok(())
+    }
+
+    fn mac(&self, path: &RelativePath, expires: u64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("any key length");
+        mac.update(path.normalize().as_str().as_bytes());
+        mac.update(b"\n");
+        mac.update(expires.to_string().as_bytes());
+        mac
+    }
+}
+
+impl fmt::Debug for UrlSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UrlSigner").finish_non_exhaustive()
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Only opens files when the token of the current [`scope`] is valid for the path, failing
+/// with `PermissionDenied` otherwise. Everything else is passed through.
+///
+/// ```ignore
+/// let signer = UrlSigner::new(secret);
+/// let store = Signed::new(private, signer.clone());
+///
+/// // Share a link
+/// let link = signer.url(&base, "reports/q3.pdf".as_ref(), SystemTime::now() + day)?;
+///
+/// // Serve it
+/// let file = signed::scope(query.token, store.open_file(path)).await?;
+/// ```
+pub struct Signed<T> {
+    inner: T,
+    signer: UrlSigner,
+}
+
+impl<T> Signed<T> {
+    pub fn new(inner: T, signer: UrlSigner) -> Signed<T> {
+        Signed { inner, signer }
+    }
+
+    pub fn signer(&self) -> &UrlSigner {
+        &self.signer
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> AsyncFileStore for Signed<T>
+where
+    T: AsyncFileStore + Send + Sync,
+{
+    type File = T::File;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        self.inner.metadata(path)
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            let token = token()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, "missing token"))?;
+            self.signer.verify(path, &token)?;
+            self.inner.open_file(path).await
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        self.inner.rm_file(path)
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        self.inner.write_file(path, init)
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.inner.list()
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        self.inner.list_ref()
+    }
+
+    fn exists(&self, path: &RelativePath) -> impl Future<Output = bool> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.exists(path)
+    }
+
+    fn stats(&self) -> impl Future<Output = io::Result<StoreStats>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.stats()
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.health()
+    }
+
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.prefetch(paths)
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.prefetch_prefix(prefix)
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        self.inner.set_len(path, len)
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.set_permissions(path, permissions)
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.symlink(target, link)
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.hard_link(src, dst)
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        self.inner.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.close()
+    }
+}
@@ -0,0 +1,385 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use bytes::{Bytes, BytesMut};
+use futures::{stream::BoxStream, Future, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    AsyncFileBody, AsyncFileInit, AsyncFileStore, Attributes, Link, Metadata, Permissions,
+    StoreStats,
+};
+
+/// Default size up to which a body is kept in memory.
+const THRESHOLD: usize = 8 * 1024 * 1024;
+
+static SPOOLS: AtomicU64 = AtomicU64::new(0);
+
+/// Buffers streamed writes before handing them to the wrapped store, so a failed write can be
+/// attempted again.
+///
+/// Bodies are held in memory up to a threshold, 8MiB by default, and spill to a temporary file
+/// beyond it. Bodies which can already be replayed, bytes and paths, are passed through
+/// without spooling. Spilling uses tokio and must run within a tokio runtime.
+///
+/// ```ignore
+/// let store = Spooled::new(s3).threshold(1024 * 1024).attempts(3);
+/// store.write_file(path, AsyncFileInit::stream(upload)).await?;
+/// ```
+pub struct Spooled<T> {
+    inner: T,
+    threshold: usize,
+    dir: PathBuf,
+    attempts: usize,
+}
+
+impl<T> Spooled<T> {
+    pub fn new(inner: T) -> Spooled<T> {
+        Spooled {
+            inner,
+            threshold: THRESHOLD,
+            dir: std::env::temp_dir(),
+            attempts: 1,
+        }
+    }
+
+    /// Keep bodies of up to `threshold` bytes in memory.
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Spill larger bodies into `dir` instead of the system temporary directory.
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// Write up to `attempts` times before giving up. Errors which won't go away by trying
+    /// again, like `PermissionDenied` or `InvalidInput`, are returned right away.
+    pub fn attempts(mut self, attempts: usize) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Consume the body of `init` into a spool which can be written any number of times.
+    pub async fn spool(&self, init: AsyncFileInit) -> io::Result<Spool> {
+        let AsyncFileInit {
+            body,
+            len,
+            mime,
+            attributes,
+        } = init;
+
+        let data = match body {
+            AsyncFileBody::Bytes(bytes) => SpoolData::Bytes(bytes),
+            AsyncFileBody::Path(path) => SpoolData::Path(path),
+            body => {
+                let stream = body.into_stream().await?;
+                spool_stream(stream, self.threshold, &self.dir).await?
+            }
+        };
+
+        Ok(Spool {
+            data,
+            len,
+            mime,
+            attributes,
+        })
+    }
+}
+
+/// A body consumed by [`Spooled::spool`].
+pub struct Spool {
+    data: SpoolData,
+    len: Option<u64>,
+    mime: Option<mime::Mime>,
+    attributes: Attributes,
+}
+
+enum SpoolData {
+    Bytes(Bytes),
+    Path(PathBuf),
+    Spilled(TempFile),
+}
+
+impl Spool {
+    /// Whether the body was spilled to disk.
+    pub fn is_spilled(&self) -> bool {
+        matches!(self.data, SpoolData::Spilled(_))
+    }
+
+    /// A new init reading the spooled body.
+    pub fn init(&self) -> AsyncFileInit {
+        let (mut init, len) = match &self.data {
+            SpoolData::Bytes(bytes) => (AsyncFileInit::from(bytes.clone()), self.len),
+            SpoolData::Path(path) => (AsyncFileInit::from(path.clone()), self.len),
+            SpoolData::Spilled(file) => (AsyncFileInit::from(file.path.clone()), Some(file.len)),
+        };
+
+        if let Some(len) = len {
+            init = init.with_len(len);
+        }
+        if let Some(mime) = &self.mime {
+            init = init.with_mime(mime.clone());
+        }
+        init.attributes = self.attributes.clone();
+        init
+    }
+}
+
+/// A spilled body, removed when dropped.
+struct TempFile {
+    path: PathBuf,
+    len: u64,
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn spool_stream(
+    stream: BoxStream<'static, io::Result<Bytes>>,
+    threshold: usize,
+    dir: &Path,
+) -> io::Result<SpoolData> {
+    let mut stream = stream;
+    let mut buffer = BytesMut::new();
+
+    while let Some(chunk) = stream.try_next().await? {
+        if buffer.len() + chunk.len() <= threshold {
+            buffer.extend_from_slice(&chunk);
+            continue;
+        }
+
+        let (mut file, mut output) = create_spool(dir).await?;
+        output.write_all(&buffer).await?;
+        output.write_all(&chunk).await?;
+        file.len = (buffer.len() + chunk.len()) as u64;
+
+        while let Some(chunk) = stream.try_next().await? {
+            output.write_all(&chunk).await?;
+            file.len += chunk.len() as u64;
+        }
+        output.flush().await?;
+
+        return Ok(SpoolData::Spilled(file));
+    }
+
+    Ok(SpoolData::Bytes(buffer.freeze()))
+}
+
+/// Create a new spool file only the current user can read. Names are predictable, so files
+/// which already exist, possibly planted links, are never opened.
+async fn create_spool(dir: &Path) -> io::Result<(TempFile, tokio::fs::File)> {
+    loop {
+        let id = SPOOLS.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!(".samling-spool-{}-{id}", std::process::id()));
+
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        match options.open(&path).await {
+            Ok(output) => return Ok((TempFile { path, len: 0 }, output)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn retryable(err: &io::Error) -> bool {
+    !matches!(
+        err.kind(),
+        io::ErrorKind::NotFound
+            | io::ErrorKind::PermissionDenied
+            | io::ErrorKind::AlreadyExists
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::InvalidData
+            | io::ErrorKind::Unsupported
+    )
+}
+
+impl<T> AsyncFileStore for Spooled<T>
+where
+    T: AsyncFileStore + Send + Sync,
+{
+    type File = T::File;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        self.inner.metadata(path)
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        self.inner.open_file(path)
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        self.inner.rm_file(path)
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let spool = self.spool(init).await?;
+
+            let mut attempt = 1;
+            loop {
+                match self.inner.write_file(path, spool.init()).await {
+                    Err(err) if attempt < self.attempts && retryable(&err) => attempt += 1,
+                    ret => return ret,
+                }
+            }
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.inner.list()
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        self.inner.list_ref()
+    }
+
+    fn exists(&self, path: &RelativePath) -> impl Future<Output = bool> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.exists(path)
+    }
+
+    fn stats(&self) -> impl Future<Output = io::Result<StoreStats>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.stats()
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.health()
+    }
+
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.prefetch(paths)
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.prefetch_prefix(prefix)
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        self.inner.set_len(path, len)
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.inner.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.inner.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.set_permissions(path, permissions)
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.symlink(target, link)
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.hard_link(src, dst)
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        self.inner.read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        self.inner.supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.inner.close()
+    }
+}
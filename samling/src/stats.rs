@@ -0,0 +1,65 @@
+use std::{io, time::SystemTime};
+
+use futures::{pin_mut, StreamExt, TryStreamExt};
+use relative_path::RelativePathBuf;
+
+use crate::{AsyncFileStore, Metadata};
+
+/// Number of `metadata` calls in flight while computing stats by listing the store.
+const CONCURRENCY: usize = 16;
+
+/// Totals over every file of a store, as returned by [`AsyncFileStore::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StoreStats {
+    pub files: u64,
+    pub bytes: u64,
+    /// The path and size of the largest file.
+    pub largest: Option<(RelativePathBuf, u64)>,
+    /// The most recent modification time reported for any file.
+    pub modified: Option<SystemTime>,
+}
+
+impl StoreStats {
+    /// Account for a single file.
+    pub fn add(&mut self, meta: &Metadata) {
+        self.files += 1;
+        self.bytes += meta.size;
+
+        if self
+            .largest
+            .as_ref()
+            .is_none_or(|(_, size)| meta.size > *size)
+        {
+            self.largest = Some((meta.path.clone(), meta.size));
+        }
+
+        if meta.modified > self.modified {
+            self.modified = meta.modified;
+        }
+    }
+}
+
+/// List the store and read the metadata of every file.
+pub(crate) async fn compute<S>(store: &S) -> io::Result<StoreStats>
+where
+    S: AsyncFileStore + Sync + ?Sized,
+{
+    let metas = store
+        .list_ref()
+        .map_ok(|path| async move { store.metadata(&path).await })
+        .try_buffer_unordered(CONCURRENCY);
+    pin_mut!(metas);
+
+    let mut stats = StoreStats::default();
+    while let Some(meta) = metas.next().await {
+        match meta {
+            Ok(meta) => stats.add(&meta),
+            // Removed since it was listed
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(stats)
+}
@@ -5,136 +5,252 @@ use std::{
     sync::Arc,
 };
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use core::future::Future;
-use futures::{stream::BoxStream, Stream, StreamExt, TryStreamExt};
+use futures::{stream::BoxStream, StreamExt, TryFutureExt, TryStreamExt};
 use relative_path::{RelativePath, RelativePathBuf};
+#[cfg(any(feature = "serde-json", feature = "serde-toml", feature = "serde-yaml"))]
+use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{
     boxed::{async_filestore_box, filestore_box, BoxFileStore},
-    either::Either,
-    file::{AsyncFile, Metadata},
-    BoxAsyncFileStore, File,
+    file::{link_target, AsyncFile, Capabilities, Link, Metadata, Permissions},
+    AsyncFileInit, BoxAsyncFileStore, File, FileInit, ListOptions, StoreStats, WalkEntry,
+    WalkOptions,
 };
 
-#[non_exhaustive]
-pub enum FileInit {
-    Bytes(Bytes),
-    Read(Box<dyn Read + Send>),
-    #[cfg(feature = "fs")]
-    Path(std::path::PathBuf),
-}
+pub trait AsyncFileStore {
+    type File: AsyncFile;
 
-#[non_exhaustive]
-pub enum AsyncFileInit {
-    Bytes(Bytes),
-    Stream(BoxStream<'static, io::Result<Bytes>>),
-    #[cfg(feature = "fs")]
-    Path(std::path::PathBuf),
-}
+    /// Whether [`list`](AsyncFileStore::list) yields paths in ascending path order, so sorted
+    /// listings can pass it through as is.
+    const SORTED: bool = false;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send;
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send;
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send;
 
-impl AsyncFileInit {
-    pub fn stream<T>(stream: T) -> AsyncFileInit
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send;
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send;
+
+    /// Like [`list`](AsyncFileStore::list), but the stream may borrow the store, so backends
+    /// don't have to clone their state or collect ahead of time, and listings can be fully
+    /// incremental. A failure to start listing is yielded as the first item.
+    ///
+    /// Flattens `list` by default.
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
     where
-        T: Stream<Item = io::Result<Bytes>> + Send + 'static,
+        Self: Sync,
     {
-        AsyncFileInit::Stream(stream.boxed())
+        self.list().try_flatten_stream().boxed()
     }
 
-    #[cfg(feature = "fs")]
-    pub async fn into_stream(self) -> io::Result<impl Stream<Item = io::Result<Bytes>>> {
-        let ret = match self {
-            Self::Bytes(bs) => Either::Left(futures::stream::once(async move { Ok(bs) })),
-            Self::Stream(bs) => Either::Right(Either::Left(bs)),
-
-            Self::Path(path) => {
-                Either::Right(Either::Right(crate::util::file_stream(&path).await?))
-            }
-        };
-
-        Ok(ret)
+    fn exists(&self, path: &RelativePath) -> impl Future<Output = bool> + Send
+    where
+        Self: Sync,
+    {
+        async move { self.metadata(path).await.is_ok() }
     }
 
-    #[cfg(not(feature = "fs"))]
-    pub async fn into_stream(self) -> io::Result<impl Stream<Item = io::Result<Bytes>>> {
-        let ret = match self {
-            Self::Bytes(bs) => Either::Left(futures::stream::once(async move { Ok(bs) })),
-            Self::Stream(bs) => Either::Right(bs),
-        };
+    /// Totals over every file of the store.
+    ///
+    /// Lists the store and reads the metadata of every file by default. Backends which can
+    /// answer cheaper override it.
+    fn stats(&self) -> impl Future<Output = io::Result<StoreStats>> + Send
+    where
+        Self: Sync,
+    {
+        crate::stats::compute(self)
+    }
 
-        Ok(ret)
+    /// Check that the store can be reached, e.g. for load balancers or to take a mount out of
+    /// a composite.
+    ///
+    /// Lists the store and reads the first entry by default. Backends with a cheaper probe
+    /// override it.
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            self.list_ref().try_next().await?;
+            Ok(())
+        }
     }
-}
 
-impl From<Bytes> for AsyncFileInit {
-    fn from(value: Bytes) -> Self {
-        AsyncFileInit::Bytes(value)
+    /// Hint that `paths` will be read soon, so caches can be warmed ahead of time, e.g. at
+    /// startup.
+    ///
+    /// Does nothing by default. Caching middleware and remote backends override it, and
+    /// wrappers pass it on. Paths which do not exist are ignored.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let _ = paths;
+        async { Ok(()) }
     }
-}
 
-impl From<Vec<u8>> for AsyncFileInit {
-    fn from(value: Vec<u8>) -> Self {
-        AsyncFileInit::Bytes(value.into())
+    /// Like [`prefetch`](AsyncFileStore::prefetch), for every file below `prefix`.
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let _ = prefix;
+        async { Ok(()) }
     }
-}
 
-impl From<&'static [u8]> for AsyncFileInit {
-    fn from(value: &'static [u8]) -> Self {
-        AsyncFileInit::Bytes(Bytes::from_static(value))
+    /// Cut a file short or pad it with zeros to `len` bytes, e.g. to preallocate a large file
+    /// or trim a partial write before resuming an upload.
+    ///
+    /// Rewrites the whole file by default. Backends which can resize files in place override
+    /// it, and wrappers pass it on.
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        async move {
+            let size = self.metadata(path).await?.size;
+            if size == len {
+                return Ok(());
+            }
+
+            let file = self.open_file(path).await?;
+            let data = resized(file.read_range(0..size.min(len)).await?, len);
+            self.write_file(path, AsyncFileInit::from(data)).await
+        }
     }
-}
 
-#[cfg(feature = "fs")]
-impl From<std::path::PathBuf> for AsyncFileInit {
-    fn from(value: std::path::PathBuf) -> Self {
-        AsyncFileInit::Path(value)
+    /// Overwrite part of an existing file with `data`, starting at `offset`. Writing past the
+    /// end extends the file, filling any gap with zeros. Lets files be patched in place, like
+    /// the index block of an archive.
+    ///
+    /// Fails with `Unsupported` unless the backend can write at an offset, which
+    /// [`supports_write_range`](AsyncFileStore::supports_write_range) tells.
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let _ = (path, offset, data);
+        async { Err(io::ErrorKind::Unsupported.into()) }
     }
-}
 
-impl From<BoxStream<'static, io::Result<Bytes>>> for AsyncFileInit {
-    fn from(value: BoxStream<'static, io::Result<Bytes>>) -> Self {
-        AsyncFileInit::Stream(value)
+    /// Whether [`write_range`](AsyncFileStore::write_range) is supported. Stores combining
+    /// others report whether any of them supports it.
+    fn supports_write_range(&self) -> bool {
+        false
     }
-}
 
-pub trait AsyncFileStore {
-    type File: AsyncFile;
+    /// Whether [`Metadata::attributes`] are filled in and the attributes of an
+    /// [`AsyncFileInit`] are kept when writing. Other stores leave attributes out.
+    fn supports_attributes(&self) -> bool {
+        false
+    }
 
-    fn metadata(
+    /// Change the permissions of a file, e.g. to restore the executable bit of a script. The
+    /// current ones are in [`Metadata::permissions`].
+    ///
+    /// Fails with `Unsupported` by default, for backends which don't keep permissions.
+    fn set_permissions(
         &self,
         path: &RelativePath,
-    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send;
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let _ = (path, permissions);
+        async { Err(io::ErrorKind::Unsupported.into()) }
+    }
 
-    fn open_file(
+    /// Create a symbolic link at `link` pointing at `target`, which is relative to the
+    /// directory containing the link. Targets leading out of the store are rejected with
+    /// `PermissionDenied`.
+    ///
+    /// Fails with `Unsupported` unless the backend has links, which
+    /// [`supports_links`](AsyncFileStore::supports_links) tells.
+    fn symlink(
         &self,
-        path: &RelativePath,
-    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send;
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let _ = (target, link);
+        async { Err(io::ErrorKind::Unsupported.into()) }
+    }
 
-    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send;
+    /// Give the existing file `src` a second name, `dst`. Both share their contents.
+    ///
+    /// Fails with `Unsupported` unless the backend has links.
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        let _ = (src, dst);
+        async { Err(io::ErrorKind::Unsupported.into()) }
+    }
 
-    fn write_file(
+    /// What `path` is a link to, or `None` when it is a plain file. Reading and writing a
+    /// symlink goes through to its target.
+    ///
+    /// Backends without links always return `None`.
+    fn read_link(
         &self,
         path: &RelativePath,
-        init: AsyncFileInit,
-    ) -> impl Future<Output = Result<(), io::Error>> + Send;
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        let _ = path;
+        async { Ok(None) }
+    }
 
-    fn list(
-        &self,
-    ) -> impl Future<
-        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
-    > + Send;
+    /// Whether [`symlink`](AsyncFileStore::symlink) and
+    /// [`hard_link`](AsyncFileStore::hard_link) are supported.
+    fn supports_links(&self) -> bool {
+        false
+    }
 
-    fn exists(&self, path: &RelativePath) -> impl Future<Output = bool> + Send
+    /// Write out anything buffered, so it is durable once this returns.
+    ///
+    /// Does nothing by default. Middleware which buffers writes overrides it, and wrappers
+    /// pass it on.
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
     where
         Self: Sync,
     {
-        async move { self.metadata(path).await.is_ok() }
+        async { Ok(()) }
+    }
+
+    /// Flush and release what the store holds on to, e.g. at shutdown. The store should not
+    /// be used afterwards.
+    ///
+    /// Only flushes by default.
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.flush()
     }
 }
 
 impl<'a, T> AsyncFileStore for &'a T
 where
-    T: AsyncFileStore,
+    T: AsyncFileStore + Sync,
 {
     type File = T::File;
 
@@ -171,6 +287,124 @@ where
     > + Send {
         (*self).list()
     }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        (*self).list_ref()
+    }
+
+    fn exists(&self, path: &RelativePath) -> impl Future<Output = bool> + Send
+    where
+        Self: Sync,
+    {
+        (*self).exists(path)
+    }
+
+    fn stats(&self) -> impl Future<Output = io::Result<StoreStats>> + Send
+    where
+        Self: Sync,
+    {
+        (*self).stats()
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (*self).health()
+    }
+
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (*self).prefetch(paths)
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (*self).prefetch_prefix(prefix)
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        (*self).set_len(path, len)
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        (*self).write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        (*self).supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        (*self).supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        (*self).set_permissions(path, permissions)
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        (*self).symlink(target, link)
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        (*self).hard_link(src, dst)
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        (*self).read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        (*self).supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (*self).flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (*self).close()
+    }
+
+    const SORTED: bool = T::SORTED;
 }
 
 // Sync
@@ -179,6 +413,10 @@ pub trait FileStore {
     type File: File;
     type List: Iterator<Item = Result<RelativePathBuf, io::Error>>;
 
+    /// Whether [`list`](FileStore::list) yields paths in ascending path order.
+    /// See [`AsyncFileStore::SORTED`].
+    const SORTED: bool = false;
+
     fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error>;
 
     fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error>;
@@ -192,6 +430,72 @@ pub trait FileStore {
     fn exists(&self, path: &RelativePath) -> bool {
         self.metadata(path).is_ok()
     }
+
+    /// Cut a file short or pad it with zeros. See [`AsyncFileStore::set_len`].
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        let size = self.metadata(path)?.size;
+        if size == len {
+            return Ok(());
+        }
+
+        let data = resized(self.open_file(path)?.read_range(0..size.min(len))?, len);
+        self.write_file(path, FileInit::from(data))
+    }
+
+    /// Overwrite part of an existing file. See [`AsyncFileStore::write_range`].
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        let _ = (path, offset, data);
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// See [`AsyncFileStore::supports_write_range`].
+    fn supports_write_range(&self) -> bool {
+        false
+    }
+
+    /// See [`AsyncFileStore::supports_attributes`].
+    fn supports_attributes(&self) -> bool {
+        false
+    }
+
+    /// Change the permissions of a file. See [`AsyncFileStore::set_permissions`].
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        let _ = (path, permissions);
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Create a symbolic link. See [`AsyncFileStore::symlink`].
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        let _ = (target, link);
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Give a file a second name. See [`AsyncFileStore::hard_link`].
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        let _ = (src, dst);
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// See [`AsyncFileStore::read_link`].
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        let _ = path;
+        Ok(None)
+    }
+
+    /// See [`AsyncFileStore::supports_links`].
+    fn supports_links(&self) -> bool {
+        false
+    }
+
+    /// Write out anything buffered. See [`AsyncFileStore::flush`].
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Flush and release what the store holds on to. See [`AsyncFileStore::close`].
+    fn close(&self) -> io::Result<()> {
+        self.flush()
+    }
 }
 
 impl<'a, T> FileStore for &'a T
@@ -203,6 +507,8 @@ where
 
     type List = T::List;
 
+    const SORTED: bool = T::SORTED;
+
     fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
         (*self).metadata(path)
     }
@@ -222,6 +528,50 @@ where
     fn list(&self) -> Self::List {
         (*self).list()
     }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        (*self).set_len(path, len)
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        (*self).write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        (*self).supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        (*self).supports_attributes()
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        (*self).set_permissions(path, permissions)
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        (*self).symlink(target, link)
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        (*self).hard_link(src, dst)
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        (*self).read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        (*self).supports_links()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        (*self).flush()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        (*self).close()
+    }
 }
 
 pub trait FileStoreExt: FileStore {
@@ -234,6 +584,81 @@ pub trait FileStoreExt: FileStore {
     {
         filestore_box(self)
     }
+
+    /// What the store supports. See [`AsyncFileStoreExt::capabilities`].
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            sorted: Self::SORTED,
+            write_range: self.supports_write_range(),
+            attributes: self.supports_attributes(),
+            links: self.supports_links(),
+        }
+    }
+
+    /// Read a whole file into memory.
+    fn read(&self, path: &RelativePath) -> io::Result<Bytes> {
+        let size = self.metadata(path)?.size;
+        let mut output = Vec::with_capacity(size as usize);
+        self.open_file(path)?.reader()?.read_to_end(&mut output)?;
+        Ok(output.into())
+    }
+
+    /// Read a whole file into memory as utf8.
+    fn read_to_string(&self, path: &RelativePath) -> io::Result<String> {
+        into_string(self.read(path)?)
+    }
+
+    /// Walk every file of the store with its metadata and depth.
+    fn walk(&self, options: WalkOptions) -> impl Iterator<Item = io::Result<WalkEntry>> + '_
+    where
+        Self: Sized,
+    {
+        crate::walk::walk_sync(self, options)
+    }
+
+    /// List the store, e.g. in a stable order.
+    fn list_with(&self, options: ListOptions) -> impl Iterator<Item = io::Result<RelativePathBuf>>
+    where
+        Self: Sized,
+    {
+        crate::list::list_with_sync(self, options)
+    }
+
+    /// Read a file and deserialize it from JSON.
+    #[cfg(feature = "serde-json")]
+    fn read_json<D: DeserializeOwned>(&self, path: &RelativePath) -> io::Result<D> {
+        crate::format::from_json(&self.read(path)?)
+    }
+
+    /// Serialize `value` as JSON into a file.
+    #[cfg(feature = "serde-json")]
+    fn write_json<S: Serialize + ?Sized>(&self, path: &RelativePath, value: &S) -> io::Result<()> {
+        self.write_file(path, FileInit::json(value)?)
+    }
+
+    /// Read a file and deserialize it from TOML.
+    #[cfg(feature = "serde-toml")]
+    fn read_toml<D: DeserializeOwned>(&self, path: &RelativePath) -> io::Result<D> {
+        crate::format::from_toml(&self.read(path)?)
+    }
+
+    /// Serialize `value` as TOML into a file.
+    #[cfg(feature = "serde-toml")]
+    fn write_toml<S: Serialize + ?Sized>(&self, path: &RelativePath, value: &S) -> io::Result<()> {
+        self.write_file(path, FileInit::from(crate::format::to_toml(value)?))
+    }
+
+    /// Read a file and deserialize it from YAML.
+    #[cfg(feature = "serde-yaml")]
+    fn read_yaml<D: DeserializeOwned>(&self, path: &RelativePath) -> io::Result<D> {
+        crate::format::from_yaml(&self.read(path)?)
+    }
+
+    /// Serialize `value` as YAML into a file.
+    #[cfg(feature = "serde-yaml")]
+    fn write_yaml<S: Serialize + ?Sized>(&self, path: &RelativePath, value: &S) -> io::Result<()> {
+        self.write_file(path, FileInit::from(crate::format::to_yaml(value)?))
+    }
 }
 
 impl<T> FileStoreExt for T where T: FileStore {}
@@ -247,6 +672,162 @@ pub trait AsyncFileStoreExt: AsyncFileStore {
     {
         async_filestore_box(self)
     }
+
+    /// What the store supports, collected from its `supports_*` methods.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            sorted: Self::SORTED,
+            write_range: self.supports_write_range(),
+            attributes: self.supports_attributes(),
+            links: self.supports_links(),
+        }
+    }
+
+    /// Read a whole file into memory.
+    fn read(&self, path: &RelativePath) -> impl Future<Output = io::Result<Bytes>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+        <Self::File as AsyncFile>::Body: Send,
+    {
+        async move {
+            let size = self.metadata(path).await?.size;
+            let reader = self.open_file(path).await?.reader().await?;
+            crate::util::collect(reader, size).await
+        }
+    }
+
+    /// Read a whole file into memory as utf8.
+    fn read_to_string(&self, path: &RelativePath) -> impl Future<Output = io::Result<String>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+        <Self::File as AsyncFile>::Body: Send,
+    {
+        async move { into_string(self.read(path).await?) }
+    }
+
+    /// Walk every file of the store with its metadata and depth.
+    fn walk(
+        &self,
+        options: WalkOptions,
+    ) -> impl Future<Output = io::Result<BoxStream<'_, io::Result<WalkEntry>>>> + Send
+    where
+        Self: Sized + Sync,
+        Self::File: Send,
+    {
+        crate::walk::walk(self, options)
+    }
+
+    /// List the store, e.g. in a stable order.
+    fn list_with(
+        &self,
+        options: ListOptions,
+    ) -> impl Future<Output = io::Result<BoxStream<'static, io::Result<RelativePathBuf>>>> + Send
+    where
+        Self: Sized + Sync,
+    {
+        crate::list::list_with(self, options)
+    }
+
+    /// Read a file and deserialize it from JSON.
+    #[cfg(feature = "serde-json")]
+    fn read_json<D: DeserializeOwned>(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<D>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+        <Self::File as AsyncFile>::Body: Send,
+    {
+        async move { crate::format::from_json(&self.read(path).await?) }
+    }
+
+    /// Serialize `value` as JSON into a file.
+    #[cfg(feature = "serde-json")]
+    fn write_json<S: Serialize + ?Sized>(
+        &self,
+        path: &RelativePath,
+        value: &S,
+    ) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let init = AsyncFileInit::json(value);
+        async move { self.write_file(path, init?).await }
+    }
+
+    /// Read a file and deserialize it from TOML.
+    #[cfg(feature = "serde-toml")]
+    fn read_toml<D: DeserializeOwned>(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<D>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+        <Self::File as AsyncFile>::Body: Send,
+    {
+        async move { crate::format::from_toml(&self.read(path).await?) }
+    }
+
+    /// Serialize `value` as TOML into a file.
+    #[cfg(feature = "serde-toml")]
+    fn write_toml<S: Serialize + ?Sized>(
+        &self,
+        path: &RelativePath,
+        value: &S,
+    ) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let data = crate::format::to_toml(value);
+        async move { self.write_file(path, AsyncFileInit::from(data?)).await }
+    }
+
+    /// Read a file and deserialize it from YAML.
+    #[cfg(feature = "serde-yaml")]
+    fn read_yaml<D: DeserializeOwned>(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<D>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+        <Self::File as AsyncFile>::Body: Send,
+    {
+        async move { crate::format::from_yaml(&self.read(path).await?) }
+    }
+
+    /// Serialize `value` as YAML into a file.
+    #[cfg(feature = "serde-yaml")]
+    fn write_yaml<S: Serialize + ?Sized>(
+        &self,
+        path: &RelativePath,
+        value: &S,
+    ) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let data = crate::format::to_yaml(value);
+        async move { self.write_file(path, AsyncFileInit::from(data?)).await }
+    }
+}
+
+/// `data` padded with zeros to `len` bytes.
+fn resized(data: Bytes, len: u64) -> Bytes {
+    if data.len() as u64 == len {
+        return data;
+    }
+    let mut data = BytesMut::from(&data[..]);
+    data.resize(len as usize, 0);
+    data.freeze()
+}
+
+fn into_string(bytes: Bytes) -> io::Result<String> {
+    String::from_utf8(Vec::from(bytes))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
 }
 
 impl<T> AsyncFileStoreExt for T where T: AsyncFileStore {}
@@ -266,8 +847,9 @@ where
     ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
         async move {
             for fs in self {
-                if fs.exists(path).await {
-                    return fs.metadata(path).await;
+                match fs.metadata(path).await {
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                    ret => return ret,
                 }
             }
             Err(io::Error::new(io::ErrorKind::NotFound, ""))
@@ -280,8 +862,9 @@ where
     ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
         async move {
             for fs in self {
-                if fs.exists(path).await {
-                    return fs.open_file(path).await;
+                match fs.open_file(path).await {
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                    ret => return ret,
                 }
             }
             Err(io::Error::new(io::ErrorKind::NotFound, ""))
@@ -326,58 +909,316 @@ where
                 streams.push(fs.list().await?);
             }
 
-            Ok(futures::stream::iter(streams).flatten().boxed())
+            Ok(futures::stream::iter(streams).flatten().boxed())
+        }
+    }
+
+    /// Lists each store only once the one before it is done.
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        futures::stream::iter(self)
+            .flat_map(|fs| fs.list_ref())
+            .boxed()
+    }
+
+    /// Healthy when every store is.
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            futures::future::try_join_all(self.iter().map(|fs| fs.health())).await?;
+            Ok(())
+        }
+    }
+
+    /// Passes the hint on to every store.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            futures::future::try_join_all(self.iter().map(|fs| fs.prefetch(paths))).await?;
+            Ok(())
+        }
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            futures::future::try_join_all(self.iter().map(|fs| fs.prefetch_prefix(prefix))).await?;
+            Ok(())
+        }
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        async move {
+            for fs in self {
+                if fs.exists(path).await {
+                    return fs.set_len(path, len).await;
+                }
+            }
+            Err(io::Error::new(io::ErrorKind::NotFound, ""))
+        }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            for fs in self {
+                if fs.exists(path).await {
+                    return fs.write_range(path, offset, data).await;
+                }
+            }
+            Err(io::Error::new(io::ErrorKind::NotFound, ""))
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.iter().any(|fs| fs.supports_write_range())
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.iter().any(|fs| fs.supports_attributes())
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            for fs in self {
+                if fs.exists(path).await {
+                    return fs.set_permissions(path, permissions).await;
+                }
+            }
+            Err(io::Error::new(io::ErrorKind::NotFound, ""))
+        }
+    }
+
+    /// Creates the link in the store containing its target.
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let resolved = link_target(link, target)?;
+            for fs in self {
+                if fs.exists(&resolved).await {
+                    return fs.symlink(target, link).await;
+                }
+            }
+            Err(io::Error::new(io::ErrorKind::NotFound, ""))
+        }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            for fs in self {
+                if fs.exists(src).await {
+                    return fs.hard_link(src, dst).await;
+                }
+            }
+            Err(io::Error::new(io::ErrorKind::NotFound, ""))
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        async move {
+            for fs in self {
+                if fs.exists(path).await {
+                    return fs.read_link(path).await;
+                }
+            }
+            Err(io::Error::new(io::ErrorKind::NotFound, ""))
+        }
+    }
+
+    fn supports_links(&self) -> bool {
+        self.iter().any(|fs| fs.supports_links())
+    }
+
+    /// Flushes every store, even when one fails, and returns the first error.
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            futures::future::join_all(self.iter().map(|fs| fs.flush()))
+                .await
+                .into_iter()
+                .collect()
+        }
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            futures::future::join_all(self.iter().map(|fs| fs.close()))
+                .await
+                .into_iter()
+                .collect()
+        }
+    }
+}
+
+impl<T> FileStore for Vec<T>
+where
+    T: FileStore,
+{
+    type File = T::File;
+
+    type List = Flatten<std::vec::IntoIter<T::List>>;
+
+    fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
+        for fs in self {
+            match fs.metadata(path) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                ret => return ret,
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, ""))
+    }
+
+    fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
+        for fs in self {
+            match fs.open_file(path) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                ret => return ret,
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, ""))
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
+        for fs in self {
+            if fs.exists(path) {
+                return fs.rm_file(path);
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, ""))
+    }
+
+    fn write_file(&self, path: &RelativePath, init: FileInit) -> Result<(), io::Error> {
+        for fs in self {
+            if fs.exists(path) {
+                return fs.write_file(path, init);
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, ""))
+    }
+
+    fn list(&self) -> Self::List {
+        let list = self.iter().map(|m| m.list()).collect::<Vec<_>>();
+        list.into_iter().flatten()
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        for fs in self {
+            if fs.exists(path) {
+                return fs.set_len(path, len);
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, ""))
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        for fs in self {
+            if fs.exists(path) {
+                return fs.write_range(path, offset, data);
+            }
         }
+        Err(io::Error::new(io::ErrorKind::NotFound, ""))
     }
-}
 
-impl<T> FileStore for Vec<T>
-where
-    T: FileStore,
-{
-    type File = T::File;
+    fn supports_write_range(&self) -> bool {
+        self.iter().any(|fs| fs.supports_write_range())
+    }
 
-    type List = Flatten<std::vec::IntoIter<T::List>>;
+    fn supports_attributes(&self) -> bool {
+        self.iter().any(|fs| fs.supports_attributes())
+    }
 
-    fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
         for fs in self {
             if fs.exists(path) {
-                return fs.metadata(path);
+                return fs.set_permissions(path, permissions);
             }
         }
         Err(io::Error::new(io::ErrorKind::NotFound, ""))
     }
 
-    fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
+    /// Creates the link in the store containing its target.
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        let resolved = link_target(link, target)?;
         for fs in self {
-            if fs.exists(path) {
-                return fs.open_file(path);
+            if fs.exists(&resolved) {
+                return fs.symlink(target, link);
             }
         }
         Err(io::Error::new(io::ErrorKind::NotFound, ""))
     }
 
-    fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
         for fs in self {
-            if fs.exists(path) {
-                return fs.rm_file(path);
+            if fs.exists(src) {
+                return fs.hard_link(src, dst);
             }
         }
         Err(io::Error::new(io::ErrorKind::NotFound, ""))
     }
 
-    fn write_file(&self, path: &RelativePath, init: FileInit) -> Result<(), io::Error> {
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
         for fs in self {
             if fs.exists(path) {
-                return fs.write_file(path, init);
+                return fs.read_link(path);
             }
         }
         Err(io::Error::new(io::ErrorKind::NotFound, ""))
     }
 
-    fn list(&self) -> Self::List {
-        let list = self.iter().map(|m| m.list()).collect::<Vec<_>>();
-        list.into_iter().flatten()
+    fn supports_links(&self) -> bool {
+        self.iter().any(|fs| fs.supports_links())
+    }
+
+    /// Flushes every store, even when one fails, and returns the first error.
+    fn flush(&self) -> io::Result<()> {
+        self.iter()
+            .map(|fs| fs.flush())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.iter()
+            .map(|fs| fs.close())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect()
     }
 }
 
@@ -446,17 +1287,30 @@ where
     Err(io::ErrorKind::NotFound.into())
 }
 
+/// The prefix within the store mounted at `mount` covering the files below `prefix`, if
+/// there are any in it.
+fn overlap(mount: &RelativePath, prefix: &RelativePath) -> Option<RelativePathBuf> {
+    match prefix.strip_prefix(mount) {
+        Ok(inner) => Some(inner.to_relative_path_buf()),
+        Err(_) => mount.starts_with(prefix).then(RelativePathBuf::new),
+    }
+}
+
 impl<T> FileStore for HashMap<RelativePathBuf, Vec<T>>
 where
     T: FileStore,
+    T::List: Send + 'static,
 {
     type File = T::File;
 
     type List = Box<dyn Iterator<Item = io::Result<RelativePathBuf>> + Send>;
 
+    /// The path of the metadata is the one given, not the one within the mount.
     fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
-        let (fs, path) = find(&self, path)?;
-        fs.metadata(path)
+        let (fs, inner) = find(&self, path)?;
+        let mut meta = fs.metadata(inner)?;
+        meta.path = path.normalize();
+        Ok(meta)
     }
 
     fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
@@ -475,7 +1329,62 @@ where
     }
 
     fn list(&self) -> Self::List {
-        todo!()
+        let lists = self
+            .iter()
+            .map(|(mount, stores)| {
+                let mount = mount.clone();
+                stores
+                    .list()
+                    .map(move |next| next.map(|path| mount.join(path)))
+            })
+            .collect::<Vec<_>>();
+
+        Box::new(lists.into_iter().flatten())
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        let (fs, path) = find(self, path)?;
+        fs.set_len(path, len)
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        let (fs, path) = find(self, path)?;
+        fs.write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.values().flatten().any(|fs| fs.supports_write_range())
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.values().flatten().any(|fs| fs.supports_attributes())
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        let (fs, path) = find(self, path)?;
+        fs.set_permissions(path, permissions)
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        let (fs, path) = find(self, path)?;
+        fs.read_link(path)
+    }
+
+    /// Flushes every mounted store, even when one fails, and returns the first error.
+    fn flush(&self) -> io::Result<()> {
+        self.values()
+            .map(|fs| fs.flush())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.values()
+            .map(|fs| fs.close())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect()
     }
 }
 
@@ -486,14 +1395,17 @@ where
 {
     type File = T::File;
 
+    /// The path of the metadata is the one given, not the one within the mount.
     fn metadata(
         &self,
         path: &relative_path::RelativePath,
     ) -> impl futures::prelude::Future<Output = Result<crate::Metadata, std::io::Error>> + Send
     {
         async move {
-            let (fs, path) = find_async(self, path).await?;
-            fs.metadata(path).await
+            let (fs, inner) = find_async(self, path).await?;
+            let mut meta = fs.metadata(inner).await?;
+            meta.path = path.normalize();
+            Ok(meta)
         }
     }
 
@@ -556,6 +1468,130 @@ where
             Ok(futures::stream::iter(ret).flatten().boxed())
         }
     }
+
+    /// Lists each mount only once the one before it is done.
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        futures::stream::iter(self)
+            .flat_map(|(root, stores)| stores.list_ref().map_ok(move |path| root.join(path)))
+            .boxed()
+    }
+
+    /// Healthy when every mounted store is.
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            futures::future::try_join_all(self.values().map(|routes| routes.health())).await?;
+            Ok(())
+        }
+    }
+
+    /// Passes each path on to the mount containing it.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            crate::prefetch::each(paths, |path| async move {
+                let (fs, inner) = find_async(self, &path).await?;
+                fs.prefetch(&[inner.to_relative_path_buf()]).await
+            })
+            .await
+        }
+    }
+
+    /// Passes the prefix on to every mount with files below it.
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let prefix = prefix.normalize();
+        async move {
+            let mounts = self.iter().filter_map(|(mount, fs)| {
+                let inner = overlap(mount, &prefix)?;
+                Some(async move { fs.prefetch_prefix(&inner).await })
+            });
+            futures::future::try_join_all(mounts).await?;
+            Ok(())
+        }
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        async move {
+            let (fs, path) = find_async(self, path).await?;
+            fs.set_len(path, len).await
+        }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let (fs, path) = find_async(self, path).await?;
+            fs.write_range(path, offset, data).await
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.values().flatten().any(|fs| fs.supports_write_range())
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let (fs, path) = find_async(self, path).await?;
+            fs.set_permissions(path, permissions).await
+        }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        async move {
+            let (fs, path) = find_async(self, path).await?;
+            fs.read_link(path).await
+        }
+    }
+
+    /// Flushes every mounted store, even when one fails, and returns the first error.
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            futures::future::join_all(self.values().map(|fs| fs.flush()))
+                .await
+                .into_iter()
+                .collect()
+        }
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            futures::future::join_all(self.values().map(|fs| fs.close()))
+                .await
+                .into_iter()
+                .collect()
+        }
+    }
 }
 
 impl<T> FileStore for Arc<T>
@@ -566,6 +1602,8 @@ where
 
     type List = T::List;
 
+    const SORTED: bool = T::SORTED;
+
     fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
         (**self).metadata(path)
     }
@@ -585,4 +1623,207 @@ where
     fn list(&self) -> Self::List {
         (**self).list()
     }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> io::Result<()> {
+        (**self).set_len(path, len)
+    }
+
+    fn write_range(&self, path: &RelativePath, offset: u64, data: Bytes) -> io::Result<()> {
+        (**self).write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        (**self).supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        (**self).supports_attributes()
+    }
+
+    fn set_permissions(&self, path: &RelativePath, permissions: Permissions) -> io::Result<()> {
+        (**self).set_permissions(path, permissions)
+    }
+
+    fn symlink(&self, target: &RelativePath, link: &RelativePath) -> io::Result<()> {
+        (**self).symlink(target, link)
+    }
+
+    fn hard_link(&self, src: &RelativePath, dst: &RelativePath) -> io::Result<()> {
+        (**self).hard_link(src, dst)
+    }
+
+    fn read_link(&self, path: &RelativePath) -> io::Result<Option<Link>> {
+        (**self).read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        (**self).supports_links()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        (**self).flush()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        (**self).close()
+    }
+}
+
+impl<T> AsyncFileStore for Arc<T>
+where
+    T: AsyncFileStore + Send + Sync,
+{
+    type File = T::File;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        (**self).metadata(path)
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        (**self).open_file(path)
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        (**self).rm_file(path)
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        (**self).write_file(path, init)
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        (**self).list()
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        (**self).list_ref()
+    }
+
+    fn exists(&self, path: &RelativePath) -> impl Future<Output = bool> + Send
+    where
+        Self: Sync,
+    {
+        (**self).exists(path)
+    }
+
+    fn stats(&self) -> impl Future<Output = io::Result<StoreStats>> + Send
+    where
+        Self: Sync,
+    {
+        (**self).stats()
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (**self).health()
+    }
+
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (**self).prefetch(paths)
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (**self).prefetch_prefix(prefix)
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        (**self).set_len(path, len)
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        (**self).write_range(path, offset, data)
+    }
+
+    fn supports_write_range(&self) -> bool {
+        (**self).supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        (**self).supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        (**self).set_permissions(path, permissions)
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        (**self).symlink(target, link)
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        (**self).hard_link(src, dst)
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        (**self).read_link(path)
+    }
+
+    fn supports_links(&self) -> bool {
+        (**self).supports_links()
+    }
+
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (**self).flush()
+    }
+
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        (**self).close()
+    }
 }
@@ -0,0 +1,391 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future};
+use relative_path::{Component, RelativePath, RelativePathBuf};
+
+use crate::{AsyncFileInit, AsyncFileStore, Link, Metadata, Permissions, Prefixed};
+
+/// Consulted before a tenant writes a file, with the tenant id, the path within the tenant and
+/// the length of the body if it is known. An error rejects the write.
+pub type QuotaHook = Arc<dyn Fn(&str, &RelativePath, Option<u64>) -> io::Result<()> + Send + Sync>;
+
+/// Kept tenants with the time they were last asked for.
+type Tenants<T> = Mutex<HashMap<String, (Arc<Tenant<T>>, Instant)>>;
+
+/// Hands out a [`Tenant`] per tenant id, each confined to its own directory of a shared store.
+///
+/// Handles are created on first use and kept for reuse. Tenants which have not been asked for
+/// within the idle timeout are evicted, as is the least recently used tenant once there are
+/// more than `max_tenants`. Eviction only forgets the handle; handles still held keep working.
+///
+/// ```ignore
+/// let tenants = TenantStores::new(AsyncFiles::new(s3))
+///     .root("customers")
+///     .idle_timeout(Duration::from_secs(600))
+///     .quota(|tenant, _path, len| usage.reserve(tenant, len.unwrap_or(0)));
+///
+/// let store = tenants.get(&claims.tenant)?;
+/// store.write_file("avatar.png".as_ref(), init).await?;
+/// ```
+pub struct TenantStores<T> {
+    store: T,
+    root: RelativePathBuf,
+    quota: Option<QuotaHook>,
+    idle_timeout: Option<Duration>,
+    max_tenants: Option<usize>,
+    tenants: Tenants<T>,
+}
+
+impl<T> TenantStores<T> {
+    pub fn new(store: T) -> TenantStores<T> {
+        TenantStores {
+            store,
+            root: RelativePathBuf::from("tenants"),
+            quota: None,
+            idle_timeout: None,
+            max_tenants: None,
+            tenants: Mutex::default(),
+        }
+    }
+
+    /// Put tenant directories below `root` instead of `tenants`.
+    pub fn root(mut self, root: impl Into<RelativePathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    pub fn quota<F>(mut self, quota: F) -> Self
+    where
+        F: Fn(&str, &RelativePath, Option<u64>) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.quota = Some(Arc::new(quota));
+        self
+    }
+
+    /// Evict tenants which have not been asked for in this long.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Keep at most `max` tenants, evicting the least recently used.
+    pub fn max_tenants(mut self, max: usize) -> Self {
+        self.max_tenants = Some(max.max(1));
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.store
+    }
+
+    /// The number of tenants currently kept.
+    pub fn len(&self) -> usize {
+        self.tenants.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget the handle of a tenant. Returns whether it was kept.
+    pub fn evict(&self, id: &str) -> bool {
+        self.tenants.lock().unwrap().remove(id).is_some()
+    }
+
+    /// Forget the handles of tenants past the idle timeout. Returns how many were evicted.
+    pub fn evict_idle(&self) -> usize {
+        let Some(timeout) = self.idle_timeout else {
+            return 0;
+        };
+
+        let mut tenants = self.tenants.lock().unwrap();
+        let before = tenants.len();
+        tenants.retain(|_, (_, used)| used.elapsed() < timeout);
+        before - tenants.len()
+    }
+}
+
+impl<T> TenantStores<T>
+where
+    T: Clone,
+{
+    /// The store of tenant `id`, created if it isn't kept already.
+    ///
+    /// Fails with `InvalidInput` unless the id is a plain name of ascii letters, digits, `-`,
+    /// `_` and `.`, not starting with a dot.
+    pub fn get(&self, id: &str) -> io::Result<Arc<Tenant<T>>> {
+        validate(id)?;
+        self.evict_idle();
+
+        let now = Instant::now();
+        let mut tenants = self.tenants.lock().unwrap();
+
+        if let Some((tenant, used)) = tenants.get_mut(id) {
+            *used = now;
+            return Ok(tenant.clone());
+        }
+
+        let tenant = Arc::new(Tenant {
+            id: id.to_string(),
+            store: Prefixed::new(self.store.clone(), self.root.join(id)),
+            quota: self.quota.clone(),
+        });
+        tenants.insert(id.to_string(), (tenant.clone(), now));
+
+        if let Some(max) = self.max_tenants {
+            while tenants.len() > max {
+                let oldest = tenants
+                    .iter()
+                    .filter(|(key, _)| key.as_str() != id)
+                    .min_by_key(|(_, (_, used))| *used)
+                    .map(|(key, _)| key.clone());
+                match oldest {
+                    Some(oldest) => tenants.remove(&oldest),
+                    None => break,
+                };
+            }
+        }
+
+        Ok(tenant)
+    }
+}
+
+impl<T> fmt::Debug for TenantStores<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TenantStores")
+            .field("root", &self.root)
+            .field("quota", &self.quota.is_some())
+            .field("idle_timeout", &self.idle_timeout)
+            .field("max_tenants", &self.max_tenants)
+            .field("tenants", &self.len())
+            .finish()
+    }
+}
+
+fn validate(id: &str) -> io::Result<()> {
+    let valid = !id.is_empty()
+        && id.len() <= 255
+        && !id.starts_with('.')
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid tenant id: {id:?}"),
+        ))
+    }
+}
+
+/// The store of a single tenant, handed out by [`TenantStores`].
+///
+/// Paths are resolved below the directory of the tenant. Paths which would climb out of it
+/// are rejected with `PermissionDenied`, and listings only contain the files of the tenant.
+pub struct Tenant<T> {
+    id: String,
+    store: Prefixed<T>,
+    quota: Option<QuotaHook>,
+}
+
+impl<T> Tenant<T> {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn inner(&self) -> &Prefixed<T> {
+        &self.store
+    }
+}
+
+impl<T> fmt::Debug for Tenant<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tenant")
+            .field("id", &self.id)
+            .field("mount", self.store.mount())
+            .finish()
+    }
+}
+
+fn confine(path: &RelativePath) -> io::Result<RelativePathBuf> {
+    let path = path.normalize();
+    if path.components().next() == Some(Component::ParentDir) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("path escapes the tenant: {path}"),
+        ));
+    }
+    Ok(path)
+}
+
+impl<T> AsyncFileStore for Tenant<T>
+where
+    T: AsyncFileStore + Send + Sync,
+{
+    type File = T::File;
+
+    const SORTED: bool = T::SORTED;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            let path = confine(path)?;
+            let mut meta = self.store.metadata(&path).await?;
+            meta.path = path;
+            Ok(meta)
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move { self.store.open_file(&confine(path)?).await }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move { self.store.rm_file(&confine(path)?).await }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let path = confine(path)?;
+            if let Some(quota) = &self.quota {
+                quota(&self.id, &path, init.len)?;
+            }
+            self.store.write_file(&path, init).await
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.store.list()
+    }
+
+    fn list_ref(&self) -> BoxStream<'_, io::Result<RelativePathBuf>>
+    where
+        Self: Sync,
+    {
+        self.store.list_ref()
+    }
+
+    /// Paths which would escape the tenant are left out.
+    fn prefetch(&self, paths: &[RelativePathBuf]) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        let paths = paths
+            .iter()
+            .filter_map(|path| confine(path).ok())
+            .collect::<Vec<_>>();
+        async move { self.store.prefetch(&paths).await }
+    }
+
+    fn prefetch_prefix(&self, prefix: &RelativePath) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move { self.store.prefetch_prefix(&confine(prefix)?).await }
+    }
+
+    fn set_len(&self, path: &RelativePath, len: u64) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+        Self::File: Send,
+    {
+        async move {
+            let path = confine(path)?;
+            if let Some(quota) = &self.quota {
+                quota(&self.id, &path, Some(len))?;
+            }
+            self.store.set_len(&path, len).await
+        }
+    }
+
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let path = confine(path)?;
+            if let Some(quota) = &self.quota {
+                quota(&self.id, &path, Some(offset + data.len() as u64))?;
+            }
+            self.store.write_range(&path, offset, data).await
+        }
+    }
+
+    fn supports_write_range(&self) -> bool {
+        self.store.supports_write_range()
+    }
+
+    fn supports_attributes(&self) -> bool {
+        self.store.supports_attributes()
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.store
+                .set_permissions(&confine(path)?, permissions)
+                .await
+        }
+    }
+
+    fn symlink(
+        &self,
+        target: &RelativePath,
+        link: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move { self.store.symlink(target, &confine(link)?).await }
+    }
+
+    fn hard_link(
+        &self,
+        src: &RelativePath,
+        dst: &RelativePath,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move { self.store.hard_link(&confine(src)?, &confine(dst)?).await }
+    }
+
+    fn read_link(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = io::Result<Option<Link>>> + Send {
+        async move { self.store.read_link(&confine(path)?).await }
+    }
+
+    fn supports_links(&self) -> bool {
+        self.store.supports_links()
+    }
+
+    /// Flushes the shared store. Closing a tenant only flushes, as the store outlives it.
+    fn flush(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        self.store.flush()
+    }
+}
@@ -0,0 +1,168 @@
+use std::{collections::BTreeMap, io};
+
+use futures::TryStreamExt;
+use relative_path::{Component, RelativePath, RelativePathBuf};
+
+use crate::{AsyncFileStore, FileStore, Metadata};
+
+/// A snapshot of the directory hierarchy of a store.
+///
+/// Stores only list files, so directories are implied by the paths of the files they contain
+/// and there are no empty directories.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tree {
+    pub children: BTreeMap<String, Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Node {
+    Dir(Tree),
+    File(Metadata),
+}
+
+/// Differences between two snapshots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeDiff {
+    pub added: Vec<RelativePathBuf>,
+    pub removed: Vec<RelativePathBuf>,
+    /// Files present in both snapshots with a different size or modification time.
+    pub changed: Vec<RelativePathBuf>,
+}
+
+impl Tree {
+    pub async fn from_store<S>(store: &S) -> io::Result<Tree>
+    where
+        S: AsyncFileStore + Sync,
+    {
+        store
+            .list()
+            .await?
+            .map_ok(|path| async move { store.metadata(&path).await })
+            .try_buffer_unordered(16)
+            .try_fold(Tree::default(), |mut tree, meta| async move {
+                tree.insert(meta);
+                Ok(tree)
+            })
+            .await
+    }
+
+    pub fn from_sync_store<S>(store: &S) -> io::Result<Tree>
+    where
+        S: FileStore,
+    {
+        let mut tree = Tree::default();
+        for path in store.list() {
+            tree.insert(store.metadata(&path?)?);
+        }
+        Ok(tree)
+    }
+
+    /// Add a file, creating the directories leading up to it.
+    pub fn insert(&mut self, meta: Metadata) {
+        let path = meta.path.normalize();
+        let names = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let Some((file, dirs)) = names.split_last() else {
+            return;
+        };
+
+        let mut tree = self;
+        for name in dirs {
+            let node = tree
+                .children
+                .entry(name.to_string())
+                .or_insert_with(|| Node::Dir(Tree::default()));
+            if let Node::File(_) = node {
+                *node = Node::Dir(Tree::default());
+            }
+            let Node::Dir(dir) = node else { unreachable!() };
+            tree = dir;
+        }
+
+        tree.children.insert(file.to_string(), Node::File(meta));
+    }
+
+    pub fn get(&self, path: &RelativePath) -> Option<&Node> {
+        let path = path.normalize();
+        let mut tree = self;
+        let mut found = None;
+
+        for component in path.components() {
+            let Component::Normal(name) = component else {
+                continue;
+            };
+            let node = tree.children.get(name)?;
+            found = Some(node);
+            match node {
+                Node::Dir(dir) => tree = dir,
+                Node::File(_) => tree = &EMPTY,
+            }
+        }
+
+        found
+    }
+
+    /// All files of the tree, depth first in name order.
+    pub fn files(&self) -> impl Iterator<Item = &Metadata> {
+        let mut files = Vec::new();
+        self.collect_files(&mut files);
+        files.into_iter()
+    }
+
+    fn collect_files<'a>(&'a self, files: &mut Vec<&'a Metadata>) {
+        for node in self.children.values() {
+            match node {
+                Node::Dir(dir) => dir.collect_files(files),
+                Node::File(meta) => files.push(meta),
+            }
+        }
+    }
+
+    /// Compare this snapshot against a newer one.
+    pub fn diff(&self, newer: &Tree) -> TreeDiff {
+        let old = self
+            .files()
+            .map(|meta| (meta.path.normalize(), meta))
+            .collect::<BTreeMap<_, _>>();
+        let new = newer
+            .files()
+            .map(|meta| (meta.path.normalize(), meta))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut diff = TreeDiff::default();
+
+        for (path, meta) in &new {
+            match old.get(path) {
+                None => diff.added.push(path.clone()),
+                Some(prev) if prev.size != meta.size || prev.modified != meta.modified => {
+                    diff.changed.push(path.clone())
+                }
+                Some(_) => {}
+            }
+        }
+
+        diff.removed = old
+            .keys()
+            .filter(|path| !new.contains_key(*path))
+            .cloned()
+            .collect();
+
+        diff
+    }
+}
+
+static EMPTY: Tree = Tree {
+    children: BTreeMap::new(),
+};
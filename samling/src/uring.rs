@@ -0,0 +1,266 @@
+use std::{io, path::PathBuf};
+
+use bytes::Bytes;
+use futures::{
+    channel::{mpsc, oneshot},
+    future::LocalBoxFuture,
+    stream::BoxStream,
+    Future, SinkExt, StreamExt, TryStreamExt,
+};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{
+    fs::{FsFileStore, Runtime},
+    util::Config,
+    AsyncFile, AsyncFileBody, AsyncFileInit, AsyncFileStore, Metadata,
+};
+
+type Job = Box<dyn FnOnce() -> LocalBoxFuture<'static, ()> + Send>;
+
+/// Handle to a thread running a tokio-uring runtime.
+///
+/// io_uring futures are tied to the thread that owns the ring, so all operations are shipped to
+/// a dedicated thread and their results sent back. The thread exits once every handle is dropped.
+#[derive(Clone)]
+pub struct Uring {
+    tx: mpsc::UnboundedSender<Job>,
+}
+
+impl Uring {
+    pub fn start() -> io::Result<Uring> {
+        let (tx, mut rx) = mpsc::unbounded::<Job>();
+
+        std::thread::Builder::new()
+            .name("samling-uring".into())
+            .spawn(move || {
+                tokio_uring::start(async move {
+                    while let Some(job) = rx.next().await {
+                        tokio_uring::spawn(job());
+                    }
+                })
+            })?;
+
+        Ok(Uring { tx })
+    }
+
+    fn spawn<F, U>(&self, func: F) -> io::Result<()>
+    where
+        F: FnOnce() -> U + Send + 'static,
+        U: Future<Output = ()> + 'static,
+    {
+        self.tx
+            .unbounded_send(Box::new(move || Box::pin(func())))
+            .map_err(|_| closed())
+    }
+
+    fn run<F, U, T>(&self, func: F) -> impl Future<Output = io::Result<T>> + Send
+    where
+        F: FnOnce() -> U + Send + 'static,
+        U: Future<Output = io::Result<T>> + 'static,
+        T: Send + 'static,
+    {
+        let (ret_tx, ret_rx) = oneshot::channel();
+        let sent = self.spawn(move || async move {
+            let _ = ret_tx.send(func().await);
+        });
+
+        async move {
+            sent?;
+            ret_rx.await.map_err(|_| closed())?
+        }
+    }
+}
+
+impl Runtime for Uring {
+    fn unblock<F, T>(&self, func: F) -> impl Future<Output = io::Result<T>> + Send
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.run(move || async move {
+            tokio::task::spawn_blocking(func)
+                .await
+                .map_err(io::Error::other)?
+        })
+    }
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "uring runtime has shut down")
+}
+
+/// A filesystem store doing its file io through io_uring.
+///
+/// Reads and writes go through the ring; metadata, listing and removal run on the blocking pool
+/// of the ring thread, exactly like `FsFileStore`.
+pub struct UringFileStore {
+    fs: FsFileStore<Uring>,
+    uring: Uring,
+}
+
+impl UringFileStore {
+    pub fn new(path: impl AsRef<std::path::Path>) -> io::Result<UringFileStore> {
+        UringFileStore::with_uring(path, Uring::start()?)
+    }
+
+    /// Create a store sharing the ring thread of `uring`.
+    pub fn with_uring(
+        path: impl AsRef<std::path::Path>,
+        uring: Uring,
+    ) -> io::Result<UringFileStore> {
+        Ok(UringFileStore {
+            fs: FsFileStore::with_runtime(path, uring.clone())?,
+            uring,
+        })
+    }
+}
+
+impl AsyncFileStore for UringFileStore {
+    type File = UringFile;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        self.fs.metadata(path)
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        let full_path = path.to_logical_path(self.fs.root());
+        let uring = self.uring.clone();
+        async move {
+            self.fs.metadata(path).await?;
+            Ok(UringFile {
+                path: full_path,
+                uring,
+            })
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        self.fs.rm_file(path)
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        let full_path = path.to_logical_path(self.fs.root());
+        async move {
+            let len = init.len;
+            let mut stream = match init.body {
+                AsyncFileBody::Path(_) => return self.fs.write_file(path, init).await,
+                body => body.into_stream().await?,
+            };
+
+            self.uring
+                .run(move || async move {
+                    let file = tokio_uring::fs::File::create(&full_path).await?;
+                    if let Some(len) = len {
+                        // Reserve the blocks up front without changing the visible size
+                        file.fallocate(0, len, libc::FALLOC_FL_KEEP_SIZE).await?;
+                    }
+
+                    let mut pos = 0;
+                    while let Some(next) = stream.try_next().await? {
+                        let len = next.len() as u64;
+                        let (ret, _) = file.write_all_at(next, pos).await;
+                        ret?;
+                        pos += len;
+                    }
+
+                    file.close().await
+                })
+                .await
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        self.fs.list()
+    }
+}
+
+pub struct UringFile {
+    path: PathBuf,
+    uring: Uring,
+}
+
+impl AsyncFile for UringFile {
+    type Body = mpsc::Receiver<io::Result<Bytes>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        let path = self.path.clone();
+        self.uring.run(move || async move {
+            let file = tokio_uring::fs::File::open(&path).await?;
+
+            let len = range.end.saturating_sub(range.start) as usize;
+            let mut output = Vec::with_capacity(len);
+            while output.len() < len {
+                let buf = Vec::with_capacity(len - output.len());
+                let (ret, buf) = file.read_at(buf, range.start + output.len() as u64).await;
+                if ret? == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                output.extend_from_slice(&buf);
+            }
+
+            file.close().await?;
+
+            Ok(output.into())
+        })
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        let path = self.path.clone();
+        let chunk_size = Config::current().chunk_size.max(1);
+        let (mut tx, rx) = mpsc::channel(1);
+
+        let spawned = self.uring.spawn(move || async move {
+            let ret = async {
+                let file = tokio_uring::fs::File::open(&path).await?;
+
+                let mut pos = 0;
+                loop {
+                    let (ret, buf) = file.read_at(Vec::with_capacity(chunk_size), pos).await;
+                    let read = ret?;
+                    if read == 0 {
+                        break;
+                    }
+                    pos += read as u64;
+
+                    if tx.send(Ok(Bytes::from(buf))).await.is_err() {
+                        // The reader was dropped
+                        break;
+                    }
+                }
+
+                file.close().await
+            }
+            .await;
+
+            if let Err(err) = ret {
+                let _ = tx.send(Err(err)).await;
+            }
+        });
+
+        async move {
+            spawned?;
+            Ok(rx)
+        }
+    }
+
+    fn native_path(&self) -> Option<&std::path::Path> {
+        Some(&self.path)
+    }
+}
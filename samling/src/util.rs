@@ -1,32 +1,136 @@
-use std::io;
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(any(feature = "kube", feature = "consul", feature = "rclone"))]
+pub(crate) mod base64;
+mod changeset;
+mod copy;
+mod diff;
+#[cfg(feature = "find")]
+mod find;
+#[cfg(feature = "hash")]
+mod hash;
+#[cfg(feature = "archive")]
+mod import;
+pub(crate) mod lru;
+mod mirror;
+mod pool;
+mod progress;
+#[cfg(any(feature = "kube", feature = "rclone"))]
+pub(crate) mod rfc3339;
+#[cfg(feature = "search")]
+mod search;
+mod sync;
+mod usage;
+
+use std::{io, sync::Arc};
 
 use bytes::{Bytes, BytesMut};
-use futures::{pin_mut, TryStreamExt};
+use futures::{pin_mut, Stream, StreamExt, TryStreamExt};
+use relative_path::RelativePath;
 #[cfg(feature = "fs")]
 use tokio_util::io::ReaderStream;
 
-use crate::{AsyncFile, AsyncFileStore, Path};
+use crate::{AsyncFile, AsyncFileInit, AsyncFileStore, AsyncPath};
+
+#[cfg(feature = "archive")]
+pub use self::archive::{export_tar, export_tar_with_progress, export_zip};
+pub use self::changeset::{changeset, Changeset, ChangesetEntry};
+pub use self::copy::{copy_all, CopyFilter, CopyOptions, CopyReport};
+pub use self::diff::{diff, diff_stream, DiffCompare, DiffEntry, DiffOptions, DiffReport};
+#[cfg(feature = "find")]
+pub use self::find::{find, FindOptions};
+#[cfg(feature = "hash")]
+use self::hash::StreamHasher;
+#[cfg(feature = "hash")]
+pub use self::hash::{hash_bytes, hash_contents, hash_file, hash_store, Digest, HashAlgorithm};
+#[cfg(feature = "archive")]
+pub use self::import::{
+    import_archive, import_archive_with_progress, import_tar, import_zip, ImportEntry,
+};
+pub use self::mirror::{mirror, MirrorAction, MirrorOptions, MirrorReport};
+pub(crate) use self::pool::Buffer;
+pub use self::pool::Config;
+pub use self::progress::{Progress, ProgressCounter, ProgressState};
+#[cfg(feature = "search")]
+pub use self::search::{search, SearchMatch, SearchOptions};
+pub use self::sync::{sync, SyncCompare, SyncOptions, SyncReport};
+pub use self::usage::{usage, Usage};
 
-pub async fn copy<S, T>(source: Path<S>, target: Path<T>) -> io::Result<()>
+/// Copy a file between stores, streaming it so only a chunk at a time is held in memory.
+pub async fn copy<S, T>(source: AsyncPath<S>, target: AsyncPath<T>) -> io::Result<()>
 where
     S: AsyncFileStore,
+    <S::File as AsyncFile>::Body: Send + 'static,
+    T: AsyncFileStore,
+{
+    copy_chunked(source, target, None).await
+}
+
+/// Like [`copy`], but regroups the source into chunks of `chunk_size` bytes before they reach
+/// the target. Useful when the source yields many tiny chunks, or the target prefers
+/// writes of a particular size.
+pub async fn copy_chunked<S, T>(
+    source: AsyncPath<S>,
+    target: AsyncPath<T>,
+    chunk_size: Option<usize>,
+) -> io::Result<()>
+where
+    S: AsyncFileStore,
+    <S::File as AsyncFile>::Body: Send + 'static,
     T: AsyncFileStore,
 {
     let file = source.open().await?;
     let meta = source.metadata().await?;
 
+    #[cfg(feature = "fs")]
+    if let Some(path) = file.native_path() {
+        let init = AsyncFileInit::from(path.to_path_buf())
+            .with_len(meta.size)
+            .with_mime(meta.mime);
+        return target.write(init).await;
+    }
+
     let reader = file.reader().await?;
-    pin_mut!(reader);
+    let init = match chunk_size {
+        Some(size) => AsyncFileInit::stream(rechunk(reader, size)),
+        None => AsyncFileInit::stream(reader),
+    };
 
-    let mut output = BytesMut::with_capacity(meta.size as usize);
+    target
+        .write(init.with_len(meta.size).with_mime(meta.mime))
+        .await
+}
 
-    while let Some(next) = reader.try_next().await? {
-        output.extend(next);
-    }
+/// Regroup a body into chunks of exactly `size` bytes, except for the last one.
+pub fn rechunk<S>(body: S, size: usize) -> impl Stream<Item = io::Result<Bytes>>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    let size = size.max(1);
+    async_stream::try_stream! {
+        pin_mut!(body);
+        let mut pending = BytesMut::new();
 
-    target.write(output.freeze()).await?;
+        while let Some(mut next) = body.try_next().await? {
+            if pending.is_empty() {
+                // Pass whole chunks through without copying them
+                while next.len() >= size {
+                    yield next.split_to(size);
+                }
+                pending.extend_from_slice(&next);
+                continue;
+            }
 
-    Ok(())
+            pending.extend_from_slice(&next);
+            while pending.len() >= size {
+                yield pending.split_to(size).freeze();
+            }
+        }
+
+        if !pending.is_empty() {
+            yield pending.freeze();
+        }
+    }
 }
 
 #[cfg(feature = "fs")]
@@ -36,14 +140,178 @@ pub async fn file_stream(path: &std::path::Path) -> io::Result<ReaderStream<toki
     Ok(ReaderStream::new(file))
 }
 
+/// Slice `range` out of an in-memory file, failing if it reaches past the end.
+pub(crate) fn slice(data: &Bytes, range: std::ops::Range<u64>) -> io::Result<Bytes> {
+    if range.start > range.end || range.end > data.len() as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "range out of bounds",
+        ));
+    }
+
+    Ok(data.slice(range.start as usize..range.end as usize))
+}
+
+/// Stream `range` of a file as a series of `read_range` calls of at most `Config::chunk_size`
+/// bytes, so large ranges are never held in memory at once.
+pub fn read_range_stream<F>(
+    file: F,
+    range: std::ops::Range<u64>,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static
+where
+    F: AsyncFile + Send + Sync + 'static,
+{
+    async_stream::try_stream! {
+        let chunks = range_chunks(&file, range);
+        pin_mut!(chunks);
+        while let Some(chunk) = chunks.try_next().await? {
+            yield chunk;
+        }
+    }
+}
+
+pub(crate) fn range_chunks<F>(
+    file: &F,
+    range: std::ops::Range<u64>,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + '_
+where
+    F: AsyncFile + Sync,
+{
+    let chunk_size = Config::current().chunk_size.max(1) as u64;
+    async_stream::try_stream! {
+        let mut pos = range.start;
+        while pos < range.end {
+            let end = range.end.min(pos + chunk_size);
+            let chunk = file.read_range(pos..end).await?;
+            pos = end;
+            yield chunk;
+        }
+    }
+}
+
 pub async fn read<T: AsyncFile>(file: &mut T) -> io::Result<Bytes> {
-    let mut output = BytesMut::new();
-    let reader = file.reader().await?;
-    pin_mut!(reader);
+    collect(file.reader().await?, 0).await
+}
+
+/// Collect a body into memory, preallocating `size_hint` bytes.
+pub(crate) async fn collect<S>(body: S, size_hint: u64) -> io::Result<Bytes>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    pin_mut!(body);
 
-    while let Some(next) = reader.try_next().await? {
-        output.extend(next);
+    let mut output = BytesMut::with_capacity(size_hint as usize);
+    while let Some(next) = body.try_next().await? {
+        output.extend_from_slice(&next);
     }
 
     Ok(output.freeze())
 }
+
+/// What to do besides copying the bytes of a file in [`transfer`].
+#[derive(Default, Clone, Copy)]
+pub(crate) struct Transfer<'a> {
+    pub progress: Option<&'a Arc<dyn Progress>>,
+    /// Hash the file while it is written and check the written copy against it.
+    #[cfg(feature = "hash")]
+    pub verify: Option<HashAlgorithm>,
+}
+
+impl Transfer<'_> {
+    #[cfg(feature = "fs")]
+    fn verifying(&self) -> bool {
+        #[cfg(feature = "hash")]
+        return self.verify.is_some();
+        #[cfg(not(feature = "hash"))]
+        return false;
+    }
+}
+
+/// Stream a file from one store into another without buffering it.
+pub(crate) async fn transfer<S, T>(
+    source: &S,
+    target: &T,
+    from: &RelativePath,
+    to: &RelativePath,
+    options: Transfer<'_>,
+) -> io::Result<()>
+where
+    S: AsyncFileStore,
+    <S::File as AsyncFile>::Body: Send + 'static,
+    T: AsyncFileStore,
+{
+    let ret = transfer_file(source, target, from, to, options).await;
+    if let Some(progress) = options.progress {
+        progress.finished(from, ret.as_ref().map(|_| ()));
+    }
+    ret
+}
+
+async fn transfer_file<S, T>(
+    source: &S,
+    target: &T,
+    from: &RelativePath,
+    to: &RelativePath,
+    options: Transfer<'_>,
+) -> io::Result<()>
+where
+    S: AsyncFileStore,
+    <S::File as AsyncFile>::Body: Send + 'static,
+    T: AsyncFileStore,
+{
+    let meta = source.metadata(from).await?;
+    let file = source.open_file(from).await?;
+
+    if let Some(progress) = options.progress {
+        progress.started(from, Some(meta.size));
+    }
+
+    // Verifying needs to see the bytes, so the native fast path is only taken without it
+    #[cfg(feature = "fs")]
+    if let Some(path) = file.native_path().filter(|_| !options.verifying()) {
+        let init = AsyncFileInit::from(path.to_path_buf())
+            .with_len(meta.size)
+            .with_mime(meta.mime);
+        target.write_file(to, init).await?;
+        if let Some(progress) = options.progress {
+            progress.bytes(from, meta.size);
+        }
+        return Ok(());
+    }
+
+    let mut reader = file.reader().await?.boxed();
+
+    if let Some(progress) = options.progress {
+        let progress = progress.clone();
+        let path = from.to_relative_path_buf();
+        reader = reader
+            .inspect_ok(move |chunk| progress.bytes(&path, chunk.len() as u64))
+            .boxed();
+    }
+
+    #[cfg(feature = "hash")]
+    let hasher = options.verify.map(StreamHasher::new);
+    #[cfg(feature = "hash")]
+    if let Some(hasher) = hasher.clone() {
+        reader = reader.inspect_ok(move |chunk| hasher.update(chunk)).boxed();
+    }
+
+    let init = AsyncFileInit::stream(reader)
+        .with_len(meta.size)
+        .with_mime(meta.mime);
+    target.write_file(to, init).await?;
+
+    #[cfg(feature = "hash")]
+    if let Some(hasher) = hasher {
+        let (digest, size) = hasher.finish();
+        if size != meta.size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{from} was {size} bytes while read, expected {}", meta.size),
+            ));
+        }
+        hash::verify_file(target, to, &digest, size).await?;
+    }
+
+    Ok(())
+}
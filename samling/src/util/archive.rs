@@ -0,0 +1,411 @@
+use std::{
+    collections::HashMap,
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::{io::AsyncWrite, pin_mut, AsyncWriteExt, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use super::Progress;
+use crate::{AsyncFile, AsyncFileStore, Link};
+
+const BLOCK: usize = 512;
+
+/// Stream every file in `store` into a tar archive.
+///
+/// Files are written one chunk at a time, so nothing is buffered beyond a single chunk. Where
+/// the store [`supports_links`](AsyncFileStore::supports_links), symlinks are archived as
+/// links and further names of a hard linked file as hard links to the first one.
+pub async fn export_tar<S, W>(store: &S, writer: W) -> io::Result<()>
+where
+    S: AsyncFileStore,
+    W: AsyncWrite + Unpin,
+{
+    export_tar_with_progress(store, writer, &()).await
+}
+
+/// Like [`export_tar`], reporting every file to `progress` as it is archived.
+///
+/// Any error aborts the export, so only the file it happened on is finished with an error.
+pub async fn export_tar_with_progress<S, W>(
+    store: &S,
+    mut writer: W,
+    progress: &dyn Progress,
+) -> io::Result<()>
+where
+    S: AsyncFileStore,
+    W: AsyncWrite + Unpin,
+{
+    let mut list = store.list().await?;
+    let mut hard_links = HashMap::new();
+
+    while let Some(path) = list.try_next().await? {
+        let ret = match tar_link(store, &path, &mut hard_links).await {
+            Ok(Some((kind, target))) => {
+                export_tar_link(store, &mut writer, &path, kind, &target).await
+            }
+            Ok(None) => export_tar_entry(store, &mut writer, &path, progress).await,
+            Err(err) => Err(err),
+        };
+        progress.finished(&path, ret.as_ref().map(|_| ()));
+        ret?;
+    }
+
+    writer.write_all(&[0; BLOCK * 2]).await?;
+    writer.flush().await
+}
+
+async fn export_tar_entry<S, W>(
+    store: &S,
+    writer: &mut W,
+    path: &RelativePath,
+    progress: &dyn Progress,
+) -> io::Result<()>
+where
+    S: AsyncFileStore,
+    W: AsyncWrite + Unpin,
+{
+    let meta = store.metadata(path).await?;
+    let file = store.open_file(path).await?;
+    progress.started(path, Some(meta.size));
+
+    for header in tar_headers(path, meta.size, meta.modified, None)? {
+        writer.write_all(&header).await?;
+    }
+
+    let reader = file.reader().await?;
+    pin_mut!(reader);
+
+    let mut written = 0u64;
+    while let Some(next) = reader.try_next().await? {
+        written += next.len() as u64;
+        if written > meta.size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{path} grew while being archived"),
+            ));
+        }
+        writer.write_all(&next).await?;
+        progress.bytes(path, next.len() as u64);
+    }
+
+    if written != meta.size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("{path} shrank while being archived"),
+        ));
+    }
+
+    writer.write_all(&[0; BLOCK][..padding(written)]).await?;
+
+    Ok(())
+}
+
+/// The tar entry type and link name to archive `path` as, if it is a link. The first name
+/// of a hard linked file is archived as a regular file.
+async fn tar_link<S>(
+    store: &S,
+    path: &RelativePath,
+    hard_links: &mut HashMap<u64, RelativePathBuf>,
+) -> io::Result<Option<(u8, RelativePathBuf)>>
+where
+    S: AsyncFileStore,
+{
+    if !store.supports_links() {
+        return Ok(None);
+    }
+
+    Ok(match store.read_link(path).await? {
+        Some(Link::Symlink(target)) => Some((b'2', target)),
+        Some(Link::Hard(id)) => match hard_links.get(&id) {
+            Some(first) => Some((b'1', first.clone())),
+            None => {
+                hard_links.insert(id, path.to_relative_path_buf());
+                None
+            }
+        },
+        None => None,
+    })
+}
+
+async fn export_tar_link<S, W>(
+    store: &S,
+    writer: &mut W,
+    path: &RelativePath,
+    kind: u8,
+    target: &RelativePath,
+) -> io::Result<()>
+where
+    S: AsyncFileStore,
+    W: AsyncWrite + Unpin,
+{
+    // The target of a symlink may be missing, which is no reason to fail the export
+    let modified = store
+        .metadata(path)
+        .await
+        .ok()
+        .and_then(|meta| meta.modified);
+
+    for header in tar_headers(path, 0, modified, Some((kind, target)))? {
+        writer.write_all(&header).await?;
+    }
+
+    Ok(())
+}
+
+/// Stream every file in `store` into a zip archive.
+///
+/// Entries are stored without compression and use data descriptors, so the archive can be
+/// written in a single pass. Archives requiring zip64 (more than 65535 entries or 4GiB) are
+/// not supported.
+pub async fn export_zip<S, W>(store: &S, mut writer: W) -> io::Result<()>
+where
+    S: AsyncFileStore,
+    W: AsyncWrite + Unpin,
+{
+    let mut list = store.list().await?;
+    let mut central = Vec::new();
+    let mut offset = 0u64;
+    let mut entries = 0u64;
+
+    while let Some(path) = list.try_next().await? {
+        let meta = store.metadata(&path).await?;
+        let file = store.open_file(&path).await?;
+
+        let name = path.as_str().as_bytes();
+        let (time, date) = dos_datetime(meta.modified);
+        let header_offset = zip32(offset)?;
+
+        let mut local = Vec::with_capacity(30 + name.len());
+        local.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        local.extend_from_slice(&20u16.to_le_bytes());
+        local.extend_from_slice(&ZIP_FLAGS.to_le_bytes());
+        local.extend_from_slice(&0u16.to_le_bytes());
+        local.extend_from_slice(&time.to_le_bytes());
+        local.extend_from_slice(&date.to_le_bytes());
+        local.extend_from_slice(&[0; 12]);
+        local.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        local.extend_from_slice(&0u16.to_le_bytes());
+        local.extend_from_slice(name);
+        writer.write_all(&local).await?;
+
+        let reader = file.reader().await?;
+        pin_mut!(reader);
+
+        let mut hasher = crc32fast::Hasher::new();
+        let mut size = 0u64;
+        while let Some(next) = reader.try_next().await? {
+            hasher.update(&next);
+            size += next.len() as u64;
+            writer.write_all(&next).await?;
+        }
+
+        let crc = hasher.finalize();
+        let size32 = zip32(size)?;
+
+        let mut descriptor = Vec::with_capacity(16);
+        descriptor.extend_from_slice(&0x08074b50u32.to_le_bytes());
+        descriptor.extend_from_slice(&crc.to_le_bytes());
+        descriptor.extend_from_slice(&size32.to_le_bytes());
+        descriptor.extend_from_slice(&size32.to_le_bytes());
+        writer.write_all(&descriptor).await?;
+
+        central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes());
+        central.extend_from_slice(&ZIP_FLAGS.to_le_bytes());
+        central.extend_from_slice(&0u16.to_le_bytes());
+        central.extend_from_slice(&time.to_le_bytes());
+        central.extend_from_slice(&date.to_le_bytes());
+        central.extend_from_slice(&crc.to_le_bytes());
+        central.extend_from_slice(&size32.to_le_bytes());
+        central.extend_from_slice(&size32.to_le_bytes());
+        central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        central.extend_from_slice(&[0; 12]);
+        central.extend_from_slice(&header_offset.to_le_bytes());
+        central.extend_from_slice(name);
+
+        offset += (local.len() + descriptor.len()) as u64 + size;
+        entries += 1;
+    }
+
+    if entries > u16::MAX as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "too many entries for a zip archive without zip64",
+        ));
+    }
+
+    let mut end = Vec::with_capacity(22);
+    end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    end.extend_from_slice(&[0; 4]);
+    end.extend_from_slice(&(entries as u16).to_le_bytes());
+    end.extend_from_slice(&(entries as u16).to_le_bytes());
+    end.extend_from_slice(&zip32(central.len() as u64)?.to_le_bytes());
+    end.extend_from_slice(&zip32(offset)?.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes());
+
+    writer.write_all(&central).await?;
+    writer.write_all(&end).await?;
+    writer.flush().await
+}
+
+// Data descriptor + utf8 file names
+const ZIP_FLAGS: u16 = 0x0808;
+
+fn zip32(value: u64) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "archive exceeds 4GiB, zip64 is not supported",
+        )
+    })
+}
+
+fn padding(size: u64) -> usize {
+    (BLOCK - (size % BLOCK as u64) as usize) % BLOCK
+}
+
+/// The headers of an entry. Links have the entry type and link name in `link`.
+fn tar_headers(
+    path: &RelativePath,
+    size: u64,
+    modified: Option<SystemTime>,
+    link: Option<(u8, &RelativePath)>,
+) -> io::Result<Vec<[u8; BLOCK]>> {
+    let name = path.as_str().as_bytes();
+    let mtime = modified
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|m| m.as_secs())
+        .unwrap_or_default();
+
+    let mut headers = Vec::with_capacity(3);
+
+    let mut header = [0u8; BLOCK];
+    match split_ustar(name) {
+        Some((prefix, name)) => {
+            header[..name.len()].copy_from_slice(name);
+            header[345..345 + prefix.len()].copy_from_slice(prefix);
+        }
+        None => {
+            headers.extend(gnu_long_name(b'L', name)?);
+            header[..100].copy_from_slice(&name[..100]);
+        }
+    }
+
+    let (kind, size) = match link {
+        Some((kind, target)) => {
+            let target = target.as_str().as_bytes();
+            if target.len() > 100 {
+                headers.extend(gnu_long_name(b'K', target)?);
+            }
+            let len = target.len().min(100);
+            header[157..157 + len].copy_from_slice(&target[..len]);
+            (kind, 0)
+        }
+        None => (b'0', size),
+    };
+
+    tar_fields(&mut header, size, mtime, kind)?;
+    headers.push(header);
+
+    Ok(headers)
+}
+
+/// Gnu long name extension, the name follows as the body of a pseudo entry of type `kind`,
+/// `L` for entry names and `K` for link names.
+fn gnu_long_name(kind: u8, name: &[u8]) -> io::Result<Vec<[u8; BLOCK]>> {
+    let mut blocks = Vec::with_capacity(name.len() / BLOCK + 2);
+
+    let mut long = [0u8; BLOCK];
+    long[..13].copy_from_slice(b"././@LongLink");
+    tar_fields(&mut long, name.len() as u64 + 1, 0, kind)?;
+    blocks.push(long);
+
+    for chunk in name.chunks(BLOCK) {
+        let mut block = [0u8; BLOCK];
+        block[..chunk.len()].copy_from_slice(chunk);
+        blocks.push(block);
+    }
+    if name.len().is_multiple_of(BLOCK) {
+        blocks.push([0u8; BLOCK]);
+    }
+
+    Ok(blocks)
+}
+
+fn tar_fields(header: &mut [u8; BLOCK], size: u64, mtime: u64, kind: u8) -> io::Result<()> {
+    if size >= 0o77777777777 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "file too large for a tar archive",
+        ));
+    }
+
+    octal(&mut header[100..108], 0o644);
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    octal(&mut header[124..136], size);
+    octal(&mut header[136..148], mtime.min(0o77777777777));
+    header[156] = kind;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    header[148..156].fill(b' ');
+    let sum = header.iter().map(|m| *m as u32).sum::<u32>();
+    octal(&mut header[148..155], sum as u64);
+
+    Ok(())
+}
+
+fn octal(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    let digits = format!("{value:0width$o}");
+    field[..width].copy_from_slice(&digits.as_bytes()[digits.len() - width..]);
+    field[width] = 0;
+}
+
+fn split_ustar(name: &[u8]) -> Option<(&[u8], &[u8])> {
+    if name.len() <= 100 {
+        return Some((&name[..0], name));
+    }
+
+    name.iter()
+        .enumerate()
+        .filter(|(_, c)| **c == b'/')
+        .map(|(idx, _)| (&name[..idx], &name[idx + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && name.len() <= 100 && !name.is_empty())
+}
+
+fn dos_datetime(time: Option<SystemTime>) -> (u16, u16) {
+    let Some(secs) = time
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|m| m.as_secs())
+    else {
+        return (0, (1 << 5) | 1);
+    };
+
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+
+    // Civil date from days since the unix epoch
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    if !(1980..=2107).contains(&year) {
+        return (0, (1 << 5) | 1);
+    }
+
+    let time = ((rem / 3600) << 11) | (((rem % 3600) / 60) << 5) | ((rem % 60) / 2);
+    let date = ((year - 1980) << 9) | (month << 5) | day;
+
+    (time as u16, date as u16)
+}
@@ -0,0 +1,45 @@
+//! Standard base64 with padding, as used by the json apis of remote backends.
+use std::io;
+
+use bytes::{Bytes, BytesMut};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn encode(data: &[u8]) -> String {
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(BASE64[(bits >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+#[cfg_attr(not(feature = "kube"), allow(dead_code))]
+pub(crate) fn decode(data: &str) -> io::Result<Bytes> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid base64");
+
+    let data = data.trim_end_matches('=').as_bytes();
+    let mut output = BytesMut::with_capacity(data.len() * 3 / 4);
+    for chunk in data.chunks(4) {
+        if chunk.len() == 1 {
+            return Err(invalid());
+        }
+        let mut bits = 0u32;
+        for (i, &byte) in chunk.iter().enumerate() {
+            let value = BASE64.iter().position(|&c| c == byte).ok_or_else(invalid)?;
+            bits |= (value as u32) << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            output.extend_from_slice(&[(bits >> (16 - 8 * i)) as u8]);
+        }
+    }
+    Ok(output.freeze())
+}
@@ -0,0 +1,217 @@
+use std::io;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::{StreamExt, TryStreamExt};
+use mime::Mime;
+use relative_path::{Component, RelativePathBuf};
+
+use super::{diff, DiffOptions, DiffReport};
+use crate::{AsyncFile, AsyncFileInit, AsyncFileStore};
+
+const MAGIC: &[u8; 8] = b"SMLCSET1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum ChangesetEntry {
+    /// Write a file, replacing it if it exists.
+    Put {
+        path: RelativePathBuf,
+        #[cfg_attr(feature = "serde", serde(with = "crate::file::mime_serde"))]
+        mime: Mime,
+        data: Bytes,
+    },
+    /// Remove a file.
+    Remove(RelativePathBuf),
+}
+
+/// The writes and removals turning one store into another, with the contents of every
+/// written file, so it can be shipped on its own and applied elsewhere.
+///
+/// ```ignore
+/// let changeset = util::changeset(&deployed, &build, DiffOptions::default()).await?;
+/// upload(changeset.encode()).await?;
+/// // elsewhere
+/// Changeset::decode(download().await?)?.apply(&live).await?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Changeset {
+    pub entries: Vec<ChangesetEntry>,
+}
+
+/// Diff `a` against `b` and collect the changes turning `a` into `b`.
+pub async fn changeset<A, B>(a: &A, b: &B, options: DiffOptions) -> io::Result<Changeset>
+where
+    A: AsyncFileStore,
+    <A::File as AsyncFile>::Body: Send + 'static,
+    B: AsyncFileStore,
+    <B::File as AsyncFile>::Body: Send + 'static,
+{
+    let concurrency = options.concurrency;
+    let report = diff(a, b, options).await?;
+    Changeset::from_report(&report, b, concurrency).await
+}
+
+impl Changeset {
+    /// Collect the changes of a diff, reading the added and changed files from `source`, the
+    /// `b` side of the diff.
+    pub async fn from_report<S>(
+        report: &DiffReport,
+        source: &S,
+        concurrency: usize,
+    ) -> io::Result<Changeset>
+    where
+        S: AsyncFileStore,
+    {
+        let mut entries = futures::stream::iter(report.added.iter().chain(&report.changed))
+            .map(|path| async move {
+                let meta = source.metadata(path).await?;
+                let file = source.open_file(path).await?;
+                let data = super::collect(file.reader().await?, meta.size).await?;
+                io::Result::Ok(ChangesetEntry::Put {
+                    path: path.normalize(),
+                    mime: meta.mime,
+                    data,
+                })
+            })
+            .buffered(concurrency.max(1))
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        entries.extend(
+            report
+                .removed
+                .iter()
+                .map(|path| ChangesetEntry::Remove(path.normalize())),
+        );
+
+        Ok(Changeset { entries })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Apply the changes to `target`. Writes happen before removals, and files which are
+    /// already gone are not an error.
+    pub async fn apply<T>(&self, target: &T) -> io::Result<()>
+    where
+        T: AsyncFileStore,
+    {
+        for entry in &self.entries {
+            if let ChangesetEntry::Put { path, mime, data } = entry {
+                let init = AsyncFileInit::from(data.clone())
+                    .with_len(data.len() as u64)
+                    .with_mime(mime.clone());
+                target.write_file(path, init).await?;
+            }
+        }
+
+        for entry in &self.entries {
+            if let ChangesetEntry::Remove(path) = entry {
+                match target.rm_file(path).await {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serialize into a self contained binary form.
+    pub fn encode(&self) -> Bytes {
+        let mut output = BytesMut::new();
+        output.put_slice(MAGIC);
+        output.put_u64_le(self.entries.len() as u64);
+
+        for entry in &self.entries {
+            match entry {
+                ChangesetEntry::Put { path, mime, data } => {
+                    output.put_u8(0);
+                    put_str(&mut output, path.as_str());
+                    put_str(&mut output, mime.as_ref());
+                    output.put_u64_le(data.len() as u64);
+                    output.put_slice(data);
+                }
+                ChangesetEntry::Remove(path) => {
+                    output.put_u8(1);
+                    put_str(&mut output, path.as_str());
+                }
+            }
+        }
+
+        output.freeze()
+    }
+
+    /// Deserialize a changeset produced by [`Changeset::encode`].
+    pub fn decode(mut input: Bytes) -> io::Result<Changeset> {
+        if take(&mut input, MAGIC.len())?.as_ref() != MAGIC {
+            return Err(invalid("not a changeset"));
+        }
+
+        let count = take(&mut input, 8)?.get_u64_le();
+        let mut entries = Vec::new();
+
+        for _ in 0..count {
+            let entry = match take(&mut input, 1)?.get_u8() {
+                0 => {
+                    let path = get_path(&mut input)?;
+                    let mime = get_str(&mut input)?
+                        .parse()
+                        .map_err(|_| invalid("invalid mime type"))?;
+                    let len = take(&mut input, 8)?.get_u64_le();
+                    let len = usize::try_from(len).map_err(|_| invalid("truncated changeset"))?;
+                    ChangesetEntry::Put {
+                        path,
+                        mime,
+                        data: take(&mut input, len)?,
+                    }
+                }
+                1 => ChangesetEntry::Remove(get_path(&mut input)?),
+                _ => return Err(invalid("unknown entry")),
+            };
+            entries.push(entry);
+        }
+
+        Ok(Changeset { entries })
+    }
+}
+
+fn put_str(output: &mut BytesMut, value: &str) {
+    output.put_u32_le(value.len() as u32);
+    output.put_slice(value.as_bytes());
+}
+
+fn get_str(input: &mut Bytes) -> io::Result<String> {
+    let len = take(input, 4)?.get_u32_le() as usize;
+    String::from_utf8(take(input, len)?.to_vec()).map_err(|_| invalid("invalid utf-8"))
+}
+
+/// A path which stays inside the store it is applied to, as changesets may come from
+/// anywhere.
+fn get_path(input: &mut Bytes) -> io::Result<RelativePathBuf> {
+    let path = RelativePathBuf::from(get_str(input)?);
+    let mut components = path.components().peekable();
+    if components.peek().is_none()
+        || !components.all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(invalid("invalid path"));
+    }
+    Ok(path)
+}
+
+fn take(input: &mut Bytes, len: usize) -> io::Result<Bytes> {
+    if input.remaining() < len {
+        return Err(invalid("truncated changeset"));
+    }
+    Ok(input.split_to(len))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
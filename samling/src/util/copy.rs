@@ -0,0 +1,187 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    sync::{Arc, Mutex},
+};
+
+use futures::{pin_mut, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+#[cfg(feature = "hash")]
+use super::HashAlgorithm;
+use super::{Progress, Transfer};
+use crate::{AsyncFile, AsyncFileStore, Link};
+
+pub type CopyFilter = Arc<dyn Fn(&RelativePath) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+pub struct CopyOptions {
+    /// Maximum number of files copied at once.
+    pub concurrency: usize,
+    /// Replace files which already exist in the target.
+    pub overwrite: bool,
+    /// Only copy paths for which the filter returns `true`.
+    pub filter: Option<CopyFilter>,
+    /// Told about every file as it is copied or skipped.
+    pub progress: Option<Arc<dyn Progress>>,
+    /// Recreate symlinks and hard links in the target instead of copying what they point at,
+    /// when both stores [`support links`](AsyncFileStore::supports_links). Hard links are
+    /// only kept between files which are copied.
+    pub links: bool,
+    /// Hash every file while it is copied, and re-read the copy to check it against the hash.
+    /// A mismatch fails the file with `InvalidData`.
+    #[cfg(feature = "hash")]
+    pub verify: Option<HashAlgorithm>,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        CopyOptions {
+            concurrency: 16,
+            overwrite: true,
+            filter: None,
+            progress: None,
+            links: false,
+            #[cfg(feature = "hash")]
+            verify: None,
+        }
+    }
+}
+
+impl fmt::Debug for CopyOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("CopyOptions");
+        debug
+            .field("concurrency", &self.concurrency)
+            .field("overwrite", &self.overwrite)
+            .field("filter", &self.filter.is_some())
+            .field("progress", &self.progress.is_some())
+            .field("links", &self.links);
+        #[cfg(feature = "hash")]
+        debug.field("verify", &self.verify);
+        debug.finish()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CopyReport {
+    pub copied: Vec<RelativePathBuf>,
+    /// Links recreated in the target, see [`CopyOptions::links`].
+    pub linked: Vec<RelativePathBuf>,
+    pub skipped: Vec<RelativePathBuf>,
+    pub failed: Vec<(RelativePathBuf, io::Error)>,
+}
+
+enum Outcome {
+    Copied,
+    Skipped,
+    Link(Link),
+}
+
+/// Copy every file of `source` into `target`.
+///
+/// The listing is consumed as it is produced and each file is streamed, with at most
+/// `concurrency` files in flight. Errors for individual files are collected in the report.
+/// Links are recreated once every file is copied, so hard links find the file they name.
+pub async fn copy_all<S, T>(source: &S, target: &T, options: CopyOptions) -> io::Result<CopyReport>
+where
+    S: AsyncFileStore,
+    <S::File as AsyncFile>::Body: Send + 'static,
+    T: AsyncFileStore + Sync,
+{
+    let overwrite = options.overwrite;
+    let links = options.links && source.supports_links() && target.supports_links();
+    let hard_links = Mutex::new(HashMap::new());
+    let hard_links = &hard_links;
+    let filter = options.filter.as_ref();
+    let progress = options.progress.as_ref();
+    let transfer = Transfer {
+        progress,
+        #[cfg(feature = "hash")]
+        verify: options.verify,
+    };
+
+    let results = source
+        .list()
+        .await?
+        .try_filter(|path| {
+            let keep = filter.map(|filter| filter(path)).unwrap_or(true);
+            async move { keep }
+        })
+        .map_ok(|path| async move {
+            if !overwrite && target.exists(&path).await {
+                if let Some(progress) = progress {
+                    progress.skipped(&path);
+                }
+                return io::Result::Ok((path, Ok(Outcome::Skipped)));
+            }
+            if links {
+                match source.read_link(&path).await {
+                    Ok(Some(Link::Hard(id))) => {
+                        let mut hard_links = hard_links.lock().unwrap();
+                        if hard_links.contains_key(&id) {
+                            return Ok((path, Ok(Outcome::Link(Link::Hard(id)))));
+                        }
+                        hard_links.insert(id, path.clone());
+                    }
+                    Ok(Some(link)) => return Ok((path, Ok(Outcome::Link(link)))),
+                    Ok(None) => {}
+                    Err(err) => return Ok((path, Err(err))),
+                }
+            }
+            let ret = super::transfer(source, target, &path, &path, transfer).await;
+            Ok((path, ret.map(|_| Outcome::Copied)))
+        })
+        .try_buffer_unordered(options.concurrency.max(1));
+    pin_mut!(results);
+
+    let mut report = CopyReport::default();
+    let mut deferred = Vec::new();
+
+    while let Some((path, ret)) = results.try_next().await? {
+        match ret {
+            Ok(Outcome::Copied) => report.copied.push(path),
+            Ok(Outcome::Skipped) => report.skipped.push(path),
+            Ok(Outcome::Link(link)) => deferred.push((path, link)),
+            Err(err) => report.failed.push((path, err)),
+        }
+    }
+
+    let hard_links = hard_links.lock().unwrap().clone();
+    for (path, link) in deferred {
+        let ret = relink(target, &path, &link, &hard_links).await;
+        if let Some(progress) = progress {
+            progress.finished(&path, ret.as_ref().map(|_| ()));
+        }
+        match ret {
+            Ok(()) => report.linked.push(path),
+            Err(err) => report.failed.push((path, err)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Recreate `link` at `path` in `target`, replacing what is there.
+async fn relink<T>(
+    target: &T,
+    path: &RelativePath,
+    link: &Link,
+    hard_links: &HashMap<u64, RelativePathBuf>,
+) -> io::Result<()>
+where
+    T: AsyncFileStore,
+{
+    match target.rm_file(path).await {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+
+    match link {
+        Link::Symlink(to) => target.symlink(to, path).await,
+        Link::Hard(id) => match hard_links.get(id) {
+            Some(first) => target.hard_link(first, path).await,
+            None => Err(io::ErrorKind::NotFound.into()),
+        },
+    }
+}
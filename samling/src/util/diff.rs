@@ -0,0 +1,174 @@
+use std::{collections::HashSet, io};
+
+use async_stream::try_stream;
+use futures::{pin_mut, Stream, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use super::sync::same_content;
+use crate::{AsyncFile, AsyncFileStore};
+
+/// How a file present in both stores is determined to be changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum DiffCompare {
+    /// Changed when the sizes differ.
+    Size,
+    /// Changed when the sizes or modification times differ.
+    /// Falls back to `Size` when a store does not report modification times.
+    #[default]
+    SizeAndModified,
+    /// Changed when the contents differ. Reads both files.
+    Content,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    pub compare: DiffCompare,
+    /// Maximum number of files compared at once.
+    pub concurrency: usize,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            compare: DiffCompare::default(),
+            concurrency: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum DiffEntry {
+    /// Present in `b` but not in `a`.
+    Added(RelativePathBuf),
+    /// Present in `a` but not in `b`.
+    Removed(RelativePathBuf),
+    Changed(RelativePathBuf),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiffReport {
+    pub added: Vec<RelativePathBuf>,
+    pub removed: Vec<RelativePathBuf>,
+    pub changed: Vec<RelativePathBuf>,
+    pub unchanged: usize,
+}
+
+impl DiffReport {
+    /// Whether both stores hold the same files.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare the files of `a` against `b`.
+pub async fn diff<A, B>(a: &A, b: &B, options: DiffOptions) -> io::Result<DiffReport>
+where
+    A: AsyncFileStore,
+    <A::File as AsyncFile>::Body: Send + 'static,
+    B: AsyncFileStore,
+    <B::File as AsyncFile>::Body: Send + 'static,
+{
+    let entries = diff_stream(a, b, options);
+    pin_mut!(entries);
+
+    let mut report = DiffReport::default();
+    while let Some(entry) = entries.try_next().await? {
+        match entry {
+            Some(DiffEntry::Added(path)) => report.added.push(path),
+            Some(DiffEntry::Removed(path)) => report.removed.push(path),
+            Some(DiffEntry::Changed(path)) => report.changed.push(path),
+            None => report.unchanged += 1,
+        }
+    }
+
+    Ok(report)
+}
+
+/// Compare the files of `a` against `b`, yielding differences as they are found.
+///
+/// Only the listing of `a` is held in memory; `b` is consumed as it is produced. Removed files
+/// are yielded once `b` has been listed completely. Unchanged files yield `None`.
+pub fn diff_stream<'a, A, B>(
+    a: &'a A,
+    b: &'a B,
+    options: DiffOptions,
+) -> impl Stream<Item = io::Result<Option<DiffEntry>>> + 'a
+where
+    A: AsyncFileStore,
+    <A::File as AsyncFile>::Body: Send + 'static,
+    B: AsyncFileStore,
+    <B::File as AsyncFile>::Body: Send + 'static,
+{
+    try_stream! {
+        let mut remaining = a.list().await?.try_collect::<HashSet<_>>().await?;
+        let mut seen = Vec::new();
+
+        {
+            let existing = &remaining;
+            let compare = options.compare;
+            let results = b
+                .list()
+                .await?
+                .map_ok(|path| async move {
+                    if !existing.contains(&path) {
+                        return io::Result::Ok((None, Some(DiffEntry::Added(path))));
+                    }
+                    let entry = changed(a, b, &path, compare)
+                        .await?
+                        .then(|| DiffEntry::Changed(path.clone()));
+                    Ok((Some(path), entry))
+                })
+                .try_buffer_unordered(options.concurrency.max(1));
+            pin_mut!(results);
+
+            while let Some((path, entry)) = results.try_next().await? {
+                seen.extend(path);
+                yield entry;
+            }
+        }
+
+        for path in seen {
+            remaining.remove(&path);
+        }
+
+        for path in remaining {
+            yield Some(DiffEntry::Removed(path));
+        }
+    }
+}
+
+async fn changed<A, B>(a: &A, b: &B, path: &RelativePath, compare: DiffCompare) -> io::Result<bool>
+where
+    A: AsyncFileStore,
+    B: AsyncFileStore,
+{
+    let (left, right) = futures::try_join!(a.metadata(path), b.metadata(path))?;
+
+    if left.size != right.size {
+        return Ok(true);
+    }
+
+    match compare {
+        DiffCompare::Size => Ok(false),
+        DiffCompare::SizeAndModified => match (left.modified, right.modified) {
+            (Some(left), Some(right)) => Ok(left != right),
+            _ => Ok(false),
+        },
+        DiffCompare::Content => {
+            let (left, right) = futures::try_join!(a.open_file(path), b.open_file(path))?;
+            let (left, right) = futures::try_join!(left.reader(), right.reader())?;
+            Ok(!same_content(left, right).await?)
+        }
+    }
+}
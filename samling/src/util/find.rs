@@ -0,0 +1,103 @@
+use std::{io, time::SystemTime};
+
+use async_stream::try_stream;
+use futures::{pin_mut, Stream, TryStreamExt};
+use globset::Glob;
+use mime::Mime;
+
+use crate::{AsyncFileStore, Metadata};
+
+#[derive(Debug, Clone)]
+pub struct FindOptions {
+    /// Only files whose path matches the glob, e.g. `assets/**/*.png`.
+    pub glob: Option<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Only files modified after this time. Files without a modification time never match.
+    pub modified_after: Option<SystemTime>,
+    /// Only files of this content type. `image/*` matches every image type.
+    pub mime: Option<Mime>,
+    /// Maximum number of `metadata` calls in flight.
+    pub concurrency: usize,
+}
+
+impl Default for FindOptions {
+    fn default() -> Self {
+        FindOptions {
+            glob: None,
+            min_size: None,
+            max_size: None,
+            modified_after: None,
+            mime: None,
+            concurrency: 16,
+        }
+    }
+}
+
+impl FindOptions {
+    /// Whether a file matches the metadata predicates. The glob is not checked.
+    fn matches(&self, meta: &Metadata) -> bool {
+        if self.min_size.is_some_and(|min| meta.size < min)
+            || self.max_size.is_some_and(|max| meta.size > max)
+        {
+            return false;
+        }
+
+        if let Some(after) = self.modified_after {
+            if meta.modified.is_none_or(|modified| modified <= after) {
+                return false;
+            }
+        }
+
+        match &self.mime {
+            Some(mime) if mime.subtype() == mime::STAR => mime.type_() == meta.mime.type_(),
+            Some(mime) => mime.essence_str() == meta.mime.essence_str(),
+            None => true,
+        }
+    }
+}
+
+/// Find the files of a store matching `options`, yielding their metadata as they are found.
+///
+/// Paths are matched against the glob before their metadata is read.
+///
+/// ```ignore
+/// let stale = util::find(&store, FindOptions {
+///     glob: Some("tmp/**".into()),
+///     ..Default::default()
+/// });
+/// ```
+pub fn find<S>(store: &S, options: FindOptions) -> impl Stream<Item = io::Result<Metadata>> + '_
+where
+    S: AsyncFileStore + Sync,
+{
+    try_stream! {
+        let matcher = match &options.glob {
+            Some(glob) => Some(
+                Glob::new(glob)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+                    .compile_matcher(),
+            ),
+            None => None,
+        };
+
+        let results = store
+            .list()
+            .await?
+            .try_filter(|path| {
+                let keep = matcher
+                    .as_ref()
+                    .is_none_or(|matcher| matcher.is_match(path.as_str()));
+                async move { keep }
+            })
+            .map_ok(|path| async move { store.metadata(&path).await })
+            .try_buffer_unordered(options.concurrency.max(1));
+        pin_mut!(results);
+
+        while let Some(meta) = results.try_next().await? {
+            if options.matches(&meta) {
+                yield meta;
+            }
+        }
+    }
+}
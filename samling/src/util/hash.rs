@@ -0,0 +1,213 @@
+use std::{
+    fmt, io,
+    sync::{Arc, Mutex},
+};
+
+use futures::{StreamExt, TryStreamExt};
+use relative_path::RelativePath;
+use sha2::Digest as _;
+
+use crate::{AsyncFile, AsyncFileStore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+/// The output of a hash.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Digest {
+    algorithm: HashAlgorithm,
+    bytes: Vec<u8>,
+}
+
+impl Digest {
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl AsRef<[u8]> for Digest {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.bytes {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+enum Hasher {
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Hasher {
+        match algorithm {
+            HashAlgorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::default()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    fn finish(self) -> Digest {
+        match self {
+            Hasher::Sha256(hasher) => Digest {
+                algorithm: HashAlgorithm::Sha256,
+                bytes: hasher.finalize().to_vec(),
+            },
+            Hasher::Blake3(hasher) => Digest {
+                algorithm: HashAlgorithm::Blake3,
+                bytes: hasher.finalize().as_bytes().to_vec(),
+            },
+        }
+    }
+}
+
+/// Hashes a body as it streams past, so the copy written from it can be verified afterwards.
+#[derive(Clone)]
+pub(crate) struct StreamHasher(Arc<Mutex<(Hasher, u64)>>);
+
+impl StreamHasher {
+    pub fn new(algorithm: HashAlgorithm) -> StreamHasher {
+        StreamHasher(Arc::new(Mutex::new((Hasher::new(algorithm), 0))))
+    }
+
+    pub fn update(&self, data: &[u8]) {
+        let mut state = self.0.lock().unwrap();
+        state.0.update(data);
+        state.1 += data.len() as u64;
+    }
+
+    /// The digest and length of everything seen so far.
+    pub fn finish(&self) -> (Digest, u64) {
+        let state = self.0.lock().unwrap();
+        (state.0.clone().finish(), state.1)
+    }
+}
+
+/// Re-read `path` and check that it is `size` bytes long and hashes to `expected`.
+pub(crate) async fn verify_file<S>(
+    store: &S,
+    path: &RelativePath,
+    expected: &Digest,
+    size: u64,
+) -> io::Result<()>
+where
+    S: AsyncFileStore,
+{
+    let meta = store.metadata(path).await?;
+    if meta.size != size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{path} failed verification: expected {size} bytes, found {}",
+                meta.size
+            ),
+        ));
+    }
+
+    let found = hash_file(store, path, expected.algorithm()).await?;
+    if &found != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{path} failed verification: expected digest {expected}, found {found}"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Hash the contents of a single file, streaming it through the hasher.
+pub async fn hash_file<S>(
+    store: &S,
+    path: &RelativePath,
+    algorithm: HashAlgorithm,
+) -> io::Result<Digest>
+where
+    S: AsyncFileStore,
+{
+    let file = store.open_file(path).await?;
+    hash_contents(&file, algorithm).await
+}
+
+/// Hash the contents of an opened file.
+pub async fn hash_contents<F>(file: &F, algorithm: HashAlgorithm) -> io::Result<Digest>
+where
+    F: AsyncFile,
+{
+    let reader = file.reader().await?;
+    futures::pin_mut!(reader);
+
+    let mut hasher = Hasher::new(algorithm);
+    while let Some(chunk) = reader.try_next().await? {
+        hasher.update(&chunk);
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Hash data already in memory.
+pub fn hash_bytes(data: &[u8], algorithm: HashAlgorithm) -> Digest {
+    let mut hasher = Hasher::new(algorithm);
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// Hash every file of a store into a single digest.
+///
+/// Paths are normalized and sorted, and each contributes its path followed by the digest of its
+/// contents, so the result only depends on the files in the store and not on listing order.
+pub async fn hash_store<S>(store: &S, algorithm: HashAlgorithm) -> io::Result<Digest>
+where
+    S: AsyncFileStore,
+{
+    let mut paths = store
+        .list()
+        .await?
+        .map_ok(|path| path.normalize())
+        .try_collect::<Vec<_>>()
+        .await?;
+    paths.sort();
+    paths.dedup();
+
+    let mut digests = futures::stream::iter(&paths)
+        .map(|path| async move { hash_file(store, path, algorithm).await })
+        .buffered(8);
+
+    let mut hasher = Hasher::new(algorithm);
+    let mut paths = paths.iter();
+    while let Some(digest) = digests.try_next().await? {
+        let path = paths.next().expect("one digest per path");
+        hasher.update(path.as_str().as_bytes());
+        hasher.update(&[0]);
+        hasher.update(digest.as_bytes());
+    }
+
+    Ok(hasher.finish())
+}
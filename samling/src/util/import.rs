@@ -0,0 +1,584 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_compression::futures::bufread::{DeflateDecoder, GzipDecoder};
+use async_stream::try_stream;
+use bytes::Bytes;
+use futures::{
+    channel::mpsc,
+    io::{AsyncBufRead, AsyncRead, BufReader},
+    pin_mut, AsyncBufReadExt, AsyncReadExt, SinkExt, Stream, TryStreamExt,
+};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use super::{Buffer, Progress};
+use crate::{AsyncFileInit, AsyncFileStore};
+
+const CHUNK: usize = 64 * 1024;
+const BLOCK: usize = 512;
+/// Largest long name or pax header read into memory.
+const MAX_HEADER: u64 = 64 * 1024;
+
+const ZIP_LOCAL: u32 = 0x04034b50;
+const ZIP_DESCRIPTOR: u32 = 0x08074b50;
+const ZIP_CENTRAL: u32 = 0x02014b50;
+const ZIP_END: u32 = 0x06054b50;
+
+/// The outcome of importing a single archive entry.
+#[derive(Debug)]
+pub struct ImportEntry {
+    pub path: RelativePathBuf,
+    pub result: io::Result<()>,
+}
+
+/// Unpack a tar, gzipped tar or zip archive into `store`, detecting the format from its header.
+///
+/// Entries are streamed into the store as they are read. Failing to write an entry is reported
+/// in its `ImportEntry`, while a malformed archive aborts the import.
+pub async fn import_archive<R, S>(reader: R, store: &S) -> io::Result<Vec<ImportEntry>>
+where
+    R: AsyncRead + Unpin,
+    S: AsyncFileStore,
+{
+    import_archive_with_progress(reader, store, &()).await
+}
+
+/// Like [`import_archive`], reporting every entry to `progress` as it is written.
+pub async fn import_archive_with_progress<R, S>(
+    reader: R,
+    store: &S,
+    progress: &dyn Progress,
+) -> io::Result<Vec<ImportEntry>>
+where
+    R: AsyncRead + Unpin,
+    S: AsyncFileStore,
+{
+    let mut reader = BufReader::with_capacity(CHUNK, reader);
+
+    let (gzip, zip) = {
+        let magic = reader.fill_buf().await?;
+        (
+            magic.starts_with(&[0x1f, 0x8b]),
+            magic.starts_with(b"PK\x03\x04") || magic.starts_with(b"PK\x05\x06"),
+        )
+    };
+
+    if gzip {
+        unpack_tar(GzipDecoder::new(reader), store, progress).await
+    } else if zip {
+        unpack_zip(reader, store, progress).await
+    } else {
+        unpack_tar(reader, store, progress).await
+    }
+}
+
+pub async fn import_tar<R, S>(reader: R, store: &S) -> io::Result<Vec<ImportEntry>>
+where
+    R: AsyncRead + Unpin,
+    S: AsyncFileStore,
+{
+    unpack_tar(reader, store, &()).await
+}
+
+pub async fn import_zip<R, S>(reader: R, store: &S) -> io::Result<Vec<ImportEntry>>
+where
+    R: AsyncBufRead + Unpin,
+    S: AsyncFileStore,
+{
+    unpack_zip(reader, store, &()).await
+}
+
+async fn unpack_tar<R, S>(
+    mut reader: R,
+    store: &S,
+    progress: &dyn Progress,
+) -> io::Result<Vec<ImportEntry>>
+where
+    R: AsyncRead + Unpin,
+    S: AsyncFileStore,
+{
+    let mut entries = Vec::new();
+    let mut long_name = None;
+    let mut header = [0u8; BLOCK];
+
+    loop {
+        if !read_block(&mut reader, &mut header).await? || header.iter().all(|m| *m == 0) {
+            break;
+        }
+
+        let size = tar_number(&header[124..136])?;
+        let kind = header[156];
+
+        match kind {
+            b'L' | b'x' => {
+                if size > MAX_HEADER {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("tar header entry of {size} bytes is too large"),
+                    ));
+                }
+                let mut data = Vec::new();
+                (&mut reader).take(size).read_to_end(&mut data).await?;
+                if kind == b'L' {
+                    long_name = Some(
+                        String::from_utf8_lossy(&data)
+                            .trim_end_matches('\0')
+                            .to_string(),
+                    );
+                } else if let Some(path) = pax_path(&data) {
+                    long_name = Some(path);
+                }
+            }
+            0 | b'0' | b'7' => {
+                let name = long_name.take().unwrap_or_else(|| ustar_name(&header));
+                let mut body = (&mut reader).take(size);
+                entries.push(import_entry(store, &name, read_chunks(&mut body), progress).await?);
+                if body.limit() != 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+            _ => {
+                long_name = None;
+                discard(&mut reader, size).await?;
+            }
+        }
+
+        discard(
+            &mut reader,
+            ((BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64) as u64,
+        )
+        .await?;
+    }
+
+    Ok(entries)
+}
+
+async fn unpack_zip<R, S>(
+    reader: R,
+    store: &S,
+    progress: &dyn Progress,
+) -> io::Result<Vec<ImportEntry>>
+where
+    R: AsyncBufRead + Unpin,
+    S: AsyncFileStore,
+{
+    let mut reader = Unread::new(reader);
+    let mut entries = Vec::new();
+
+    loop {
+        let mut signature = [0u8; 4];
+        if !read_block(&mut reader, &mut signature).await? {
+            break;
+        }
+
+        match u32::from_le_bytes(signature) {
+            ZIP_LOCAL => {}
+            ZIP_CENTRAL | ZIP_END => break,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid zip entry signature",
+                ))
+            }
+        }
+
+        let mut header = [0u8; 26];
+        reader.read_exact(&mut header).await?;
+
+        let flags = u16::from_le_bytes([header[2], header[3]]);
+        let method = u16::from_le_bytes([header[4], header[5]]);
+        let compressed = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+        let uncompressed = u32::from_le_bytes([header[18], header[19], header[20], header[21]]);
+        let name_len = u16::from_le_bytes([header[22], header[23]]) as usize;
+        let extra_len = u16::from_le_bytes([header[24], header[25]]) as u64;
+
+        if compressed == u32::MAX || uncompressed == u32::MAX {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "zip64 archives are not supported",
+            ));
+        }
+
+        let mut name = vec![0; name_len];
+        reader.read_exact(&mut name).await?;
+        let name = String::from_utf8_lossy(&name).into_owned();
+        discard(&mut reader, extra_len).await?;
+
+        let descriptor = flags & 0x08 != 0;
+        let directory = name.ends_with('/');
+
+        let entry = match (method, descriptor) {
+            (0, false) => {
+                let mut body = (&mut reader).take(compressed as u64);
+                let entry = if directory {
+                    drain(read_chunks(&mut body)).await?;
+                    None
+                } else {
+                    Some(import_entry(store, &name, read_chunks(&mut body), progress).await?)
+                };
+                if body.limit() != 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                entry
+            }
+            (0, true) => {
+                let body = stored_until_descriptor(&mut reader);
+                if directory {
+                    drain(body).await?;
+                    None
+                } else {
+                    Some(import_entry(store, &name, body, progress).await?)
+                }
+            }
+            (8, _) => {
+                let mut decoder = DeflateDecoder::new(&mut reader);
+                let entry = if directory {
+                    drain(read_chunks(&mut decoder)).await?;
+                    None
+                } else {
+                    Some(import_entry(store, &name, read_chunks(&mut decoder), progress).await?)
+                };
+                drop(decoder);
+
+                if descriptor {
+                    skip_descriptor(&mut reader).await?;
+                }
+                entry
+            }
+            (_, false) => {
+                discard(&mut reader, compressed as u64).await?;
+                Some(ImportEntry {
+                    path: RelativePathBuf::from(name),
+                    result: Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        format!("unsupported zip compression method {method}"),
+                    )),
+                })
+            }
+            (_, true) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("unsupported zip compression method {method}"),
+                ))
+            }
+        };
+
+        entries.extend(entry);
+    }
+
+    Ok(entries)
+}
+
+async fn import_entry<S, B>(
+    store: &S,
+    name: &str,
+    body: B,
+    progress: &dyn Progress,
+) -> io::Result<ImportEntry>
+where
+    S: AsyncFileStore,
+    B: Stream<Item = io::Result<Bytes>>,
+{
+    let path = RelativePath::new(name.trim_start_matches('/')).normalize();
+
+    if path.as_str().is_empty() || path.as_str().starts_with("..") {
+        drain(body).await?;
+        return Ok(ImportEntry {
+            path,
+            result: Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid entry path '{name}'"),
+            )),
+        });
+    }
+
+    progress.started(&path, None);
+    let (mut tx, rx) = mpsc::channel(4);
+
+    let write = store.write_file(&path, AsyncFileInit::stream(rx));
+    let written = &path;
+    let feed = async move {
+        pin_mut!(body);
+        let mut open = true;
+        loop {
+            match body.try_next().await {
+                Ok(Some(next)) => {
+                    progress.bytes(written, next.len() as u64);
+                    // Keep reading if the store gave up, the archive has to be consumed anyway
+                    if open && tx.send(Ok(next)).await.is_err() {
+                        open = false;
+                    }
+                }
+                Ok(None) => return Ok(()),
+                Err(err) => {
+                    let _ = tx
+                        .send(Err(io::Error::new(err.kind(), err.to_string())))
+                        .await;
+                    return Err(err);
+                }
+            }
+        }
+    };
+
+    let (result, read) = futures::join!(write, feed);
+    progress.finished(&path, result.as_ref().map(|_| ()));
+    read?;
+
+    Ok(ImportEntry { path, result })
+}
+
+fn read_chunks<R>(mut reader: R) -> impl Stream<Item = io::Result<Bytes>>
+where
+    R: AsyncRead + Unpin,
+{
+    try_stream! {
+        let mut buffer = Buffer::acquire();
+        loop {
+            let chunk = buffer.read_chunk_async(&mut reader).await?;
+            if chunk.is_empty() {
+                break;
+            }
+            yield chunk;
+        }
+    }
+}
+
+/// Stored zip entries written with a data descriptor carry no size, so the entry ends at the
+/// first descriptor whose checksum and size match the data preceding it.
+fn stored_until_descriptor<R>(reader: &mut Unread<R>) -> impl Stream<Item = io::Result<Bytes>> + '_
+where
+    R: AsyncBufRead + Unpin,
+{
+    try_stream! {
+        let mut hasher = crc32fast::Hasher::new();
+        let mut size = 0u64;
+        let mut window = Vec::new();
+
+        loop {
+            let chunk = reader.fill_buf().await?.to_vec();
+            if chunk.is_empty() {
+                Err::<(), _>(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+            }
+            reader.consume_unpin(chunk.len());
+            window.extend_from_slice(&chunk);
+
+            let mut found = None;
+            let mut from = 0;
+            while let Some(pos) = find_descriptor(&window[from..]) {
+                let idx = from + pos;
+                if idx + 16 > window.len() {
+                    break;
+                }
+
+                let field = |at: usize| {
+                    u32::from_le_bytes([window[at], window[at + 1], window[at + 2], window[at + 3]])
+                };
+                let (crc, compressed, uncompressed) =
+                    (field(idx + 4), field(idx + 8), field(idx + 12));
+
+                if compressed == uncompressed && compressed as u64 == size + idx as u64 {
+                    let mut candidate = hasher.clone();
+                    candidate.update(&window[..idx]);
+                    if candidate.finalize() == crc {
+                        found = Some(idx);
+                        break;
+                    }
+                }
+
+                from = idx + 1;
+            }
+
+            if let Some(idx) = found {
+                reader.unread(&window[idx + 16..]);
+                if idx > 0 {
+                    yield Bytes::copy_from_slice(&window[..idx]);
+                }
+                break;
+            }
+
+            // The tail might hold the start of a descriptor
+            let keep = window.len().saturating_sub(15);
+            if keep > 0 {
+                hasher.update(&window[..keep]);
+                size += keep as u64;
+                let data = Bytes::copy_from_slice(&window[..keep]);
+                window.drain(..keep);
+                yield data;
+            }
+        }
+    }
+}
+
+fn find_descriptor(data: &[u8]) -> Option<usize> {
+    let signature = ZIP_DESCRIPTOR.to_le_bytes();
+    data.windows(4).position(|m| m == signature)
+}
+
+async fn skip_descriptor<R>(reader: &mut R) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut first = [0u8; 4];
+    reader.read_exact(&mut first).await?;
+    let rest = if u32::from_le_bytes(first) == ZIP_DESCRIPTOR {
+        12
+    } else {
+        8
+    };
+    discard(reader, rest).await
+}
+
+async fn drain<B>(body: B) -> io::Result<()>
+where
+    B: Stream<Item = io::Result<Bytes>>,
+{
+    pin_mut!(body);
+    while body.try_next().await?.is_some() {}
+    Ok(())
+}
+
+async fn discard<R>(reader: &mut R, len: u64) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    let copied = futures::io::copy(reader.take(len), &mut futures::io::sink()).await?;
+    if copied != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Like `read_exact`, but returns `false` if the reader is exhausted before the first byte.
+async fn read_block<R>(reader: &mut R, block: &mut [u8]) -> io::Result<bool>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut read = 0;
+    while read < block.len() {
+        let n = reader.read(&mut block[read..]).await?;
+        if n == 0 {
+            if read == 0 {
+                return Ok(false);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        read += n;
+    }
+    Ok(true)
+}
+
+fn tar_number(field: &[u8]) -> io::Result<u64> {
+    // Base-256 encoding for values which do not fit the octal field
+    if field[0] & 0x80 != 0 {
+        let mut value = (field[0] & 0x7f) as u64;
+        for byte in &field[1..] {
+            value = value
+                .checked_mul(256)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "tar size overflow"))?
+                | *byte as u64;
+        }
+        return Ok(value);
+    }
+
+    let digits = field
+        .iter()
+        .take_while(|m| **m != 0)
+        .map(|m| *m as char)
+        .collect::<String>();
+    let digits = digits.trim();
+
+    if digits.is_empty() {
+        return Ok(0);
+    }
+
+    u64::from_str_radix(digits, 8)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid tar header"))
+}
+
+fn ustar_name(header: &[u8; BLOCK]) -> String {
+    let field = |data: &[u8]| {
+        let end = data.iter().position(|m| *m == 0).unwrap_or(data.len());
+        String::from_utf8_lossy(&data[..end]).into_owned()
+    };
+
+    let name = field(&header[..100]);
+    if &header[257..262] == b"ustar" {
+        let prefix = field(&header[345..500]);
+        if !prefix.is_empty() {
+            return format!("{prefix}/{name}");
+        }
+    }
+    name
+}
+
+fn pax_path(data: &[u8]) -> Option<String> {
+    let data = String::from_utf8_lossy(data);
+    data.lines().find_map(|record| {
+        let (_, pair) = record.split_once(' ')?;
+        let (key, value) = pair.split_once('=')?;
+        (key == "path").then(|| value.to_string())
+    })
+}
+
+/// A buffered reader which allows pushing back bytes read past the end of an entry.
+struct Unread<R> {
+    head: Vec<u8>,
+    pos: usize,
+    inner: R,
+}
+
+impl<R> Unread<R> {
+    fn new(inner: R) -> Unread<R> {
+        Unread {
+            head: Vec::new(),
+            pos: 0,
+            inner,
+        }
+    }
+
+    fn unread(&mut self, data: &[u8]) {
+        let mut head = data.to_vec();
+        head.extend_from_slice(&self.head[self.pos..]);
+        self.head = head;
+        self.pos = 0;
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Unread<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.pos < this.head.len() {
+            let n = (this.head.len() - this.pos).min(buf.len());
+            buf[..n].copy_from_slice(&this.head[this.pos..this.pos + n]);
+            this.pos += n;
+            return Poll::Ready(Ok(n));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for Unread<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.pos < this.head.len() {
+            return Poll::Ready(Ok(&this.head[this.pos..]));
+        }
+        Pin::new(&mut this.inner).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        if this.pos < this.head.len() {
+            this.pos = (this.pos + amt).min(this.head.len());
+        } else {
+            Pin::new(&mut this.inner).consume(amt);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{collections::HashSet, fmt, io};
+
+use futures::{StreamExt, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use super::{sync::changed, CopyFilter, SyncCompare, Transfer};
+use crate::{AsyncFile, AsyncFileStore};
+
+#[derive(Clone)]
+pub struct MirrorOptions {
+    pub compare: SyncCompare,
+    /// Remove files from the target which are not present in the source.
+    pub delete_extraneous: bool,
+    /// Only plan the actions, without touching the target.
+    pub dry_run: bool,
+    /// Only mirror paths for which the filter returns `true`. Other paths are left alone on
+    /// both sides, and are never deleted.
+    pub filter: Option<CopyFilter>,
+    /// Maximum number of files compared or transferred at once.
+    pub concurrency: usize,
+}
+
+impl Default for MirrorOptions {
+    fn default() -> Self {
+        MirrorOptions {
+            compare: SyncCompare::default(),
+            delete_extraneous: true,
+            dry_run: false,
+            filter: None,
+            concurrency: 8,
+        }
+    }
+}
+
+impl fmt::Debug for MirrorOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MirrorOptions")
+            .field("compare", &self.compare)
+            .field("delete_extraneous", &self.delete_extraneous)
+            .field("dry_run", &self.dry_run)
+            .field("filter", &self.filter.is_some())
+            .field("concurrency", &self.concurrency)
+            .finish()
+    }
+}
+
+/// A change made to the target by [`mirror`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "action", content = "path", rename_all = "lowercase")
+)]
+pub enum MirrorAction {
+    /// Copy a file missing from the target.
+    Create(RelativePathBuf),
+    /// Copy a file which differs in the target.
+    Update(RelativePathBuf),
+    /// Remove a file not present in the source.
+    Delete(RelativePathBuf),
+}
+
+impl MirrorAction {
+    pub fn path(&self) -> &RelativePath {
+        match self {
+            MirrorAction::Create(path)
+            | MirrorAction::Update(path)
+            | MirrorAction::Delete(path) => path,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MirrorReport {
+    /// Whether the actions were only planned.
+    pub dry_run: bool,
+    /// The actions taken, or planned when running dry, ordered by path.
+    pub actions: Vec<MirrorAction>,
+    pub unchanged: usize,
+    /// Actions which failed, or files which could not be compared.
+    pub failed: Vec<(MirrorAction, io::Error)>,
+}
+
+/// Make `target` hold exactly the files of `source`, reporting every action.
+///
+/// The plan is made first by comparing both listings, then carried out unless running dry, so
+/// a dry run reports exactly what a real run would do to an unchanging store.
+///
+/// ```ignore
+/// let options = MirrorOptions { dry_run: true, ..Default::default() };
+/// let preview = util::mirror(&build, &site, options).await?;
+/// for action in &preview.actions {
+///     println!("{action:?}");
+/// }
+/// ```
+pub async fn mirror<S, T>(
+    source: &S,
+    target: &T,
+    options: MirrorOptions,
+) -> io::Result<MirrorReport>
+where
+    S: AsyncFileStore,
+    <S::File as AsyncFile>::Body: Send + 'static,
+    T: AsyncFileStore,
+{
+    let filter = options.filter.as_ref();
+    let keep = |path: &RelativePath| filter.is_none_or(|filter| filter(path));
+
+    let existing = target
+        .list()
+        .await?
+        .try_filter(|path| futures::future::ready(keep(path)))
+        .try_collect::<HashSet<_>>()
+        .await?;
+    let sources = source
+        .list()
+        .await?
+        .try_filter(|path| futures::future::ready(keep(path)))
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let concurrency = options.concurrency.max(1);
+    let compare = options.compare;
+    let existing = &existing;
+
+    let mut report = MirrorReport {
+        dry_run: options.dry_run,
+        ..Default::default()
+    };
+    let mut plan = Vec::new();
+
+    let mut results = futures::stream::iter(sources.iter())
+        .map(|path| async move {
+            if !existing.contains(path) {
+                return (MirrorAction::Create(path.clone()), Ok(true));
+            }
+            let ret = changed(source, target, path, compare).await;
+            (MirrorAction::Update(path.clone()), ret)
+        })
+        .buffer_unordered(concurrency);
+
+    while let Some((action, ret)) = results.next().await {
+        match ret {
+            Ok(true) => plan.push(action),
+            Ok(false) => report.unchanged += 1,
+            Err(err) => report.failed.push((action, err)),
+        }
+    }
+
+    if options.delete_extraneous {
+        let sources = sources.iter().collect::<HashSet<_>>();
+        plan.extend(
+            existing
+                .iter()
+                .filter(|path| !sources.contains(path))
+                .map(|path| MirrorAction::Delete(path.clone())),
+        );
+    }
+
+    if options.dry_run {
+        report.actions = plan;
+    } else {
+        let mut results = futures::stream::iter(plan)
+            .map(|action| async move {
+                let ret = match &action {
+                    MirrorAction::Create(path) | MirrorAction::Update(path) => {
+                        super::transfer(source, target, path, path, Transfer::default()).await
+                    }
+                    MirrorAction::Delete(path) => target.rm_file(path).await,
+                };
+                (action, ret)
+            })
+            .buffer_unordered(concurrency);
+
+        while let Some((action, ret)) = results.next().await {
+            match ret {
+                Ok(()) => report.actions.push(action),
+                Err(err) => report.failed.push((action, err)),
+            }
+        }
+    }
+
+    report.actions.sort_by(|a, b| a.path().cmp(b.path()));
+    report
+        .failed
+        .sort_by(|(a, _), (b, _)| a.path().cmp(b.path()));
+
+    Ok(report)
+}
@@ -0,0 +1,133 @@
+use std::{
+    io::{self, Read},
+    sync::{Mutex, RwLock},
+};
+
+use bytes::{Bytes, BytesMut};
+
+/// Sizing of the buffers used when streaming file bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Maximum size of a single chunk read from a file.
+    pub chunk_size: usize,
+    /// Size of the allocations chunks are split from.
+    pub buffer_capacity: usize,
+    /// Maximum number of idle buffers kept around for reuse.
+    pub max_pooled: usize,
+}
+
+impl Config {
+    pub const DEFAULT: Config = Config {
+        chunk_size: 64 * 1024,
+        buffer_capacity: 256 * 1024,
+        max_pooled: 64,
+    };
+
+    /// The configuration currently in use.
+    pub fn current() -> Config {
+        *CONFIG.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Use this configuration for buffers acquired from now on.
+    pub fn apply(self) {
+        *CONFIG.write().unwrap_or_else(|err| err.into_inner()) = self;
+
+        let mut pool = POOL.lock().unwrap_or_else(|err| err.into_inner());
+        pool.truncate(self.max_pooled);
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::DEFAULT
+    }
+}
+
+static CONFIG: RwLock<Config> = RwLock::new(Config::DEFAULT);
+
+static POOL: Mutex<Vec<BytesMut>> = Mutex::new(Vec::new());
+
+/// A pooled buffer chunks are split off from.
+///
+/// Frozen chunks share the allocation, which is reclaimed once they are all dropped.
+/// The buffer returns to the pool when dropped.
+pub(crate) struct Buffer {
+    inner: BytesMut,
+    config: Config,
+}
+
+impl Buffer {
+    pub fn acquire() -> Buffer {
+        let config = Config::current();
+        let inner = POOL
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .pop()
+            .unwrap_or_default();
+
+        Buffer { inner, config }
+    }
+
+    /// Override the configured chunk size for this buffer.
+    #[cfg(feature = "fs")]
+    pub fn chunk_size(mut self, size: usize) -> Buffer {
+        self.config.chunk_size = size;
+        self
+    }
+
+    fn prepare(&mut self) -> &mut [u8] {
+        let chunk = self.config.chunk_size.max(1);
+        if self.inner.capacity() < chunk {
+            self.inner.reserve(self.config.buffer_capacity.max(chunk));
+        }
+        self.inner.resize(chunk, 0);
+        &mut self.inner[..]
+    }
+
+    fn finish(&mut self, read: usize) -> Bytes {
+        self.inner.truncate(read);
+        self.inner.split().freeze()
+    }
+
+    /// Read a single chunk. Returns an empty chunk at the end of the reader.
+    pub fn read_chunk<R: Read>(&mut self, reader: &mut R) -> io::Result<Bytes> {
+        match reader.read(self.prepare()) {
+            Ok(read) => Ok(self.finish(read)),
+            Err(err) => {
+                self.inner.clear();
+                Err(err)
+            }
+        }
+    }
+
+    /// Read a single chunk. Returns an empty chunk at the end of the reader.
+    pub async fn read_chunk_async<R>(&mut self, reader: &mut R) -> io::Result<Bytes>
+    where
+        R: futures::io::AsyncRead + Unpin,
+    {
+        use futures::AsyncReadExt;
+
+        match reader.read(self.prepare()).await {
+            Ok(read) => Ok(self.finish(read)),
+            Err(err) => {
+                self.inner.clear();
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        let mut inner = std::mem::take(&mut self.inner);
+        inner.clear();
+        if inner.capacity() == 0 {
+            return;
+        }
+
+        let mut pool = POOL.lock().unwrap_or_else(|err| err.into_inner());
+        if pool.len() < self.config.max_pooled {
+            pool.push(inner);
+        }
+    }
+}
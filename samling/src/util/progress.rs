@@ -0,0 +1,120 @@
+use std::{
+    io,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use relative_path::{RelativePath, RelativePathBuf};
+
+/// Receives progress while files are transferred by [`copy_all`](super::copy_all),
+/// [`sync`](super::sync), [`export_tar`](super::export_tar) or
+/// [`import_archive`](super::import_archive).
+///
+/// Every method defaults to doing nothing. Files may be transferred concurrently, so calls for
+/// different paths can interleave.
+#[allow(unused_variables)]
+pub trait Progress: Send + Sync {
+    /// The number of files which will be looked at, once it is known.
+    fn total(&self, files: u64) {}
+
+    /// A file is about to be transferred. `size` is its expected size, if known.
+    fn started(&self, path: &RelativePath, size: Option<u64>) {}
+
+    /// `bytes` more bytes of `path` were transferred.
+    fn bytes(&self, path: &RelativePath, bytes: u64) {}
+
+    /// A file was transferred, or failed to.
+    fn finished(&self, path: &RelativePath, result: Result<(), &io::Error>) {}
+
+    /// A file was left alone, because it is unchanged or already exists.
+    fn skipped(&self, path: &RelativePath) {}
+}
+
+/// Ignores all progress.
+impl Progress for () {}
+
+/// A point in time view of a [`ProgressCounter`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgressState {
+    pub total: Option<u64>,
+    /// Files transferred, failed or skipped.
+    pub done: u64,
+    pub failed: u64,
+    pub bytes: u64,
+    /// The most recently started file.
+    pub current: Option<RelativePathBuf>,
+    pub elapsed: Duration,
+}
+
+impl ProgressState {
+    /// Estimate the time remaining from the rate files have been done at so far.
+    pub fn eta(&self) -> Option<Duration> {
+        let total = self.total?;
+        if self.done == 0 {
+            return None;
+        }
+        let remaining = total.saturating_sub(self.done) as f64;
+        Some(self.elapsed.mul_f64(remaining / self.done as f64))
+    }
+}
+
+/// A [`Progress`] which tallies what it is told, to be polled for rendering.
+///
+/// ```ignore
+/// let counter = Arc::new(ProgressCounter::new());
+/// let options = CopyOptions { progress: Some(counter.clone()), ..Default::default() };
+/// let copy = util::copy_all(&source, &target, options);
+/// // Meanwhile, render `counter.state()` every so often
+/// ```
+#[derive(Debug)]
+pub struct ProgressCounter {
+    start: Instant,
+    state: Mutex<ProgressState>,
+}
+
+impl Default for ProgressCounter {
+    fn default() -> Self {
+        ProgressCounter::new()
+    }
+}
+
+impl ProgressCounter {
+    pub fn new() -> ProgressCounter {
+        ProgressCounter {
+            start: Instant::now(),
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn state(&self) -> ProgressState {
+        let mut state = self.state.lock().unwrap().clone();
+        state.elapsed = self.start.elapsed();
+        state
+    }
+}
+
+impl Progress for ProgressCounter {
+    fn total(&self, files: u64) {
+        self.state.lock().unwrap().total = Some(files);
+    }
+
+    fn started(&self, path: &RelativePath, _size: Option<u64>) {
+        self.state.lock().unwrap().current = Some(path.to_relative_path_buf());
+    }
+
+    fn bytes(&self, _path: &RelativePath, bytes: u64) {
+        self.state.lock().unwrap().bytes += bytes;
+    }
+
+    fn finished(&self, _path: &RelativePath, result: Result<(), &io::Error>) {
+        let mut state = self.state.lock().unwrap();
+        state.done += 1;
+        if result.is_err() {
+            state.failed += 1;
+        }
+    }
+
+    fn skipped(&self, _path: &RelativePath) {
+        self.state.lock().unwrap().done += 1;
+    }
+}
@@ -0,0 +1,50 @@
+//! Timestamps as written by the json apis of remote backends, e.g. `2024-05-01T12:30:00Z` or
+//! `2024-05-01T14:30:00.123456789+02:00`.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Parse an RFC 3339 timestamp, or `None` if it isn't one or is before the unix epoch.
+pub(crate) fn parse(time: &str) -> Option<SystemTime> {
+    let (date, clock) = time.split_once(['T', 't'])?;
+
+    // Seconds east of UTC
+    let (clock, offset) = match clock.strip_suffix(['Z', 'z']) {
+        Some(clock) => (clock, 0),
+        None => {
+            let (clock, offset) = clock.split_at(clock.rfind(['+', '-'])?);
+            let (sign, offset) = offset.split_at(1);
+            let (hours, minutes) = offset.split_once(':')?;
+            let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+            (clock, if sign == "-" { -offset } else { offset })
+        }
+    };
+
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let mut clock = clock.splitn(3, ':');
+    let (hours, minutes) = (
+        clock.next()?.parse::<i64>().ok()?,
+        clock.next()?.parse::<i64>().ok()?,
+    );
+    let seconds = clock.next()?;
+    let (seconds, nanos) = match seconds.split_once('.') {
+        Some((seconds, fraction)) => {
+            // Only nanoseconds are kept
+            let digits = fraction.get(..9).unwrap_or(fraction);
+            let nanos = digits.parse::<u32>().ok()? * 10u32.pow(9 - digits.len() as u32);
+            (seconds, nanos)
+        }
+        None => (seconds, 0),
+    };
+    let seconds = seconds.parse::<i64>().ok()?;
+
+    // Days since the unix epoch from a civil date
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs = days * 86400 + hours * 3600 + minutes * 60 + seconds - offset;
+    Some(UNIX_EPOCH + Duration::new(u64::try_from(secs).ok()?, nanos))
+}
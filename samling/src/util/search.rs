@@ -0,0 +1,155 @@
+use std::{io, ops::Range};
+
+use async_stream::try_stream;
+use bytes::BytesMut;
+use futures::{pin_mut, Stream, TryStreamExt};
+use regex::bytes::{Regex, RegexBuilder};
+use relative_path::RelativePathBuf;
+
+use super::{find, FindOptions};
+use crate::{AsyncFile, AsyncFileStore};
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Which files to search.
+    pub filter: FindOptions,
+    /// Match the pattern literally instead of as a regular expression.
+    pub literal: bool,
+    pub case_insensitive: bool,
+    /// Maximum number of files searched at once.
+    pub concurrency: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            filter: FindOptions::default(),
+            literal: false,
+            case_insensitive: false,
+            concurrency: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub path: RelativePathBuf,
+    /// The line of the match, starting at 1.
+    pub line: u64,
+    /// The matching line without its line ending. Invalid utf8 is replaced.
+    pub text: String,
+    /// The byte range of the match within the line.
+    pub range: Range<usize>,
+}
+
+/// Search the bodies of the files of a store for `pattern`, yielding every match.
+///
+/// Files are streamed and scanned line by line; files whose first chunk contains a NUL byte are
+/// considered binary and skipped. Matches of a file are yielded together, in order, once the
+/// file has been scanned.
+///
+/// ```ignore
+/// let options = SearchOptions {
+///     filter: FindOptions { glob: Some("**/*.html".into()), ..Default::default() },
+///     ..Default::default()
+/// };
+/// let matches = util::search(&store, r"\{\{\s*unsafe", options);
+/// ```
+pub fn search<'a, S>(
+    store: &'a S,
+    pattern: &str,
+    options: SearchOptions,
+) -> impl Stream<Item = io::Result<SearchMatch>> + 'a
+where
+    S: AsyncFileStore + Sync,
+    S::File: Send,
+    <S::File as AsyncFile>::Body: Send,
+{
+    let pattern = if options.literal {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    };
+
+    try_stream! {
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(options.case_insensitive)
+            .build()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let regex = &regex;
+
+        let files = find(store, options.filter)
+            .map_ok(|meta| async move { search_file(store, meta.path, regex).await })
+            .try_buffer_unordered(options.concurrency.max(1));
+        pin_mut!(files);
+
+        while let Some(matches) = files.try_next().await? {
+            for found in matches {
+                yield found;
+            }
+        }
+    }
+}
+
+async fn search_file<S>(
+    store: &S,
+    path: RelativePathBuf,
+    regex: &Regex,
+) -> io::Result<Vec<SearchMatch>>
+where
+    S: AsyncFileStore,
+{
+    let body = store.open_file(&path).await?.reader().await?;
+    pin_mut!(body);
+
+    let mut matches = Vec::new();
+    let mut pending = BytesMut::new();
+    let mut line = 0;
+    let mut first = true;
+
+    while let Some(chunk) = body.try_next().await? {
+        if chunk.is_empty() {
+            continue;
+        }
+
+        if first && chunk.contains(&0) {
+            return Ok(Vec::new());
+        }
+        first = false;
+
+        // Only the new chunk can contain the end of the pending line
+        let mut searched = pending.len();
+        pending.extend_from_slice(&chunk);
+
+        while let Some(end) = pending[searched..].iter().position(|b| *b == b'\n') {
+            let text = pending.split_to(searched + end + 1);
+            line += 1;
+            scan(&path, line, &text[..text.len() - 1], regex, &mut matches);
+            searched = 0;
+        }
+    }
+
+    if !pending.is_empty() {
+        scan(&path, line + 1, &pending, regex, &mut matches);
+    }
+
+    Ok(matches)
+}
+
+fn scan(
+    path: &RelativePathBuf,
+    line: u64,
+    text: &[u8],
+    regex: &Regex,
+    matches: &mut Vec<SearchMatch>,
+) {
+    let text = text.strip_suffix(b"\r").unwrap_or(text);
+    for found in regex.find_iter(text) {
+        matches.push(SearchMatch {
+            path: path.clone(),
+            line,
+            text: String::from_utf8_lossy(text).into_owned(),
+            range: found.range(),
+        });
+    }
+}
@@ -0,0 +1,229 @@
+use std::{collections::HashSet, fmt, io, sync::Arc};
+
+use bytes::{Buf, Bytes};
+use futures::{pin_mut, Stream, StreamExt, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+#[cfg(feature = "hash")]
+use super::HashAlgorithm;
+use super::{Progress, Transfer};
+use crate::{AsyncFile, AsyncFileStore};
+
+/// How a file present on both sides is determined to be changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncCompare {
+    /// Copy when the sizes differ.
+    Size,
+    /// Copy when the sizes differ or the source is newer than the target.
+    /// Falls back to `Size` when a store does not report modification times.
+    #[default]
+    SizeAndModified,
+    /// Copy when the contents differ. Reads both files.
+    Content,
+}
+
+#[derive(Clone)]
+pub struct SyncOptions {
+    pub compare: SyncCompare,
+    /// Remove files from the target which are not present in the source.
+    pub delete: bool,
+    /// Maximum number of files transferred at once.
+    pub concurrency: usize,
+    /// Told about every file of the source as it is copied or found unchanged.
+    pub progress: Option<Arc<dyn Progress>>,
+    /// Hash every file while it is copied, and re-read the copy to check it against the hash.
+    /// A mismatch fails the file with `InvalidData`.
+    #[cfg(feature = "hash")]
+    pub verify: Option<HashAlgorithm>,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions {
+            compare: SyncCompare::default(),
+            delete: false,
+            concurrency: 8,
+            progress: None,
+            #[cfg(feature = "hash")]
+            verify: None,
+        }
+    }
+}
+
+impl fmt::Debug for SyncOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("SyncOptions");
+        debug
+            .field("compare", &self.compare)
+            .field("delete", &self.delete)
+            .field("concurrency", &self.concurrency)
+            .field("progress", &self.progress.is_some());
+        #[cfg(feature = "hash")]
+        debug.field("verify", &self.verify);
+        debug.finish()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub copied: Vec<RelativePathBuf>,
+    pub deleted: Vec<RelativePathBuf>,
+    pub unchanged: usize,
+    pub failed: Vec<(RelativePathBuf, io::Error)>,
+}
+
+/// Make `target` contain the files of `source`, only copying files which have changed.
+///
+/// Errors for individual files are collected in the report, only failing to list either store
+/// aborts the sync.
+pub async fn sync<S, T>(source: &S, target: &T, options: SyncOptions) -> io::Result<SyncReport>
+where
+    S: AsyncFileStore,
+    <S::File as AsyncFile>::Body: Send + 'static,
+    T: AsyncFileStore,
+{
+    let existing = target.list().await?.try_collect::<HashSet<_>>().await?;
+    let sources = source.list().await?.try_collect::<Vec<_>>().await?;
+    let concurrency = options.concurrency.max(1);
+    let compare = options.compare;
+    let existing = &existing;
+    let progress = options.progress.as_ref();
+    let transfer = Transfer {
+        progress,
+        #[cfg(feature = "hash")]
+        verify: options.verify,
+    };
+
+    if let Some(progress) = progress {
+        progress.total(sources.len() as u64);
+    }
+
+    let mut report = SyncReport::default();
+
+    let mut results = futures::stream::iter(sources.iter())
+        .map(|path| async move {
+            let ret = if existing.contains(path) {
+                match changed(source, target, path, compare).await {
+                    Ok(true) => super::transfer(source, target, path, path, transfer)
+                        .await
+                        .map(|_| true),
+                    Ok(false) => {
+                        if let Some(progress) = progress {
+                            progress.skipped(path);
+                        }
+                        Ok(false)
+                    }
+                    Err(err) => {
+                        if let Some(progress) = progress {
+                            progress.finished(path, Err(&err));
+                        }
+                        Err(err)
+                    }
+                }
+            } else {
+                super::transfer(source, target, path, path, transfer)
+                    .await
+                    .map(|_| true)
+            };
+            (path, ret)
+        })
+        .buffer_unordered(concurrency);
+
+    while let Some((path, ret)) = results.next().await {
+        match ret {
+            Ok(true) => report.copied.push(path.clone()),
+            Ok(false) => report.unchanged += 1,
+            Err(err) => report.failed.push((path.clone(), err)),
+        }
+    }
+
+    if options.delete {
+        let sources = sources.iter().collect::<HashSet<_>>();
+        let extraneous = existing.iter().filter(|path| !sources.contains(path));
+
+        let mut results = futures::stream::iter(extraneous)
+            .map(|path| async move { (path, target.rm_file(path).await) })
+            .buffer_unordered(concurrency);
+
+        while let Some((path, ret)) = results.next().await {
+            match ret {
+                Ok(_) => report.deleted.push(path.clone()),
+                Err(err) => report.failed.push((path.clone(), err)),
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+pub(super) async fn changed<S, T>(
+    source: &S,
+    target: &T,
+    path: &RelativePath,
+    compare: SyncCompare,
+) -> io::Result<bool>
+where
+    S: AsyncFileStore,
+    T: AsyncFileStore,
+{
+    let (from, to) = futures::try_join!(source.metadata(path), target.metadata(path))?;
+
+    if from.size != to.size {
+        return Ok(true);
+    }
+
+    match compare {
+        SyncCompare::Size => Ok(false),
+        SyncCompare::SizeAndModified => match (from.modified, to.modified) {
+            (Some(from), Some(to)) => Ok(from > to),
+            _ => Ok(false),
+        },
+        SyncCompare::Content => {
+            let (from, to) = futures::try_join!(source.open_file(path), target.open_file(path))?;
+            let (from, to) = futures::try_join!(from.reader(), to.reader())?;
+            Ok(!same_content(from, to).await?)
+        }
+    }
+}
+
+pub(super) async fn same_content<A, B>(a: A, b: B) -> io::Result<bool>
+where
+    A: Stream<Item = io::Result<Bytes>>,
+    B: Stream<Item = io::Result<Bytes>>,
+{
+    let a = a.fuse();
+    let b = b.fuse();
+    pin_mut!(a);
+    pin_mut!(b);
+
+    let mut left = Bytes::new();
+    let mut right = Bytes::new();
+
+    loop {
+        if left.is_empty() {
+            if let Some(next) = a.try_next().await? {
+                left = next;
+                continue;
+            }
+        }
+
+        if right.is_empty() {
+            if let Some(next) = b.try_next().await? {
+                right = next;
+                continue;
+            }
+        }
+
+        if left.is_empty() || right.is_empty() {
+            return Ok(left.is_empty() && right.is_empty());
+        }
+
+        let len = left.len().min(right.len());
+        if left[..len] != right[..len] {
+            return Ok(false);
+        }
+
+        left.advance(len);
+        right.advance(len);
+    }
+}
@@ -0,0 +1,92 @@
+use std::{collections::BTreeMap, io};
+
+use futures::TryStreamExt;
+use relative_path::{Component, RelativePath, RelativePathBuf};
+
+use crate::{AsyncFileStore, Metadata};
+
+/// The size and number of files below a directory, as computed by [`usage`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Usage {
+    /// The directory, empty for the root of the store.
+    pub path: RelativePathBuf,
+    /// Number of files in the directory and all of its subdirectories.
+    pub files: u64,
+    /// Total size of the files in the directory and all of its subdirectories.
+    pub bytes: u64,
+    pub children: BTreeMap<String, Usage>,
+}
+
+impl Usage {
+    /// Account for a file in this directory and its subdirectories up to `depth` levels down.
+    /// Files nested deeper are counted in their ancestor at `depth`.
+    pub fn add(&mut self, meta: &Metadata, depth: usize) {
+        let path = meta.path.normalize();
+        let mut dirs = path
+            .parent()
+            .into_iter()
+            .flat_map(|parent| parent.components())
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name),
+                _ => None,
+            })
+            .take(depth);
+
+        let mut usage = self;
+        loop {
+            usage.files += 1;
+            usage.bytes += meta.size;
+
+            let Some(name) = dirs.next() else {
+                break;
+            };
+
+            let parent = usage.path.clone();
+            usage = usage
+                .children
+                .entry(name.to_string())
+                .or_insert_with(|| Usage {
+                    path: parent.join(name),
+                    ..Usage::default()
+                });
+        }
+    }
+
+    /// The usage of the directory at `path` below this one.
+    pub fn get(&self, path: impl AsRef<RelativePath>) -> Option<&Usage> {
+        let path = path.as_ref().normalize();
+        let mut usage = self;
+        for component in path.components() {
+            if let Component::Normal(name) = component {
+                usage = usage.children.get(name)?;
+            }
+        }
+        Some(usage)
+    }
+}
+
+/// Aggregate the size and number of files per directory, down to `depth` levels below the root.
+///
+/// The store is listed once and the metadata of every file read as it is listed.
+///
+/// ```ignore
+/// let usage = util::usage(&store, 1).await?;
+/// for (tenant, usage) in &usage.children {
+///     println!("{tenant}: {} bytes in {} files", usage.bytes, usage.files);
+/// }
+/// ```
+pub async fn usage<S>(store: &S, depth: usize) -> io::Result<Usage>
+where
+    S: AsyncFileStore + Sync,
+{
+    store
+        .list_ref()
+        .map_ok(|path| async move { store.metadata(&path).await })
+        .try_buffer_unordered(16)
+        .try_fold(Usage::default(), |mut usage, meta| async move {
+            usage.add(&meta, depth);
+            Ok(usage)
+        })
+        .await
+}
@@ -0,0 +1,375 @@
+use std::{
+    collections::BTreeSet,
+    fmt,
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    sync::Arc,
+};
+
+use ::vfs::{
+    error::VfsErrorKind, FileSystem, SeekAndRead, SeekAndWrite, VfsError, VfsFileType, VfsMetadata,
+    VfsPath, VfsResult,
+};
+use bytes::Bytes;
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{Attributes, File, FileBody, FileInit, FileStore, Metadata};
+
+/// Exposes a `FileStore` as a `vfs::FileSystem`.
+///
+/// Samling has no notion of directories, so they are derived from the listing. Files are
+/// buffered in memory when opened, and written back to the store on flush or drop.
+pub struct VfsAdapter<T> {
+    store: Arc<T>,
+}
+
+impl<T> VfsAdapter<T> {
+    pub fn new(store: T) -> VfsAdapter<T> {
+        VfsAdapter {
+            store: Arc::new(store),
+        }
+    }
+}
+
+impl<T> fmt::Debug for VfsAdapter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VfsAdapter").finish_non_exhaustive()
+    }
+}
+
+impl<T> VfsAdapter<T>
+where
+    T: FileStore,
+{
+    fn read(&self, path: &RelativePath) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        self.store
+            .open_file(path)?
+            .reader()?
+            .read_to_end(&mut output)?;
+        Ok(output)
+    }
+
+    fn children(&self, dir: &str) -> VfsResult<BTreeSet<String>> {
+        let prefix = if dir.is_empty() {
+            String::new()
+        } else {
+            format!("{dir}/")
+        };
+
+        let mut children = BTreeSet::new();
+        for next in self.store.list() {
+            let next = next?;
+            if let Some(rest) = next.as_str().strip_prefix(prefix.as_str()) {
+                let name = rest.split('/').next().unwrap_or(rest);
+                if !name.is_empty() {
+                    children.insert(name.to_string());
+                }
+            }
+        }
+
+        Ok(children)
+    }
+}
+
+impl<T> FileSystem for VfsAdapter<T>
+where
+    T: FileStore + Send + Sync + 'static,
+{
+    fn read_dir(&self, path: &str) -> VfsResult<Box<dyn Iterator<Item = String> + Send>> {
+        let children = self.children(&normalize(path))?;
+        if children.is_empty() && !path.trim_matches('/').is_empty() {
+            return Err(VfsErrorKind::FileNotFound.into());
+        }
+        Ok(Box::new(children.into_iter()))
+    }
+
+    fn create_dir(&self, _path: &str) -> VfsResult<()> {
+        // Directories are implied by the files they contain
+        Ok(())
+    }
+
+    fn open_file(&self, path: &str) -> VfsResult<Box<dyn SeekAndRead + Send>> {
+        let data = self.read(RelativePath::new(&normalize(path)))?;
+        Ok(Box::new(Cursor::new(data)))
+    }
+
+    fn create_file(&self, path: &str) -> VfsResult<Box<dyn SeekAndWrite + Send>> {
+        let path = RelativePathBuf::from(normalize(path));
+        let mut writer = VfsWriter {
+            store: self.store.clone(),
+            path,
+            buffer: Cursor::new(Vec::new()),
+            dirty: true,
+        };
+        writer.commit()?;
+        Ok(Box::new(writer))
+    }
+
+    fn append_file(&self, path: &str) -> VfsResult<Box<dyn SeekAndWrite + Send>> {
+        let path = RelativePathBuf::from(normalize(path));
+        let mut buffer = Cursor::new(self.read(&path)?);
+        buffer.seek(SeekFrom::End(0))?;
+        Ok(Box::new(VfsWriter {
+            store: self.store.clone(),
+            path,
+            buffer,
+            dirty: false,
+        }))
+    }
+
+    fn metadata(&self, path: &str) -> VfsResult<VfsMetadata> {
+        let path = normalize(path);
+
+        if !path.is_empty() {
+            match self.store.metadata(RelativePath::new(&path)) {
+                Ok(meta) => {
+                    return Ok(VfsMetadata {
+                        file_type: VfsFileType::File,
+                        len: meta.size,
+                        created: None,
+                        modified: meta.modified,
+                        accessed: None,
+                    })
+                }
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                Err(_) => {}
+            }
+        }
+
+        if path.is_empty() || !self.children(&path)?.is_empty() {
+            return Ok(VfsMetadata {
+                file_type: VfsFileType::Directory,
+                len: 0,
+                created: None,
+                modified: None,
+                accessed: None,
+            });
+        }
+
+        Err(VfsErrorKind::FileNotFound.into())
+    }
+
+    fn exists(&self, path: &str) -> VfsResult<bool> {
+        match self.metadata(path) {
+            Ok(_) => Ok(true),
+            Err(err) if matches!(err.kind(), VfsErrorKind::FileNotFound) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn remove_file(&self, path: &str) -> VfsResult<()> {
+        Ok(self.store.rm_file(RelativePath::new(&normalize(path)))?)
+    }
+
+    fn remove_dir(&self, path: &str) -> VfsResult<()> {
+        if !self.children(&normalize(path))?.is_empty() {
+            return Err(VfsErrorKind::Other("directory not empty".into()).into());
+        }
+        Ok(())
+    }
+}
+
+struct VfsWriter<T>
+where
+    T: FileStore,
+{
+    store: Arc<T>,
+    path: RelativePathBuf,
+    buffer: Cursor<Vec<u8>>,
+    dirty: bool,
+}
+
+impl<T> VfsWriter<T>
+where
+    T: FileStore,
+{
+    fn commit(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let data = Bytes::copy_from_slice(self.buffer.get_ref());
+        self.store.write_file(&self.path, FileInit::from(data))?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl<T> Write for VfsWriter<T>
+where
+    T: FileStore,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.dirty = true;
+        self.buffer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.commit()
+    }
+}
+
+impl<T> Seek for VfsWriter<T>
+where
+    T: FileStore,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.buffer.seek(pos)
+    }
+}
+
+impl<T> Drop for VfsWriter<T>
+where
+    T: FileStore,
+{
+    fn drop(&mut self) {
+        let _ = self.commit();
+    }
+}
+
+/// Exposes a `vfs::VfsPath` as a `FileStore` rooted at that path.
+#[derive(Debug, Clone)]
+pub struct VfsStore {
+    root: VfsPath,
+}
+
+impl VfsStore {
+    pub fn new(root: VfsPath) -> VfsStore {
+        VfsStore { root }
+    }
+
+    pub fn from_fs<T: FileSystem>(fs: T) -> VfsStore {
+        VfsStore::new(VfsPath::new(fs))
+    }
+
+    fn resolve(&self, path: &RelativePath) -> io::Result<VfsPath> {
+        self.root.join(path.normalize().as_str()).map_err(vfs_error)
+    }
+}
+
+impl FileStore for VfsStore {
+    type File = VfsFile;
+
+    type List = Box<dyn Iterator<Item = io::Result<RelativePathBuf>> + Send>;
+
+    fn metadata(&self, path: &RelativePath) -> Result<Metadata, io::Error> {
+        let meta = self.resolve(path)?.metadata().map_err(vfs_error)?;
+
+        if meta.file_type != VfsFileType::File {
+            return Err(io::Error::other("not a file"));
+        }
+
+        let mime = if let Some(ext) = path.extension() {
+            mime_guess::from_ext(ext).first_or_octet_stream()
+        } else {
+            mime::APPLICATION_OCTET_STREAM
+        };
+
+        Ok(Metadata {
+            path: path.to_relative_path_buf(),
+            size: meta.len,
+            mime,
+            modified: meta.modified,
+            permissions: None,
+            attributes: Attributes::new(),
+        })
+    }
+
+    fn open_file(&self, path: &RelativePath) -> Result<Self::File, io::Error> {
+        let path = self.resolve(path)?;
+        if !path.is_file().map_err(vfs_error)? {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(VfsFile { path })
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> Result<(), io::Error> {
+        self.resolve(path)?.remove_file().map_err(vfs_error)
+    }
+
+    fn write_file(&self, path: &RelativePath, init: FileInit) -> Result<(), io::Error> {
+        let path = self.resolve(path)?;
+        path.parent().create_dir_all().map_err(vfs_error)?;
+
+        let mut file = path.create_file().map_err(vfs_error)?;
+        match init.body {
+            FileBody::Bytes(bs) => file.write_all(&bs)?,
+            FileBody::Read(mut reader) => {
+                io::copy(&mut reader, &mut file)?;
+            }
+            #[cfg(feature = "fs")]
+            FileBody::Path(source) => {
+                io::copy(&mut std::fs::File::open(source)?, &mut file)?;
+            }
+        }
+        file.flush()
+    }
+
+    fn list(&self) -> Self::List {
+        let root = self.root.as_str().to_string();
+        let walk = match self.root.walk_dir() {
+            Ok(walk) => walk,
+            Err(err) => return Box::new(std::iter::once(Err(vfs_error(err)))),
+        };
+
+        let files = walk
+            .filter_map(move |next| {
+                let next = match next {
+                    Ok(next) => next,
+                    Err(err) => return Some(Err(vfs_error(err))),
+                };
+
+                match next.is_file() {
+                    Ok(true) => {}
+                    Ok(false) => return None,
+                    Err(err) => return Some(Err(vfs_error(err))),
+                }
+
+                let rel = next
+                    .as_str()
+                    .strip_prefix(root.as_str())
+                    .unwrap_or(next.as_str());
+                Some(Ok(RelativePathBuf::from(rel.trim_start_matches('/'))))
+            })
+            .collect::<Vec<_>>();
+
+        Box::new(files.into_iter())
+    }
+}
+
+pub struct VfsFile {
+    path: VfsPath,
+}
+
+impl File for VfsFile {
+    type Body = Box<dyn SeekAndRead + Send>;
+
+    fn read_range(&self, range: std::ops::Range<u64>) -> Result<Bytes, io::Error> {
+        let mut file = self.path.open_file().map_err(vfs_error)?;
+        file.seek(SeekFrom::Start(range.start))?;
+
+        let mut buf = vec![0; (range.end - range.start) as usize];
+        file.read_exact(&mut buf)?;
+
+        Ok(buf.into())
+    }
+
+    fn reader(&self) -> Result<Self::Body, io::Error> {
+        self.path.open_file().map_err(vfs_error)
+    }
+}
+
+fn normalize(path: &str) -> String {
+    RelativePath::new(path.trim_start_matches('/'))
+        .normalize()
+        .into_string()
+}
+
+fn vfs_error(err: VfsError) -> io::Error {
+    match err.kind() {
+        VfsErrorKind::FileNotFound => io::ErrorKind::NotFound.into(),
+        VfsErrorKind::NotSupported => io::ErrorKind::Unsupported.into(),
+        VfsErrorKind::IoError(err) => io::Error::new(err.kind(), err.to_string()),
+        _ => io::Error::other(err),
+    }
+}
@@ -0,0 +1,259 @@
+use std::{collections::HashSet, fmt, io, sync::Arc};
+
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{AsyncFile, AsyncFileStore, Attributes, File, FileStore, Metadata};
+
+pub type DirFilter = Arc<dyn Fn(&RelativePath) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+pub struct WalkOptions {
+    /// Skip files nested deeper than this many directories.
+    pub max_depth: Option<usize>,
+    /// Include files which are symbolic links. Only stores exposing a native path can
+    /// report links; for other stores every file is included.
+    pub follow_symlinks: bool,
+    /// Only descend into directories for which the filter returns `true`.
+    pub filter: Option<DirFilter>,
+    /// Also yield the directories containing files, each before the first file inside it.
+    /// Stores only list files, so empty directories are not seen.
+    pub dirs: bool,
+    /// Tell symbolic links apart from files, yielding them as [`EntryKind::Symlink`]. This
+    /// opens every file to find its native path, so only local stores can report links.
+    pub symlinks: bool,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        WalkOptions {
+            max_depth: None,
+            follow_symlinks: true,
+            filter: None,
+            dirs: false,
+            symlinks: false,
+        }
+    }
+}
+
+impl fmt::Debug for WalkOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WalkOptions")
+            .field("max_depth", &self.max_depth)
+            .field("follow_symlinks", &self.follow_symlinks)
+            .field("filter", &self.filter.is_some())
+            .field("dirs", &self.dirs)
+            .field("symlinks", &self.symlinks)
+            .finish()
+    }
+}
+
+/// What a [`WalkEntry`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WalkEntry {
+    pub path: RelativePathBuf,
+    pub kind: EntryKind,
+    /// The metadata of the file. Directories have a size of 0 and the `inode/directory` mime
+    /// type.
+    pub metadata: Metadata,
+    /// Number of directories between the root of the store and the entry.
+    pub depth: usize,
+}
+
+impl WalkEntry {
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+
+    fn dir(path: RelativePathBuf, depth: usize) -> WalkEntry {
+        WalkEntry {
+            metadata: Metadata {
+                path: path.clone(),
+                size: 0,
+                mime: "inode/directory".parse().expect("mime"),
+                modified: None,
+                permissions: None,
+                attributes: Attributes::new(),
+            },
+            path,
+            kind: EntryKind::Dir,
+            depth,
+        }
+    }
+}
+
+impl WalkOptions {
+    /// The depth of `path`, or `None` if the options exclude it.
+    fn depth(&self, path: &RelativePath) -> Option<usize> {
+        let path = path.normalize();
+        let mut depth = 0;
+        let mut parent = path.parent();
+
+        while let Some(dir) = parent {
+            if dir.as_str().is_empty() {
+                break;
+            }
+            if let Some(filter) = &self.filter {
+                if !filter(dir) {
+                    return None;
+                }
+            }
+            depth += 1;
+            parent = dir.parent();
+        }
+
+        match self.max_depth {
+            Some(max) if depth > max => None,
+            _ => Some(depth),
+        }
+    }
+
+    /// Whether files have to be opened to look for links.
+    fn probe(&self) -> bool {
+        self.symlinks || !self.follow_symlinks
+    }
+
+    /// The directories above `path` not seen before, from the outermost in.
+    fn dirs(&self, seen: &mut HashSet<RelativePathBuf>, path: &RelativePath) -> Vec<WalkEntry> {
+        if !self.dirs {
+            return Vec::new();
+        }
+
+        let path = path.normalize();
+        let mut dirs = Vec::new();
+        let mut parent = path.parent();
+
+        while let Some(dir) = parent {
+            if dir.as_str().is_empty() || seen.contains(dir) {
+                break;
+            }
+            seen.insert(dir.to_relative_path_buf());
+            dirs.push(dir);
+            parent = dir.parent();
+        }
+
+        dirs.into_iter()
+            .rev()
+            .filter_map(|dir| {
+                let depth = self.depth(dir)?;
+                Some(WalkEntry::dir(dir.to_relative_path_buf(), depth))
+            })
+            .collect()
+    }
+
+    fn kind(&self, link: bool) -> EntryKind {
+        if link && self.symlinks {
+            EntryKind::Symlink
+        } else {
+            EntryKind::File
+        }
+    }
+}
+
+fn is_symlink(path: Option<&std::path::Path>) -> bool {
+    path.and_then(|path| std::fs::symlink_metadata(path).ok())
+        .is_some_and(|meta| meta.file_type().is_symlink())
+}
+
+/// Walk every file of an async store, yielding its metadata and depth.
+pub(crate) async fn walk<S>(
+    store: &S,
+    options: WalkOptions,
+) -> io::Result<BoxStream<'_, io::Result<WalkEntry>>>
+where
+    S: AsyncFileStore + Sync,
+    S::File: Send,
+{
+    let options = Arc::new(options);
+    let mut seen = HashSet::new();
+    let stream = store
+        .list()
+        .await?
+        .map_ok({
+            let options = options.clone();
+            move |path| (options.dirs(&mut seen, &path), path)
+        })
+        .and_then(move |(mut entries, path)| {
+            let options = options.clone();
+            async move {
+                let Some(depth) = options.depth(&path) else {
+                    return io::Result::Ok(entries);
+                };
+
+                let mut link = false;
+                if options.probe() {
+                    let file = store.open_file(&path).await?;
+                    link = is_symlink(file.native_path());
+                    if link && !options.follow_symlinks {
+                        return Ok(entries);
+                    }
+                }
+
+                let metadata = store.metadata(&path).await?;
+                entries.push(WalkEntry {
+                    path,
+                    kind: options.kind(link),
+                    metadata,
+                    depth,
+                });
+                Ok(entries)
+            }
+        })
+        .map_ok(|entries| futures::stream::iter(entries.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed();
+
+    Ok(stream)
+}
+
+/// Walk every file of a sync store, yielding its metadata and depth.
+pub(crate) fn walk_sync<S>(
+    store: &S,
+    options: WalkOptions,
+) -> impl Iterator<Item = io::Result<WalkEntry>> + '_
+where
+    S: FileStore,
+{
+    let mut seen = HashSet::new();
+    store.list().flat_map(move |path| {
+        let entry = || -> io::Result<Vec<WalkEntry>> {
+            let path = path?;
+            let mut entries = options.dirs(&mut seen, &path);
+            let Some(depth) = options.depth(&path) else {
+                return Ok(entries);
+            };
+
+            let link = options.probe() && is_symlink(store.open_file(&path)?.native_path());
+            if link && !options.follow_symlinks {
+                return Ok(entries);
+            }
+
+            let metadata = store.metadata(&path)?;
+            entries.push(WalkEntry {
+                path,
+                kind: options.kind(link),
+                metadata,
+                depth,
+            });
+            Ok(entries)
+        };
+
+        match entry() {
+            Ok(entries) => entries.into_iter().map(Ok).collect::<Vec<_>>(),
+            Err(err) => vec![Err(err)],
+        }
+    })
+}
@@ -0,0 +1,99 @@
+//! Change notifications for any store, by polling its listing and metadata.
+
+use std::{
+    collections::HashMap,
+    io,
+    time::{Duration, SystemTime},
+};
+
+use futures::{Stream, StreamExt, TryStreamExt};
+use relative_path::RelativePathBuf;
+
+use crate::AsyncFileStore;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Removed => "removed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub path: RelativePathBuf,
+}
+
+type Snapshot = HashMap<RelativePathBuf, (u64, Option<SystemTime>)>;
+
+/// Poll `store` every `interval` and yield the files added, modified or removed since the
+/// previous poll. A file counts as modified when its size or modification time changed.
+///
+/// The first poll only records the current state. The stream ends with the first error.
+pub fn watch<S>(store: S, interval: Duration) -> impl Stream<Item = io::Result<Change>>
+where
+    S: AsyncFileStore,
+{
+    async_stream::try_stream! {
+        let mut previous = snapshot(&store).await?;
+        loop {
+            futures_timer::Delay::new(interval).await;
+            let current = snapshot(&store).await?;
+
+            for (path, state) in &current {
+                match previous.get(path) {
+                    None => yield change(ChangeKind::Added, path),
+                    Some(old) if old != state => yield change(ChangeKind::Modified, path),
+                    Some(_) => {}
+                }
+            }
+            for path in previous.keys() {
+                if !current.contains_key(path) {
+                    yield change(ChangeKind::Removed, path);
+                }
+            }
+
+            previous = current;
+        }
+    }
+}
+
+fn change(kind: ChangeKind, path: &RelativePathBuf) -> Change {
+    Change {
+        kind,
+        path: path.clone(),
+    }
+}
+
+async fn snapshot<S: AsyncFileStore>(store: &S) -> io::Result<Snapshot> {
+    let paths = store
+        .list()
+        .await?
+        .map_ok(|path| path.normalize())
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    futures::stream::iter(paths)
+        .map(|path| async move {
+            match store.metadata(&path).await {
+                Ok(meta) => Ok(Some((path, (meta.size, meta.modified)))),
+                // Removed between listing and stat, picked up by the next poll
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err),
+            }
+        })
+        .buffer_unordered(8)
+        .try_filter_map(|entry| async move { Ok(entry) })
+        .try_collect()
+        .await
+}
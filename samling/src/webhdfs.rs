@@ -0,0 +1,485 @@
+//! A backend for Hadoop clusters, speaking the WebHDFS REST API of the namenode.
+//!
+//! Reads and writes are redirected by the namenode to a datanode holding the data, which
+//! [`WebHdfsFileStore`] follows. Only plain `http` is spoken, like [`RemoteClient`].
+use std::{
+    io,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use async_stream::try_stream;
+use bytes::Bytes;
+use futures::{stream::BoxStream, Future, StreamExt};
+use hyper::{body::Incoming, header, http::request, Method, Request, Response, StatusCode};
+use relative_path::{Component, RelativePath, RelativePathBuf};
+use serde::{de::DeserializeOwned, Deserialize};
+use url::Url;
+
+use crate::{
+    remote::{self, RemoteBody, RemoteClient},
+    AsyncFile, AsyncFileBody, AsyncFileInit, AsyncFileStore, Attributes, Metadata, Permissions,
+};
+
+/// Files of a directory in a Hadoop cluster, through WebHDFS.
+///
+/// Paths are resolved below the directory of the url the store is created with. Files are
+/// created with their parent directories, and listings descend into subdirectories.
+/// [`write_range`](AsyncFileStore::write_range) can only append at the end of a file.
+///
+/// ```ignore
+/// let store = WebHdfsFileStore::new(&"webhdfs://alice@namenode:9870/data/events".parse()?)?;
+/// store.write_file("2024/01/01.json".as_ref(), init).await?;
+/// store.append("2024/01/01.json".as_ref(), more).await?;
+/// ```
+#[derive(Clone)]
+pub struct WebHdfsFileStore {
+    client: RemoteClient,
+    /// The REST endpoint of the root directory, e.g. `http://namenode:9870/webhdfs/v1/data`.
+    endpoint: Url,
+    user: Option<String>,
+    delegation: Option<String>,
+}
+
+impl WebHdfsFileStore {
+    /// A store of the directory at `url`, e.g. `http://namenode:9870/data` or
+    /// `webhdfs://alice@namenode:9870/data`. The user name of the url, if any, is sent as the
+    /// user of every request.
+    pub fn new(url: &Url) -> io::Result<WebHdfsFileStore> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, msg.to_string());
+
+        if !matches!(url.scheme(), "http" | "webhdfs") {
+            return Err(invalid("webhdfs urls must be http or webhdfs"));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| invalid("missing namenode host"))?;
+
+        let mut endpoint = Url::parse(&format!("http://{host}"))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        endpoint
+            .set_port(url.port())
+            .map_err(|_| invalid("invalid namenode port"))?;
+        endpoint
+            .path_segments_mut()
+            .map_err(|_| invalid("invalid namenode url"))?
+            .clear()
+            .extend(["webhdfs", "v1"])
+            .extend(
+                url.path_segments()
+                    .into_iter()
+                    .flatten()
+                    .filter(|segment| !segment.is_empty()),
+            );
+
+        let user = Some(url.username())
+            .filter(|user| !user.is_empty())
+            .map(str::to_string);
+
+        Ok(WebHdfsFileStore {
+            client: RemoteClient::shared(),
+            endpoint,
+            user,
+            delegation: None,
+        })
+    }
+
+    /// Send requests through `client` instead of the shared one.
+    pub fn client(mut self, client: RemoteClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Make requests as `user`, for clusters with simple authentication.
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Authenticate with a delegation token instead of a user name.
+    pub fn delegation(mut self, token: impl Into<String>) -> Self {
+        self.delegation = Some(token.into());
+        self
+    }
+
+    /// Append `init` to the end of the existing file at `path`.
+    pub async fn append(&self, path: &RelativePath, init: AsyncFileInit) -> io::Result<()> {
+        let url = self.url(path, "APPEND")?;
+        self.upload(Method::POST, url, init).await
+    }
+
+    /// The url of operation `op` on `path`. Fails with `PermissionDenied` when the path would
+    /// lead out of the store.
+    fn url(&self, path: &RelativePath, op: &str) -> io::Result<Url> {
+        let mut url = self.endpoint.clone();
+        {
+            let mut segments = url.path_segments_mut().expect("http url");
+            for component in path.normalize().components() {
+                match component {
+                    Component::Normal(name) => {
+                        segments.push(name);
+                    }
+                    Component::CurDir => {}
+                    Component::ParentDir => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::PermissionDenied,
+                            format!("path escapes the store: {path}"),
+                        ))
+                    }
+                }
+            }
+        }
+
+        let mut query = url.query_pairs_mut();
+        query.append_pair("op", op);
+        if let Some(token) = &self.delegation {
+            query.append_pair("delegation", token);
+        } else if let Some(user) = &self.user {
+            query.append_pair("user.name", user);
+        }
+        drop(query);
+
+        Ok(url)
+    }
+
+    async fn send(
+        &self,
+        request: request::Builder,
+        body: RemoteBody,
+    ) -> io::Result<Response<Incoming>> {
+        let request = request
+            .body(body)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        check(self.client.request(request).await?).await
+    }
+
+    /// Send a request without a body.
+    async fn call(&self, method: Method, url: &Url) -> io::Result<Response<Incoming>> {
+        let request = Request::builder().method(method).uri(url.as_str());
+        self.send(request, remote::empty()).await
+    }
+
+    async fn json<T>(&self, method: Method, url: &Url) -> io::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.call(method, url).await?;
+        let body = remote::collect(response).await?;
+        serde_json::from_slice(&body).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Read from `url`, following the redirect to a datanode.
+    async fn open(&self, url: &Url) -> io::Result<Response<Incoming>> {
+        let response = self.call(Method::GET, url).await?;
+        match redirect(&response)? {
+            Some(location) => self.call(Method::GET, &location).await,
+            None => Ok(response),
+        }
+    }
+
+    /// Ask the namenode where to write, and send the body to the datanode it redirects to.
+    async fn upload(&self, method: Method, url: Url, init: AsyncFileInit) -> io::Result<()> {
+        let response = self.call(method.clone(), &url).await?;
+        let location = redirect(&response)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "expected a redirect to a datanode",
+            )
+        })?;
+
+        let mut request = Request::builder()
+            .method(method)
+            .uri(location.as_str())
+            .header(header::CONTENT_TYPE, "application/octet-stream");
+        let body = match init.body {
+            AsyncFileBody::Bytes(bytes) => remote::full(bytes),
+            body => {
+                if let Some(len) = init.len {
+                    request = request.header(header::CONTENT_LENGTH, len);
+                }
+                remote::stream(body.into_stream().await?)
+            }
+        };
+        self.send(request, body).await.map(drop)
+    }
+
+    async fn status(&self, path: &RelativePath) -> io::Result<FileStatus> {
+        let url = self.url(path, "GETFILESTATUS")?;
+        let status: FileStatusResponse = self.json(Method::GET, &url).await?;
+        Ok(status.file_status)
+    }
+}
+
+impl AsyncFileStore for WebHdfsFileStore {
+    type File = WebHdfsFile;
+
+    fn metadata(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Metadata, io::Error>> + Send {
+        async move {
+            let status = self.status(path).await?;
+            if status.kind != "FILE" {
+                return Err(not_a_file());
+            }
+
+            let path = path.normalize();
+            let mime = match path.extension() {
+                Some(ext) => mime_guess::from_ext(ext).first_or_octet_stream(),
+                None => mime::APPLICATION_OCTET_STREAM,
+            };
+
+            Ok(Metadata {
+                path,
+                size: status.length,
+                mime,
+                modified: Some(UNIX_EPOCH + Duration::from_millis(status.modification_time)),
+                permissions: u32::from_str_radix(&status.permission, 8)
+                    .ok()
+                    .map(Permissions::from_mode),
+                attributes: Attributes::default(),
+            })
+        }
+    }
+
+    fn open_file(
+        &self,
+        path: &RelativePath,
+    ) -> impl Future<Output = Result<Self::File, io::Error>> + Send {
+        async move {
+            if self.status(path).await?.kind != "FILE" {
+                return Err(not_a_file());
+            }
+
+            Ok(WebHdfsFile {
+                url: self.url(path, "OPEN")?,
+                store: self.clone(),
+            })
+        }
+    }
+
+    fn rm_file(&self, path: &RelativePath) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let mut url = self.url(path, "DELETE")?;
+            url.query_pairs_mut().append_pair("recursive", "false");
+
+            let deleted: BooleanResponse = self.json(Method::DELETE, &url).await?;
+            if deleted.boolean {
+                Ok(())
+            } else {
+                Err(io::ErrorKind::NotFound.into())
+            }
+        }
+    }
+
+    fn write_file(
+        &self,
+        path: &RelativePath,
+        init: AsyncFileInit,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            let mut url = self.url(path, "CREATE")?;
+            url.query_pairs_mut().append_pair("overwrite", "true");
+            self.upload(Method::PUT, url, init).await
+        }
+    }
+
+    fn list(
+        &self,
+    ) -> impl Future<
+        Output = Result<BoxStream<'static, Result<RelativePathBuf, io::Error>>, io::Error>,
+    > + Send {
+        let store = self.clone();
+        async move {
+            let stream = try_stream! {
+                let mut dirs = vec![RelativePathBuf::new()];
+
+                while let Some(dir) = dirs.pop() {
+                    let url = store.url(&dir, "LISTSTATUS")?;
+                    let list: ListStatusResponse = store.json(Method::GET, &url).await?;
+
+                    let mut found = Vec::new();
+                    for status in list.file_statuses.file_status {
+                        let path = dir.join(&status.path_suffix);
+                        match status.kind.as_str() {
+                            "FILE" => yield path,
+                            "DIRECTORY" => found.push(path),
+                            _ => {}
+                        }
+                    }
+                    // Descend in name order
+                    dirs.extend(found.into_iter().rev());
+                }
+            };
+
+            Ok(stream.boxed())
+        }
+    }
+
+    fn health(&self) -> impl Future<Output = io::Result<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move { self.status(RelativePath::new("")).await.map(drop) }
+    }
+
+    /// Appends when `offset` is the end of the file, and fails with `Unsupported` otherwise.
+    fn write_range(
+        &self,
+        path: &RelativePath,
+        offset: u64,
+        data: Bytes,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            if self.metadata(path).await?.size != offset {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "webhdfs can only write at the end of a file",
+                ));
+            }
+            self.append(path, AsyncFileInit::from(data)).await
+        }
+    }
+
+    fn set_permissions(
+        &self,
+        path: &RelativePath,
+        permissions: Permissions,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            let mut url = self.url(path, "SETPERMISSION")?;
+            url.query_pairs_mut()
+                .append_pair("permission", &format!("{:o}", permissions.mode()));
+            self.call(Method::PUT, &url).await.map(drop)
+        }
+    }
+}
+
+/// A file in a [`WebHdfsFileStore`].
+pub struct WebHdfsFile {
+    store: WebHdfsFileStore,
+    /// The url of the `OPEN` operation on the file.
+    url: Url,
+}
+
+impl AsyncFile for WebHdfsFile {
+    type Body = BoxStream<'static, io::Result<Bytes>>;
+
+    fn read_range(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Future<Output = Result<Bytes, io::Error>> + Send {
+        async move {
+            if range.is_empty() {
+                return Ok(Bytes::new());
+            }
+
+            let mut url = self.url.clone();
+            url.query_pairs_mut()
+                .append_pair("offset", &range.start.to_string())
+                .append_pair("length", &(range.end - range.start).to_string());
+            remote::collect(self.store.open(&url).await?).await
+        }
+    }
+
+    fn reader(&self) -> impl Future<Output = Result<Self::Body, io::Error>> + Send {
+        async move {
+            let response = self.store.open(&self.url).await?;
+            Ok(remote::body_stream(response).boxed())
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileStatus {
+    length: u64,
+    modification_time: u64,
+    path_suffix: String,
+    permission: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct FileStatusResponse {
+    #[serde(rename = "FileStatus")]
+    file_status: FileStatus,
+}
+
+#[derive(Deserialize)]
+struct ListStatusResponse {
+    #[serde(rename = "FileStatuses")]
+    file_statuses: FileStatuses,
+}
+
+#[derive(Deserialize)]
+struct FileStatuses {
+    #[serde(rename = "FileStatus")]
+    file_status: Vec<FileStatus>,
+}
+
+#[derive(Deserialize)]
+struct BooleanResponse {
+    boolean: bool,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    #[serde(rename = "RemoteException")]
+    remote_exception: RemoteException,
+}
+
+#[derive(Deserialize)]
+struct RemoteException {
+    exception: String,
+    message: String,
+}
+
+fn not_a_file() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "not a file")
+}
+
+/// Where the response redirects to, if it is a redirect.
+fn redirect(response: &Response<Incoming>) -> io::Result<Option<Url>> {
+    if !response.status().is_redirection() {
+        return Ok(None);
+    }
+
+    let location = response
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "redirect without location"))?;
+    Url::parse(location)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Turn error responses into errors, with the kind of the exception thrown by the cluster.
+async fn check(response: Response<Incoming>) -> io::Result<Response<Incoming>> {
+    let status = response.status();
+    if status.is_success() || status.is_redirection() {
+        return Ok(response);
+    }
+
+    let body = remote::collect(response).await.unwrap_or_default();
+    let (exception, message) = match serde_json::from_slice::<ErrorResponse>(&body) {
+        Ok(error) => (
+            error.remote_exception.exception,
+            error.remote_exception.message,
+        ),
+        Err(_) => (String::new(), String::from_utf8_lossy(&body).into_owned()),
+    };
+
+    let kind = match (status, exception.as_str()) {
+        (_, "FileNotFoundException") | (StatusCode::NOT_FOUND, _) => io::ErrorKind::NotFound,
+        (_, "FileAlreadyExistsException") => io::ErrorKind::AlreadyExists,
+        (_, "UnsupportedOperationException") => io::ErrorKind::Unsupported,
+        (_, "AccessControlException" | "SecurityException")
+        | (StatusCode::FORBIDDEN | StatusCode::UNAUTHORIZED, _) => io::ErrorKind::PermissionDenied,
+        (StatusCode::BAD_REQUEST, _) => io::ErrorKind::InvalidInput,
+        _ => io::ErrorKind::Other,
+    };
+
+    Err(io::Error::new(kind, format!("webhdfs {status}: {message}")))
+}